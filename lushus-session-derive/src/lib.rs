@@ -0,0 +1,110 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitInt, LitStr};
+
+/// Derives `lushus_session::SessionData`.
+///
+/// Container attributes: `#[session(key = "...", version = N)]`. The key defaults
+/// to the snake-cased type name and the version to 1.
+///
+/// Field attributes: `#[session(redact)]` and `#[session(classification = "...")]`.
+#[proc_macro_derive(SessionData, attributes(session))]
+pub fn derive_session_data(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let mut key = snake_case(&name.to_string());
+    let mut version = 1u32;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("session")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("key") {
+                key = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("version") {
+                version = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+            } else {
+                return Err(meta.error("expected `key` or `version`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "SessionData can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "SessionData can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut infos = Vec::new();
+    for field in fields {
+        let field_name = field.ident.as_ref().map(ToString::to_string);
+        let mut redact = false;
+        let mut classification = None;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("session")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("redact") {
+                    redact = true;
+                } else if meta.path.is_ident("classification") {
+                    classification = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else {
+                    return Err(meta.error("expected `redact` or `classification`"));
+                }
+                Ok(())
+            })?;
+        }
+        let classification = match classification {
+            Some(classification) => quote!(::core::option::Option::Some(#classification)),
+            None => quote!(::core::option::Option::None),
+        };
+        infos.push(quote! {
+            ::lushus_session::FieldInfo {
+                name: #field_name,
+                classification: #classification,
+                redact: #redact,
+            }
+        });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::lushus_session::SessionData for #name #ty_generics #where_clause {
+            const KEY: &'static str = #key;
+            const VERSION: u32 = #version;
+
+            fn fields() -> &'static [::lushus_session::FieldInfo] {
+                &[#(#infos),*]
+            }
+        }
+    })
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.char_indices() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
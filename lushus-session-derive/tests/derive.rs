@@ -0,0 +1,57 @@
+use lushus_session::{Session, SessionData, Storage};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, SessionData)]
+struct ShoppingCart {
+    items: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, SessionData)]
+#[session(key = "user", version = 2)]
+struct User {
+    username: String,
+    #[session(redact, classification = "secret")]
+    password: String,
+    #[session(classification = "pii")]
+    email: String,
+}
+
+#[test]
+fn derive_defaults_the_key_to_the_snake_cased_type_name() {
+    assert_eq!(ShoppingCart::KEY, "shopping_cart");
+    assert_eq!(ShoppingCart::VERSION, 1);
+}
+
+#[test]
+fn derive_reads_container_and_field_attributes() {
+    assert_eq!(User::KEY, "user");
+    assert_eq!(User::VERSION, 2);
+    let fields = User::fields();
+    assert_eq!(fields.len(), 3);
+    assert!(fields[1].redact);
+    assert_eq!(fields[1].classification, Some("secret"));
+    assert_eq!(fields[2].classification, Some("pii"));
+    assert!(!fields[2].redact);
+}
+
+#[test]
+fn derived_data_round_trips_through_the_session() {
+    let user = User {
+        username: "brandon".to_string(),
+        password: "hunter2".to_string(),
+        email: "brandon@example.com".to_string(),
+    };
+    let mut session = Session::default();
+    session.insert_data(&user).expect("unable to insert user");
+
+    assert!(session.get::<serde_json::Value>("user").unwrap().is_some());
+    let loaded = session
+        .get_data::<User>()
+        .expect("unable to get user")
+        .expect("user not found");
+    assert_eq!(loaded, user);
+
+    let redacted = user.redacted().expect("unable to redact user");
+    assert_eq!(redacted["password"], "[REDACTED]");
+    assert_eq!(redacted["username"], "brandon");
+}
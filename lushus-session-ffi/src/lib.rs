@@ -0,0 +1,153 @@
+//! C-ABI bindings for the session wire format, for use from Python (ctypes,
+//! cffi), Node (ffi-napi) and other languages with a C FFI.
+//!
+//! Strings cross the boundary as NUL-terminated UTF-8. Strings returned by
+//! this library must be released with [`lushus_string_free`]. Functions that
+//! can fail write one of the `LUSHUS_*` codes to `error` when it is not null.
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    ptr,
+};
+
+use lushus_session::{SessionState, WireEnvelope, WireError};
+
+pub const LUSHUS_OK: i32 = 0;
+pub const LUSHUS_INVALID_ARGUMENT: i32 = 1;
+pub const LUSHUS_MALFORMED: i32 = 2;
+pub const LUSHUS_UNSUPPORTED_VERSION: i32 = 3;
+pub const LUSHUS_UNSUPPORTED_CODEC: i32 = 4;
+pub const LUSHUS_UNSUPPORTED_COMPRESSION: i32 = 5;
+
+fn code(error: &WireError) -> i32 {
+    match error {
+        WireError::VersionError(_) => LUSHUS_UNSUPPORTED_VERSION,
+        WireError::CodecError(_) => LUSHUS_UNSUPPORTED_CODEC,
+        WireError::CompressionError => LUSHUS_UNSUPPORTED_COMPRESSION,
+        WireError::SerializationError(_) => LUSHUS_MALFORMED,
+    }
+}
+
+unsafe fn read<'a>(value: *const c_char) -> Result<&'a str, i32> {
+    if value.is_null() {
+        return Err(LUSHUS_INVALID_ARGUMENT);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| LUSHUS_INVALID_ARGUMENT)
+}
+
+unsafe fn report(result: Result<String, i32>, error: *mut i32) -> *mut c_char {
+    let (value, status) = match result.and_then(|v| CString::new(v).map_err(|_| LUSHUS_MALFORMED)) {
+        Ok(value) => (value.into_raw(), LUSHUS_OK),
+        Err(status) => (ptr::null_mut(), status),
+    };
+    if !error.is_null() {
+        *error = status;
+    }
+    value
+}
+
+/// Wraps a JSON object of codec-encoded values in a wire envelope.
+///
+/// # Safety
+///
+/// `state` must be null or a valid NUL-terminated string, and `error` must be
+/// null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lushus_wire_encode(state: *const c_char, error: *mut i32) -> *mut c_char {
+    let result = read(state).and_then(|state| {
+        let state = serde_json::from_str::<SessionState>(state).map_err(|_| LUSHUS_MALFORMED)?;
+        WireEnvelope::new(state).encode().map_err(|e| code(&e))
+    });
+    report(result, error)
+}
+
+/// Returns the `state` object of a wire envelope as JSON.
+///
+/// # Safety
+///
+/// `envelope` must be null or a valid NUL-terminated string, and `error` must
+/// be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lushus_wire_decode(
+    envelope: *const c_char,
+    error: *mut i32,
+) -> *mut c_char {
+    let result = read(envelope).and_then(|envelope| {
+        let envelope = WireEnvelope::decode(envelope).map_err(|e| code(&e))?;
+        serde_json::to_string(&envelope.state).map_err(|_| LUSHUS_MALFORMED)
+    });
+    report(result, error)
+}
+
+/// Checks that `envelope` is a wire envelope this version can read.
+///
+/// # Safety
+///
+/// `envelope` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn lushus_wire_verify(envelope: *const c_char) -> i32 {
+    match read(envelope) {
+        Ok(envelope) => match WireEnvelope::decode(envelope) {
+            Ok(_) => LUSHUS_OK,
+            Err(e) => code(&e),
+        },
+        Err(status) => status,
+    }
+}
+
+/// Releases a string returned by this library.
+///
+/// # Safety
+///
+/// `value` must be null or a pointer returned by this library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lushus_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take(value: *mut c_char) -> String {
+        let owned = unsafe { CStr::from_ptr(value) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { lushus_string_free(value) };
+        owned
+    }
+
+    #[test]
+    fn encode_and_decode_round_trip_the_state() {
+        let state = CString::new(r#"{"user_id":"\"beavis\""}"#).unwrap();
+        let mut error = -1;
+        let envelope = unsafe { lushus_wire_encode(state.as_ptr(), &mut error) };
+        assert_eq!(error, LUSHUS_OK);
+        let envelope = CString::new(take(envelope)).unwrap();
+        assert_eq!(unsafe { lushus_wire_verify(envelope.as_ptr()) }, LUSHUS_OK);
+
+        let decoded = unsafe { lushus_wire_decode(envelope.as_ptr(), &mut error) };
+        assert_eq!(error, LUSHUS_OK);
+        assert_eq!(take(decoded), state.to_str().unwrap());
+    }
+
+    #[test]
+    fn failures_return_null_and_an_error_code() {
+        let envelope =
+            CString::new(r#"{"version":2,"codec":"json","compressed":false,"state":{}}"#).unwrap();
+        let mut error = -1;
+        let decoded = unsafe { lushus_wire_decode(envelope.as_ptr(), &mut error) };
+        assert!(decoded.is_null());
+        assert_eq!(error, LUSHUS_UNSUPPORTED_VERSION);
+        assert_eq!(
+            unsafe { lushus_wire_verify(ptr::null()) },
+            LUSHUS_INVALID_ARGUMENT
+        );
+    }
+}
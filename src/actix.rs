@@ -0,0 +1,470 @@
+//! actix-web integration: wrap an app in [`SessionMiddleware`] and take
+//! [`Session`] in handlers.
+//!
+//! ```ignore
+//! HttpServer::new(move || {
+//!     App::new()
+//!         .wrap(SessionMiddleware::new(store.clone()))
+//!         .route("/", web::get().to(index))
+//! })
+//! ```
+
+use std::{
+    cell::RefCell,
+    future::{ready, Ready},
+    rc::Rc,
+    time::Duration,
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::ErrorInternalServerError,
+    http::{
+        header::{self, HeaderName, HeaderValue},
+        StatusCode,
+    },
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError, Route,
+};
+use futures::future::LocalBoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    policy::{PolicyError, PolicyOverride},
+    storage::{Storage, StorageError},
+    web::{self, Backend, CookieAction, Progress, RequestParts, Settings},
+    SessionError, SessionKey, SessionStatus,
+};
+
+struct Inner<Store> {
+    store: Rc<Store>,
+    settings: Settings,
+}
+
+/// Loads the session named by the request cookie into the request
+/// extensions and writes it back once the handler has run.
+pub struct SessionMiddleware<Store> {
+    inner: Rc<Inner<Store>>,
+}
+
+impl<Store: Backend> SessionMiddleware<Store> {
+    pub fn new(store: Store) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                store: Rc::new(store),
+                settings: Settings::default(),
+            }),
+        }
+    }
+
+    web::settings_builders!();
+
+    /// A middleware for routes that need `route`'s changes to the policy,
+    /// e.g. a checkout that requires MFA, sharing this middleware's store
+    /// and cookie. Wrap those routes' scope in it instead of, not inside,
+    /// this one.
+    pub fn with_override(&self, route: &PolicyOverride) -> Result<Self, PolicyError> {
+        let inner = Inner {
+            store: self.inner.store.clone(),
+            settings: self.inner.settings.with_override(route)?,
+        };
+        Ok(Self {
+            inner: Rc::new(inner),
+        })
+    }
+
+    fn settings(self, update: impl FnOnce(&mut Settings)) -> Self {
+        let mut inner = Rc::try_unwrap(self.inner)
+            .unwrap_or_else(|_| panic!("SessionMiddleware is configured before it is shared"));
+        update(&mut inner.settings);
+        Self {
+            inner: Rc::new(inner),
+        }
+    }
+}
+
+impl<S, B, Store> Transform<S, ServiceRequest> for SessionMiddleware<Store>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+    Store: Backend + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SessionService<S, Store>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        self.inner.settings.check();
+        ready(Ok(SessionService {
+            service: Rc::new(service),
+            inner: self.inner.clone(),
+        }))
+    }
+}
+
+pub struct SessionService<S, Store> {
+    service: Rc<S>,
+    inner: Rc<Inner<Store>>,
+}
+
+impl<S, B, Store> Service<ServiceRequest> for SessionService<S, Store>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+    Store: Backend + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let cookie = request
+                .cookie(inner.settings.cookies.config().name())
+                .map(|cookie| cookie.value().to_string());
+            let keyring = inner.settings.cookies.keyring();
+            let parts =
+                RequestParts::new(&inner.settings.policy, keyring, cookie.as_deref(), |name| {
+                    request.headers().get(name)?.to_str().ok()
+                });
+            let (session, progress) =
+                web::load(&*inner.store, &parts, &inner.settings.policy).await?;
+            let session = Session {
+                session: Rc::new(RefCell::new(session)),
+                progress: Rc::new(RefCell::new(progress)),
+                flusher: inner.clone(),
+            };
+            request.extensions_mut().insert(session.clone());
+
+            let mut response = service.call(request).await?;
+
+            let action = session.finish().await?;
+            if let Some(cookie) = web::set_cookie(&inner.settings.cookies, &action) {
+                let value = HeaderValue::from_str(&cookie).map_err(ErrorInternalServerError)?;
+                response.headers_mut().append(header::SET_COOKIE, value);
+            }
+            if inner.settings.expires_in_header {
+                let session_key = session.id();
+                let progress = session.progress.borrow().clone();
+                let timeout = inner.settings.policy.idle_timeout();
+                let expires_in = web::expires_in(&*inner.store, &session_key, &progress, timeout);
+                // The header is only a hint, so store errors leave it out.
+                if let Ok(Some(expires_in)) = expires_in.await {
+                    let value = HeaderValue::from(expires_in.as_secs());
+                    response
+                        .headers_mut()
+                        .insert(HeaderName::from_static(web::EXPIRES_IN_HEADER), value);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// A `POST` route for SPAs to keep the session alive: it touches the
+/// session, rotates the CSRF token when called with `?rotate_csrf=true`, and
+/// answers with the TTL the session now has as `{"expires_in": seconds}`.
+pub fn session_refresh_handler() -> Route {
+    actix_web::web::post().to(refresh)
+}
+
+async fn refresh(session: Session, request: HttpRequest) -> Result<HttpResponse, Error> {
+    let body = session
+        .refresh(Some(request.query_string()))
+        .map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
+
+/// Writes a [`Session`] to the store it was loaded from.
+trait Flush {
+    fn flush<'a>(
+        &'a self,
+        session: &'a mut crate::Session,
+        progress: &'a mut Progress,
+    ) -> LocalBoxFuture<'a, Result<(), SessionError>>;
+    fn timeout(&self) -> Duration;
+}
+
+impl<Store> Flush for Inner<Store>
+where
+    Store: Backend,
+{
+    fn flush<'a>(
+        &'a self,
+        session: &'a mut crate::Session,
+        progress: &'a mut Progress,
+    ) -> LocalBoxFuture<'a, Result<(), SessionError>> {
+        Box::pin(web::flush(
+            &*self.store,
+            session,
+            progress,
+            &self.settings.policy,
+        ))
+    }
+
+    fn timeout(&self) -> Duration {
+        self.settings.policy.idle_timeout()
+    }
+}
+
+/// The request's session. Changes are written once by
+/// [`SessionMiddleware`] after the handler returns, unless a handler calls
+/// [`Session::flush_now`].
+#[derive(Clone)]
+pub struct Session {
+    session: Rc<RefCell<crate::Session>>,
+    progress: Rc<RefCell<Progress>>,
+    flusher: Rc<dyn Flush>,
+}
+
+impl Session {
+    pub fn id(&self) -> SessionKey {
+        self.session.borrow().id().clone()
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        self.session.borrow().status()
+    }
+
+    /// Moves the session to a fresh key, e.g. after login.
+    pub fn regenerate(&self) {
+        self.session.borrow_mut().regenerate();
+    }
+
+    /// Clears the session and destroys it in the store, e.g. on logout.
+    pub fn purge(&self) {
+        self.session.borrow_mut().purge();
+    }
+
+    /// Makes this a "remember me" session, stored and kept by the browser
+    /// for `duration`.
+    pub fn persist_for(&self, duration: Duration) -> Result<(), StorageError> {
+        self.session.borrow_mut().persist_for(duration)
+    }
+
+    /// Writes the changes made so far instead of waiting for the response,
+    /// for handlers that need them durable mid-request. The response-time
+    /// write then only covers later changes. Other clones of the session
+    /// can read it meanwhile, but changes they make before this returns are
+    /// lost.
+    pub async fn flush_now(&self) -> Result<(), SessionError> {
+        self.flush().await
+    }
+
+    /// Touches the session so the response-time write extends its expiry,
+    /// rotating the CSRF token if `query` has `rotate_csrf=true`, and
+    /// returns the JSON body [`session_refresh_handler`] answers with.
+    pub fn refresh(&self, query: Option<&str>) -> Result<String, StorageError> {
+        web::refresh(
+            &mut self.session.borrow_mut(),
+            query,
+            self.flusher.timeout(),
+        )
+    }
+
+    async fn finish(&self) -> Result<CookieAction, SessionError> {
+        self.flush().await?;
+        Ok(self.progress.borrow().cookie().clone())
+    }
+
+    async fn flush(&self) -> Result<(), SessionError> {
+        if self.status() == SessionStatus::Unchanged {
+            return Ok(());
+        }
+        let copy = self.session.borrow().read_copy();
+        let mut session = self.session.replace(copy);
+        let mut progress = self.progress.borrow().clone();
+        let result = self.flusher.flush(&mut session, &mut progress).await;
+        self.session.replace(session);
+        self.progress.replace(progress);
+        result
+    }
+}
+
+impl<K: AsRef<str>> Storage<K> for Session {
+    type Error = StorageError;
+
+    fn insert<T: Serialize>(&mut self, key: K, value: &T) -> Result<(), Self::Error> {
+        self.session.borrow_mut().insert(key, value)
+    }
+
+    fn remove<T: DeserializeOwned>(&mut self, key: K) -> Result<Option<T>, Self::Error> {
+        self.session.borrow_mut().remove(key)
+    }
+
+    fn get<T: DeserializeOwned>(&self, key: K) -> Result<Option<T>, Self::Error> {
+        self.session.borrow().get(key)
+    }
+}
+
+impl FromRequest for Session {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let session = request
+            .extensions()
+            .get::<Session>()
+            .cloned()
+            .ok_or_else(|| ErrorInternalServerError("SessionMiddleware is not installed"));
+        ready(session)
+    }
+}
+
+impl ResponseError for SessionError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(retry_after) = web::retry_after(self) {
+            response.insert_header((header::RETRY_AFTER, retry_after));
+        }
+        response.body(self.code().as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        cookie::Cookie,
+        test::{call_service, init_service, try_call_service, TestRequest},
+        App,
+    };
+
+    use super::*;
+    use crate::{
+        session_store::testing::FaultyStore, Keyring, MemorySessionStore, PolicyOverride,
+        SessionPolicy,
+    };
+
+    async fn visit(mut session: Session) -> HttpResponse {
+        let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+        session.insert("visits", &visits).unwrap();
+        HttpResponse::Ok().body(visits.to_string())
+    }
+
+    fn app<Store>(
+        middleware: SessionMiddleware<Store>,
+    ) -> App<
+        impl actix_web::dev::ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = Error,
+            InitError = (),
+        >,
+    >
+    where
+        Store: Backend + 'static,
+    {
+        App::new()
+            .wrap(middleware)
+            .route("/visit", actix_web::web::get().to(visit))
+    }
+
+    fn session_cookie<B>(response: &ServiceResponse<B>) -> Cookie<'static> {
+        let cookie = response.response().cookies().next().unwrap();
+        cookie.into_owned()
+    }
+
+    #[tokio::test]
+    async fn handlers_extract_the_session_the_middleware_loaded() {
+        let middleware = SessionMiddleware::new(MemorySessionStore::new());
+        let service = init_service(app(middleware)).await;
+        let response = call_service(&service, TestRequest::get().uri("/visit").to_request()).await;
+        assert_eq!(session_cookie(&response).name(), "id");
+        assert_eq!(actix_web::test::read_body(response).await, "1");
+    }
+
+    #[tokio::test]
+    async fn forged_signatures_fail_the_request_as_tampered() {
+        let keyring = Keyring::new("1", b"secret");
+        let middleware = SessionMiddleware::new(MemorySessionStore::new()).with_keyring(keyring);
+        let service = init_service(app(middleware)).await;
+        let response = call_service(&service, TestRequest::get().uri("/visit").to_request()).await;
+        let signed = session_cookie(&response);
+
+        let (value, signature) = signed.value().rsplit_once('.').unwrap();
+        let flipped = if signature.starts_with('0') { "1" } else { "0" };
+        let forged = Cookie::new("id", format!("{value}.{flipped}{}", &signature[1..]));
+        let request = TestRequest::get().uri("/visit").cookie(forged);
+        let error = try_call_service(&service, request.to_request())
+            .await
+            .err()
+            .unwrap();
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        assert_eq!(body, "session.tampered");
+
+        let retired = Keyring::new("0", b"old").sign(SessionKey::generate().as_ref());
+        let request = TestRequest::get()
+            .uri("/visit")
+            .cookie(Cookie::new("id", retired));
+        let response = call_service(&service, request.to_request()).await;
+        assert_eq!(actix_web::test::read_body(response).await, "1");
+    }
+
+    #[tokio::test]
+    async fn other_handles_read_the_session_while_it_is_flushed() {
+        let store = FaultyStore::new().with_delay(Duration::from_millis(10));
+        let service = init_service(App::new().wrap(SessionMiddleware::new(store)).route(
+            "/",
+            actix_web::web::get().to(|mut session: Session| async move {
+                session.insert("user_id", &"beavis").unwrap();
+                let reader = session.clone();
+                let (flushed, read) = futures::join!(session.flush_now(), async {
+                    reader.get::<String>("user_id")
+                });
+                flushed?;
+                Ok::<_, Error>(HttpResponse::Ok().body(read.unwrap().unwrap_or_default()))
+            }),
+        ))
+        .await;
+
+        let response = call_service(&service, TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(actix_web::test::read_body(response).await, "beavis");
+    }
+
+    #[tokio::test]
+    async fn overrides_only_apply_to_the_scopes_they_wrap() {
+        let policy = SessionPolicy::builder()
+            .with_levels(&["password", "mfa"])
+            .build()
+            .unwrap();
+        let sessions = SessionMiddleware::new(MemorySessionStore::new()).with_policy(policy);
+        let checkout = sessions
+            .with_override(&PolicyOverride::new().require_level("mfa"))
+            .unwrap();
+        let app = App::new()
+            .service(
+                actix_web::web::scope("/checkout")
+                    .wrap(checkout)
+                    .route("", actix_web::web::get().to(visit)),
+            )
+            .service(
+                actix_web::web::scope("")
+                    .wrap(sessions)
+                    .route("/visit", actix_web::web::get().to(visit)),
+            );
+        let service = init_service(app).await;
+
+        let response = call_service(&service, TestRequest::get().uri("/visit").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let request = TestRequest::get().uri("/checkout").to_request();
+        let error = try_call_service(&service, request).await.err().unwrap();
+        assert_eq!(error.error_response().status(), StatusCode::FORBIDDEN);
+    }
+}
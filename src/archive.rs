@@ -0,0 +1,209 @@
+#[cfg(feature = "s3")]
+mod object_store_storage;
+
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    redaction::{BoxedRedactor, Redactor},
+    session_state::SessionState,
+    SessionKey,
+};
+
+#[cfg(feature = "s3")]
+pub use object_store_storage::ObjectStoreStorage;
+
+#[cfg(feature = "json")]
+const EXTENSION: &str = "jsonl";
+#[cfg(not(feature = "json"))]
+const EXTENSION: &str = "cbor-lines";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveReason {
+    Destroyed,
+    Expired,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveRecord {
+    pub session_key: SessionKey,
+    pub archived_at: SystemTime,
+    pub reason: ArchiveReason,
+    pub state: SessionState,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError<E> {
+    #[error("Unable to serialize archive record: {0}")]
+    SerializationError(#[from] CodecError),
+    #[error("Object storage error: {0}")]
+    StorageError(E),
+}
+
+#[async_trait::async_trait(?Send)]
+pub trait ObjectStorage {
+    type Error;
+
+    async fn put(&self, path: &str, body: Vec<u8>) -> Result<(), Self::Error>;
+}
+
+/// Writes archived sessions to object storage as JSONL files partitioned by hour,
+/// e.g. `sessions/dt=2022-08-01/hour=14/<random>.jsonl`. Built without the
+/// `json` feature, each line is a record in the crate's codec instead.
+pub struct Archiver<Storage> {
+    storage: Storage,
+    prefix: String,
+    redaction: Option<BoxedRedactor>,
+}
+
+impl<Storage: ObjectStorage> Archiver<Storage> {
+    pub fn new(storage: Storage) -> Self {
+        Self {
+            storage,
+            prefix: "sessions".to_string(),
+            redaction: None,
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_redaction(mut self, redaction: impl Redactor + Send + Sync + 'static) -> Self {
+        self.redaction = Some(Box::new(redaction));
+        self
+    }
+
+    pub fn record(
+        &self,
+        session_key: &SessionKey,
+        state: &SessionState,
+        reason: ArchiveReason,
+    ) -> ArchiveRecord {
+        let mut state = state.clone();
+        if let Some(redaction) = &self.redaction {
+            redaction.redact(&mut state);
+        }
+        ArchiveRecord {
+            session_key: session_key.clone(),
+            archived_at: SystemTime::now(),
+            reason,
+            state,
+        }
+    }
+
+    /// Writes `records` as a single JSONL object and returns its path.
+    pub async fn archive(
+        &self,
+        records: &[ArchiveRecord],
+    ) -> Result<Option<String>, ArchiveError<Storage::Error>> {
+        let first = match records.first() {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+        let mut body = Vec::new();
+        for record in records {
+            body.extend(ValueCodec::encode(record)?.into_bytes());
+            body.push(b'\n');
+        }
+        let path = self.path_for(first.archived_at);
+        self.storage
+            .put(&path, body)
+            .await
+            .map_err(ArchiveError::StorageError)?;
+        Ok(Some(path))
+    }
+
+    fn path_for(&self, archived_at: SystemTime) -> String {
+        let secs = archived_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+        let hour = (secs % 86_400) / 3_600;
+        let name = std::iter::repeat(())
+            .map(|()| OsRng.sample(Alphanumeric) as char)
+            .take(16)
+            .collect::<String>();
+        format!(
+            "{}/dt={:04}-{:02}-{:02}/hour={:02}/{}.{EXTENSION}",
+            self.prefix, year, month, day, hour, name
+        )
+    }
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{convert::Infallible, sync::Mutex, time::Duration};
+
+    #[derive(Default)]
+    struct RecordingStorage {
+        objects: Mutex<Vec<(String, Vec<u8>)>>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl ObjectStorage for RecordingStorage {
+        type Error = Infallible;
+
+        async fn put(&self, path: &str, body: Vec<u8>) -> Result<(), Self::Error> {
+            self.objects.lock().unwrap().push((path.to_string(), body));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn civil_from_days_converts_unix_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_205), (2022, 8, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+
+    #[tokio::test]
+    async fn archive_writes_redacted_records_as_partitioned_jsonl() {
+        let archiver = Archiver::new(RecordingStorage::default())
+            .with_prefix("archive/")
+            .with_redaction(|state: &mut SessionState| {
+                state.remove("password");
+            });
+        let mut state = SessionState::default();
+        state.insert("user_id", "\"beavis\"".to_string());
+        state.insert("password", "\"hunter2\"".to_string());
+        let mut record = archiver.record(&SessionKey::generate(), &state, ArchiveReason::Expired);
+        record.archived_at = UNIX_EPOCH + Duration::from_secs(19_205 * 86_400 + 14 * 3_600);
+
+        let path = archiver
+            .archive(&[record.clone(), record])
+            .await
+            .unwrap()
+            .expect("expected an object to be written");
+        assert!(path.starts_with("archive/dt=2022-08-01/hour=14/"));
+        assert!(path.ends_with(EXTENSION));
+
+        let objects = archiver.storage.objects.lock().unwrap();
+        let body = String::from_utf8(objects[0].1.clone()).unwrap();
+        let lines = body.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let decoded: ArchiveRecord = ValueCodec::decode(lines[0]).unwrap();
+        assert_eq!(decoded.reason, ArchiveReason::Expired);
+        assert_eq!(decoded.state.get("password"), None);
+        assert_eq!(decoded.state.get("user_id").unwrap(), "\"beavis\"");
+    }
+}
@@ -0,0 +1,25 @@
+use object_store::{path::Path, ObjectStore, ObjectStoreExt};
+
+use crate::archive::ObjectStorage;
+
+/// Adapts any [`object_store::ObjectStore`] (S3, GCS, Azure, local) for archival.
+pub struct ObjectStoreStorage<S> {
+    store: S,
+}
+
+impl<S: ObjectStore> ObjectStoreStorage<S> {
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<S: ObjectStore> ObjectStorage for ObjectStoreStorage<S> {
+    type Error = object_store::Error;
+
+    async fn put(&self, path: &str, body: Vec<u8>) -> Result<(), Self::Error> {
+        let location = Path::parse(path)?;
+        self.store.put(&location, body.into()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! axum integration: add [`SessionLayer`] to a router and take [`Session`]
+//! in handlers.
+//!
+//! ```ignore
+//! let app = Router::new()
+//!     .route("/", get(index))
+//!     .layer(SessionLayer::new(store));
+//!
+//! async fn index(mut session: Session) -> String {
+//!     let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+//!     session.insert("visits", &visits).unwrap();
+//!     format!("{visits} visits")
+//! }
+//! ```
+//!
+//! Routes that need a stricter policy, e.g. MFA for checkout, get their own
+//! layer from [`SessionLayer::with_override`]:
+//!
+//! ```ignore
+//! let sessions = SessionLayer::new(store).with_policy(policy);
+//! let mfa = sessions.with_override(&PolicyOverride::new().require_level("mfa"))?;
+//! let app = Router::new()
+//!     .route("/checkout", post(checkout))
+//!     .route_layer(mfa)
+//!     .merge(Router::new().route("/", get(index)).route_layer(sessions));
+//! ```
+//!
+//! The layer is the generic [`tower`](crate::tower) one.
+
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{post, MethodRouter},
+};
+
+use crate::{web, SessionError};
+
+pub use crate::tower::{Session, SessionLayer, SessionService};
+
+impl<S: Send + Sync> FromRequestParts<S> for Session {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<Session>().cloned().ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "SessionLayer is not installed",
+        ))
+    }
+}
+
+/// A `POST` route for SPAs to keep the session alive: it touches the
+/// session, rotates the CSRF token when called with `?rotate_csrf=true`, and
+/// answers with the TTL the session now has as `{"expires_in": seconds}`.
+pub fn session_refresh_handler<S: Clone + Send + Sync + 'static>() -> MethodRouter<S> {
+    post(refresh)
+}
+
+async fn refresh(session: Session, uri: Uri) -> Result<Response, SessionError> {
+    let body = session.refresh(uri.query())?;
+    Ok(([(header::CONTENT_TYPE, "application/json")], body).into_response())
+}
+
+impl IntoResponse for SessionError {
+    fn into_response(self) -> Response {
+        let status =
+            StatusCode::from_u16(self.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let retry_after =
+            web::retry_after(&self).map(|retry_after| [(header::RETRY_AFTER, retry_after)]);
+        (status, retry_after, self.code().as_str()).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{CookieSessionStore, JwtAlgorithm, JwtSessionStore, MemorySessionStore};
+
+    async fn visit(session: Session) -> String {
+        let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+        session.insert("visits", &visits).unwrap();
+        visits.to_string()
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn handlers_extract_the_session_the_layer_loaded() {
+        let app = Router::new()
+            .route("/", get(visit))
+            .layer(SessionLayer::new(MemorySessionStore::new()));
+        let response = app.oneshot(Request::new(Body::empty())).await.unwrap();
+        assert!(response.headers().contains_key(header::SET_COOKIE));
+        assert_eq!(body(response).await, "1");
+    }
+
+    #[tokio::test]
+    async fn extracting_a_session_without_the_layer_fails() {
+        let app = Router::new().route("/", get(visit));
+        let response = app.oneshot(Request::new(Body::empty())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body(response).await, "SessionLayer is not installed");
+    }
+
+    #[tokio::test]
+    async fn session_errors_answer_with_their_status_and_code() {
+        let response = SessionError::SessionTamperedError.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body(response).await, "session.tampered");
+    }
+
+    #[tokio::test]
+    async fn handlers_use_sessions_carried_by_client_side_stores() {
+        async fn visit_twice<Store>(store: Store)
+        where
+            Store: web::Backend + Send + Sync + 'static,
+        {
+            let app = Router::new()
+                .route("/", get(visit))
+                .layer(SessionLayer::new(store));
+            let response = app
+                .clone()
+                .oneshot(Request::new(Body::empty()))
+                .await
+                .unwrap();
+            let set_cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+            let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+            let request = Request::builder()
+                .header(header::COOKIE, cookie)
+                .body(Body::empty())
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(body(response).await, "2");
+        }
+
+        visit_twice(CookieSessionStore::new(&[7; 32])).await;
+        visit_twice(JwtSessionStore::new(JwtAlgorithm::Hs256, b"secret")).await;
+    }
+}
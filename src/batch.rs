@@ -0,0 +1,337 @@
+use std::{cell::Cell, fmt, future::Future, time::Duration};
+
+use futures::{stream, Stream, StreamExt};
+
+use crate::{Session, SessionKey, SessionStore};
+
+const DEFAULT_CONCURRENCY: usize = 16;
+const DEFAULT_IMPORT_RETRIES: u32 = 3;
+const DEFAULT_IMPORT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The outcome of a fan-out: every item either succeeded or failed with
+/// its own error, so one bad key does not abort the rest of the batch.
+#[derive(Debug)]
+pub struct BatchResult<T, E> {
+    pub ok: Vec<T>,
+    pub failed: Vec<(SessionKey, E)>,
+}
+
+impl<T, E> BatchResult<T, E> {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl<T, E> Default for BatchResult<T, E> {
+    fn default() -> Self {
+        Self {
+            ok: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+/// Runs `op` for every key with at most `limit` operations in flight,
+/// polling them all on the current task instead of spawning.
+pub async fn fan_out<T, E, F, Fut>(keys: &[SessionKey], limit: usize, op: F) -> BatchResult<T, E>
+where
+    F: Fn(SessionKey) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let op = &op;
+    stream::iter(keys.iter().cloned())
+        .map(|key| async move {
+            let result = op(key.clone()).await;
+            (key, result)
+        })
+        .buffer_unordered(limit.max(1))
+        .fold(
+            BatchResult::default(),
+            |mut batch, (key, result)| async move {
+                match result {
+                    Ok(value) => batch.ok.push(value),
+                    Err(error) => batch.failed.push((key, error)),
+                }
+                batch
+            },
+        )
+        .await
+}
+
+/// Bulk operations over a store with bounded concurrency.
+pub struct Batch<Store: SessionStore> {
+    store: Store,
+    concurrency: usize,
+}
+
+impl<Store: SessionStore> Batch<Store> {
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub async fn load_many(
+        &self,
+        keys: &[SessionKey],
+    ) -> BatchResult<(SessionKey, Option<Session>), Store::Error> {
+        fan_out(keys, self.concurrency, |key| async move {
+            let session = self.store.load(&key).await?;
+            Ok((key, session))
+        })
+        .await
+    }
+
+    pub async fn destroy_many(&self, keys: &[SessionKey]) -> BatchResult<SessionKey, Store::Error> {
+        fan_out(keys, self.concurrency, |key| async move {
+            self.store.destroy(&key).await?;
+            Ok(key)
+        })
+        .await
+    }
+
+    /// Loads each session, applies `update` and writes it back with its
+    /// remaining time to live. Sessions that no longer exist are skipped.
+    pub async fn update_many<F>(
+        &self,
+        keys: &[SessionKey],
+        update: F,
+    ) -> BatchResult<SessionKey, Store::Error>
+    where
+        F: Fn(&mut Session),
+    {
+        let update = &update;
+        let mut batch = fan_out(keys, self.concurrency, |key| async move {
+            let Some(mut session) = self.store.load(&key).await? else {
+                return Ok(None);
+            };
+            let ttl = self.store.ttl(&key).await?;
+            update(&mut session);
+            self.store
+                .update(&session, ttl.max(Duration::from_secs(1)))
+                .await?;
+            Ok(Some(key))
+        })
+        .await;
+        BatchResult {
+            ok: batch.ok.drain(..).flatten().collect(),
+            failed: batch.failed,
+        }
+    }
+}
+
+/// How far an import has got.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportProgress {
+    pub imported: usize,
+    pub failed: usize,
+    /// Saves that failed and were tried again.
+    pub retries: usize,
+}
+
+/// The final progress of an import and the sessions that could not be saved
+/// after every retry.
+#[derive(Debug)]
+pub struct ImportReport<E> {
+    pub progress: ImportProgress,
+    pub failed: Vec<(SessionKey, E)>,
+}
+
+impl<E> ImportReport<E> {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+type ReportProgress = Box<dyn Fn(&ImportProgress)>;
+
+/// Limits and reporting for [`SessionStore::import_stream`].
+pub struct ImportOptions {
+    concurrency: usize,
+    retries: u32,
+    backoff: Duration,
+    progress: Option<ReportProgress>,
+}
+
+impl ImportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Saves at most `concurrency` sessions at once; the stream is read no
+    /// faster than they complete.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Tries a failed save up to `retries` more times, waiting `backoff`
+    /// longer before each attempt.
+    pub fn with_retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// Calls `report` after every session is imported or given up on.
+    pub fn with_progress(mut self, report: impl Fn(&ImportProgress) + 'static) -> Self {
+        self.progress = Some(Box::new(report));
+        self
+    }
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            retries: DEFAULT_IMPORT_RETRIES,
+            backoff: DEFAULT_IMPORT_BACKOFF,
+            progress: None,
+        }
+    }
+}
+
+impl fmt::Debug for ImportOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImportOptions")
+            .field("concurrency", &self.concurrency)
+            .field("retries", &self.retries)
+            .field("backoff", &self.backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+pub(crate) async fn import<Store, Sessions>(
+    store: &Store,
+    sessions: Sessions,
+    options: &ImportOptions,
+) -> ImportReport<Store::Error>
+where
+    Store: SessionStore + ?Sized,
+    Sessions: Stream<Item = (Session, Duration)>,
+{
+    let retries = &Cell::new(0);
+    let save = |(session, timeout): (Session, Duration)| async move {
+        let mut attempt = 0;
+        loop {
+            match store.save(&session, timeout).await {
+                Ok(()) => return (session.id().clone(), Ok(())),
+                Err(error) if attempt == options.retries => {
+                    return (session.id().clone(), Err(error))
+                }
+                Err(_) => {
+                    attempt += 1;
+                    retries.set(retries.get() + 1);
+                    tokio::time::sleep(options.backoff * attempt).await;
+                }
+            }
+        }
+    };
+    let mut report = sessions
+        .map(save)
+        .buffer_unordered(options.concurrency)
+        .fold(
+            ImportReport {
+                progress: ImportProgress::default(),
+                failed: Vec::new(),
+            },
+            |mut report, (session_key, result)| async move {
+                match result {
+                    Ok(()) => report.progress.imported += 1,
+                    Err(error) => {
+                        report.progress.failed += 1;
+                        report.failed.push((session_key, error));
+                    }
+                }
+                report.progress.retries = retries.get();
+                if let Some(progress) = &options.progress {
+                    progress(&report.progress);
+                }
+                report
+            },
+        )
+        .await;
+    report.progress.retries = retries.get();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashSet, rc::Rc, sync::Mutex};
+
+    use super::*;
+    use crate::session_store::{testing::FaultyStore, StoreOperation};
+
+    #[tokio::test]
+    async fn fan_out_bounds_concurrency_and_reports_partial_failures() {
+        let keys = (0..10).map(|_| SessionKey::generate()).collect::<Vec<_>>();
+        let poison = keys[3].clone();
+        let in_flight = Cell::new(0);
+        let peak = Cell::new(0);
+
+        let batch = fan_out(&keys, 3, |key| {
+            let poison = &poison;
+            let in_flight = &in_flight;
+            let peak = &peak;
+            async move {
+                in_flight.set(in_flight.get() + 1);
+                peak.set(peak.get().max(in_flight.get()));
+                tokio::task::yield_now().await;
+                in_flight.set(in_flight.get() - 1);
+                if &key == poison {
+                    Err("poisoned")
+                } else {
+                    Ok(key)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(peak.get(), 3);
+        assert_eq!(batch.ok.len(), 9);
+        assert_eq!(batch.failed.len(), 1);
+        assert_eq!(batch.failed[0].0, poison);
+        assert!(!batch.is_complete());
+    }
+
+    /// Fails the first save of every session, and every save of `poison`.
+    fn flaky(poison: SessionKey) -> FaultyStore {
+        let attempted = Mutex::new(HashSet::new());
+        FaultyStore::new().with_failures(move |operation, session_key| {
+            matches!(operation, StoreOperation::Save | StoreOperation::Update)
+                && (attempted.lock().unwrap().insert(session_key.clone()) || session_key == &poison)
+        })
+    }
+
+    #[tokio::test]
+    async fn import_stream_retries_failed_saves_and_reports_progress() {
+        let sessions = (0..5).map(|_| Session::default()).collect::<Vec<_>>();
+        let poison = sessions[0].id().clone();
+        let store = flaky(poison.clone());
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let options = ImportOptions::new()
+            .with_concurrency(2)
+            .with_retries(1, Duration::from_millis(1))
+            .with_progress({
+                let reports = reports.clone();
+                move |progress| reports.borrow_mut().push(*progress)
+            });
+
+        let timeout = Duration::from_secs(60);
+        let stream = stream::iter(sessions.into_iter().map(|session| (session, timeout)));
+        let report = store.import_stream(stream, &options).await;
+
+        assert_eq!(report.progress.imported, 4);
+        assert_eq!(report.progress.failed, 1);
+        assert_eq!(report.progress.retries, 5);
+        assert_eq!(report.failed[0].0, poison);
+        assert_eq!(reports.borrow().len(), 5);
+        assert_eq!(store.inner().len(), 4);
+    }
+}
@@ -0,0 +1,43 @@
+#[cfg(feature = "nats")]
+mod nats_broadcast;
+mod redis_broadcast;
+
+use futures::stream::LocalBoxStream;
+use serde::{Deserialize, Serialize};
+
+use crate::SessionKey;
+
+#[cfg(feature = "nats")]
+pub use nats_broadcast::{NatsBroadcast, NatsBroadcastError};
+pub use redis_broadcast::{RedisBroadcast, RedisBroadcastError};
+
+/// Tells other nodes to drop what they cache of a session.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invalidation {
+    pub session_key: SessionKey,
+}
+
+/// Tells other nodes to reject a session for good, including stateless
+/// sessions that no store holds; see [`RevocationFilter`](crate::RevocationFilter).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revocation {
+    pub session_key: SessionKey,
+}
+
+/// What travels on a [`Broadcast`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BroadcastMessage {
+    Invalidation(Invalidation),
+    Revocation(Revocation),
+}
+
+/// Fans session invalidations and revocations out to every node in the
+/// fleet.
+#[async_trait::async_trait(?Send)]
+pub trait Broadcast {
+    type Error;
+
+    async fn publish(&self, message: &BroadcastMessage) -> Result<(), Self::Error>;
+    async fn subscribe(&self) -> Result<LocalBoxStream<'static, BroadcastMessage>, Self::Error>;
+}
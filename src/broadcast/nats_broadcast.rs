@@ -0,0 +1,68 @@
+use futures::{stream::LocalBoxStream, StreamExt};
+
+use crate::{
+    broadcast::{Broadcast, BroadcastMessage},
+    codec::{Codec, CodecError, ValueCodec},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum NatsBroadcastError {
+    #[error("NATS connection error: {0}")]
+    ConnectionError(String),
+    #[error("NATS publish error: {0}")]
+    PublishError(String),
+    #[error("NATS subscribe error: {0}")]
+    SubscribeError(String),
+    #[error("Unable to serialize broadcast message: {0}")]
+    SerializationError(#[from] CodecError),
+}
+
+pub struct NatsBroadcast {
+    subject: String,
+    client: async_nats::Client,
+}
+
+impl NatsBroadcast {
+    pub async fn new(url: &str, subject: &str) -> Result<Self, NatsBroadcastError> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(NatsBroadcastError::ConnectionError)?;
+        Ok(Self::from_client(client, subject))
+    }
+
+    pub fn from_client(client: async_nats::Client, subject: &str) -> Self {
+        Self {
+            subject: subject.to_owned(),
+            client,
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Broadcast for NatsBroadcast {
+    type Error = NatsBroadcastError;
+
+    async fn publish(&self, message: &BroadcastMessage) -> Result<(), Self::Error> {
+        let body = ValueCodec::encode(message)?.into_bytes();
+        self.client
+            .publish(self.subject.clone(), body.into())
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(NatsBroadcastError::PublishError)
+    }
+
+    async fn subscribe(&self) -> Result<LocalBoxStream<'static, BroadcastMessage>, Self::Error> {
+        let subscriber = self
+            .client
+            .subscribe(self.subject.clone())
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(NatsBroadcastError::SubscribeError)?;
+        let messages = subscriber.filter_map(|message| async move {
+            let body = std::str::from_utf8(&message.payload).ok()?;
+            ValueCodec::decode(body).ok()
+        });
+        Ok(messages.boxed_local())
+    }
+}
@@ -0,0 +1,76 @@
+use futures::{stream::LocalBoxStream, StreamExt};
+use redis::aio::ConnectionManager;
+
+use crate::{
+    broadcast::{Broadcast, BroadcastMessage},
+    codec::{Codec, CodecError, ValueCodec},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RedisBroadcastError {
+    #[error("Redis connection error: {0}")]
+    ConnectionError(String),
+    #[error("Redis query error: {0}")]
+    QueryError(String),
+    #[error("Unable to serialize broadcast message: {0}")]
+    SerializationError(#[from] CodecError),
+}
+
+pub struct RedisBroadcast {
+    channel: String,
+    client: redis::Client,
+    connection: ConnectionManager,
+}
+
+impl RedisBroadcast {
+    pub async fn new(url: &str, channel: &str) -> Result<Self, RedisBroadcastError> {
+        let client = redis::Client::open(url)
+            .map_err(|e| e.to_string())
+            .map_err(RedisBroadcastError::ConnectionError)?;
+        let connection = ConnectionManager::new(client.clone())
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(RedisBroadcastError::ConnectionError)?;
+        Ok(Self {
+            channel: channel.to_owned(),
+            client,
+            connection,
+        })
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Broadcast for RedisBroadcast {
+    type Error = RedisBroadcastError;
+
+    async fn publish(&self, message: &BroadcastMessage) -> Result<(), Self::Error> {
+        let body = ValueCodec::encode(message)?;
+        redis::cmd("PUBLISH")
+            .arg(&self.channel)
+            .arg(body)
+            .query_async::<_, u64>(&mut self.connection.clone())
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(RedisBroadcastError::QueryError)?;
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<LocalBoxStream<'static, BroadcastMessage>, Self::Error> {
+        let mut pubsub = self
+            .client
+            .get_async_pubsub()
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(RedisBroadcastError::ConnectionError)?;
+        pubsub
+            .subscribe(&self.channel)
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(RedisBroadcastError::QueryError)?;
+        let messages = pubsub.into_on_message().filter_map(|message| async move {
+            let body = message.get_payload::<String>().ok()?;
+            ValueCodec::decode(&body).ok()
+        });
+        Ok(messages.boxed_local())
+    }
+}
@@ -0,0 +1,220 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    hash::fnv1a,
+    storage::{Storage, StorageError, StorageGetError, StorageInsertError},
+    Session,
+};
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    chunks: Vec<ChunkDigest>,
+    hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChunkDigest {
+    len: usize,
+    checksum: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Chunk {
+    index: usize,
+    data: String,
+}
+
+/// What [`Session::verify_chunked`] found wrong with a chunked value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    pub key: String,
+    pub missing: Vec<usize>,
+    pub corrupted: Vec<usize>,
+    pub misplaced: Vec<usize>,
+    pub manifest_mismatch: bool,
+}
+
+impl RepairReport {
+    pub fn is_intact(&self) -> bool {
+        self.missing.is_empty()
+            && self.corrupted.is_empty()
+            && self.misplaced.is_empty()
+            && !self.manifest_mismatch
+    }
+}
+
+fn checksum(data: &str) -> String {
+    format!("{:016x}", fnv1a(&[data.as_bytes()]))
+}
+
+fn chunk_key(key: &str, index: usize) -> String {
+    format!("{key}:chunk:{index}")
+}
+
+fn split(value: &str, chunk_size: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = value;
+    while !rest.is_empty() {
+        let mut end = chunk_size.max(1).min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end += 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+impl Session {
+    /// Stores `value` under `key` as a manifest plus chunks of at most
+    /// `chunk_size` bytes, each with its own checksum.
+    pub fn insert_chunked<T: Serialize>(
+        &mut self,
+        key: &str,
+        value: &T,
+        chunk_size: usize,
+    ) -> Result<(), StorageError> {
+        let encoded = ValueCodec::encode(value)
+            .map_err(|e| StorageInsertError::SerializeError(key.to_string(), e.to_string()))?;
+        self.remove_chunked(key)?;
+        let chunks = split(&encoded, chunk_size);
+        let manifest = Manifest {
+            chunks: chunks
+                .iter()
+                .map(|data| ChunkDigest {
+                    len: data.len(),
+                    checksum: checksum(data),
+                })
+                .collect(),
+            hash: checksum(&encoded),
+        };
+        for (index, data) in chunks.into_iter().enumerate() {
+            let chunk = Chunk {
+                index,
+                data: data.to_string(),
+            };
+            self.insert(chunk_key(key, index), &chunk)?;
+        }
+        self.insert(key, &manifest)
+    }
+
+    /// Reassembles a chunked value, failing rather than returning data that
+    /// does not match its manifest.
+    pub fn get_chunked<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, StorageError> {
+        let Some(manifest) = self.get::<Manifest>(key)? else {
+            return Ok(None);
+        };
+        let report = self.inspect(key, &manifest)?;
+        if !report.is_intact() {
+            return Err(
+                StorageGetError::CorruptedError(key.to_string(), format!("{report:?}")).into(),
+            );
+        }
+        let mut encoded = String::new();
+        for index in 0..manifest.chunks.len() {
+            if let Some(chunk) = self.get::<Chunk>(chunk_key(key, index))? {
+                encoded.push_str(&chunk.data);
+            }
+        }
+        ValueCodec::decode(&encoded)
+            .map(Some)
+            .map_err(|e| StorageGetError::DeserializeError(key.to_string(), e.to_string()))
+            .map_err(StorageError::from)
+    }
+
+    pub fn verify_chunked(&self, key: &str) -> Result<Option<RepairReport>, StorageError> {
+        match self.get::<Manifest>(key)? {
+            Some(manifest) => self.inspect(key, &manifest).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn remove_chunked(&mut self, key: &str) -> Result<(), StorageError> {
+        if let Some(manifest) = self.remove::<Manifest>(key)? {
+            for index in 0..manifest.chunks.len() {
+                self.remove::<Chunk>(chunk_key(key, index))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn inspect(&self, key: &str, manifest: &Manifest) -> Result<RepairReport, StorageError> {
+        let mut report = RepairReport {
+            key: key.to_string(),
+            ..Default::default()
+        };
+        let mut assembled = String::new();
+        for (index, digest) in manifest.chunks.iter().enumerate() {
+            // Unreadable chunks are reported rather than failing verification.
+            let chunk = self.get::<Chunk>(chunk_key(key, index)).ok().flatten();
+            match chunk {
+                None => report.missing.push(index),
+                Some(chunk) if chunk.index != index => report.misplaced.push(index),
+                Some(chunk)
+                    if chunk.data.len() != digest.len
+                        || checksum(&chunk.data) != digest.checksum =>
+                {
+                    report.corrupted.push(index)
+                }
+                Some(chunk) => assembled.push_str(&chunk.data),
+            }
+        }
+        report.manifest_mismatch = report.missing.is_empty()
+            && report.corrupted.is_empty()
+            && report.misplaced.is_empty()
+            && checksum(&assembled) != manifest.hash;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        let mut session = Session::default();
+        let cart = (0..50).map(|n| format!("item-{n}")).collect::<Vec<_>>();
+        session.insert_chunked("cart", &cart, 64).unwrap();
+        session
+    }
+
+    #[test]
+    fn chunked_values_round_trip_in_order() {
+        let session = session();
+        let cart = session.get_chunked::<Vec<String>>("cart").unwrap().unwrap();
+        assert_eq!(cart.len(), 50);
+        assert_eq!(cart[49], "item-49");
+        assert!(session.verify_chunked("cart").unwrap().unwrap().is_intact());
+    }
+
+    #[test]
+    fn missing_and_corrupted_chunks_are_reported_and_not_returned() {
+        let mut session = session();
+        session.remove::<Chunk>(chunk_key("cart", 1)).unwrap();
+        let corrupted = Chunk {
+            index: 2,
+            data: "garbage".to_string(),
+        };
+        session.insert(chunk_key("cart", 2), &corrupted).unwrap();
+        let swapped = session.get::<Chunk>(chunk_key("cart", 3)).unwrap().unwrap();
+        session.insert(chunk_key("cart", 4), &swapped).unwrap();
+
+        let report = session.verify_chunked("cart").unwrap().unwrap();
+        assert_eq!(report.missing, vec![1]);
+        assert_eq!(report.corrupted, vec![2]);
+        assert_eq!(report.misplaced, vec![4]);
+        assert!(matches!(
+            session.get_chunked::<Vec<String>>("cart"),
+            Err(StorageError::StorageGetError(
+                StorageGetError::CorruptedError(..)
+            ))
+        ));
+    }
+
+    #[test]
+    fn split_keeps_multibyte_characters_whole() {
+        assert_eq!(split("aé b", 2), vec!["a\u{e9}", " b"]);
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Encodes session values to and from the text stored in a `SessionState`.
+pub trait Codec {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    fn encode<T: Serialize>(value: &T) -> Result<String, Self::Error>;
+    fn decode<T: DeserializeOwned>(value: &str) -> Result<T, Self::Error>;
+}
+
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl Codec for JsonCodec {
+    type Error = serde_json::Error;
+
+    fn encode<T: Serialize>(value: &T) -> Result<String, Self::Error> {
+        serde_json::to_string(value)
+    }
+
+    fn decode<T: DeserializeOwned>(value: &str) -> Result<T, Self::Error> {
+        serde_json::from_str(value)
+    }
+}
+
+#[cfg(feature = "cbor")]
+#[derive(Debug, thiserror::Error)]
+pub enum CborError {
+    #[error("Could not encode CBOR: {0}")]
+    EncodeError(String),
+    #[error("Could not decode CBOR: {0}")]
+    DecodeError(String),
+}
+
+/// CBOR, written as unpadded base64url so it fits wherever text is stored.
+#[cfg(feature = "cbor")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CborCodec;
+
+#[cfg(feature = "cbor")]
+impl Codec for CborCodec {
+    type Error = CborError;
+
+    fn encode<T: Serialize>(value: &T) -> Result<String, Self::Error> {
+        let mut body = Vec::new();
+        ciborium::into_writer(value, &mut body)
+            .map_err(|error| CborError::EncodeError(error.to_string()))?;
+        Ok(crate::session_store::encode_base64_url(&body))
+    }
+
+    fn decode<T: DeserializeOwned>(value: &str) -> Result<T, Self::Error> {
+        let body = crate::session_store::decode_base64_url(value)
+            .ok_or_else(|| CborError::DecodeError("not base64url".to_string()))?;
+        ciborium::from_reader(body.as_slice())
+            .map_err(|error| CborError::DecodeError(error.to_string()))
+    }
+}
+
+#[cfg(not(any(feature = "json", feature = "cbor")))]
+compile_error!("lushus-session needs a codec: enable the `json` or `cbor` feature");
+
+/// The codec every `Storage` implementation and session store in this crate
+/// encodes values and session state with: JSON, or CBOR when built without
+/// the `json` feature.
+#[cfg(feature = "json")]
+pub(crate) type ValueCodec = JsonCodec;
+#[cfg(all(feature = "cbor", not(feature = "json")))]
+pub(crate) type ValueCodec = CborCodec;
+
+/// What encoding or decoding a value or session state fails with.
+pub type CodecError = <ValueCodec as Codec>::Error;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Cart {
+        items: Vec<String>,
+        total: u64,
+    }
+
+    fn round_trips<C: Codec>() {
+        let cart = Cart {
+            items: vec!["book".to_string()],
+            total: 12,
+        };
+        let encoded = C::encode(&cart).unwrap();
+        assert_eq!(C::decode::<Cart>(&encoded).unwrap(), cart);
+        assert!(C::decode::<Cart>("not an encoded cart").is_err());
+
+        let state = HashMap::from([("cart".to_string(), encoded)]);
+        let decoded = C::decode::<HashMap<String, String>>(&C::encode(&state).unwrap());
+        assert_eq!(decoded.unwrap(), state);
+    }
+
+    #[test]
+    fn value_codec_round_trips_values_and_state() {
+        round_trips::<ValueCodec>();
+        #[cfg(feature = "json")]
+        round_trips::<JsonCodec>();
+        #[cfg(feature = "cbor")]
+        round_trips::<CborCodec>();
+    }
+}
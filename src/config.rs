@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use crate::{session::KeyDefaults, session_store::KeyFormat};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Session timeout must be at least one second, got {0:?}")]
+    TimeoutError(Duration),
+    #[error("Absolute timeout {absolute:?} is shorter than the idle timeout {idle:?}")]
+    AbsoluteTimeoutError { idle: Duration, absolute: Duration },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionConfig {
+    timeout: Duration,
+    absolute_timeout: Option<Duration>,
+    defaults: KeyDefaults,
+    device_header: Option<String>,
+    locales: Vec<String>,
+    store_deadline: Option<Duration>,
+    fail_open: bool,
+    key_format: KeyFormat,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(24 * 60 * 60),
+            absolute_timeout: None,
+            defaults: KeyDefaults::default(),
+            device_header: None,
+            locales: Vec::new(),
+            store_deadline: None,
+            fail_open: false,
+            key_format: KeyFormat::default(),
+        }
+    }
+}
+
+impl SessionConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long an idle session lives; stores expire at whole seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The longest a session may live regardless of activity.
+    pub fn with_absolute_timeout(mut self, absolute_timeout: Duration) -> Self {
+        self.absolute_timeout = Some(absolute_timeout);
+        self
+    }
+
+    /// Defaults the web integrations install on every session they load.
+    pub fn with_defaults(mut self, defaults: KeyDefaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Scopes each session to the device named by this request header, so
+    /// one logical login keeps separate state per device. See
+    /// [`SessionKey::for_device`](crate::SessionKey::for_device).
+    pub fn with_device_header(mut self, name: &str) -> Self {
+        self.device_header = Some(name.to_ascii_lowercase());
+        self
+    }
+
+    /// The locales the app supports, preferred first. The web integrations
+    /// negotiate one from `Accept-Language` for sessions that have no
+    /// locale yet; see [`Session::negotiate_locale`](crate::Session::negotiate_locale).
+    pub fn with_locales(mut self, locales: &[&str]) -> Self {
+        self.locales = locales.iter().map(|locale| locale.to_string()).collect();
+        self
+    }
+
+    /// Bounds the time the web integrations spend loading a request's
+    /// session to `budget`, failing the request with a
+    /// [`StoreUnavailableError`](crate::SessionError::StoreUnavailableError)
+    /// once it runs out, unless the config fails open.
+    pub fn with_store_deadline(mut self, budget: Duration) -> Self {
+        self.store_deadline = Some(budget);
+        self
+    }
+
+    /// Lets requests continue with a fresh session when the store cannot
+    /// load theirs within the [store deadline](Self::with_store_deadline).
+    pub fn with_fail_open(mut self, fail_open: bool) -> Self {
+        self.fail_open = fail_open;
+        self
+    }
+
+    /// The format the web integrations generate session keys in and that
+    /// cookies must match to be looked up, e.g. 32 base64url bytes for
+    /// shorter cookies.
+    pub fn with_key_format(mut self, key_format: KeyFormat) -> Self {
+        self.key_format = key_format;
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn absolute_timeout(&self) -> Option<Duration> {
+        self.absolute_timeout
+    }
+
+    pub fn defaults(&self) -> &KeyDefaults {
+        &self.defaults
+    }
+
+    pub fn device_header(&self) -> Option<&str> {
+        self.device_header.as_deref()
+    }
+
+    pub fn locales(&self) -> &[String] {
+        &self.locales
+    }
+
+    pub fn store_deadline(&self) -> Option<Duration> {
+        self.store_deadline
+    }
+
+    pub fn fail_open(&self) -> bool {
+        self.fail_open
+    }
+
+    pub fn key_format(&self) -> KeyFormat {
+        self.key_format
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.timeout < Duration::from_secs(1) {
+            return Err(ConfigError::TimeoutError(self.timeout));
+        }
+        match self.absolute_timeout {
+            Some(absolute) if absolute < self.timeout => Err(ConfigError::AbsoluteTimeoutError {
+                idle: self.timeout,
+                absolute,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_timeouts_stores_cannot_expire() {
+        assert!(SessionConfig::new().validate().is_ok());
+        let config = SessionConfig::new().with_timeout(Duration::from_millis(500));
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::TimeoutError(_))
+        ));
+    }
+
+    #[test]
+    fn validate_rejects_an_absolute_timeout_shorter_than_the_idle_timeout() {
+        let config = SessionConfig::new()
+            .with_timeout(Duration::from_secs(60))
+            .with_absolute_timeout(Duration::from_secs(30));
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::AbsoluteTimeoutError { .. })
+        ));
+    }
+}
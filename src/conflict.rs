@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    crdt::Crdt,
+    session_state::SessionState,
+};
+
+pub trait ConflictResolver {
+    /// Merges a `local` write with the `remote` state it raced against.
+    fn resolve(&self, local: &SessionState, remote: &SessionState) -> SessionState;
+}
+
+impl<R: ConflictResolver> ConflictResolver for &R {
+    fn resolve(&self, local: &SessionState, remote: &SessionState) -> SessionState {
+        <R as ConflictResolver>::resolve(self, local, remote)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct LastWriteWins;
+
+impl ConflictResolver for LastWriteWins {
+    fn resolve(&self, local: &SessionState, _remote: &SessionState) -> SessionState {
+        local.clone()
+    }
+}
+
+type Merge = Box<dyn Fn(&str, &str) -> Option<String> + Send + Sync>;
+
+/// Merges registered keys as CRDT values; every other key is last-write-wins.
+#[derive(Default)]
+pub struct CrdtResolver {
+    merges: HashMap<String, Merge>,
+}
+
+impl CrdtResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Crdt>(mut self, key: &str) -> Self {
+        let merge = |local: &str, remote: &str| {
+            let mut local = ValueCodec::decode::<T>(local).ok()?;
+            let remote = ValueCodec::decode::<T>(remote).ok()?;
+            local.merge(&remote);
+            ValueCodec::encode(&local).ok()
+        };
+        self.merges.insert(key.to_string(), Box::new(merge));
+        self
+    }
+}
+
+impl ConflictResolver for CrdtResolver {
+    fn resolve(&self, local: &SessionState, remote: &SessionState) -> SessionState {
+        let mut resolved = local.clone();
+        for (key, merge) in &self.merges {
+            match (local.get(key), remote.get(key)) {
+                (Some(local_value), Some(remote_value)) => {
+                    if let Some(merged) = merge(local_value, remote_value) {
+                        resolved.insert(key, merged);
+                    }
+                }
+                (None, Some(remote_value)) => resolved.insert(key, remote_value.clone()),
+                _ => {}
+            }
+        }
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::GCounter;
+
+    #[test]
+    fn crdt_resolver_merges_registered_keys() {
+        let mut local_counter = GCounter::default();
+        local_counter.increment("eu", 2);
+        let mut remote_counter = GCounter::default();
+        remote_counter.increment("us", 5);
+
+        let mut local = SessionState::default();
+        local.insert("views", ValueCodec::encode(&local_counter).unwrap());
+        local.insert("theme", "\"dark\"".to_string());
+        let mut remote = SessionState::default();
+        remote.insert("views", ValueCodec::encode(&remote_counter).unwrap());
+        remote.insert("theme", "\"light\"".to_string());
+
+        let resolver = CrdtResolver::new().register::<GCounter>("views");
+        let resolved = resolver.resolve(&local, &remote);
+
+        let views: GCounter = ValueCodec::decode(resolved.get("views").unwrap()).unwrap();
+        assert_eq!(views.value(), 7);
+        assert_eq!(resolved.get("theme").unwrap(), "\"dark\"");
+    }
+}
@@ -0,0 +1,189 @@
+use std::{future::Future, pin::pin, time::Duration};
+
+use futures::future::{self, Either};
+
+use crate::{
+    shared_session::SharedSession, Session, SessionError, SessionKey, SessionStatus, SessionStore,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectionSessionError<S> {
+    #[error("Session store error: {0}")]
+    StoreError(S),
+    #[error(transparent)]
+    SessionError(#[from] SessionError),
+}
+
+/// A session held for the lifetime of a long-lived connection, such as a
+/// WebSocket, which no per-request middleware touches once the upgrade is
+/// done.
+///
+/// Open it while handling the upgrade, hand [`session`](Self::session) to
+/// the connection and drive the connection through
+/// [`keep_alive`](Self::keep_alive), which rewrites the session often
+/// enough that it never expires mid-connection, then [`close`](Self::close)
+/// it to write the final changes.
+pub struct ConnectionSession<Store> {
+    store: Store,
+    session: SharedSession,
+    timeout: Duration,
+}
+
+impl<Store: SessionStore> ConnectionSession<Store> {
+    /// Loads the session for the connection, returning `None` if it does
+    /// not exist and the upgrade should be refused.
+    pub async fn open(
+        store: Store,
+        session_key: &SessionKey,
+        timeout: Duration,
+    ) -> Result<Option<Self>, Store::Error> {
+        let connection = store.load(session_key).await?.map(|session| Self {
+            store,
+            session: SharedSession::new(session),
+            timeout,
+        });
+        Ok(connection)
+    }
+
+    pub fn session(&self) -> SharedSession {
+        self.session.clone()
+    }
+
+    /// How often [`keep_alive`](Self::keep_alive) refreshes: half the
+    /// timeout, so one slow refresh does not let the session lapse.
+    pub fn refresh_interval(&self) -> Duration {
+        self.timeout / 2
+    }
+
+    /// Writes the session as it is now, extending its expiry. A session
+    /// regenerated since the last write is saved under its new key and the
+    /// old key is destroyed.
+    pub async fn refresh(&self) -> Result<(), ConnectionSessionError<Store::Error>> {
+        let (current, previous) = self.session.read(|session| {
+            let current = Session::new(session.id().clone(), session.state().clone());
+            (current, session.regenerated_from())
+        })?;
+        let timeout = current.duration().ttl(self.timeout);
+        let Some(previous) = previous else {
+            return self
+                .store
+                .update(&current, timeout)
+                .await
+                .map_err(ConnectionSessionError::StoreError);
+        };
+        self.store
+            .save(&current, timeout)
+            .await
+            .map_err(ConnectionSessionError::StoreError)?;
+        self.store
+            .destroy(&previous)
+            .await
+            .map_err(ConnectionSessionError::StoreError)?;
+        self.session
+            .read(|session| session.take_regenerated_from())?;
+        Ok(())
+    }
+
+    /// Runs `connection` to completion, refreshing the session every
+    /// [`refresh_interval`](Self::refresh_interval). A failed refresh drops
+    /// the connection and returns the error.
+    pub async fn keep_alive<F: Future>(
+        &self,
+        connection: F,
+    ) -> Result<F::Output, ConnectionSessionError<Store::Error>> {
+        let mut ticks = tokio::time::interval(self.refresh_interval());
+        ticks.tick().await;
+        let mut connection = pin!(connection);
+        loop {
+            match future::select(connection, pin!(ticks.tick())).await {
+                Either::Left((output, _)) => return Ok(output),
+                Either::Right((_, pending)) => {
+                    connection = pending;
+                    self.refresh().await?;
+                }
+            }
+        }
+    }
+
+    /// Writes the final changes, or destroys the session if it was purged.
+    pub async fn close(self) -> Result<(), ConnectionSessionError<Store::Error>> {
+        let purged = self
+            .session
+            .read(|session| session.status() == SessionStatus::Purged)?;
+        if purged {
+            let session_key = self.session.read(|session| {
+                session
+                    .regenerated_from()
+                    .unwrap_or_else(|| session.id().clone())
+            })?;
+            return self
+                .store
+                .destroy(&session_key)
+                .await
+                .map_err(ConnectionSessionError::StoreError);
+        }
+        self.refresh().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        storage::Storage, MemorySessionStore, MetricsSessionStore, StoreMetrics, StoreOperation,
+    };
+
+    const TIMEOUT: Duration = Duration::from_millis(40);
+
+    #[tokio::test]
+    async fn keep_alive_refreshes_until_the_connection_closes() {
+        let inner = MemorySessionStore::new();
+        let session = Session::default();
+        let session_key = session.id().clone();
+        inner.save(&session, TIMEOUT).await.unwrap();
+
+        let metrics = StoreMetrics::new();
+        let store = MetricsSessionStore::new(&inner, metrics.clone());
+        let connection = ConnectionSession::open(&store, &session_key, TIMEOUT)
+            .await
+            .unwrap()
+            .unwrap();
+        let shared = connection.session();
+        connection
+            .keep_alive(async {
+                let mut writes = shared.write();
+                writes.insert("messages", &3).unwrap();
+                writes.commit().unwrap();
+                tokio::time::sleep(Duration::from_millis(70)).await;
+            })
+            .await
+            .unwrap();
+        assert!(metrics.snapshot()[&StoreOperation::Update].calls >= 2);
+
+        connection.close().await.unwrap();
+        let stored = inner.load(&session_key).await.unwrap().unwrap();
+        assert_eq!(stored.get::<u32>("messages").unwrap(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn refresh_moves_a_regenerated_session_to_its_new_key() {
+        let store = MemorySessionStore::new();
+        let session = Session::default();
+        let previous = session.id().clone();
+        store.save(&session, TIMEOUT).await.unwrap();
+        let connection = ConnectionSession::open(&store, &previous, TIMEOUT)
+            .await
+            .unwrap()
+            .unwrap();
+        let shared = connection.session();
+        shared.regenerate().unwrap();
+
+        connection.refresh().await.unwrap();
+        let renewed = shared.read(|session| session.id().clone()).unwrap();
+        assert!(!store.exists(&previous).await.unwrap());
+        assert!(store.exists(&renewed).await.unwrap());
+
+        connection.refresh().await.unwrap();
+        assert!(store.exists(&renewed).await.unwrap());
+    }
+}
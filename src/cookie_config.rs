@@ -0,0 +1,189 @@
+use std::{fmt, time::Duration};
+
+const DEFAULT_COOKIE_NAME: &str = "id";
+
+/// Browsers only accept cookies with this prefix when they are `Secure`,
+/// host-only and scoped to `Path=/`.
+const HOST_PREFIX: &str = "__Host-";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    /// Sent on cross-site requests too; browsers require `Secure` with it.
+    None,
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        })
+    }
+}
+
+/// The attributes of the session cookie the web integrations emit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CookieConfig {
+    name: String,
+    path: String,
+    domain: Option<String>,
+    same_site: SameSite,
+    secure: bool,
+    http_only: bool,
+    max_age: Option<Duration>,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_COOKIE_NAME.to_string(),
+            path: "/".to_string(),
+            domain: None,
+            same_site: SameSite::Lax,
+            secure: true,
+            http_only: true,
+            max_age: None,
+        }
+    }
+}
+
+impl CookieConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The defaults under a `__Host-` name, as `strict_security()` uses.
+    pub fn strict() -> Self {
+        Self::default().with_name(&format!("{HOST_PREFIX}{DEFAULT_COOKIE_NAME}"))
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    pub fn with_domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn with_http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// How long browsers keep the cookie of a browser session; by default
+    /// they drop it when they close. Persistent sessions always last their
+    /// own duration.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    pub fn same_site(&self) -> SameSite {
+        self.same_site
+    }
+
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Panics unless the cookie keeps what a `strict_security()` preset
+    /// promises: a `__Host-` name, `Secure`, `HttpOnly`, `Path=/`, no
+    /// domain and a `SameSite` of at least `Lax`.
+    pub(crate) fn enforce_strict(&self) {
+        let weakened = [
+            (!self.name.starts_with(HOST_PREFIX), "a __Host- cookie name"),
+            (!self.secure, "Secure"),
+            (!self.http_only, "HttpOnly"),
+            (self.path != "/", "Path=/"),
+            (self.domain.is_some(), "a host-only cookie"),
+            (self.same_site == SameSite::None, "SameSite=Lax or Strict"),
+        ];
+        if let Some((_, requirement)) = weakened.iter().find(|(weakened, _)| *weakened) {
+            panic!("strict security requires {requirement}, got {self:?}");
+        }
+    }
+
+    /// The `Set-Cookie` header value carrying `value`, kept for `max_age`
+    /// or until the browser closes.
+    pub(crate) fn header(&self, value: &str, max_age: Option<Duration>) -> String {
+        let mut header = format!("{}={value}; Path={}", self.name, self.path);
+        if let Some(domain) = &self.domain {
+            header.push_str(&format!("; Domain={domain}"));
+        }
+        if let Some(max_age) = max_age {
+            header.push_str(&format!("; Max-Age={}", max_age.as_secs()));
+        }
+        if self.http_only {
+            header.push_str("; HttpOnly");
+        }
+        if self.secure {
+            header.push_str("; Secure");
+        }
+        header.push_str(&format!("; SameSite={}", self.same_site));
+        header
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_includes_only_the_configured_attributes() {
+        let cookie = CookieConfig::default()
+            .with_domain("example.com")
+            .with_http_only(false)
+            .with_same_site(SameSite::Strict);
+        assert_eq!(
+            cookie.header("abc", Some(Duration::from_secs(60))),
+            "id=abc; Path=/; Domain=example.com; Max-Age=60; Secure; SameSite=Strict"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "strict security requires Secure")]
+    fn strict_cookies_cannot_drop_secure() {
+        CookieConfig::strict().enforce_strict();
+        CookieConfig::strict().with_secure(false).enforce_strict();
+    }
+}
@@ -0,0 +1,183 @@
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub trait Crdt: Serialize + DeserializeOwned {
+    fn merge(&mut self, other: &Self);
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GCounter {
+    counts: BTreeMap<String, u64>,
+}
+
+impl GCounter {
+    pub fn increment(&mut self, replica: &str, by: u64) {
+        *self.counts.entry(replica.to_string()).or_default() += by;
+    }
+
+    pub fn value(&self) -> u64 {
+        self.counts.values().sum()
+    }
+}
+
+impl Crdt for GCounter {
+    fn merge(&mut self, other: &Self) {
+        for (replica, count) in &other.counts {
+            let entry = self.counts.entry(replica.clone()).or_default();
+            *entry = (*entry).max(*count);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LwwRegister<T> {
+    value: T,
+    timestamp: u64,
+    replica: String,
+}
+
+impl<T> LwwRegister<T> {
+    pub fn new(value: T, replica: &str) -> Self {
+        Self {
+            value,
+            timestamp: now_millis(),
+            replica: replica.to_string(),
+        }
+    }
+
+    pub fn set(&mut self, value: T, replica: &str) {
+        self.value = value;
+        self.timestamp = now_millis().max(self.timestamp + 1);
+        self.replica = replica.to_string();
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Clone + Serialize + DeserializeOwned> Crdt for LwwRegister<T> {
+    fn merge(&mut self, other: &Self) {
+        if (other.timestamp, &other.replica) > (self.timestamp, &self.replica) {
+            *self = other.clone();
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrSet<T> {
+    entries: Vec<(T, String)>,
+    tombstones: BTreeSet<String>,
+}
+
+impl<T> Default for OrSet<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            tombstones: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T: PartialEq> OrSet<T> {
+    pub fn insert(&mut self, value: T) {
+        let tag = std::iter::repeat(())
+            .map(|()| OsRng.sample(Alphanumeric) as char)
+            .take(16)
+            .collect();
+        self.entries.push((value, tag));
+    }
+
+    pub fn remove(&mut self, value: &T) {
+        for (entry, tag) in &self.entries {
+            if entry == value {
+                self.tombstones.insert(tag.clone());
+            }
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.iter().any(|entry| entry == value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut seen: Vec<&T> = Vec::new();
+        self.entries
+            .iter()
+            .filter(|(_, tag)| !self.tombstones.contains(tag))
+            .filter_map(move |(value, _)| {
+                if seen.contains(&value) {
+                    None
+                } else {
+                    seen.push(value);
+                    Some(value)
+                }
+            })
+    }
+}
+
+impl<T: Clone + PartialEq + Serialize + DeserializeOwned> Crdt for OrSet<T> {
+    fn merge(&mut self, other: &Self) {
+        for (value, tag) in &other.entries {
+            if !self.entries.iter().any(|(_, existing)| existing == tag) {
+                self.entries.push((value.clone(), tag.clone()));
+            }
+        }
+        self.tombstones.extend(other.tombstones.iter().cloned());
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn g_counter_merge_keeps_the_highest_count_per_replica() {
+        let mut eu = GCounter::default();
+        eu.increment("eu", 3);
+        let mut us = eu.clone();
+        us.increment("us", 2);
+        eu.increment("eu", 1);
+
+        eu.merge(&us);
+        us.merge(&eu);
+        assert_eq!(eu.value(), 6);
+        assert_eq!(eu, us);
+    }
+
+    #[test]
+    fn lww_register_merge_keeps_the_latest_write() {
+        let mut eu = LwwRegister::new("light".to_string(), "eu");
+        let mut us = eu.clone();
+        us.set("dark".to_string(), "us");
+
+        eu.merge(&us);
+        assert_eq!(eu.value(), "dark");
+    }
+
+    #[test]
+    fn or_set_merge_keeps_concurrent_adds_and_observed_removes() {
+        let mut eu = OrSet::default();
+        eu.insert("socks".to_string());
+        let mut us = eu.clone();
+        us.remove(&"socks".to_string());
+        us.insert("shoes".to_string());
+        eu.insert("hat".to_string());
+
+        eu.merge(&us);
+        assert!(!eu.contains(&"socks".to_string()));
+        assert!(eu.contains(&"shoes".to_string()));
+        assert!(eu.contains(&"hat".to_string()));
+    }
+}
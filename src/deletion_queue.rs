@@ -0,0 +1,47 @@
+use crate::SessionKey;
+
+/// A durable queue of sessions whose deletion failed and must be retried.
+///
+/// Dequeued keys are claimed rather than removed: they stay in the queue,
+/// hidden from other dequeues, until the processor acknowledges them once
+/// the session is deleted, so a processor crashing in between does not lose
+/// the deletion. [`recover`](Self::recover) returns such orphaned claims to
+/// the queue.
+#[async_trait::async_trait(?Send)]
+pub trait DeletionQueue {
+    type Error;
+
+    async fn enqueue(&self, session_key: &SessionKey) -> Result<(), Self::Error>;
+    /// Claims up to `limit` keys.
+    async fn dequeue(&self, limit: usize) -> Result<Vec<SessionKey>, Self::Error>;
+    /// Removes a claimed key from the queue.
+    async fn acknowledge(&self, session_key: &SessionKey) -> Result<(), Self::Error>;
+    /// Returns every unacknowledged claim to the queue and tells how many
+    /// there were. Only call it while no processor holds claims, e.g. when
+    /// the only processor starts.
+    async fn recover(&self) -> Result<usize, Self::Error>;
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Q> DeletionQueue for &Q
+where
+    Q: DeletionQueue,
+{
+    type Error = Q::Error;
+
+    async fn enqueue(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        <Q as DeletionQueue>::enqueue(self, session_key).await
+    }
+
+    async fn dequeue(&self, limit: usize) -> Result<Vec<SessionKey>, Self::Error> {
+        <Q as DeletionQueue>::dequeue(self, limit).await
+    }
+
+    async fn acknowledge(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        <Q as DeletionQueue>::acknowledge(self, session_key).await
+    }
+
+    async fn recover(&self) -> Result<usize, Self::Error> {
+        <Q as DeletionQueue>::recover(self).await
+    }
+}
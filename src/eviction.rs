@@ -0,0 +1,199 @@
+use std::{cmp::Reverse, time::Duration};
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    session_state::SessionState,
+    SessionKey, SessionStore,
+};
+
+#[derive(Clone, Debug)]
+pub struct EvictionCandidate {
+    pub key: SessionKey,
+    pub idle: Duration,
+    pub state: SessionState,
+}
+
+pub trait EvictionPolicy {
+    /// Sorts `candidates` so that the first ones are evicted first.
+    fn order(&self, candidates: &mut [EvictionCandidate]);
+}
+
+impl<P: EvictionPolicy> EvictionPolicy for &P {
+    fn order(&self, candidates: &mut [EvictionCandidate]) {
+        <P as EvictionPolicy>::order(self, candidates)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct IdleLongest;
+
+impl EvictionPolicy for IdleLongest {
+    fn order(&self, candidates: &mut [EvictionCandidate]) {
+        candidates.sort_by_key(|candidate| Reverse(candidate.idle));
+    }
+}
+
+/// Evicts sessions without `identity_key` first, then the longest idle.
+pub struct AnonymousFirst {
+    identity_key: String,
+}
+
+impl AnonymousFirst {
+    pub fn new(identity_key: &str) -> Self {
+        Self {
+            identity_key: identity_key.to_string(),
+        }
+    }
+}
+
+impl EvictionPolicy for AnonymousFirst {
+    fn order(&self, candidates: &mut [EvictionCandidate]) {
+        candidates.sort_by_key(|candidate| {
+            let signed_in = candidate.state.get(&self.identity_key).is_some();
+            (signed_in, Reverse(candidate.idle))
+        });
+    }
+}
+
+/// Evicts sessions with the lowest integer under `priority_key` first; a
+/// missing or unreadable priority counts as 0.
+pub struct LowestPriority {
+    priority_key: String,
+}
+
+impl LowestPriority {
+    pub fn new(priority_key: &str) -> Self {
+        Self {
+            priority_key: priority_key.to_string(),
+        }
+    }
+}
+
+impl EvictionPolicy for LowestPriority {
+    fn order(&self, candidates: &mut [EvictionCandidate]) {
+        candidates.sort_by_key(|candidate| {
+            let priority = candidate
+                .state
+                .get(&self.priority_key)
+                .and_then(|value| ValueCodec::decode::<i64>(value).ok())
+                .unwrap_or_default();
+            (priority, Reverse(candidate.idle))
+        });
+    }
+}
+
+/// A store that can report memory pressure and list sessions to evict.
+#[async_trait::async_trait(?Send)]
+pub trait EvictionSource {
+    type Error;
+
+    /// Used capacity as a fraction of the limit, or 0 if there is no limit.
+    async fn pressure(&self) -> Result<f64, Self::Error>;
+    async fn candidates(&self, limit: usize) -> Result<Vec<EvictionCandidate>, Self::Error>;
+}
+
+pub struct Evictor<Store, Policy> {
+    store: Store,
+    policy: Policy,
+    threshold: f64,
+    batch: usize,
+    scan: usize,
+}
+
+impl<Store, Policy> Evictor<Store, Policy>
+where
+    Store: SessionStore + EvictionSource<Error = <Store as SessionStore>::Error>,
+    Policy: EvictionPolicy,
+{
+    pub fn new(store: Store, policy: Policy) -> Self {
+        Self {
+            store,
+            policy,
+            threshold: 0.9,
+            batch: 100,
+            scan: 1000,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_batch(mut self, batch: usize) -> Self {
+        self.batch = batch;
+        self
+    }
+
+    pub fn with_scan(mut self, scan: usize) -> Self {
+        self.scan = scan;
+        self
+    }
+
+    /// Evicts up to one batch of sessions if pressure is at or above the
+    /// threshold, returning the evicted keys.
+    pub async fn run_once(&self) -> Result<Vec<SessionKey>, <Store as SessionStore>::Error> {
+        if self.store.pressure().await? < self.threshold {
+            return Ok(Vec::new());
+        }
+        let mut candidates = self.store.candidates(self.scan).await?;
+        self.policy.order(&mut candidates);
+        let mut evicted = Vec::new();
+        for candidate in candidates.into_iter().take(self.batch) {
+            self.store.destroy(&candidate.key).await?;
+            evicted.push(candidate.key);
+        }
+        Ok(evicted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(idle: u64, entries: &[(&str, &str)]) -> EvictionCandidate {
+        let mut state = SessionState::default();
+        for (key, value) in entries {
+            state.insert(key, value.to_string());
+        }
+        EvictionCandidate {
+            key: SessionKey::generate(),
+            idle: Duration::from_secs(idle),
+            state,
+        }
+    }
+
+    fn idles(candidates: &[EvictionCandidate]) -> Vec<u64> {
+        candidates.iter().map(|c| c.idle.as_secs()).collect()
+    }
+
+    #[test]
+    fn idle_longest_evicts_the_most_idle_first() {
+        let mut candidates = vec![candidate(10, &[]), candidate(30, &[]), candidate(20, &[])];
+        IdleLongest.order(&mut candidates);
+        assert_eq!(idles(&candidates), vec![30, 20, 10]);
+    }
+
+    #[test]
+    fn anonymous_first_keeps_signed_in_sessions_until_last() {
+        let mut candidates = vec![
+            candidate(50, &[("user_id", "\"beavis\"")]),
+            candidate(10, &[]),
+            candidate(20, &[]),
+        ];
+        AnonymousFirst::new("user_id").order(&mut candidates);
+        assert_eq!(idles(&candidates), vec![20, 10, 50]);
+    }
+
+    #[test]
+    fn lowest_priority_treats_missing_priorities_as_zero() {
+        let priority = |priority: i64| ValueCodec::encode(&priority).unwrap();
+        let mut candidates = vec![
+            candidate(10, &[("priority", &priority(5))]),
+            candidate(20, &[("priority", &priority(-1))]),
+            candidate(30, &[]),
+        ];
+        LowestPriority::new("priority").order(&mut candidates);
+        assert_eq!(idles(&candidates), vec![20, 30, 10]);
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+use crate::{session_state::SessionState, SessionKey};
+
+#[derive(Clone)]
+pub struct HistoryEntry {
+    pub recorded_at: SystemTime,
+    pub state: SessionState,
+}
+
+#[async_trait::async_trait(?Send)]
+pub trait HistorySink {
+    type Error;
+
+    async fn record(
+        &self,
+        session_key: &SessionKey,
+        state: &SessionState,
+    ) -> Result<(), Self::Error>;
+    async fn history(&self, session_key: &SessionKey) -> Result<Vec<HistoryEntry>, Self::Error>;
+
+    /// Returns the most recent version recorded at or before `at`.
+    async fn state_at(
+        &self,
+        session_key: &SessionKey,
+        at: SystemTime,
+    ) -> Result<Option<HistoryEntry>, Self::Error> {
+        let history = self.history(session_key).await?;
+        let entry = history
+            .into_iter()
+            .rev()
+            .find(|entry| entry.recorded_at <= at);
+        Ok(entry)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct HistoryLimits {
+    pub max_versions: usize,
+    pub max_age: Duration,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_versions: 32,
+            max_age: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryHistorySink {
+    limits: HistoryLimits,
+    entries: Mutex<HashMap<SessionKey, VecDeque<HistoryEntry>>>,
+}
+
+impl MemoryHistorySink {
+    pub fn new(limits: HistoryLimits) -> Self {
+        Self {
+            limits,
+            entries: Default::default(),
+        }
+    }
+
+    fn record_at(&self, session_key: &SessionKey, state: &SessionState, recorded_at: SystemTime) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let versions = entries.entry(session_key.clone()).or_default();
+        versions.push_back(HistoryEntry {
+            recorded_at,
+            state: state.clone(),
+        });
+        while versions.len() > self.limits.max_versions {
+            versions.pop_front();
+        }
+        self.prune(versions, recorded_at);
+    }
+
+    fn prune(&self, versions: &mut VecDeque<HistoryEntry>, now: SystemTime) {
+        while let Some(oldest) = versions.front() {
+            let age = now.duration_since(oldest.recorded_at).unwrap_or_default();
+            if age <= self.limits.max_age {
+                break;
+            }
+            versions.pop_front();
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl HistorySink for MemoryHistorySink {
+    type Error = Infallible;
+
+    async fn record(
+        &self,
+        session_key: &SessionKey,
+        state: &SessionState,
+    ) -> Result<(), Self::Error> {
+        self.record_at(session_key, state, SystemTime::now());
+        Ok(())
+    }
+
+    async fn history(&self, session_key: &SessionKey) -> Result<Vec<HistoryEntry>, Self::Error> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let history = entries
+            .get_mut(session_key)
+            .map(|versions| {
+                self.prune(versions, SystemTime::now());
+                versions.iter().cloned().collect()
+            })
+            .unwrap_or_default();
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with(user_id: &str) -> SessionState {
+        let mut state = SessionState::default();
+        state.insert("user_id", user_id.to_string());
+        state
+    }
+
+    #[tokio::test]
+    async fn history_returns_recorded_versions_oldest_first() {
+        let sink = MemoryHistorySink::default();
+        let key = SessionKey::generate();
+        sink.record(&key, &state_with("beavis")).await.unwrap();
+        sink.record(&key, &state_with("butt-head")).await.unwrap();
+
+        let history = sink.history(&key).await.unwrap();
+        let user_ids = history
+            .iter()
+            .map(|entry| entry.state.get("user_id").cloned().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(user_ids, vec!["beavis", "butt-head"]);
+    }
+
+    #[tokio::test]
+    async fn history_is_bounded_by_max_versions() {
+        let sink = MemoryHistorySink::new(HistoryLimits {
+            max_versions: 2,
+            ..Default::default()
+        });
+        let key = SessionKey::generate();
+        for user_id in ["a", "b", "c"] {
+            sink.record(&key, &state_with(user_id)).await.unwrap();
+        }
+
+        let history = sink.history(&key).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].state.get("user_id").unwrap(), "b");
+    }
+
+    #[tokio::test]
+    async fn history_drops_versions_older_than_max_age() {
+        let sink = MemoryHistorySink::new(HistoryLimits {
+            max_age: Duration::from_secs(60),
+            ..Default::default()
+        });
+        let key = SessionKey::generate();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+        sink.record_at(&key, &state_with("stale"), an_hour_ago);
+
+        let history = sink.history(&key).await.unwrap();
+        assert!(history.is_empty());
+    }
+
+    #[tokio::test]
+    async fn state_at_returns_the_version_current_at_the_given_time() {
+        let sink = MemoryHistorySink::default();
+        let key = SessionKey::generate();
+        let now = SystemTime::now();
+        sink.record_at(&key, &state_with("beavis"), now - Duration::from_secs(120));
+        sink.record_at(
+            &key,
+            &state_with("butt-head"),
+            now - Duration::from_secs(60),
+        );
+
+        let entry = sink
+            .state_at(&key, now - Duration::from_secs(90))
+            .await
+            .unwrap()
+            .expect("expected a version 90 seconds ago");
+        assert_eq!(entry.state.get("user_id").unwrap(), "beavis");
+
+        let entry = sink
+            .state_at(&key, now - Duration::from_secs(180))
+            .await
+            .unwrap();
+        assert!(entry.is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    signing::Keyring,
+    SessionKey, SessionModel, SessionStore,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    pub fingerprint: String,
+    pub response: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdempotencyClaim<T> {
+    /// The caller holds the key and must process the request.
+    Claimed,
+    /// Another request with this key is still being processed.
+    InProgress,
+    /// The request was already processed; this is its cached response.
+    Completed(T),
+    /// The key was used for a request with a different fingerprint.
+    FingerprintMismatch,
+}
+
+/// Single-use idempotency keys scoped to a session.
+///
+/// `claim` must be atomic across nodes: exactly one caller may see
+/// [`IdempotencyClaim::Claimed`] for a given key until it is released or expires.
+#[async_trait::async_trait(?Send)]
+pub trait IdempotencyStore {
+    type Error;
+
+    async fn claim(
+        &self,
+        session_key: &SessionKey,
+        key: &str,
+        fingerprint: &str,
+        ttl: Duration,
+    ) -> Result<IdempotencyClaim<String>, Self::Error>;
+    async fn complete(
+        &self,
+        session_key: &SessionKey,
+        key: &str,
+        record: &IdempotencyRecord,
+        ttl: Duration,
+    ) -> Result<(), Self::Error>;
+    async fn release(&self, session_key: &SessionKey, key: &str) -> Result<(), Self::Error>;
+}
+
+#[async_trait::async_trait(?Send)]
+impl<S> IdempotencyStore for &S
+where
+    S: IdempotencyStore,
+{
+    type Error = S::Error;
+
+    async fn claim(
+        &self,
+        session_key: &SessionKey,
+        key: &str,
+        fingerprint: &str,
+        ttl: Duration,
+    ) -> Result<IdempotencyClaim<String>, Self::Error> {
+        <S as IdempotencyStore>::claim(self, session_key, key, fingerprint, ttl).await
+    }
+
+    async fn complete(
+        &self,
+        session_key: &SessionKey,
+        key: &str,
+        record: &IdempotencyRecord,
+        ttl: Duration,
+    ) -> Result<(), Self::Error> {
+        <S as IdempotencyStore>::complete(self, session_key, key, record, ttl).await
+    }
+
+    async fn release(&self, session_key: &SessionKey, key: &str) -> Result<(), Self::Error> {
+        <S as IdempotencyStore>::release(self, session_key, key).await
+    }
+}
+
+/// Hashes the parts of a request that must match for a retry to be a
+/// duplicate, keyed by `keyring` so clients cannot precompute collisions.
+pub fn fingerprint(keyring: &Keyring, parts: &[&[u8]]) -> String {
+    keyring.fingerprint(parts)
+}
+
+pub struct Idempotency<'a, Store: SessionStore> {
+    model: &'a SessionModel<Store>,
+    key: String,
+    ttl: Duration,
+}
+
+impl<'a, Store> Idempotency<'a, Store>
+where
+    Store: SessionStore + IdempotencyStore<Error = <Store as SessionStore>::Error>,
+    <Store as SessionStore>::Error: From<CodecError>,
+{
+    pub(crate) fn new(model: &'a SessionModel<Store>, key: &str, ttl: Duration) -> Self {
+        Self {
+            model,
+            key: key.to_string(),
+            ttl,
+        }
+    }
+
+    pub async fn claim<T: DeserializeOwned>(
+        &self,
+        fingerprint: &str,
+    ) -> Result<IdempotencyClaim<T>, <Store as SessionStore>::Error> {
+        let claim = self
+            .model
+            .store()
+            .claim(self.model.id(), &self.key, fingerprint, self.ttl)
+            .await?;
+        let claim = match claim {
+            IdempotencyClaim::Claimed => IdempotencyClaim::Claimed,
+            IdempotencyClaim::InProgress => IdempotencyClaim::InProgress,
+            IdempotencyClaim::FingerprintMismatch => IdempotencyClaim::FingerprintMismatch,
+            IdempotencyClaim::Completed(response) => {
+                IdempotencyClaim::Completed(ValueCodec::decode(&response)?)
+            }
+        };
+        Ok(claim)
+    }
+
+    /// Caches `response` so retries with the same fingerprint replay it.
+    pub async fn complete<T: Serialize>(
+        &self,
+        fingerprint: &str,
+        response: &T,
+    ) -> Result<(), <Store as SessionStore>::Error> {
+        let record = IdempotencyRecord {
+            fingerprint: fingerprint.to_string(),
+            response: Some(ValueCodec::encode(response)?),
+        };
+        self.model
+            .store()
+            .complete(self.model.id(), &self.key, &record, self.ttl)
+            .await
+    }
+
+    /// Gives up the claim so a retry can process the request again.
+    pub async fn release(&self) -> Result<(), <Store as SessionStore>::Error> {
+        self.model.store().release(self.model.id(), &self.key).await
+    }
+}
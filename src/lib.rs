@@ -1,10 +1,169 @@
+#[cfg(feature = "actix")]
+pub mod actix;
+mod archive;
+#[cfg(feature = "axum")]
+pub mod axum;
+mod batch;
+mod broadcast;
+mod chunked;
+mod codec;
+mod config;
+mod conflict;
+mod connection_session;
+#[cfg(any(
+    feature = "actix",
+    feature = "poem",
+    feature = "rocket",
+    feature = "tower",
+    feature = "warp"
+))]
+mod cookie_config;
+mod crdt;
+mod deletion_queue;
+mod eviction;
+mod hash;
+mod history;
+mod idempotency;
+mod merge_policy;
+mod observer;
+#[cfg(feature = "poem")]
+pub mod poem;
+mod policy;
+mod redaction;
+mod replication;
+mod resources;
+mod revocation;
+#[cfg(feature = "rocket")]
+pub mod rocket;
+mod schema;
 mod session;
+#[cfg(feature = "json")]
+mod session_data;
 mod session_model;
 mod session_state;
 mod session_store;
-mod storage;
+mod shared_session;
+mod signing;
+pub mod storage;
+mod tags;
+#[cfg(feature = "tonic")]
+pub mod tonic;
+#[cfg(feature = "tower")]
+pub mod tower;
+mod usage;
+#[cfg(feature = "warp")]
+pub mod warp;
+#[cfg(any(
+    feature = "actix",
+    feature = "poem",
+    feature = "rocket",
+    feature = "tower",
+    feature = "warp"
+))]
+mod web;
+#[cfg(feature = "json")]
+mod wire;
 
-pub use session::{Session, SessionError};
+#[cfg(feature = "s3")]
+pub use archive::ObjectStoreStorage;
+pub use archive::{ArchiveError, ArchiveReason, ArchiveRecord, Archiver, ObjectStorage};
+pub use batch::{fan_out, Batch, BatchResult, ImportOptions, ImportProgress, ImportReport};
+pub use broadcast::{
+    Broadcast, BroadcastMessage, Invalidation, RedisBroadcast, RedisBroadcastError, Revocation,
+};
+#[cfg(feature = "nats")]
+pub use broadcast::{NatsBroadcast, NatsBroadcastError};
+pub use chunked::RepairReport;
+#[cfg(feature = "json")]
+pub use codec::JsonCodec;
+#[cfg(feature = "cbor")]
+pub use codec::{CborCodec, CborError};
+pub use codec::{Codec, CodecError};
+pub use config::{ConfigError, SessionConfig};
+pub use conflict::{ConflictResolver, CrdtResolver, LastWriteWins};
+pub use connection_session::{ConnectionSession, ConnectionSessionError};
+#[cfg(any(
+    feature = "actix",
+    feature = "poem",
+    feature = "rocket",
+    feature = "tower",
+    feature = "warp"
+))]
+pub use cookie_config::{CookieConfig, SameSite};
+pub use crdt::{Crdt, GCounter, LwwRegister, OrSet};
+pub use deletion_queue::DeletionQueue;
+pub use eviction::{
+    AnonymousFirst, EvictionCandidate, EvictionPolicy, EvictionSource, Evictor, IdleLongest,
+    LowestPriority,
+};
+pub use history::{HistoryEntry, HistoryLimits, HistorySink, MemoryHistorySink};
+pub use idempotency::{
+    fingerprint, Idempotency, IdempotencyClaim, IdempotencyRecord, IdempotencyStore,
+};
+pub use merge_policy::{MergePolicies, MergePolicy};
+pub use observer::{
+    ChannelObserver, RedisStreamObserver, RedisStreamObserverError, SessionEvent, SessionEventKind,
+    SessionObserver,
+};
+#[cfg(feature = "kafka")]
+pub use observer::{KafkaObserver, KafkaObserverError, Serialization, SESSION_EVENT_AVRO_SCHEMA};
+pub use policy::{
+    FingerprintRule, PolicyError, PolicyOverride, RegenerationTrigger, SessionPolicy,
+    SessionPolicyBuilder,
+};
+pub use redaction::{KeyAllowlist, KeyDenylist, RedactionPipeline, Redactor, ValueMask};
+pub use replication::Replicator;
+pub use resources::{ResourceRegistry, ResourceRevoker, SessionResource};
+pub use revocation::RevocationFilter;
+pub use schema::{SchemaEntry, SchemaField};
+pub use session::{
+    negotiate_locale, ContextError, Exposure, Flow, FlowDefinition, FlowError, GetMany,
+    JournalEntry, JournalOperation, KeyDefaults, KeyLifetime, RestrictedSession, Session,
+    SessionContext, SessionDuration, SessionError, SessionErrorCode, SessionSnapshot,
+    SessionStatus,
+};
+#[cfg(feature = "json")]
+pub use session_data::{FieldInfo, SessionData};
 pub use session_model::SessionModel;
-pub use session_store::{RedisSessionStore, RedisSessionStoreError, SessionKey, SessionStore};
-pub use storage::{Storage, StorageError};
+pub use session_state::{SessionState, StateDiff};
+pub use session_store::conformance;
+pub use session_store::{
+    ArchivingSessionStore, ArchivingStoreError, CachedSessionStore, CookieSessionStore,
+    CookieStoreError, Deadline, DeadlineSessionStore, DeadlineStoreError, DeferredDeletionError,
+    DeferredDeletionSessionStore, EncryptedSessionStore, EncryptedStoreError, EventLog,
+    EventLogRecord, EventSourcedSessionStore, FileSessionStore, FileStoreError,
+    HistorySessionStore, HistoryStoreError, KeyEncoding, KeyFormat, Lane, Layer, MaintenanceMode,
+    MemorySessionStore, MemoryStoreError, MergingSessionStore, MetricsSessionStore,
+    ObservedSessionStore, ObservedStoreError, OperationMetrics, PreExpirySessionStore,
+    PrioritySessionStore, ReadOnlyMode, ReadOnlySessionStore, ReadOnlyStoreError, RedisEventLog,
+    RedisOptions, RedisSessionStore, RedisSessionStoreError, ReplicaSessionStore,
+    ReplicaStoreError, SelfTestError, SessionChange, SessionKey, SessionMutation, SessionSample,
+    SessionStore, ShadowMismatch, ShadowSessionStore, StoreBuilder, StoreBuilderError,
+    StoreMetrics, StoreOperation, WriteBehindSessionStore,
+};
+#[cfg(feature = "dynamodb")]
+pub use session_store::{DynamoDbSessionStore, DynamoDbStoreError};
+#[cfg(feature = "etcd")]
+pub use session_store::{EtcdSessionStore, EtcdStoreError};
+#[cfg(feature = "json")]
+pub use session_store::{JwtAlgorithm, JwtSessionStore, JwtStoreError};
+#[cfg(feature = "memcached")]
+pub use session_store::{MemcachedSessionStore, MemcachedStoreError};
+#[cfg(feature = "mongodb")]
+pub use session_store::{MongoSessionStore, MongoStoreError};
+#[cfg(feature = "mysql")]
+pub use session_store::{MySqlSessionStore, MySqlStoreError};
+#[cfg(feature = "postgres")]
+pub use session_store::{PostgresSessionStore, PostgresStoreError};
+#[cfg(feature = "sqlite")]
+pub use session_store::{SqliteSessionStore, SqliteStoreError};
+pub use shared_session::{SharedSession, WriteGuard};
+pub use signing::Keyring;
+pub use storage::{Storage, StorageError, StorageGetError, StorageInsertError, StorageRemoveError};
+pub use tags::TagStore;
+pub use usage::{KeyUsage, UsageMetrics, OTHER_KEYS};
+#[cfg(feature = "json")]
+pub use wire::{Downgrades, WireEnvelope, WireError, JSON_CODEC, WIRE_VERSION};
+
+#[cfg(feature = "derive")]
+pub use lushus_session_derive::SessionData;
@@ -0,0 +1,110 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    session_state::SessionState,
+    storage::{StorageError, StorageGetError, StorageInsertError},
+};
+
+type Combine = Box<dyn Fn(&str, &str, &str) -> Result<String, StorageError> + Send + Sync>;
+
+pub enum MergePolicy {
+    KeepGuest,
+    KeepUser,
+    Combine(Combine),
+}
+
+impl MergePolicy {
+    /// Combines the deserialized guest and user values with `combine`.
+    pub fn combine<T, F>(combine: F) -> Self
+    where
+        T: Serialize + DeserializeOwned,
+        F: Fn(T, T) -> T + Send + Sync + 'static,
+    {
+        Self::Combine(Box::new(move |key, guest, user| {
+            let deserialize = |value: &str| {
+                ValueCodec::decode::<T>(value)
+                    .map_err(|e| StorageGetError::DeserializeError(key.to_string(), e.to_string()))
+            };
+            let combined = combine(deserialize(guest)?, deserialize(user)?);
+            ValueCodec::encode(&combined)
+                .map_err(|e| StorageInsertError::SerializeError(key.to_string(), e.to_string()))
+                .map_err(StorageError::from)
+        }))
+    }
+}
+
+/// Per-key policies applied when a guest session is promoted into a user session.
+///
+/// Keys only one side has are always kept; the policy decides keys both sides have.
+pub struct MergePolicies {
+    policies: HashMap<String, MergePolicy>,
+    default: MergePolicy,
+}
+
+impl Default for MergePolicies {
+    fn default() -> Self {
+        Self {
+            policies: HashMap::new(),
+            default: MergePolicy::KeepUser,
+        }
+    }
+}
+
+impl MergePolicies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_default(mut self, policy: MergePolicy) -> Self {
+        self.default = policy;
+        self
+    }
+
+    pub fn with(mut self, key: &str, policy: MergePolicy) -> Self {
+        self.policies.insert(key.to_string(), policy);
+        self
+    }
+
+    pub fn keep_guest(self, key: &str) -> Self {
+        self.with(key, MergePolicy::KeepGuest)
+    }
+
+    pub fn keep_user(self, key: &str) -> Self {
+        self.with(key, MergePolicy::KeepUser)
+    }
+
+    pub fn combine<T, F>(self, key: &str, combine: F) -> Self
+    where
+        T: Serialize + DeserializeOwned,
+        F: Fn(T, T) -> T + Send + Sync + 'static,
+    {
+        self.with(key, MergePolicy::combine(combine))
+    }
+
+    pub(crate) fn merge(
+        &self,
+        guest: &SessionState,
+        user: &SessionState,
+    ) -> Result<SessionState, StorageError> {
+        let mut merged = user.clone();
+        for (key, guest_value) in guest.iter() {
+            let user_value = match user.get(key) {
+                Some(user_value) => user_value,
+                None => {
+                    merged.insert(key, guest_value.clone());
+                    continue;
+                }
+            };
+            match self.policies.get(key).unwrap_or(&self.default) {
+                MergePolicy::KeepGuest => merged.insert(key, guest_value.clone()),
+                MergePolicy::KeepUser => {}
+                MergePolicy::Combine(combine) => {
+                    merged.insert(key, combine(key, guest_value, user_value)?)
+                }
+            }
+        }
+        Ok(merged)
+    }
+}
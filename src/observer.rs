@@ -0,0 +1,62 @@
+mod channel_observer;
+#[cfg(feature = "kafka")]
+mod kafka_observer;
+mod redis_stream_observer;
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+use crate::{session::Exposure, session_state::StateDiff, SessionKey};
+
+pub use channel_observer::ChannelObserver;
+#[cfg(feature = "kafka")]
+pub use kafka_observer::{
+    KafkaObserver, KafkaObserverError, Serialization, SESSION_EVENT_AVRO_SCHEMA,
+};
+pub use redis_stream_observer::{RedisStreamObserver, RedisStreamObserverError};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionEventKind {
+    Created { diff: StateDiff, timeout: Duration },
+    Updated { diff: StateDiff, timeout: Duration },
+    Destroyed,
+    Exposed { exposure: Exposure },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub session_key: SessionKey,
+    pub occurred_at: SystemTime,
+    #[serde(flatten)]
+    pub kind: SessionEventKind,
+}
+
+impl SessionEvent {
+    pub fn new(session_key: SessionKey, kind: SessionEventKind) -> Self {
+        Self {
+            session_key,
+            occurred_at: SystemTime::now(),
+            kind,
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+pub trait SessionObserver {
+    type Error;
+
+    async fn notify(&self, event: &SessionEvent) -> Result<(), Self::Error>;
+}
+
+#[async_trait::async_trait(?Send)]
+impl<O> SessionObserver for &O
+where
+    O: SessionObserver,
+{
+    type Error = O::Error;
+
+    async fn notify(&self, event: &SessionEvent) -> Result<(), Self::Error> {
+        <O as SessionObserver>::notify(self, event).await
+    }
+}
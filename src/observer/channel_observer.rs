@@ -0,0 +1,40 @@
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::observer::{SessionEvent, SessionObserver};
+
+#[derive(Clone)]
+pub struct ChannelObserver {
+    sender: UnboundedSender<SessionEvent>,
+}
+
+impl ChannelObserver {
+    pub fn new() -> (Self, UnboundedReceiver<SessionEvent>) {
+        let (sender, receiver) = mpsc::unbounded();
+        (Self { sender }, receiver)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionObserver for ChannelObserver {
+    type Error = mpsc::TrySendError<SessionEvent>;
+
+    async fn notify(&self, event: &SessionEvent) -> Result<(), Self::Error> {
+        self.sender.unbounded_send(event.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{observer::SessionEventKind, SessionKey};
+
+    #[tokio::test]
+    async fn notify_sends_the_event_to_the_receiver() {
+        let (observer, mut receiver) = ChannelObserver::new();
+        let event = SessionEvent::new(SessionKey::generate(), SessionEventKind::Destroyed);
+        observer.notify(&event).await.expect("Unable to notify");
+
+        let received = receiver.try_recv().expect("No event received");
+        assert_eq!(received, event);
+    }
+}
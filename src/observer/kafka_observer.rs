@@ -0,0 +1,189 @@
+use apache_avro::{writer::datum::GenericDatumWriter, Schema};
+use rdkafka::{
+    config::ClientConfig,
+    producer::{FutureProducer, FutureRecord},
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::observer::{SessionEvent, SessionEventKind, SessionObserver};
+
+pub const SESSION_EVENT_AVRO_SCHEMA: &str = r#"{
+    "type": "record",
+    "name": "SessionEvent",
+    "namespace": "lushus.session",
+    "fields": [
+        {"name": "session_key", "type": "string"},
+        {"name": "occurred_at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+        {"name": "kind", "type": {"type": "enum", "name": "SessionEventKind", "symbols": ["created", "updated", "destroyed", "exposed"]}},
+        {"name": "timeout_secs", "type": ["null", "long"], "default": null},
+        {"name": "inserted", "type": {"type": "map", "values": "string"}},
+        {"name": "updated", "type": {"type": "map", "values": "string"}},
+        {"name": "removed", "type": {"type": "array", "items": "string"}},
+        {"name": "experiment", "type": ["null", "string"], "default": null},
+        {"name": "bucket", "type": ["null", "string"], "default": null}
+    ]
+}"#;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Serialization {
+    #[default]
+    Json,
+    Avro,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KafkaObserverError {
+    #[error("Kafka client error: {0}")]
+    ClientError(String),
+    #[error("Kafka delivery error: {0}")]
+    DeliveryError(String),
+    #[error("Unable to serialize session event: {0}")]
+    SerializationError(String),
+}
+
+#[derive(Serialize)]
+struct AvroSessionEvent<'a> {
+    session_key: &'a str,
+    occurred_at: i64,
+    kind: &'static str,
+    timeout_secs: Option<i64>,
+    inserted: HashMap<String, String>,
+    updated: HashMap<String, String>,
+    removed: Vec<String>,
+    experiment: Option<&'a str>,
+    bucket: Option<&'a str>,
+}
+
+impl<'a> From<&'a SessionEvent> for AvroSessionEvent<'a> {
+    fn from(event: &'a SessionEvent) -> Self {
+        let occurred_at = event
+            .occurred_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+        let (kind, diff, timeout, exposure) = match &event.kind {
+            SessionEventKind::Created { diff, timeout } => {
+                ("created", Some(diff), Some(timeout), None)
+            }
+            SessionEventKind::Updated { diff, timeout } => {
+                ("updated", Some(diff), Some(timeout), None)
+            }
+            SessionEventKind::Destroyed => ("destroyed", None, None, None),
+            SessionEventKind::Exposed { exposure } => ("exposed", None, None, Some(exposure)),
+        };
+        let diff = diff.cloned().unwrap_or_default();
+        Self {
+            session_key: event.session_key.as_ref(),
+            occurred_at,
+            kind,
+            timeout_secs: timeout.map(|t| t.as_secs() as i64),
+            inserted: diff.inserted,
+            updated: diff.updated,
+            removed: diff.removed,
+            experiment: exposure.map(|e| e.experiment.as_str()),
+            bucket: exposure.map(|e| e.bucket.as_str()),
+        }
+    }
+}
+
+/// Publishes session lifecycle events to a Kafka topic, keyed by session key.
+pub struct KafkaObserver {
+    topic: String,
+    serialization: Serialization,
+    queue_timeout: Duration,
+    producer: FutureProducer,
+    avro_schema: Schema,
+}
+
+impl KafkaObserver {
+    pub fn new(brokers: &str, topic: &str) -> Result<Self, KafkaObserverError> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create()
+            .map_err(|e| KafkaObserverError::ClientError(e.to_string()))?;
+        Ok(Self::from_producer(producer, topic))
+    }
+
+    pub fn from_producer(producer: FutureProducer, topic: &str) -> Self {
+        let avro_schema = Schema::parse_str(SESSION_EVENT_AVRO_SCHEMA)
+            .expect("SESSION_EVENT_AVRO_SCHEMA is a valid schema");
+        Self {
+            topic: topic.to_owned(),
+            serialization: Serialization::default(),
+            queue_timeout: Duration::from_secs(5),
+            producer,
+            avro_schema,
+        }
+    }
+
+    pub fn with_serialization(mut self, serialization: Serialization) -> Self {
+        self.serialization = serialization;
+        self
+    }
+
+    pub fn with_queue_timeout(mut self, queue_timeout: Duration) -> Self {
+        self.queue_timeout = queue_timeout;
+        self
+    }
+
+    fn encode(&self, event: &SessionEvent) -> Result<Vec<u8>, KafkaObserverError> {
+        match self.serialization {
+            Serialization::Json => serde_json::to_vec(event)
+                .map_err(|e| KafkaObserverError::SerializationError(e.to_string())),
+            Serialization::Avro => encode_avro(&self.avro_schema, event)
+                .map_err(|e| KafkaObserverError::SerializationError(e.to_string())),
+        }
+    }
+}
+
+fn encode_avro(schema: &Schema, event: &SessionEvent) -> Result<Vec<u8>, apache_avro::Error> {
+    let value = apache_avro::to_value(AvroSessionEvent::from(event))?.resolve(schema)?;
+    GenericDatumWriter::builder(schema)
+        .build()?
+        .write_value_to_vec(value)
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionObserver for KafkaObserver {
+    type Error = KafkaObserverError;
+
+    async fn notify(&self, event: &SessionEvent) -> Result<(), Self::Error> {
+        let payload = self.encode(event)?;
+        let record = FutureRecord::to(&self.topic)
+            .key(event.session_key.as_ref())
+            .payload(&payload);
+        self.producer
+            .send(record, self.queue_timeout)
+            .await
+            .map_err(|(e, _)| KafkaObserverError::DeliveryError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{session_state::StateDiff, SessionKey};
+
+    #[test]
+    fn avro_encoding_matches_the_published_schema() {
+        let schema = Schema::parse_str(SESSION_EVENT_AVRO_SCHEMA).unwrap();
+        let mut diff = StateDiff::default();
+        diff.inserted
+            .insert("user_id".to_string(), "\"beavis\"".to_string());
+        let event = SessionEvent::new(
+            SessionKey::generate(),
+            SessionEventKind::Created {
+                diff,
+                timeout: Duration::from_secs(60),
+            },
+        );
+
+        let encoded = encode_avro(&schema, &event).expect("unable to encode event");
+        assert!(!encoded.is_empty());
+    }
+}
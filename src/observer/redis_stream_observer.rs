@@ -0,0 +1,70 @@
+use redis::aio::ConnectionManager;
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    observer::{SessionEvent, SessionObserver},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RedisStreamObserverError {
+    #[error("Redis connection error: {0}")]
+    ConnectionError(String),
+    #[error("Redis query error: {0}")]
+    QueryError(String),
+    #[error("Unable to serialize session event: {0}")]
+    SerializationError(#[from] CodecError),
+}
+
+pub struct RedisStreamObserver {
+    stream: String,
+    max_len: Option<usize>,
+    connection: ConnectionManager,
+}
+
+impl RedisStreamObserver {
+    pub async fn new(url: &str, stream: &str) -> Result<Self, RedisStreamObserverError> {
+        let client = redis::Client::open(url)
+            .map_err(|e| e.to_string())
+            .map_err(RedisStreamObserverError::ConnectionError)?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(RedisStreamObserverError::ConnectionError)?;
+        Ok(Self {
+            stream: stream.to_owned(),
+            max_len: None,
+            connection,
+        })
+    }
+
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionObserver for RedisStreamObserver {
+    type Error = RedisStreamObserverError;
+
+    async fn notify(&self, event: &SessionEvent) -> Result<(), Self::Error> {
+        let body = ValueCodec::encode(event)?;
+        let mut command = redis::cmd("XADD");
+        command.arg(&self.stream);
+        if let Some(max_len) = self.max_len {
+            command.arg("MAXLEN").arg("~").arg(max_len);
+        }
+        command
+            .arg("*")
+            .arg("session_key")
+            .arg(event.session_key.as_ref())
+            .arg("event")
+            .arg(body);
+        command
+            .query_async::<_, String>(&mut self.connection.clone())
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(RedisStreamObserverError::QueryError)?;
+        Ok(())
+    }
+}
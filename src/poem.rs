@@ -0,0 +1,258 @@
+//! poem integration: wrap routes with [`SessionMiddleware`] and take
+//! [`Session`] in handlers.
+//!
+//! ```ignore
+//! #[handler]
+//! fn index(session: Session) -> String {
+//!     let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+//!     session.insert("visits", &visits).unwrap();
+//!     format!("{visits} visits")
+//! }
+//!
+//! let app = Route::new()
+//!     .at("/", get(index))
+//!     .with(SessionMiddleware::new(store));
+//! ```
+//!
+//! Session stores are not `Send`, so store calls run on worker threads of
+//! the middleware's own and any tokio runtime can serve requests.
+
+use std::sync::Arc;
+
+use poem::{
+    endpoint::make_sync,
+    error::ResponseError,
+    http::{header, HeaderValue, StatusCode},
+    post, Endpoint, Error, FromRequest, IntoResponse, Middleware, Request, RequestBody, Response,
+    Result, RouteMethod,
+};
+
+use crate::{
+    policy::{PolicyError, PolicyOverride},
+    web::{self, Backend, DetachedStore, RequestParts, Settings},
+    SessionError,
+};
+
+pub use crate::web::detached::SessionHandle as Session;
+
+struct Inner<Store> {
+    store: DetachedStore<Store>,
+    settings: Settings,
+}
+
+/// Loads the session named by the request cookie into the request
+/// extensions and persists it after the endpoint responds.
+pub struct SessionMiddleware<Store> {
+    inner: Arc<Inner<Store>>,
+}
+
+impl<Store: Backend> SessionMiddleware<Store> {
+    pub fn new(store: Store) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                store: DetachedStore::new(store),
+                settings: Settings::default(),
+            }),
+        }
+    }
+
+    web::settings_builders!();
+
+    /// A middleware for routes that need `route`'s changes to the policy,
+    /// e.g. a checkout that requires MFA, sharing this middleware's store
+    /// and cookie. Give those routes this middleware instead of, not
+    /// inside, this one.
+    pub fn with_override(&self, route: &PolicyOverride) -> Result<Self, PolicyError> {
+        let inner = Inner {
+            store: self.inner.store.clone(),
+            settings: self.inner.settings.with_override(route)?,
+        };
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    fn settings(self, update: impl FnOnce(&mut Settings)) -> Self {
+        let mut inner = Arc::try_unwrap(self.inner)
+            .unwrap_or_else(|_| panic!("SessionMiddleware is configured before it is shared"));
+        update(&mut inner.settings);
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl<E, Store> Middleware<E> for SessionMiddleware<Store>
+where
+    E: Endpoint,
+    Store: Backend + Send + Sync + 'static,
+{
+    type Output = SessionEndpoint<E, Store>;
+
+    fn transform(&self, endpoint: E) -> Self::Output {
+        self.inner.settings.check();
+        SessionEndpoint {
+            endpoint,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+pub struct SessionEndpoint<E, Store> {
+    endpoint: E,
+    inner: Arc<Inner<Store>>,
+}
+
+impl<E, Store> Endpoint for SessionEndpoint<E, Store>
+where
+    E: Endpoint,
+    Store: Backend + Send + Sync + 'static,
+{
+    type Output = Response;
+
+    async fn call(&self, mut request: Request) -> Result<Self::Output> {
+        let inner = &self.inner;
+        let cookie = request
+            .headers()
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|value| web::cookie_value(value, inner.settings.cookies.config().name()));
+        let parts = RequestParts::new(
+            &inner.settings.policy,
+            inner.settings.cookies.keyring(),
+            cookie,
+            |name| request.headers().get(name)?.to_str().ok(),
+        );
+        let session = web::load_detached(&inner.store, parts, &inner.settings.policy).await?;
+        request.extensions_mut().insert(session.clone());
+
+        let mut response = self.endpoint.call(request).await?.into_response();
+
+        let action = session.finish().await?;
+        let cookie = web::set_cookie(&inner.settings.cookies, &action);
+        if let Some(value) = cookie.and_then(|cookie| HeaderValue::from_str(&cookie).ok()) {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+        if inner.settings.expires_in_header {
+            let timeout = inner.settings.policy.idle_timeout();
+            if let Some(expires_in) =
+                web::expires_in_detached(&inner.store, &session, timeout).await
+            {
+                let value = HeaderValue::from(expires_in.as_secs());
+                response.headers_mut().insert(web::EXPIRES_IN_HEADER, value);
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// A `POST` endpoint for SPAs to keep the session alive, to be served
+/// behind [`SessionMiddleware`]: it touches the session, rotates the CSRF
+/// token when called with `?rotate_csrf=true`, and answers with the TTL the
+/// session now has as `{"expires_in": seconds}`.
+pub fn session_refresh_handler() -> RouteMethod {
+    post(make_sync(|request: Request| -> Result<Response> {
+        let session = request
+            .extensions()
+            .get::<Session>()
+            .ok_or_else(not_installed)?;
+        let body = session
+            .refresh(request.uri().query())
+            .map_err(SessionError::from)?;
+        Ok(Response::builder()
+            .content_type("application/json")
+            .body(body))
+    }))
+}
+
+impl<'a> FromRequest<'a> for Session {
+    async fn from_request(request: &'a Request, _: &mut RequestBody) -> Result<Self> {
+        request
+            .extensions()
+            .get::<Session>()
+            .cloned()
+            .ok_or_else(not_installed)
+    }
+}
+
+fn not_installed() -> Error {
+    Error::from_string(
+        "SessionMiddleware is not installed",
+        StatusCode::INTERNAL_SERVER_ERROR,
+    )
+}
+
+impl ResponseError for SessionError {
+    fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn as_response(&self) -> Response {
+        let mut response = Response::builder().status(self.status());
+        if let Some(retry_after) = web::retry_after(self) {
+            response = response.header(header::RETRY_AFTER, retry_after);
+        }
+        response.body(self.code().as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::{get, handler, test::TestClient, EndpointExt, Route};
+
+    use super::*;
+    use crate::{MemorySessionStore, PolicyOverride, SessionPolicy};
+
+    #[handler]
+    fn visit(session: Session) -> String {
+        let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+        session.insert("visits", &visits).unwrap();
+        visits.to_string()
+    }
+
+    #[tokio::test]
+    async fn handlers_extract_the_session_the_middleware_loaded() {
+        let route = Route::new()
+            .at("/", get(visit))
+            .with(SessionMiddleware::new(MemorySessionStore::new()));
+        let response = TestClient::new(route).get("/").send().await;
+        response.assert_header_exist(header::SET_COOKIE);
+        response.assert_text("1").await;
+    }
+
+    #[tokio::test]
+    async fn extracting_a_session_without_the_middleware_fails() {
+        let route = Route::new().at("/", get(visit));
+        let response = TestClient::new(route).get("/").send().await;
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn session_errors_answer_with_their_status_and_code() {
+        let response = SessionError::SessionTamperedError.as_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.into_body().into_string().await.unwrap();
+        assert_eq!(body, "session.tampered");
+    }
+
+    #[tokio::test]
+    async fn overrides_only_apply_to_the_routes_given_their_middleware() {
+        let policy = SessionPolicy::builder()
+            .with_levels(&["password", "mfa"])
+            .build()
+            .unwrap();
+        let sessions = SessionMiddleware::new(MemorySessionStore::new()).with_policy(policy);
+        let checkout = sessions
+            .with_override(&PolicyOverride::new().require_level("mfa"))
+            .unwrap();
+        let route = Route::new()
+            .at("/", get(visit).with(sessions))
+            .at("/checkout", get(visit).with(checkout));
+        let client = TestClient::new(route);
+
+        client.get("/").send().await.assert_status_is_ok();
+        let response = client.get("/checkout").send().await;
+        response.assert_status(StatusCode::FORBIDDEN);
+    }
+}
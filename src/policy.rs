@@ -0,0 +1,571 @@
+use std::time::Duration;
+
+use crate::{
+    config::{ConfigError, SessionConfig},
+    session::key_class::now_millis,
+    signing::{is_valid_environment, Keyring},
+    storage::StorageError,
+    KeyLifetime, Session,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PolicyError {
+    #[error(transparent)]
+    ConfigError(#[from] ConfigError),
+    #[error("Session size limit must be positive")]
+    SizeLimitError,
+    #[error("Regeneration interval {0:?} must be shorter than the idle timeout")]
+    RegenerationIntervalError(Duration),
+    #[error("Session is {size} bytes, over the {limit} byte limit")]
+    SessionTooLargeError { size: usize, limit: usize },
+    #[error("Authentication level \"{0}\" is not one of the policy's levels")]
+    UnknownLevelError(String),
+    #[error("Key class \"{0}\" must have a positive lifetime")]
+    KeyClassLifetimeError(String),
+    #[error("Key class \"{0}\" is not one of the policy's key classes")]
+    UnknownKeyClassError(String),
+    #[error("Environment tag \"{0}\" must be non-empty and contain no '.'")]
+    EnvironmentError(String),
+    #[error("A fingerprint rule needs a keyring: call with_keyring")]
+    FingerprintKeyringError,
+    #[error(transparent)]
+    StorageError(#[from] StorageError),
+}
+
+/// Which request attributes a session is bound to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FingerprintRule {
+    #[default]
+    Off,
+    UserAgent,
+    UserAgentAndIp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegenerationTrigger {
+    Login,
+    PrivilegeChange,
+    Interval(Duration),
+}
+
+/// Everything middleware and stores need to know about how sessions behave,
+/// validated once by [`SessionPolicyBuilder::build`]. The default policy
+/// has the default config and enforces nothing else.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SessionPolicy {
+    config: SessionConfig,
+    fingerprint: FingerprintRule,
+    max_size: Option<usize>,
+    regenerate_on: Vec<RegenerationTrigger>,
+    levels: Vec<String>,
+    required_level: Option<String>,
+    privileged_keys: Vec<String>,
+    key_classes: Vec<(String, KeyLifetime)>,
+    environment: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SessionPolicyBuilder {
+    config: SessionConfig,
+    fingerprint: FingerprintRule,
+    max_size: Option<usize>,
+    regenerate_on: Vec<RegenerationTrigger>,
+    levels: Vec<String>,
+    required_level: Option<String>,
+    privileged_keys: Vec<String>,
+    key_classes: Vec<(String, KeyLifetime)>,
+    environment: Option<String>,
+}
+
+/// Route-specific changes to a [`SessionPolicy`], e.g. a shorter idle
+/// timeout and MFA for checkout; unset fields keep the base policy's value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PolicyOverride {
+    idle_timeout: Option<Duration>,
+    absolute_timeout: Option<Duration>,
+    fingerprint: Option<FingerprintRule>,
+    max_size: Option<usize>,
+    required_level: Option<String>,
+}
+
+impl PolicyOverride {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_absolute_timeout(mut self, timeout: Duration) -> Self {
+        self.absolute_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_fingerprint(mut self, rule: FingerprintRule) -> Self {
+        self.fingerprint = Some(rule);
+        self
+    }
+
+    pub fn with_max_size(mut self, bytes: usize) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    pub fn require_level(mut self, level: &str) -> Self {
+        self.required_level = Some(level.to_string());
+        self
+    }
+}
+
+impl SessionPolicy {
+    pub fn builder() -> SessionPolicyBuilder {
+        SessionPolicyBuilder::default()
+    }
+
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Replaces the config, for integrations whose `with_config` is called
+    /// after `with_policy`.
+    #[cfg(any(
+        feature = "actix",
+        feature = "poem",
+        feature = "rocket",
+        feature = "tower",
+        feature = "warp"
+    ))]
+    pub(crate) fn set_config(&mut self, config: SessionConfig) {
+        self.config = config;
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.config.timeout()
+    }
+
+    pub fn absolute_timeout(&self) -> Option<Duration> {
+        self.config.absolute_timeout()
+    }
+
+    pub fn fingerprint_rule(&self) -> FingerprintRule {
+        self.fingerprint
+    }
+
+    /// The fingerprint to bind a session to, keyed by `keyring`, or `None`
+    /// if the rule is off.
+    pub fn fingerprint(&self, keyring: &Keyring, user_agent: &str, ip: &str) -> Option<String> {
+        match self.fingerprint {
+            FingerprintRule::Off => None,
+            FingerprintRule::UserAgent => Some(keyring.fingerprint(&[user_agent.as_bytes()])),
+            FingerprintRule::UserAgentAndIp => {
+                Some(keyring.fingerprint(&[user_agent.as_bytes(), ip.as_bytes()]))
+            }
+        }
+    }
+
+    pub fn max_size(&self) -> Option<usize> {
+        self.max_size
+    }
+
+    /// Fails if the session's encoded values exceed the size limit.
+    pub fn check_size(&self, session: &Session) -> Result<(), PolicyError> {
+        let Some(limit) = self.max_size else {
+            return Ok(());
+        };
+        let size = session
+            .state()
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum();
+        if size > limit {
+            return Err(PolicyError::SessionTooLargeError { size, limit });
+        }
+        Ok(())
+    }
+
+    pub fn regenerates_on(&self, trigger: RegenerationTrigger) -> bool {
+        self.regenerate_on.contains(&trigger)
+    }
+
+    /// Whether writing `key` crosses a privilege boundary that calls for a
+    /// fresh session key.
+    pub fn regenerates_on_change(&self, key: &str) -> bool {
+        self.privileged_keys
+            .iter()
+            .any(|privileged| privileged == key)
+    }
+
+    /// Regenerates the session if it changed a privileged key, returning
+    /// whether it did.
+    pub fn enforce_regeneration(&self, session: &mut Session) -> bool {
+        let crossed = session
+            .changed_keys()
+            .any(|key| self.regenerates_on_change(key));
+        if crossed && session.regenerated_from().is_none() {
+            session.regenerate();
+        }
+        crossed
+    }
+
+    pub fn key_lifetime(&self, class: &str) -> Option<KeyLifetime> {
+        self.key_classes
+            .iter()
+            .find(|(known, _)| known == class)
+            .map(|(_, lifetime)| *lifetime)
+    }
+
+    /// Removes the classed keys whose lifetime has run out and restarts the
+    /// idle clock of time-to-idle keys this `Session` value used, returning
+    /// the removed keys. Call it before recording usage metrics, which
+    /// resets what counts as used.
+    pub fn enforce_key_lifetimes(&self, session: &mut Session) -> Result<Vec<String>, PolicyError> {
+        let now = now_millis();
+        let usage = session.usage_stats();
+        let mut expired = Vec::new();
+        for (key, stamp) in session.class_stamps()? {
+            let lifetime = self
+                .key_lifetime(&stamp.class)
+                .ok_or_else(|| PolicyError::UnknownKeyClassError(stamp.class.clone()))?;
+            let used = usage
+                .get(&key)
+                .is_some_and(|usage| usage.reads > 0 || usage.writes > 0);
+            let age = Duration::from_millis(now.saturating_sub(stamp.at));
+            match lifetime {
+                KeyLifetime::TimeToIdle(_) if used => {
+                    session.stamp_class(&key, &stamp.class, now)?;
+                }
+                _ if age >= lifetime.duration() => {
+                    session.expire_classed(&key);
+                    expired.push(key);
+                }
+                _ => {}
+            }
+        }
+        Ok(expired)
+    }
+
+    pub fn regeneration_interval(&self) -> Option<Duration> {
+        self.regenerate_on.iter().find_map(|trigger| match trigger {
+            RegenerationTrigger::Interval(interval) => Some(*interval),
+            _ => None,
+        })
+    }
+
+    /// Whether requests continue without a session when the store is down.
+    pub fn fail_open(&self) -> bool {
+        self.config.fail_open()
+    }
+
+    pub fn required_level(&self) -> Option<&str> {
+        self.required_level.as_deref()
+    }
+
+    /// Whether the session's authentication level is at least the required
+    /// one; levels are ordered as given to [`SessionPolicyBuilder::with_levels`].
+    pub fn permits(&self, session: &Session) -> bool {
+        let Some(required) = &self.required_level else {
+            return true;
+        };
+        let rank = |level: &str| self.levels.iter().position(|known| known == level);
+        let level = session.auth_level().ok().flatten();
+        match (level.as_deref().and_then(rank), rank(required)) {
+            (Some(level), Some(required)) => level >= required,
+            _ => false,
+        }
+    }
+
+    pub fn environment(&self) -> Option<&str> {
+        self.environment.as_deref()
+    }
+
+    /// `keyring` tagged with this policy's environment, if it has one, so
+    /// it rejects values signed in any other.
+    pub fn keyring(&self, keyring: Keyring) -> Keyring {
+        match &self.environment {
+            Some(environment) => keyring.with_environment(environment),
+            None => keyring,
+        }
+    }
+
+    /// This policy with `route`'s overrides applied, validated again.
+    pub fn with_override(&self, route: &PolicyOverride) -> Result<SessionPolicy, PolicyError> {
+        let mut builder = SessionPolicyBuilder {
+            config: self.config.clone(),
+            fingerprint: route.fingerprint.unwrap_or(self.fingerprint),
+            max_size: route.max_size.or(self.max_size),
+            regenerate_on: self.regenerate_on.clone(),
+            levels: self.levels.clone(),
+            required_level: route
+                .required_level
+                .clone()
+                .or_else(|| self.required_level.clone()),
+            privileged_keys: self.privileged_keys.clone(),
+            key_classes: self.key_classes.clone(),
+            environment: self.environment.clone(),
+        };
+        if let Some(timeout) = route.idle_timeout {
+            builder = builder.with_idle_timeout(timeout);
+        }
+        if let Some(timeout) = route.absolute_timeout {
+            builder = builder.with_absolute_timeout(timeout);
+        }
+        builder.build()
+    }
+}
+
+impl SessionPolicyBuilder {
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config = self.config.with_timeout(timeout);
+        self
+    }
+
+    pub fn with_absolute_timeout(mut self, timeout: Duration) -> Self {
+        self.config = self.config.with_absolute_timeout(timeout);
+        self
+    }
+
+    pub fn with_fingerprint(mut self, rule: FingerprintRule) -> Self {
+        self.fingerprint = rule;
+        self
+    }
+
+    pub fn with_max_size(mut self, bytes: usize) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    pub fn regenerate_on(mut self, trigger: RegenerationTrigger) -> Self {
+        if !self.regenerate_on.contains(&trigger) {
+            self.regenerate_on.push(trigger);
+        }
+        self
+    }
+
+    /// Regenerates the session key whenever `key` is written, e.g. `user_id`
+    /// or `role`.
+    pub fn regenerate_on_change(mut self, key: &str) -> Self {
+        if !self
+            .privileged_keys
+            .iter()
+            .any(|privileged| privileged == key)
+        {
+            self.privileged_keys.push(key.to_string());
+        }
+        self
+    }
+
+    pub fn with_fail_open(mut self, fail_open: bool) -> Self {
+        self.config = self.config.with_fail_open(fail_open);
+        self
+    }
+
+    /// Authentication levels from weakest to strongest, e.g.
+    /// `["password", "mfa"]`.
+    pub fn with_levels(mut self, levels: &[&str]) -> Self {
+        self.levels = levels.iter().map(|level| level.to_string()).collect();
+        self
+    }
+
+    pub fn require_level(mut self, level: &str) -> Self {
+        self.required_level = Some(level.to_string());
+        self
+    }
+
+    /// Defines the lifetime of the key class `class`, e.g. `tokens`, that
+    /// values join through [`Session::insert_in_class`].
+    pub fn with_key_class(mut self, class: &str, lifetime: KeyLifetime) -> Self {
+        self.key_classes.retain(|(known, _)| known != class);
+        self.key_classes.push((class.to_string(), lifetime));
+        self
+    }
+
+    /// The deployment environment, e.g. `prod` or `staging`, that signed
+    /// cookies and tokens are confined to; see [`SessionPolicy::keyring`].
+    pub fn with_environment(mut self, environment: &str) -> Self {
+        self.environment = Some(environment.to_string());
+        self
+    }
+
+    pub fn build(self) -> Result<SessionPolicy, PolicyError> {
+        self.config.validate()?;
+        if let Some(level) = &self.required_level {
+            if !self.levels.contains(level) {
+                return Err(PolicyError::UnknownLevelError(level.clone()));
+            }
+        }
+        if self.max_size == Some(0) {
+            return Err(PolicyError::SizeLimitError);
+        }
+        if let Some(environment) = &self.environment {
+            if !is_valid_environment(environment) {
+                return Err(PolicyError::EnvironmentError(environment.clone()));
+            }
+        }
+        for (class, lifetime) in &self.key_classes {
+            if lifetime.duration().is_zero() {
+                return Err(PolicyError::KeyClassLifetimeError(class.clone()));
+            }
+        }
+        for trigger in &self.regenerate_on {
+            if let RegenerationTrigger::Interval(interval) = trigger {
+                if interval.is_zero() || *interval >= self.config.timeout() {
+                    return Err(PolicyError::RegenerationIntervalError(*interval));
+                }
+            }
+        }
+        Ok(SessionPolicy {
+            config: self.config,
+            fingerprint: self.fingerprint,
+            max_size: self.max_size,
+            regenerate_on: self.regenerate_on,
+            levels: self.levels,
+            required_level: self.required_level,
+            privileged_keys: self.privileged_keys,
+            key_classes: self.key_classes,
+            environment: self.environment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+
+    #[test]
+    fn build_validates_the_combined_settings() {
+        let policy = SessionPolicy::builder()
+            .with_idle_timeout(Duration::from_secs(1800))
+            .with_absolute_timeout(Duration::from_secs(86400))
+            .with_fingerprint(FingerprintRule::UserAgent)
+            .regenerate_on(RegenerationTrigger::Login)
+            .regenerate_on(RegenerationTrigger::Interval(Duration::from_secs(600)))
+            .build()
+            .unwrap();
+        assert!(policy.regenerates_on(RegenerationTrigger::Login));
+        assert_eq!(
+            policy.regeneration_interval(),
+            Some(Duration::from_secs(600))
+        );
+        let keyring = Keyring::new("a", b"secret");
+        assert_eq!(
+            policy.fingerprint(&keyring, "curl", "10.0.0.1"),
+            policy.fingerprint(&keyring, "curl", "10.0.0.2")
+        );
+
+        let invalid = SessionPolicy::builder()
+            .with_idle_timeout(Duration::from_secs(60))
+            .regenerate_on(RegenerationTrigger::Interval(Duration::from_secs(60)))
+            .build();
+        assert!(matches!(
+            invalid,
+            Err(PolicyError::RegenerationIntervalError(_))
+        ));
+        let invalid = SessionPolicy::builder().with_max_size(0).build();
+        assert!(matches!(invalid, Err(PolicyError::SizeLimitError)));
+    }
+
+    #[test]
+    fn check_size_rejects_sessions_over_the_limit() {
+        let policy = SessionPolicy::builder().with_max_size(16).build().unwrap();
+        let mut session = Session::default();
+        session.insert("cart", &"socks").unwrap();
+        assert!(policy.check_size(&session).is_ok());
+        session.insert("cart", &"socks and shoes").unwrap();
+        assert!(matches!(
+            policy.check_size(&session),
+            Err(PolicyError::SessionTooLargeError { limit: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn route_overrides_tighten_the_base_policy() {
+        let base = SessionPolicy::builder()
+            .with_idle_timeout(Duration::from_secs(3600))
+            .with_levels(&["password", "mfa"])
+            .build()
+            .unwrap();
+        let checkout = base
+            .with_override(
+                &PolicyOverride::new()
+                    .with_idle_timeout(Duration::from_secs(900))
+                    .require_level("mfa"),
+            )
+            .unwrap();
+        assert_eq!(checkout.idle_timeout(), Duration::from_secs(900));
+
+        let mut session = Session::default();
+        assert!(base.permits(&session));
+        assert!(!checkout.permits(&session));
+        session.set_auth_level("password").unwrap();
+        assert!(!checkout.permits(&session));
+        session.set_auth_level("mfa").unwrap();
+        assert!(checkout.permits(&session));
+
+        let unknown = base.with_override(&PolicyOverride::new().require_level("webauthn"));
+        assert!(matches!(unknown, Err(PolicyError::UnknownLevelError(_))));
+    }
+
+    #[test]
+    fn enforce_regeneration_rotates_the_key_when_a_privileged_key_changes() {
+        let policy = SessionPolicy::builder()
+            .regenerate_on_change("user_id")
+            .build()
+            .unwrap();
+        let mut session = Session::default();
+        session.insert("theme", &"dark").unwrap();
+        assert!(!policy.enforce_regeneration(&mut session));
+
+        let anonymous = session.id().clone();
+        session.insert("user_id", &"beavis").unwrap();
+        assert!(policy.enforce_regeneration(&mut session));
+        assert_ne!(session.id(), &anonymous);
+        assert_eq!(session.regenerated_from(), Some(anonymous));
+    }
+
+    #[test]
+    fn enforce_key_lifetimes_expires_stale_keys_and_keeps_used_idle_ones() {
+        let minute = Duration::from_secs(60);
+        let policy = SessionPolicy::builder()
+            .with_key_class("tokens", KeyLifetime::TimeToLive(minute))
+            .with_key_class("ui-prefs", KeyLifetime::TimeToIdle(minute))
+            .build()
+            .unwrap();
+        let mut written = Session::default();
+        let stale = now_millis() - 2 * 60 * 1000;
+        for (key, class) in [
+            ("reset", "tokens"),
+            ("theme", "ui-prefs"),
+            ("font", "ui-prefs"),
+        ] {
+            written.insert_in_class(key, &"x", class).unwrap();
+            written.stamp_class(key, class, stale).unwrap();
+        }
+
+        let mut session = Session::new(written.id().clone(), written.state().clone());
+        session.get::<String>("theme").unwrap();
+        let mut expired = policy.enforce_key_lifetimes(&mut session).unwrap();
+        expired.sort();
+        assert_eq!(expired, vec!["font", "reset"]);
+        assert_eq!(
+            session.get::<String>("theme").unwrap().as_deref(),
+            Some("x")
+        );
+        assert_eq!(session.key_class("font").unwrap(), None);
+    }
+
+    #[test]
+    fn environment_is_validated_and_tags_the_keyring() {
+        let invalid = SessionPolicy::builder().with_environment("prod.eu").build();
+        assert!(matches!(invalid, Err(PolicyError::EnvironmentError(_))));
+
+        let policy = SessionPolicy::builder()
+            .with_environment("staging")
+            .build()
+            .unwrap();
+        let keyring = policy.keyring(Keyring::new("a", b"secret"));
+        assert_eq!(keyring.environment(), Some("staging"));
+    }
+}
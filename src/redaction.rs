@@ -0,0 +1,197 @@
+use regex::Regex;
+use std::collections::HashSet;
+
+use crate::session_state::{SessionState, StateDiff};
+
+/// Strips or masks sensitive data from session state before it leaves the
+/// store through this crate's tooling: archives, history and observer events.
+///
+/// Closures taking `&mut SessionState` are redactors, and
+/// [`RedactionPipeline`] runs several in order.
+pub trait Redactor {
+    fn redact(&self, state: &mut SessionState);
+
+    /// Redacts the values in `diff`. Removed keys carry no values and are
+    /// kept as they are.
+    fn redact_diff(&self, diff: &mut StateDiff) {
+        for entries in [&mut diff.inserted, &mut diff.updated] {
+            let mut state = SessionState::default();
+            for (key, value) in entries.drain() {
+                state.insert(&key, value);
+            }
+            self.redact(&mut state);
+            entries.extend(
+                state
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+        }
+    }
+}
+
+impl<F> Redactor for F
+where
+    F: Fn(&mut SessionState),
+{
+    fn redact(&self, state: &mut SessionState) {
+        self(state)
+    }
+}
+
+pub(crate) type BoxedRedactor = Box<dyn Redactor + Send + Sync>;
+
+/// Keeps only the listed keys.
+pub struct KeyAllowlist {
+    keys: HashSet<String>,
+}
+
+impl KeyAllowlist {
+    pub fn new<'a>(keys: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            keys: keys.into_iter().map(str::to_string).collect(),
+        }
+    }
+}
+
+impl Redactor for KeyAllowlist {
+    fn redact(&self, state: &mut SessionState) {
+        let denied = state
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| !self.keys.contains(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in denied {
+            state.remove(&key);
+        }
+    }
+}
+
+/// Drops the listed keys.
+pub struct KeyDenylist {
+    keys: Vec<String>,
+}
+
+impl KeyDenylist {
+    pub fn new<'a>(keys: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            keys: keys.into_iter().map(str::to_string).collect(),
+        }
+    }
+}
+
+impl Redactor for KeyDenylist {
+    fn redact(&self, state: &mut SessionState) {
+        for key in &self.keys {
+            state.remove(key);
+        }
+    }
+}
+
+/// Replaces every match of a pattern in the values, e.g. email addresses or
+/// card numbers, keeping the keys. Values are the serialized JSON, so the
+/// replacement must not break it; the default `[REDACTED]` does not.
+pub struct ValueMask {
+    pattern: Regex,
+    replacement: String,
+}
+
+impl ValueMask {
+    pub fn new(pattern: Regex) -> Self {
+        Self {
+            pattern,
+            replacement: "[REDACTED]".to_string(),
+        }
+    }
+
+    pub fn with_replacement(mut self, replacement: &str) -> Self {
+        self.replacement = replacement.to_string();
+        self
+    }
+}
+
+impl Redactor for ValueMask {
+    fn redact(&self, state: &mut SessionState) {
+        let masked = state
+            .iter()
+            .filter(|(_, value)| self.pattern.is_match(value))
+            .map(|(key, value)| {
+                let value = self.pattern.replace_all(value, self.replacement.as_str());
+                (key.clone(), value.into_owned())
+            })
+            .collect::<Vec<_>>();
+        for (key, value) in masked {
+            state.insert(&key, value);
+        }
+    }
+}
+
+/// Runs redactors in the order they were added.
+#[derive(Default)]
+pub struct RedactionPipeline {
+    redactors: Vec<BoxedRedactor>,
+}
+
+impl RedactionPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, redactor: impl Redactor + Send + Sync + 'static) -> Self {
+        self.redactors.push(Box::new(redactor));
+        self
+    }
+}
+
+impl Redactor for RedactionPipeline {
+    fn redact(&self, state: &mut SessionState) {
+        for redactor in &self.redactors {
+            redactor.redact(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(entries: &[(&str, &str)]) -> SessionState {
+        let mut state = SessionState::default();
+        for (key, value) in entries {
+            state.insert(key, value.to_string());
+        }
+        state
+    }
+
+    #[test]
+    fn pipeline_applies_key_lists_then_value_masks() {
+        let pipeline = RedactionPipeline::new()
+            .with(KeyAllowlist::new(["user_id", "contact", "password"]))
+            .with(KeyDenylist::new(["password"]))
+            .with(ValueMask::new(Regex::new(r"[\w.]+@[\w.]+").unwrap()));
+        let mut redacted = state(&[
+            ("user_id", "\"beavis\""),
+            ("contact", "\"beavis@example.com\""),
+            ("password", "\"hunter2\""),
+            ("cart", "[1,2]"),
+        ]);
+        pipeline.redact(&mut redacted);
+
+        assert_eq!(
+            redacted,
+            state(&[("user_id", "\"beavis\""), ("contact", "\"[REDACTED]\"")])
+        );
+    }
+
+    #[test]
+    fn redact_diff_masks_values_and_keeps_removed_keys() {
+        let old = state(&[("token", "\"abc\""), ("theme", "\"light\"")]);
+        let new = state(&[("password", "\"hunter2\""), ("theme", "\"dark\"")]);
+        let mut diff = StateDiff::between(&old, &new);
+        KeyDenylist::new(["password"]).redact_diff(&mut diff);
+
+        assert!(diff.inserted.is_empty());
+        assert_eq!(diff.updated["theme"], "\"dark\"");
+        assert_eq!(diff.removed, ["token"]);
+    }
+}
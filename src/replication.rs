@@ -0,0 +1,159 @@
+use futures::{Stream, StreamExt};
+
+use crate::{
+    conflict::ConflictResolver,
+    observer::{SessionEvent, SessionEventKind},
+    session::Session,
+    session_store::SessionStore,
+};
+
+/// Applies a change stream from [`ObservedSessionStore`](crate::ObservedSessionStore)
+/// to a store in another region.
+pub struct Replicator<Remote, Resolver> {
+    remote: Remote,
+    resolver: Resolver,
+}
+
+impl<Remote, Resolver> Replicator<Remote, Resolver>
+where
+    Remote: SessionStore,
+    Resolver: ConflictResolver,
+{
+    pub fn new(remote: Remote, resolver: Resolver) -> Self {
+        Self { remote, resolver }
+    }
+
+    pub async fn apply(&self, event: &SessionEvent) -> Result<(), Remote::Error> {
+        let session_key = &event.session_key;
+        let (diff, timeout) = match &event.kind {
+            SessionEventKind::Created { diff, timeout }
+            | SessionEventKind::Updated { diff, timeout } => (diff, *timeout),
+            SessionEventKind::Destroyed => return self.remote.destroy(session_key).await,
+            SessionEventKind::Exposed { .. } => return Ok(()),
+        };
+        let remote = self.remote.load(session_key).await?;
+        let remote_state = remote.as_ref().map(Session::state);
+        let mut local = remote_state.cloned().unwrap_or_default();
+        diff.apply(&mut local);
+        let resolved = match remote_state {
+            Some(remote_state) => self.resolver.resolve(&local, remote_state),
+            None => local,
+        };
+        let session = Session::new(session_key.clone(), resolved);
+        if remote.is_some() {
+            self.remote.update(&session, timeout).await
+        } else {
+            self.remote.save(&session, timeout).await
+        }
+    }
+
+    /// Applies events until the stream ends or an event fails to apply.
+    pub async fn run<S>(&self, events: S) -> Result<usize, (SessionEvent, Remote::Error)>
+    where
+        S: Stream<Item = SessionEvent>,
+    {
+        let mut applied = 0;
+        futures::pin_mut!(events);
+        while let Some(event) = events.next().await {
+            if let Err(e) = self.apply(&event).await {
+                return Err((event, e));
+            }
+            applied += 1;
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        codec::{Codec, ValueCodec},
+        crdt::GCounter,
+        session_state::SessionState,
+        storage::Storage,
+        CrdtResolver, LastWriteWins, MemorySessionStore, SessionKey, StateDiff,
+    };
+
+    fn state(entries: &[(&str, &str)]) -> SessionState {
+        let mut state = SessionState::default();
+        for (key, value) in entries {
+            state.insert(key, value.to_string());
+        }
+        state
+    }
+
+    fn updated(session_key: &SessionKey, old: &SessionState, new: &SessionState) -> SessionEvent {
+        let kind = SessionEventKind::Updated {
+            diff: StateDiff::between(old, new),
+            timeout: Duration::from_secs(60),
+        };
+        SessionEvent::new(session_key.clone(), kind)
+    }
+
+    #[tokio::test]
+    async fn run_applies_diffs_over_the_remote_copy_and_destroys() {
+        let remote = MemorySessionStore::new();
+        let replicator = Replicator::new(&remote, LastWriteWins);
+        let session_key = SessionKey::generate();
+        let empty = SessionState::default();
+        let created = state(&[("user_id", "\"beavis\""), ("theme", "\"light\"")]);
+        let themed = state(&[("user_id", "\"beavis\""), ("theme", "\"dark\"")]);
+        let events = vec![
+            SessionEvent::new(
+                session_key.clone(),
+                SessionEventKind::Created {
+                    diff: StateDiff::between(&empty, &created),
+                    timeout: Duration::from_secs(60),
+                },
+            ),
+            updated(&session_key, &created, &themed),
+            SessionEvent::new(
+                session_key.clone(),
+                SessionEventKind::Exposed {
+                    exposure: crate::Exposure {
+                        experiment: "checkout".to_string(),
+                        bucket: "a".to_string(),
+                    },
+                },
+            ),
+        ];
+
+        let applied = replicator.run(futures::stream::iter(events)).await;
+        assert_eq!(applied.ok(), Some(3));
+        let replicated = remote.load(&session_key).await.unwrap().unwrap();
+        assert_eq!(replicated.state(), &themed);
+
+        let destroyed = SessionEvent::new(session_key.clone(), SessionEventKind::Destroyed);
+        replicator.apply(&destroyed).await.unwrap();
+        assert!(!remote.exists(&session_key).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn apply_resolves_conflicts_with_writes_made_in_the_remote_region() {
+        let remote = MemorySessionStore::new();
+        let mut local_visits = GCounter::default();
+        local_visits.increment("eu", 2);
+        let mut remote_visits = GCounter::default();
+        remote_visits.increment("us", 3);
+        let mut session = Session::default();
+        session.insert("visits", &remote_visits).unwrap();
+        remote
+            .save(&session, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        let replicator =
+            Replicator::new(&remote, CrdtResolver::new().register::<GCounter>("visits"));
+        let mut local = SessionState::default();
+        local.insert("visits", ValueCodec::encode(&local_visits).unwrap());
+        let event = updated(session.id(), &SessionState::default(), &local);
+        replicator.apply(&event).await.unwrap();
+
+        let replicated = remote.load(session.id()).await.unwrap().unwrap();
+        let visits = replicated.get::<GCounter>("visits").unwrap().unwrap();
+        assert_eq!(visits.value(), 5);
+    }
+}
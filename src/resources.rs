@@ -0,0 +1,274 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    observer::{SessionEvent, SessionEventKind, SessionObserver},
+    SessionKey,
+};
+
+/// An external resource handed out on behalf of a session, such as a
+/// presigned URL or a temporary upload, named by a kind the revoker
+/// understands and an id within that kind.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SessionResource {
+    pub kind: String,
+    pub id: String,
+}
+
+impl SessionResource {
+    pub fn new(kind: &str, id: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            id: id.to_string(),
+        }
+    }
+}
+
+/// Revokes or deletes a resource once the session it belongs to is gone.
+#[async_trait::async_trait(?Send)]
+pub trait ResourceRevoker {
+    type Error;
+
+    async fn revoke(&self, resource: &SessionResource) -> Result<(), Self::Error>;
+}
+
+#[async_trait::async_trait(?Send)]
+impl<R> ResourceRevoker for &R
+where
+    R: ResourceRevoker,
+{
+    type Error = R::Error;
+
+    async fn revoke(&self, resource: &SessionResource) -> Result<(), Self::Error> {
+        <R as ResourceRevoker>::revoke(self, resource).await
+    }
+}
+
+#[derive(Default)]
+struct Registered {
+    resources: Vec<SessionResource>,
+    /// When the session expires, as last seen through its events.
+    expires_at: Option<Instant>,
+}
+
+/// Associates resources with sessions and revokes them when the session is
+/// destroyed or expires.
+///
+/// As a [`SessionObserver`], usually behind an
+/// [`ObservedSessionStore`](crate::ObservedSessionStore), it revokes a
+/// session's resources on its `Destroyed` event and tracks expiries from its
+/// `Created` and `Updated` events. Expired sessions produce no event, so
+/// [`collect`](Self::collect) must be driven by the GC task;
+/// [`run`](Self::run) does so on an interval. Registrations are kept in
+/// memory on this node, and resources whose revocation fails are retried by
+/// the next collection.
+pub struct ResourceRegistry<Revoker> {
+    revoker: Revoker,
+    sessions: Mutex<HashMap<SessionKey, Registered>>,
+}
+
+impl<Revoker: ResourceRevoker> ResourceRegistry<Revoker> {
+    pub fn new(revoker: Revoker) -> Self {
+        Self {
+            revoker,
+            sessions: Default::default(),
+        }
+    }
+
+    fn sessions(&self) -> MutexGuard<'_, HashMap<SessionKey, Registered>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Ties `resource` to the session, to be revoked when the session ends.
+    pub fn register(&self, session_key: &SessionKey, resource: SessionResource) {
+        let mut sessions = self.sessions();
+        let registered = sessions.entry(session_key.clone()).or_default();
+        if !registered.resources.contains(&resource) {
+            registered.resources.push(resource);
+        }
+    }
+
+    /// Forgets `resource` without revoking it, e.g. once an upload has been
+    /// moved to permanent storage.
+    pub fn unregister(&self, session_key: &SessionKey, resource: &SessionResource) {
+        let mut sessions = self.sessions();
+        if let Some(registered) = sessions.get_mut(session_key) {
+            registered
+                .resources
+                .retain(|registered| registered != resource);
+            if registered.resources.is_empty() {
+                sessions.remove(session_key);
+            }
+        }
+    }
+
+    pub fn resources(&self, session_key: &SessionKey) -> Vec<SessionResource> {
+        self.sessions()
+            .get(session_key)
+            .map(|registered| registered.resources.clone())
+            .unwrap_or_default()
+    }
+
+    /// Revokes the resources of every session past its expiry, returning how
+    /// many were revoked. Resources that fail to revoke stay registered for
+    /// the next collection, and the first failure is returned.
+    pub async fn collect(&self) -> Result<usize, Revoker::Error> {
+        let now = Instant::now();
+        let expired = self
+            .sessions()
+            .iter()
+            .filter(|(_, registered)| registered.expires_at.is_some_and(|at| at <= now))
+            .map(|(session_key, _)| session_key.clone())
+            .collect::<Vec<_>>();
+        let mut revoked = 0;
+        let mut failure = None;
+        for session_key in expired {
+            match self.revoke_session(&session_key).await {
+                Ok(count) => revoked += count,
+                Err((count, error)) => {
+                    revoked += count;
+                    failure.get_or_insert(error);
+                }
+            }
+        }
+        failure.map_or(Ok(revoked), Err)
+    }
+
+    /// Calls [`collect`](Self::collect) every `interval`, returning on the
+    /// first revocation error.
+    pub async fn run(&self, interval: Duration) -> Result<(), Revoker::Error> {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            self.collect().await?;
+        }
+    }
+
+    /// Revokes the session's resources, putting back those that fail as due
+    /// for the next collection.
+    async fn revoke_session(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<usize, (usize, Revoker::Error)> {
+        let Some(registered) = self.sessions().remove(session_key) else {
+            return Ok(0);
+        };
+        let mut revoked = 0;
+        let mut failed = Vec::new();
+        let mut failure = None;
+        for resource in registered.resources {
+            match self.revoker.revoke(&resource).await {
+                Ok(()) => revoked += 1,
+                Err(error) => {
+                    failure.get_or_insert(error);
+                    failed.push(resource);
+                }
+            }
+        }
+        let Some(error) = failure else {
+            return Ok(revoked);
+        };
+        let mut sessions = self.sessions();
+        let retry = sessions.entry(session_key.clone()).or_default();
+        retry.resources.extend(failed);
+        retry.expires_at = Some(Instant::now());
+        Err((revoked, error))
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Revoker: ResourceRevoker> SessionObserver for ResourceRegistry<Revoker> {
+    type Error = Revoker::Error;
+
+    async fn notify(&self, event: &SessionEvent) -> Result<(), Self::Error> {
+        match &event.kind {
+            SessionEventKind::Created { timeout, .. }
+            | SessionEventKind::Updated { timeout, .. } => {
+                if let Some(registered) = self.sessions().get_mut(&event.session_key) {
+                    registered.expires_at = Some(Instant::now() + *timeout);
+                }
+                Ok(())
+            }
+            SessionEventKind::Destroyed => self
+                .revoke_session(&event.session_key)
+                .await
+                .map(|_| ())
+                .map_err(|(_, error)| error),
+            SessionEventKind::Exposed { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Revoked {
+        down: RefCell<bool>,
+        ids: RefCell<Vec<String>>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl ResourceRevoker for Revoked {
+        type Error = ();
+
+        async fn revoke(&self, resource: &SessionResource) -> Result<(), ()> {
+            if *self.down.borrow() {
+                return Err(());
+            }
+            self.ids.borrow_mut().push(resource.id.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn destroying_a_session_revokes_its_resources() {
+        let revoked = Revoked::default();
+        let registry = ResourceRegistry::new(&revoked);
+        let session_key = SessionKey::generate();
+        registry.register(&session_key, SessionResource::new("upload", "tmp/1"));
+        registry.register(&session_key, SessionResource::new("url", "signed/2"));
+
+        *revoked.down.borrow_mut() = true;
+        let destroyed = SessionEvent::new(session_key.clone(), SessionEventKind::Destroyed);
+        assert!(registry.notify(&destroyed).await.is_err());
+        assert_eq!(registry.resources(&session_key).len(), 2);
+
+        *revoked.down.borrow_mut() = false;
+        assert_eq!(registry.collect().await, Ok(2));
+        assert_eq!(*revoked.ids.borrow(), ["tmp/1", "signed/2"]);
+        assert!(registry.resources(&session_key).is_empty());
+    }
+
+    #[tokio::test]
+    async fn collect_revokes_only_expired_sessions() {
+        let revoked = Revoked::default();
+        let registry = ResourceRegistry::new(&revoked);
+        let expiring = SessionKey::generate();
+        let live = SessionKey::generate();
+        for (session_key, timeout) in [(&expiring, 10), (&live, 60_000)] {
+            registry.register(
+                session_key,
+                SessionResource::new("upload", session_key.as_ref()),
+            );
+            let kind = SessionEventKind::Created {
+                diff: Default::default(),
+                timeout: Duration::from_millis(timeout),
+            };
+            let created = SessionEvent::new(session_key.clone(), kind);
+            registry.notify(&created).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(registry.collect().await, Ok(1));
+        assert_eq!(*revoked.ids.borrow(), [expiring.as_ref()]);
+        assert_eq!(registry.resources(&live).len(), 1);
+    }
+}
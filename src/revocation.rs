@@ -0,0 +1,154 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use futures::StreamExt;
+
+use crate::{
+    broadcast::{Broadcast, BroadcastMessage, Revocation},
+    hash::fnv1a,
+    SessionKey,
+};
+
+/// A bloom filter of revoked session keys, consulted before trusting a
+/// stateless session such as a JWT.
+///
+/// A miss means the key was never revoked on any node that published to
+/// this filter. A hit may be a false positive, so callers should confirm it
+/// against the store or deny the request. Nodes that start late can catch
+/// up with [`words`](Self::words) and [`merge`](Self::merge).
+///
+/// Clones share the same filter, so one can be handed to
+/// [`JwtSessionStore::with_revocations`](crate::JwtSessionStore::with_revocations)
+/// or [`CookieSessionStore::with_revocations`](crate::CookieSessionStore::with_revocations)
+/// while another [`listen`](Self::listen)s.
+#[derive(Clone)]
+pub struct RevocationFilter {
+    bits: Arc<[AtomicU64]>,
+    hashes: u32,
+}
+
+impl RevocationFilter {
+    /// Sizes the filter to hold `capacity` revocations at the given false
+    /// positive rate.
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let capacity = capacity.max(1) as f64;
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let bits = (-capacity * rate.ln() / std::f64::consts::LN_2.powi(2)).ceil();
+        let words = ((bits / 64.0).ceil() as usize).max(1);
+        let hashes = ((words * 64) as f64 / capacity * std::f64::consts::LN_2).round();
+        Self {
+            bits: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            hashes: (hashes as u32).max(1),
+        }
+    }
+
+    pub fn revoke(&self, session_key: &SessionKey) {
+        for bit in self.positions(session_key) {
+            self.bits[bit / 64].fetch_or(1 << (bit % 64), Ordering::Relaxed);
+        }
+    }
+
+    /// Whether `session_key` was revoked, or, for a device-scoped key, the
+    /// whole login it belongs to.
+    pub fn might_be_revoked(&self, session_key: &SessionKey) -> bool {
+        let revoked = |key: &SessionKey| {
+            self.positions(key)
+                .all(|bit| self.bits[bit / 64].load(Ordering::Relaxed) & (1 << (bit % 64)) != 0)
+        };
+        revoked(session_key)
+            || (session_key.device_id().is_some() && revoked(&session_key.without_device()))
+    }
+
+    /// The raw filter, for seeding a node that joins later.
+    pub fn words(&self) -> Vec<u64> {
+        self.bits
+            .iter()
+            .map(|word| word.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Adds every revocation recorded in `words`, taken from a filter of the
+    /// same size. Returns `false` and changes nothing if the sizes differ.
+    pub fn merge(&self, words: &[u64]) -> bool {
+        if words.len() != self.bits.len() {
+            return false;
+        }
+        for (word, other) in self.bits.iter().zip(words) {
+            word.fetch_or(*other, Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Revokes the key locally and tells every other node.
+    pub async fn publish<B: Broadcast>(
+        &self,
+        broadcast: &B,
+        session_key: &SessionKey,
+    ) -> Result<(), B::Error> {
+        self.revoke(session_key);
+        let revocation = Revocation {
+            session_key: session_key.clone(),
+        };
+        broadcast
+            .publish(&BroadcastMessage::Revocation(revocation))
+            .await
+    }
+
+    /// Applies revocations published by other nodes until the subscription
+    /// ends, ignoring other messages.
+    pub async fn listen<B: Broadcast>(&self, broadcast: &B) -> Result<(), B::Error> {
+        let mut messages = broadcast.subscribe().await?;
+        while let Some(message) = messages.next().await {
+            if let BroadcastMessage::Revocation(revocation) = message {
+                self.revoke(&revocation.session_key);
+            }
+        }
+        Ok(())
+    }
+
+    fn positions<'a>(&'a self, session_key: &SessionKey) -> impl Iterator<Item = usize> + 'a {
+        let key = session_key.as_ref().as_bytes();
+        let first = fnv1a(&[key]);
+        let second = fnv1a(&[b"revocation:", key]) | 1;
+        let size = (self.bits.len() * 64) as u64;
+        (0..u64::from(self.hashes))
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % size) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revocation_filter_has_no_false_negatives() {
+        let filter = RevocationFilter::new(1000, 0.01);
+        let revoked = (0..1000)
+            .map(|_| SessionKey::generate())
+            .collect::<Vec<_>>();
+        for key in &revoked {
+            filter.revoke(key);
+        }
+        assert!(revoked.iter().all(|key| filter.might_be_revoked(key)));
+
+        let false_positives = (0..1000)
+            .filter(|_| filter.might_be_revoked(&SessionKey::generate()))
+            .count();
+        assert!(false_positives < 50, "{false_positives} false positives");
+    }
+
+    #[test]
+    fn merge_carries_revocations_between_filters_of_the_same_size() {
+        let seed = RevocationFilter::new(100, 0.01);
+        let revoked = SessionKey::generate();
+        seed.revoke(&revoked);
+
+        let joining = RevocationFilter::new(100, 0.01);
+        assert!(!joining.might_be_revoked(&revoked));
+        assert!(joining.merge(&seed.words()));
+        assert!(joining.might_be_revoked(&revoked));
+        assert!(!RevocationFilter::new(10_000, 0.01).merge(&seed.words()));
+    }
+}
@@ -0,0 +1,253 @@
+//! Rocket integration: attach [`SessionFairing`] and take `&Session` in
+//! routes.
+//!
+//! ```ignore
+//! #[get("/")]
+//! fn index(session: &Session) -> String {
+//!     let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+//!     session.insert("visits", &visits).unwrap();
+//!     format!("{visits} visits")
+//! }
+//!
+//! rocket::build()
+//!     .attach(SessionFairing::new(store))
+//!     .mount("/", routes![index])
+//! ```
+//!
+//! Session stores are not `Send`, so store calls run on worker threads of
+//! the fairing's own and any tokio runtime can serve requests.
+
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    http::{uri::Origin, ContentType, Status},
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
+    Build, Data, Request, Response, Rocket, Route,
+};
+
+use crate::{
+    policy::{PolicyError, PolicyOverride},
+    web::{self, Backend, DetachedStore, RequestParts, SessionHandle, Settings},
+    SessionError,
+};
+
+pub use crate::web::detached::SessionHandle as Session;
+
+/// The per-request session, or why it could not be loaded.
+struct Cached(Result<SessionHandle, SessionError>);
+
+/// Loads the session named by the request cookie before routing and
+/// persists it once the response is built.
+pub struct SessionFairing<Store> {
+    store: DetachedStore<Store>,
+    settings: Settings,
+}
+
+impl<Store: Backend> SessionFairing<Store> {
+    pub fn new(store: Store) -> Self {
+        Self {
+            store: DetachedStore::new(store),
+            settings: Settings::default(),
+        }
+    }
+
+    web::settings_builders!();
+
+    /// A fairing for routes that need `route`'s changes to the policy, e.g.
+    /// a checkout that requires MFA, sharing this fairing's store and
+    /// cookie. Fairings see every request, so attach it instead of this one
+    /// to the `Rocket` that serves those routes.
+    pub fn with_override(&self, route: &PolicyOverride) -> Result<Self, PolicyError> {
+        Ok(Self {
+            store: self.store.clone(),
+            settings: self.settings.with_override(route)?,
+        })
+    }
+
+    fn settings(mut self, update: impl FnOnce(&mut Settings)) -> Self {
+        update(&mut self.settings);
+        self
+    }
+}
+
+#[rocket::async_trait]
+impl<Store> Fairing for SessionFairing<Store>
+where
+    Store: Backend + Send + Sync + 'static,
+{
+    fn info(&self) -> Info {
+        Info {
+            name: "lushus-session",
+            kind: Kind::Ignite | Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        self.settings.check();
+        Ok(rocket)
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let cookie = request
+            .cookies()
+            .get(self.settings.cookies.config().name())
+            .map(|cookie| cookie.value().to_string());
+        let keyring = self.settings.cookies.keyring();
+        let parts = RequestParts::new(&self.settings.policy, keyring, cookie.as_deref(), |name| {
+            request.headers().get_one(name)
+        });
+        let loaded = web::load_detached(&self.store, parts, &self.settings.policy).await;
+        request.local_cache(|| Cached(loaded));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let session = match &request.local_cache(not_attached).0 {
+            Ok(session) => session,
+            Err(error) => {
+                if let Some(retry_after) = web::retry_after(error) {
+                    response.set_raw_header("Retry-After", retry_after);
+                }
+                return;
+            }
+        };
+        match session.finish().await {
+            Ok(action) => {
+                let cookie = web::set_cookie(&self.settings.cookies, &action);
+                if let Some(cookie) = cookie {
+                    response.adjoin_raw_header("Set-Cookie", cookie);
+                }
+                if self.settings.expires_in_header {
+                    let timeout = self.settings.policy.idle_timeout();
+                    if let Some(expires_in) =
+                        web::expires_in_detached(&self.store, session, timeout).await
+                    {
+                        let value = expires_in.as_secs().to_string();
+                        response.set_raw_header(web::EXPIRES_IN_HEADER, value);
+                    }
+                }
+            }
+            Err(error) => {
+                response.set_status(status(&error));
+                if let Some(retry_after) = web::retry_after(&error) {
+                    response.set_raw_header("Retry-After", retry_after);
+                }
+                response.set_sized_body(0, std::io::Cursor::new(""));
+            }
+        }
+    }
+}
+
+fn not_attached() -> Cached {
+    Cached(Err(SessionError::StoreUnavailableError(
+        "SessionFairing is not attached".to_string(),
+    )))
+}
+
+fn status(error: &SessionError) -> Status {
+    Status::from_code(error.http_status()).unwrap_or(Status::InternalServerError)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r Session {
+    type Error = SessionError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match &request.local_cache(not_attached).0 {
+            Ok(session) => Outcome::Success(session),
+            Err(error) => Outcome::Error((status(error), error.clone())),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for SessionError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = (status(&self), self.code().as_str()).respond_to(request)?;
+        if let Some(retry_after) = web::retry_after(&self) {
+            response.set_raw_header("Retry-After", retry_after);
+        }
+        Ok(response)
+    }
+}
+
+/// Routes for SPAs to keep the session alive; mount them and `POST` to the
+/// mount point. The route touches the session, rotates the CSRF token when
+/// called with `?rotate_csrf=true`, and answers with the TTL the session
+/// now has as `{"expires_in": seconds}`.
+pub fn session_refresh_handler() -> Vec<Route> {
+    rocket::routes![refresh]
+}
+
+#[rocket::post("/")]
+fn refresh(session: &Session, origin: &Origin<'_>) -> Result<(ContentType, String), Status> {
+    let query = origin.query().map(|query| query.as_str());
+    let body = session
+        .refresh(query)
+        .map_err(|_| Status::InternalServerError)?;
+    Ok((ContentType::JSON, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::local::asynchronous::Client;
+
+    use super::*;
+    use crate::{MemorySessionStore, PolicyOverride, SessionPolicy};
+
+    #[rocket::get("/")]
+    fn visit(session: &Session) -> String {
+        let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+        session.insert("visits", &visits).unwrap();
+        visits.to_string()
+    }
+
+    #[rocket::get("/tampered")]
+    fn tampered() -> Result<(), SessionError> {
+        Err(SessionError::SessionTamperedError)
+    }
+
+    async fn client(rocket: rocket::Rocket<rocket::Build>) -> Client {
+        let rocket = rocket.mount("/", rocket::routes![visit, tampered]);
+        Client::untracked(rocket).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn handlers_extract_the_session_the_fairing_loaded() {
+        let fairing = SessionFairing::new(MemorySessionStore::new());
+        let client = client(rocket::build().attach(fairing)).await;
+        let response = client.get("/").dispatch().await;
+        assert!(response.cookies().get("id").is_some());
+        assert_eq!(response.into_string().await.unwrap(), "1");
+    }
+
+    #[tokio::test]
+    async fn extracting_a_session_without_the_fairing_fails() {
+        let client = client(rocket::build()).await;
+        let response = client.get("/").dispatch().await;
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+    }
+
+    #[tokio::test]
+    async fn session_errors_answer_with_their_status_and_code() {
+        let client = client(rocket::build()).await;
+        let response = client.get("/tampered").dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(response.into_string().await.unwrap(), "session.tampered");
+    }
+
+    #[tokio::test]
+    async fn overrides_apply_to_the_rocket_they_are_attached_to() {
+        let policy = SessionPolicy::builder()
+            .with_levels(&["password", "mfa"])
+            .build()
+            .unwrap();
+        let sessions = SessionFairing::new(MemorySessionStore::new()).with_policy(policy);
+        let checkout = sessions
+            .with_override(&PolicyOverride::new().require_level("mfa"))
+            .unwrap();
+
+        let mfa = client(rocket::build().attach(checkout)).await;
+        assert_eq!(mfa.get("/").dispatch().await.status(), Status::Forbidden);
+        let plain = client(rocket::build().attach(sessions)).await;
+        assert_eq!(plain.get("/").dispatch().await.status(), Status::Ok);
+    }
+}
@@ -0,0 +1,131 @@
+use std::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Session, Storage, StorageError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SchemaField {
+    pub key: &'static str,
+    pub type_name: &'static str,
+}
+
+pub struct SchemaEntry<'a, T> {
+    session: &'a mut Session,
+    key: &'static str,
+    value: PhantomData<T>,
+}
+
+impl<'a, T: Serialize + DeserializeOwned> SchemaEntry<'a, T> {
+    #[doc(hidden)]
+    pub fn new(session: &'a mut Session, key: &'static str) -> Self {
+        Self {
+            session,
+            key,
+            value: PhantomData,
+        }
+    }
+
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+
+    pub fn get(&self) -> Result<Option<T>, StorageError> {
+        self.session.get(self.key)
+    }
+
+    pub fn set(&mut self, value: &T) -> Result<(), StorageError> {
+        self.session.insert(self.key, value)
+    }
+
+    pub fn remove(&mut self) -> Result<Option<T>, StorageError> {
+        self.session.remove(self.key)
+    }
+}
+
+/// Declares a typed accessor struct over a `Session`; without a name the
+/// struct is called `SessionSchema`.
+///
+/// ```
+/// lushus_session::session_schema! { user: String, csrf: String }
+///
+/// let mut session = lushus_session::Session::default();
+/// let mut schema = SessionSchema::new(&mut session);
+/// schema.csrf().set(&"token".to_string()).unwrap();
+/// assert_eq!(schema.csrf().get().unwrap().as_deref(), Some("token"));
+/// assert_eq!(SessionSchema::SCHEMA[1].key, "csrf");
+/// ```
+#[macro_export]
+macro_rules! session_schema {
+    ($vis:vis struct $name:ident { $($key:ident : $ty:ty),* $(,)? }) => {
+        $vis struct $name<'a> {
+            session: &'a mut $crate::Session,
+        }
+
+        impl<'a> $name<'a> {
+            pub const SCHEMA: &'static [$crate::SchemaField] = &[
+                $($crate::SchemaField {
+                    key: stringify!($key),
+                    type_name: stringify!($ty),
+                }),*
+            ];
+
+            pub fn new(session: &'a mut $crate::Session) -> Self {
+                Self { session }
+            }
+
+            pub fn session(&self) -> &$crate::Session {
+                self.session
+            }
+
+            $(
+                pub fn $key(&mut self) -> $crate::SchemaEntry<'_, $ty> {
+                    $crate::SchemaEntry::new(self.session, stringify!($key))
+                }
+            )*
+        }
+    };
+    ($($key:ident : $ty:ty),* $(,)?) => {
+        $crate::session_schema! {
+            pub struct SessionSchema { $($key : $ty),* }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Session, Storage};
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Cart {
+        items: Vec<String>,
+    }
+
+    crate::session_schema! {
+        struct Checkout { cart: Cart, csrf: String }
+    }
+
+    #[test]
+    fn schema_accessors_read_and_write_the_declared_keys() {
+        let mut session = Session::default();
+        let mut checkout = Checkout::new(&mut session);
+        let cart = Cart {
+            items: vec!["book".to_string()],
+        };
+        checkout.cart().set(&cart).unwrap();
+        assert_eq!(checkout.cart().get().unwrap(), Some(cart));
+        assert_eq!(checkout.csrf().remove().unwrap(), None);
+        assert!(checkout.session().get::<Cart>("cart").unwrap().is_some());
+    }
+
+    #[test]
+    fn schema_describes_keys_and_types() {
+        let keys: Vec<_> = Checkout::SCHEMA
+            .iter()
+            .map(|field| (field.key, field.type_name))
+            .collect();
+        assert_eq!(keys, vec![("cart", "Cart"), ("csrf", "String")]);
+    }
+}
@@ -1,29 +1,156 @@
+mod auth_level;
+mod autosave;
+mod context;
+mod csrf;
+mod defaults;
+mod duration;
+mod error_code;
+mod experiment;
+mod flow;
+mod get_many;
+mod hints;
+mod journal;
+pub(crate) mod key_class;
+mod lifetime;
+mod locale;
+mod one_time;
+mod post_commit;
+mod regenerate;
+mod snapshot;
+mod status;
+mod tags;
+mod transaction;
+
 use serde::{de::DeserializeOwned, Serialize};
-use std::borrow::{Borrow, BorrowMut};
+use std::{sync::Mutex, time::Duration};
 
 use crate::{
+    codec::{Codec, ValueCodec},
+    merge_policy::MergePolicies,
     session_state::SessionState,
+    session_store::{KeyFormat, ReadOnlyMode},
     storage::{Storage, StorageError, StorageGetError, StorageInsertError, StorageRemoveError},
+    usage::UsageCounters,
     SessionKey,
 };
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Clone, Debug, thiserror::Error)]
 pub enum SessionError {
     #[error(transparent)]
     SessionStorageError(#[from] StorageError),
     #[error("Session is destroyed")]
     SessionDestroyedError,
+    #[error("Experiment \"{0}\" has no buckets with a positive weight")]
+    InvalidExperimentError(String),
+    #[error("Shared sessions can only be written through a write guard")]
+    UnguardedWriteError,
+    #[error("Session lock was poisoned by a panicking writer")]
+    SessionPoisonedError,
+    #[error("Snapshot was taken from a different session")]
+    SnapshotMismatchError,
+    #[error("Session already has an open transaction")]
+    TransactionActiveError,
+    #[error("Session has no open transaction")]
+    NoTransactionError,
+    #[error("Session has expired")]
+    SessionExpiredError,
+    #[error("Session cookie or token failed verification")]
+    SessionTamperedError,
+    #[error("Session store is unavailable: {0}")]
+    StoreUnavailableError(String),
+    #[error("Session is {size} bytes, over the {limit} byte limit")]
+    SessionTooLargeError { size: usize, limit: usize },
+    #[error("Session is not authenticated at the level the policy requires")]
+    AuthLevelError,
+    #[error(transparent)]
+    ReadOnlyError(ReadOnlyMode),
+}
+
+impl SessionError {
+    /// The HTTP status a request failing with this error should get.
+    /// Client-side session problems are 4xx; everything else is a server
+    /// fault.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            SessionError::SessionDestroyedError | SessionError::SessionExpiredError => 401,
+            SessionError::SessionTamperedError => 400,
+            SessionError::AuthLevelError => 403,
+            SessionError::SnapshotMismatchError => 409,
+            SessionError::StoreUnavailableError(_) | SessionError::ReadOnlyError(_) => 503,
+            SessionError::SessionStorageError(_)
+            | SessionError::InvalidExperimentError(_)
+            | SessionError::UnguardedWriteError
+            | SessionError::SessionPoisonedError
+            | SessionError::TransactionActiveError
+            | SessionError::NoTransactionError
+            | SessionError::SessionTooLargeError { .. } => 500,
+        }
+    }
+
+    /// How long a client should wait before retrying, for errors that end
+    /// on their own: the rest of a time-boxed maintenance window.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            SessionError::ReadOnlyError(mode) => mode.retry_after(),
+            _ => None,
+        }
+    }
 }
 
+use autosave::Autosaves;
+use defaults::Defaults;
+use post_commit::PostCommit;
+
+pub use context::{ContextError, RestrictedSession, SessionContext};
+pub use defaults::KeyDefaults;
+pub use duration::SessionDuration;
+pub use error_code::SessionErrorCode;
+pub use experiment::Exposure;
+pub use flow::{Flow, FlowDefinition, FlowError};
+pub use get_many::GetMany;
+pub use journal::{JournalEntry, JournalOperation};
+pub use key_class::KeyLifetime;
+pub use locale::negotiate as negotiate_locale;
+pub use snapshot::SessionSnapshot;
+pub use status::SessionStatus;
+pub(crate) use tags::TAGS_KEY;
+
 #[derive(Default)]
 pub struct Session {
     id: SessionKey,
     state: SessionState,
+    journal: Vec<JournalEntry>,
+    source: Option<String>,
+    exposures: Vec<Exposure>,
+    staged: Option<Vec<(String, Option<String>)>>,
+    post_commit: Mutex<Vec<PostCommit>>,
+    autosaves: Autosaves,
+    defaults: Defaults,
+    usage: UsageCounters,
+    regenerated_from: Mutex<Option<SessionKey>>,
+    purged: bool,
+    touched: bool,
+    key_format: KeyFormat,
 }
 
 impl Session {
     pub fn new(id: SessionKey, state: SessionState) -> Self {
-        Session { id, state }
+        Session {
+            id,
+            state,
+            journal: Default::default(),
+            source: None,
+            exposures: Default::default(),
+            staged: None,
+            post_commit: Default::default(),
+            autosaves: Default::default(),
+            defaults: Default::default(),
+            usage: Default::default(),
+            regenerated_from: Default::default(),
+            purged: false,
+            touched: false,
+            key_format: KeyFormat::default(),
+        }
     }
 
     pub fn id(&self) -> &SessionKey {
@@ -33,6 +160,78 @@ impl Session {
     pub fn state(&self) -> &SessionState {
         &self.state
     }
+
+    /// Passes the value of `key` to `f` by reference, so reading one field
+    /// of a large value needs neither `Clone` nor a `get` into a binding.
+    pub fn with<T: DeserializeOwned, R>(
+        &self,
+        key: impl AsRef<str>,
+        f: impl FnOnce(&T) -> R,
+    ) -> Result<Option<R>, StorageError> {
+        let value = self.get::<T>(key)?;
+        Ok(value.as_ref().map(f))
+    }
+
+    /// Merges this guest session into the user's session under a fresh key.
+    pub fn promote(
+        self,
+        user: Option<Session>,
+        policies: &MergePolicies,
+    ) -> Result<Session, StorageError> {
+        let state = match user {
+            Some(user) => policies.merge(&self.state, &user.state)?,
+            None => self.state,
+        };
+        let mut promoted = Session::new(SessionKey::generate_in(self.key_format), state);
+        promoted.key_format = self.key_format;
+        promoted.source = self.source;
+        promoted.exposures = self.exposures;
+        promoted.post_commit = self.post_commit;
+        promoted.defaults = self.defaults;
+        let keys = promoted
+            .state
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in keys {
+            promoted.record(&key, JournalOperation::Insert);
+        }
+        Ok(promoted)
+    }
+
+    pub(crate) fn insert_raw(&mut self, key: &str, value: String) {
+        self.forget_default(key);
+        if self.stage(key, Some(value.clone())) {
+            return;
+        }
+        self.usage.write(key);
+        self.state.insert(key, value);
+        self.record(key, JournalOperation::Insert);
+    }
+
+    pub(crate) fn usage(&self) -> &UsageCounters {
+        &self.usage
+    }
+
+    /// The current value of `key`, including writes staged by a transaction.
+    pub(crate) fn value(&self, key: &str) -> Option<&String> {
+        match self.staged_value(key) {
+            Some(staged) => staged,
+            None => self.state.get(key),
+        }
+    }
+
+    pub(crate) fn remove_raw(&mut self, key: &str) -> Option<String> {
+        self.forget_default(key);
+        if self.in_transaction() {
+            let previous = self.value(key).cloned();
+            self.stage(key, None);
+            return previous;
+        }
+        self.usage.write(key);
+        self.record(key, JournalOperation::Remove);
+        self.state.remove(key)
+    }
 }
 
 impl From<Session> for SessionState {
@@ -47,33 +246,43 @@ impl From<&Session> for SessionState {
     }
 }
 
-impl Storage<&str> for Session {
+impl<K: AsRef<str>> Storage<K> for Session {
     type Error = StorageError;
 
-    fn insert<T: Serialize>(&mut self, key: &str, value: &T) -> Result<(), Self::Error> {
-        let insert = serde_json::to_string(value)
+    fn insert<T: Serialize>(&mut self, key: K, value: &T) -> Result<(), Self::Error> {
+        let key = key.as_ref();
+        let insert = ValueCodec::encode(value)
             .map_err(|e| StorageInsertError::SerializeError(key.to_string(), e.to_string()))
             .map_err(StorageError::from)?;
-        self.state.borrow_mut().insert(key, insert);
+        self.insert_raw(key, insert);
         Ok(())
     }
 
-    fn remove<T: DeserializeOwned>(&mut self, key: &str) -> Result<Option<T>, Self::Error> {
-        self.state
-            .borrow_mut()
-            .remove(key)
-            .map(|v| serde_json::from_str(&v))
+    fn remove<T: DeserializeOwned>(&mut self, key: K) -> Result<Option<T>, Self::Error> {
+        let key = key.as_ref();
+        self.remove_raw(key)
+            .map(|v| ValueCodec::decode(&v))
             .transpose()
             .map_err(|e| StorageRemoveError::DeserializeError(key.to_string(), e.to_string()))
             .map_err(StorageError::from)
     }
 
-    fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Self::Error> {
-        self.state
-            .borrow()
-            .get(key)
-            .map(|v| serde_json::from_str(v))
-            .transpose()
+    fn get<T: DeserializeOwned>(&self, key: K) -> Result<Option<T>, Self::Error> {
+        let key = key.as_ref();
+        self.usage.read(key);
+        let default;
+        let value = match self.value(key) {
+            Some(value) => value,
+            None => match self.default_value(key)? {
+                Some(value) => {
+                    default = value;
+                    &default
+                }
+                None => return Ok(None),
+            },
+        };
+        ValueCodec::decode(value)
+            .map(Some)
             .map_err(|e| StorageGetError::DeserializeError(key.to_string(), e.to_string()))
             .map_err(StorageError::from)
     }
@@ -124,6 +333,71 @@ mod tests {
         assert_eq!(user, None, "expected get \"user\" to return None");
     }
 
+    #[test]
+    fn changed_keys_lists_inserted_and_removed_keys() {
+        let mut session = Session::default();
+        session
+            .insert("user_id", &"beavis".to_string())
+            .expect("unable to insert user_id");
+        session
+            .remove::<String>("cart")
+            .expect("unable to remove cart");
+
+        let changed = session.changed_keys().collect::<Vec<_>>();
+        assert_eq!(changed, vec!["cart", "user_id"]);
+    }
+
+    #[test]
+    fn promote_merges_guest_state_into_the_user_session_by_policy() {
+        let mut guest = Session::default();
+        guest.insert("cart", &vec!["socks"]).unwrap();
+        guest.insert("theme", &"light").unwrap();
+        guest.insert("referrer", &"ad").unwrap();
+        let mut user = Session::default();
+        user.insert("cart", &vec!["shoes"]).unwrap();
+        user.insert("theme", &"dark").unwrap();
+        user.insert("user_id", &"brandon").unwrap();
+        let user_key = user.id().clone();
+
+        let policies = MergePolicies::new()
+            .combine("cart", |mut guest: Vec<String>, user: Vec<String>| {
+                guest.extend(user);
+                guest
+            })
+            .keep_guest("referrer");
+        let promoted = guest
+            .promote(Some(user), &policies)
+            .expect("expected promote to succeed");
+
+        assert_ne!(promoted.id(), &user_key);
+        let cart = promoted.get::<Vec<String>>("cart").unwrap().unwrap();
+        assert_eq!(cart, vec!["socks", "shoes"]);
+        let theme = promoted.get::<String>("theme").unwrap().unwrap();
+        assert_eq!(theme, "dark");
+        let referrer = promoted.get::<String>("referrer").unwrap().unwrap();
+        assert_eq!(referrer, "ad");
+        let user_id = promoted.get::<String>("user_id").unwrap().unwrap();
+        assert_eq!(user_id, "brandon");
+    }
+
+    #[test]
+    fn promote_keeps_the_guest_value_of_keep_guest_keys_on_login() {
+        let mut guest = Session::default();
+        guest.insert("referrer", &"ad").unwrap();
+        guest.insert("theme", &"light").unwrap();
+        let mut user = Session::default();
+        user.insert("referrer", &"newsletter").unwrap();
+        user.insert("theme", &"dark").unwrap();
+
+        let policies = MergePolicies::new().keep_guest("referrer");
+        let promoted = guest.promote(Some(user), &policies).unwrap();
+
+        let referrer = promoted.get::<String>("referrer").unwrap().unwrap();
+        assert_eq!(referrer, "ad");
+        let theme = promoted.get::<String>("theme").unwrap().unwrap();
+        assert_eq!(theme, "dark");
+    }
+
     #[test]
     fn get_returns_the_expected_value_for_the_given_key() {
         let mut session = Session::default();
@@ -142,4 +416,56 @@ mod tests {
         assert_eq!(user.username, "brandon".to_string());
         assert_eq!(user.password, "hunter2".to_string());
     }
+
+    #[test]
+    fn with_passes_a_reference_to_the_value() {
+        let mut session = Session::default();
+        let user = User {
+            username: "brandon".to_string(),
+            password: "hunter2".to_string(),
+        };
+        session
+            .insert("user", &user)
+            .expect("unable to insert User");
+
+        let length = session
+            .with::<User, _>("user", |user| user.username.len())
+            .expect("expected with \"user\" to succeed");
+        assert_eq!(length, Some(7));
+        let missing = session
+            .with::<User, _>("missing", |user| user.username.len())
+            .expect("expected with \"missing\" to succeed");
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn storage_accepts_any_key_that_is_a_str() {
+        enum Key {
+            Cart,
+        }
+
+        impl AsRef<str> for Key {
+            fn as_ref(&self) -> &str {
+                match self {
+                    Key::Cart => "cart",
+                }
+            }
+        }
+
+        let mut session = Session::default();
+        session.insert(Key::Cart, &vec!["socks"]).unwrap();
+        let owned = session.get::<Vec<String>>("cart".to_string()).unwrap();
+        assert_eq!(owned, Some(vec!["socks".to_string()]));
+    }
+
+    #[test]
+    fn http_status_separates_client_and_server_faults() {
+        assert_eq!(SessionError::SessionExpiredError.http_status(), 401);
+        assert_eq!(SessionError::SessionTamperedError.http_status(), 400);
+        assert_eq!(
+            SessionError::StoreUnavailableError("timeout".to_string()).http_status(),
+            503
+        );
+        assert_eq!(SessionError::SessionPoisonedError.http_status(), 500);
+    }
 }
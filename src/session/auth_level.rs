@@ -0,0 +1,16 @@
+use crate::{
+    session::Session,
+    storage::{Storage, StorageError},
+};
+
+const AUTH_LEVEL_KEY: &str = "__auth_level";
+
+impl Session {
+    pub fn auth_level(&self) -> Result<Option<String>, StorageError> {
+        self.get(AUTH_LEVEL_KEY)
+    }
+
+    pub fn set_auth_level(&mut self, level: &str) -> Result<(), StorageError> {
+        self.insert(AUTH_LEVEL_KEY, &level)
+    }
+}
@@ -0,0 +1,78 @@
+use serde::Serialize;
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    session::Session,
+    storage::{StorageError, StorageInsertError},
+};
+
+/// The debounce of each draft written since the session was last persisted.
+#[derive(Default)]
+pub(crate) struct Autosaves(HashMap<String, Duration>);
+
+impl Session {
+    /// Writes a draft under `key` and lets a [`WriteBehindSessionStore`] hold
+    /// back the write: while a session changes nothing but drafts, the layer
+    /// writes it at most once per `debounce` and keeps the latest state until
+    /// then, so a client saving on every keystroke does not hammer the store.
+    /// Any other store writes every draft.
+    ///
+    /// [`WriteBehindSessionStore`]: crate::WriteBehindSessionStore
+    pub fn autosave<T: Serialize>(
+        &mut self,
+        key: &str,
+        payload: &T,
+        debounce: Duration,
+    ) -> Result<(), StorageError> {
+        let encoded = ValueCodec::encode(payload)
+            .map_err(|e| StorageInsertError::SerializeError(key.to_string(), e.to_string()))?;
+        self.insert_raw(key, encoded);
+        let held = self.autosaves.0.entry(key.to_string()).or_insert(debounce);
+        *held = (*held).min(debounce);
+        Ok(())
+    }
+
+    /// How long writing this session may be held back: the shortest debounce
+    /// of its drafts, or `None` if anything but drafts changed since it was
+    /// last persisted.
+    pub(crate) fn autosave_debounce(&self) -> Option<Duration> {
+        let drafts_only = self
+            .changed_keys()
+            .all(|key| self.autosaves.0.contains_key(key));
+        if !drafts_only {
+            return None;
+        }
+        self.autosaves.0.values().min().copied()
+    }
+
+    pub(crate) fn forget_autosaves(&mut self) {
+        self.autosaves.0.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+
+    #[test]
+    fn only_sessions_changing_nothing_but_drafts_can_be_held_back() {
+        let mut session = Session::default();
+        assert_eq!(session.autosave_debounce(), None);
+
+        session
+            .autosave("compose", &"Dear", Duration::from_secs(60))
+            .unwrap();
+        session
+            .autosave("notes", &"todo", Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(session.autosave_debounce(), Some(Duration::from_secs(5)));
+        assert_eq!(session.get::<String>("compose").unwrap().unwrap(), "Dear");
+
+        session.insert("user_id", &"beavis").unwrap();
+        assert_eq!(session.autosave_debounce(), None);
+        session.mark_persisted();
+        assert_eq!(session.autosave_debounce(), None);
+    }
+}
@@ -0,0 +1,147 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    session_state::SessionState,
+    storage::{StorageError, StorageGetError},
+    Session, SessionKey, SessionStore,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ContextError<S> {
+    #[error(transparent)]
+    StoreError(S),
+    #[error("Session was revoked or changed hands since the context was captured")]
+    SessionRevokedError,
+}
+
+/// The session key and selected claims a background job acts under.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionContext {
+    key: SessionKey,
+    claims: SessionState,
+}
+
+/// A read-only view onto the claims of a rehydrated [`SessionContext`].
+#[derive(Clone, Debug)]
+pub struct RestrictedSession {
+    key: SessionKey,
+    claims: SessionState,
+}
+
+impl Session {
+    pub fn context(&self, claims: &[&str]) -> SessionContext {
+        SessionContext {
+            key: self.id().clone(),
+            claims: select(self.state(), claims),
+        }
+    }
+}
+
+impl SessionContext {
+    pub fn key(&self) -> &SessionKey {
+        &self.key
+    }
+
+    pub fn claims(&self) -> impl Iterator<Item = &str> {
+        self.claims.iter().map(|(key, _)| key.as_str())
+    }
+
+    /// Reloads the session and checks that it still exists and that every
+    /// captured claim is unchanged before handing out the claims.
+    pub async fn rehydrate<Store: SessionStore>(
+        &self,
+        store: &Store,
+    ) -> Result<RestrictedSession, ContextError<Store::Error>> {
+        let session = store
+            .load(&self.key)
+            .await
+            .map_err(ContextError::StoreError)?;
+        self.restrict(session)
+    }
+
+    fn restrict<S>(&self, session: Option<Session>) -> Result<RestrictedSession, ContextError<S>> {
+        let session = session.ok_or(ContextError::SessionRevokedError)?;
+        let changed = self
+            .claims
+            .iter()
+            .any(|(key, value)| session.state().get(key) != Some(value));
+        if changed {
+            return Err(ContextError::SessionRevokedError);
+        }
+        Ok(RestrictedSession {
+            key: self.key.clone(),
+            claims: self.claims.clone(),
+        })
+    }
+}
+
+impl RestrictedSession {
+    pub fn id(&self) -> &SessionKey {
+        &self.key
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, StorageError> {
+        self.claims
+            .get(key)
+            .map(|v| ValueCodec::decode(v))
+            .transpose()
+            .map_err(|e| StorageGetError::DeserializeError(key.to_string(), e.to_string()))
+            .map_err(StorageError::from)
+    }
+}
+
+fn select(state: &SessionState, keys: &[&str]) -> SessionState {
+    let mut selected = SessionState::default();
+    for key in keys {
+        if let Some(value) = state.get(key) {
+            selected.insert(key, value.clone());
+        }
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+
+    fn session() -> Session {
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        session.insert("password", &"hunter2").unwrap();
+        session
+    }
+
+    #[test]
+    fn context_carries_only_the_selected_claims() {
+        let session = session();
+        let context = session.context(&["user_id", "missing"]);
+        let encoded = ValueCodec::encode(&context).unwrap();
+        let decoded: SessionContext = ValueCodec::decode(&encoded).unwrap();
+
+        let restricted = decoded.restrict::<()>(Some(session)).unwrap();
+        assert_eq!(decoded.claims().collect::<Vec<_>>(), vec!["user_id"]);
+        assert_eq!(
+            restricted.get::<String>("user_id").unwrap().as_deref(),
+            Some("beavis")
+        );
+        assert_eq!(restricted.get::<String>("password").unwrap(), None);
+    }
+
+    #[test]
+    fn restrict_treats_missing_or_changed_sessions_as_revoked() {
+        let mut session = session();
+        let context = session.context(&["user_id"]);
+        assert!(matches!(
+            context.restrict::<()>(None),
+            Err(ContextError::SessionRevokedError)
+        ));
+
+        session.insert("user_id", &"butt-head").unwrap();
+        assert!(matches!(
+            context.restrict::<()>(Some(session)),
+            Err(ContextError::SessionRevokedError)
+        ));
+    }
+}
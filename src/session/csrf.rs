@@ -0,0 +1,35 @@
+use crate::{
+    session::Session,
+    storage::{Storage, StorageError},
+    SessionKey,
+};
+
+const CSRF_TOKEN_KEY: &str = "__csrf";
+
+impl Session {
+    pub fn csrf_token(&self) -> Result<Option<String>, StorageError> {
+        self.get(CSRF_TOKEN_KEY)
+    }
+
+    /// Replaces the CSRF token with a fresh random one and returns it.
+    pub fn rotate_csrf_token(&mut self) -> Result<String, StorageError> {
+        let token = SessionKey::generate().as_ref().to_string();
+        self.insert(CSRF_TOKEN_KEY, &token)?;
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotate_csrf_token_replaces_the_previous_token() {
+        let mut session = Session::default();
+        assert_eq!(session.csrf_token().unwrap(), None);
+        let first = session.rotate_csrf_token().unwrap();
+        let second = session.rotate_csrf_token().unwrap();
+        assert_ne!(first, second);
+        assert_eq!(session.csrf_token().unwrap(), Some(second));
+    }
+}
@@ -0,0 +1,216 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    session::Session,
+    storage::StorageGetError,
+};
+
+type Factory = Arc<dyn Fn() -> Result<String, String> + Send + Sync>;
+
+#[derive(Clone)]
+struct KeyDefault {
+    factory: Factory,
+    persist: bool,
+}
+
+/// Factories that construct the value of a key on the first `get` that
+/// finds it absent, so "first visit" initialization lives in one place on
+/// the [`SessionConfig`](crate::SessionConfig) instead of at every read.
+#[derive(Clone, Default)]
+pub struct KeyDefaults(HashMap<String, KeyDefault>);
+
+impl KeyDefaults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs `key` with `factory` whenever it is read while absent,
+    /// without writing it to the session.
+    pub fn with<T, E>(
+        self,
+        key: &str,
+        factory: impl Fn() -> Result<T, E> + Send + Sync + 'static,
+    ) -> Self
+    where
+        T: Serialize,
+        E: fmt::Display,
+    {
+        self.register(key, factory, false)
+    }
+
+    /// Like [`with`](Self::with), but the constructed value is also written
+    /// to the session, so the factory runs once per session.
+    pub fn with_persisted<T, E>(
+        self,
+        key: &str,
+        factory: impl Fn() -> Result<T, E> + Send + Sync + 'static,
+    ) -> Self
+    where
+        T: Serialize,
+        E: fmt::Display,
+    {
+        self.register(key, factory, true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn register<T, E>(
+        mut self,
+        key: &str,
+        factory: impl Fn() -> Result<T, E> + Send + Sync + 'static,
+        persist: bool,
+    ) -> Self
+    where
+        T: Serialize,
+        E: fmt::Display,
+    {
+        let factory = move || {
+            let value = factory().map_err(|e| e.to_string())?;
+            ValueCodec::encode(&value).map_err(|e| e.to_string())
+        };
+        self.0.insert(
+            key.to_string(),
+            KeyDefault {
+                factory: Arc::new(factory),
+                persist,
+            },
+        );
+        self
+    }
+
+    fn flags(&self) -> Vec<(&str, bool)> {
+        let mut flags = self
+            .0
+            .iter()
+            .map(|(key, default)| (key.as_str(), default.persist))
+            .collect::<Vec<_>>();
+        flags.sort_unstable();
+        flags
+    }
+}
+
+impl fmt::Debug for KeyDefaults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.flags()).finish()
+    }
+}
+
+/// Two sets of defaults are equal when they cover the same keys in the same
+/// way; factories cannot be compared.
+impl PartialEq for KeyDefaults {
+    fn eq(&self, other: &Self) -> bool {
+        self.flags() == other.flags()
+    }
+}
+
+impl Eq for KeyDefaults {}
+
+/// The defaults a session was given, and the persisted ones constructed by
+/// reads but not yet written into it.
+#[derive(Default)]
+pub(crate) struct Defaults {
+    factories: KeyDefaults,
+    constructed: Mutex<HashMap<String, String>>,
+}
+
+impl Defaults {
+    fn constructed(&self) -> MutexGuard<'_, HashMap<String, String>> {
+        self.constructed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Session {
+    /// Installs the per-key defaults that reads of absent keys fall back to.
+    /// The web integrations install their config's defaults on every session.
+    pub fn set_defaults(&mut self, defaults: KeyDefaults) {
+        self.defaults.factories = defaults;
+    }
+
+    /// Writes the persisted defaults constructed by reads into the session.
+    /// The web integrations do this before every write; call it before
+    /// saving a session to a store yourself.
+    pub fn settle_defaults(&mut self) {
+        let constructed = std::mem::take(&mut *self.defaults.constructed());
+        for (key, value) in constructed {
+            self.insert_raw(&key, value);
+        }
+    }
+
+    pub(crate) fn take_defaults(&mut self) -> KeyDefaults {
+        std::mem::take(&mut self.defaults.factories)
+    }
+
+    pub(crate) fn has_unsettled_defaults(&self) -> bool {
+        !self.defaults.constructed().is_empty()
+    }
+
+    /// The encoded default for an absent `key`, if one is registered.
+    pub(crate) fn default_value(&self, key: &str) -> Result<Option<String>, StorageGetError> {
+        let Some(default) = self.defaults.factories.0.get(key) else {
+            return Ok(None);
+        };
+        if let Some(value) = self.defaults.constructed().get(key) {
+            return Ok(Some(value.clone()));
+        }
+        let value =
+            (default.factory)().map_err(|e| StorageGetError::DefaultError(key.to_string(), e))?;
+        if default.persist {
+            self.defaults
+                .constructed()
+                .insert(key.to_string(), value.clone());
+        }
+        Ok(Some(value))
+    }
+
+    /// Drops a constructed default that an explicit write supersedes.
+    pub(crate) fn forget_default(&mut self, key: &str) {
+        self.defaults.constructed().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{session::SessionStatus, storage::Storage};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn persisted_defaults_are_constructed_once_and_written_on_settle() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut session = Session::default();
+        session.set_defaults(KeyDefaults::new().with_persisted("cart", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, String>(Vec::<u32>::new())
+        }));
+
+        assert_eq!(session.get::<Vec<u32>>("cart").unwrap(), Some(vec![]));
+        assert_eq!(session.get::<Vec<u32>>("cart").unwrap(), Some(vec![]));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(session.status(), SessionStatus::Changed);
+
+        session.settle_defaults();
+        assert!(session.state().get("cart").is_some());
+    }
+
+    #[test]
+    fn failing_defaults_surface_as_get_errors() {
+        let mut session = Session::default();
+        session.set_defaults(KeyDefaults::new().with("theme", || Err::<String, _>("no theme")));
+
+        assert!(session.get::<String>("theme").is_err());
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        session.insert("theme", &"dark").unwrap();
+        assert_eq!(session.get::<String>("theme").unwrap().unwrap(), "dark");
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use crate::{
+    session::Session,
+    storage::{Storage, StorageError},
+};
+
+const PERSIST_FOR_KEY: &str = "__persist_for";
+
+/// How long a session outlives the browser it was created in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SessionDuration {
+    /// Stored for the configured session timeout, with a cookie the
+    /// browser drops when it closes.
+    #[default]
+    Browser,
+    /// A "remember me" session: stored and kept by the browser for the
+    /// given duration.
+    Persistent(Duration),
+}
+
+impl SessionDuration {
+    /// The TTL to store the session with, given the configured timeout.
+    pub fn ttl(&self, timeout: Duration) -> Duration {
+        match self {
+            SessionDuration::Browser => timeout,
+            SessionDuration::Persistent(duration) => *duration,
+        }
+    }
+}
+
+impl Session {
+    /// The session's tier; it is kept in the session, so it survives later
+    /// requests.
+    pub fn duration(&self) -> SessionDuration {
+        match self.get::<u64>(PERSIST_FOR_KEY) {
+            Ok(Some(seconds)) => SessionDuration::Persistent(Duration::from_secs(seconds)),
+            _ => SessionDuration::Browser,
+        }
+    }
+
+    /// Makes this a "remember me" session that lives for `duration`,
+    /// rounded down to whole seconds.
+    pub fn persist_for(&mut self, duration: Duration) -> Result<(), StorageError> {
+        self.insert(PERSIST_FOR_KEY, &duration.as_secs())
+    }
+
+    /// Returns the session to the [`SessionDuration::Browser`] tier.
+    pub fn forget_persistence(&mut self) -> Result<(), StorageError> {
+        self.remove::<u64>(PERSIST_FOR_KEY).map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn persist_for_switches_the_tier_and_its_ttl() {
+        let timeout = Duration::from_secs(60);
+        let mut session = Session::default();
+        assert_eq!(session.duration().ttl(timeout), timeout);
+
+        let month = Duration::from_secs(30 * 24 * 60 * 60);
+        session.persist_for(month).unwrap();
+        assert_eq!(session.duration(), SessionDuration::Persistent(month));
+        assert_eq!(session.duration().ttl(timeout), month);
+
+        session.forget_persistence().unwrap();
+        assert_eq!(session.duration(), SessionDuration::Browser);
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+
+use super::SessionError;
+
+/// A stable, machine-readable identifier for a [`SessionError`].
+///
+/// Log the code and map it to your own, localized, user-facing text; the
+/// `Display` strings of the errors are for developers and may change
+/// between releases, the codes will not.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SessionErrorCode {
+    Storage,
+    Destroyed,
+    InvalidExperiment,
+    UnguardedWrite,
+    Poisoned,
+    SnapshotMismatch,
+    TransactionActive,
+    NoTransaction,
+    Expired,
+    Tampered,
+    StoreUnavailable,
+    TooLarge,
+    AuthLevel,
+    ReadOnly,
+}
+
+impl SessionErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionErrorCode::Storage => "session.storage",
+            SessionErrorCode::Destroyed => "session.destroyed",
+            SessionErrorCode::InvalidExperiment => "session.invalid_experiment",
+            SessionErrorCode::UnguardedWrite => "session.unguarded_write",
+            SessionErrorCode::Poisoned => "session.poisoned",
+            SessionErrorCode::SnapshotMismatch => "session.snapshot_mismatch",
+            SessionErrorCode::TransactionActive => "session.transaction_active",
+            SessionErrorCode::NoTransaction => "session.no_transaction",
+            SessionErrorCode::Expired => "session.expired",
+            SessionErrorCode::Tampered => "session.tampered",
+            SessionErrorCode::StoreUnavailable => "session.store_unavailable",
+            SessionErrorCode::TooLarge => "session.too_large",
+            SessionErrorCode::AuthLevel => "session.auth_level",
+            SessionErrorCode::ReadOnly => "session.read_only",
+        }
+    }
+}
+
+impl fmt::Display for SessionErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl SessionError {
+    pub fn code(&self) -> SessionErrorCode {
+        match self {
+            SessionError::SessionStorageError(_) => SessionErrorCode::Storage,
+            SessionError::SessionDestroyedError => SessionErrorCode::Destroyed,
+            SessionError::InvalidExperimentError(_) => SessionErrorCode::InvalidExperiment,
+            SessionError::UnguardedWriteError => SessionErrorCode::UnguardedWrite,
+            SessionError::SessionPoisonedError => SessionErrorCode::Poisoned,
+            SessionError::SnapshotMismatchError => SessionErrorCode::SnapshotMismatch,
+            SessionError::TransactionActiveError => SessionErrorCode::TransactionActive,
+            SessionError::NoTransactionError => SessionErrorCode::NoTransaction,
+            SessionError::SessionExpiredError => SessionErrorCode::Expired,
+            SessionError::SessionTamperedError => SessionErrorCode::Tampered,
+            SessionError::StoreUnavailableError(_) => SessionErrorCode::StoreUnavailable,
+            SessionError::SessionTooLargeError { .. } => SessionErrorCode::TooLarge,
+            SessionError::AuthLevelError => SessionErrorCode::AuthLevel,
+            SessionError::ReadOnlyError(_) => SessionErrorCode::ReadOnly,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_stable_and_independent_of_messages() {
+        let error = SessionError::StoreUnavailableError("connection refused".to_string());
+        assert_eq!(error.code(), SessionErrorCode::StoreUnavailable);
+        assert_eq!(error.code().to_string(), "session.store_unavailable");
+        assert!(!error.to_string().contains(error.code().as_str()));
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hash::fnv1a,
+    session::{Session, SessionError},
+    storage::Storage,
+};
+
+const EXPERIMENT_KEY_PREFIX: &str = "__experiment:";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exposure {
+    pub experiment: String,
+    pub bucket: String,
+}
+
+impl Session {
+    /// Assigns this session to a bucket of `experiment`, weighted by `weights`.
+    ///
+    /// The assignment is derived from the session key and persisted, so repeat
+    /// calls return the same bucket. Every call is recorded as an exposure.
+    pub fn experiment(
+        &mut self,
+        experiment: &str,
+        weights: &[(&str, u32)],
+    ) -> Result<String, SessionError> {
+        let subject = self.id().as_ref().to_string();
+        self.experiment_for(experiment, weights, &subject)
+    }
+
+    /// Like [`Session::experiment`], but buckets by `subject` (e.g. a user id)
+    /// so the same user lands in the same bucket across sessions.
+    pub fn experiment_for(
+        &mut self,
+        experiment: &str,
+        weights: &[(&str, u32)],
+        subject: &str,
+    ) -> Result<String, SessionError> {
+        let key = format!("{}{}", EXPERIMENT_KEY_PREFIX, experiment);
+        let stored = self
+            .get::<String>(&key)?
+            .filter(|bucket| weights.iter().any(|(name, _)| name == bucket));
+        let bucket = match stored {
+            Some(bucket) => bucket,
+            None => {
+                let bucket = assign(experiment, weights, subject)?;
+                self.insert(&key, &bucket)?;
+                bucket
+            }
+        };
+        self.exposures.push(Exposure {
+            experiment: experiment.to_string(),
+            bucket: bucket.clone(),
+        });
+        Ok(bucket)
+    }
+
+    /// Experiment exposures recorded since the session was created, loaded or
+    /// last persisted.
+    pub fn exposures(&self) -> &[Exposure] {
+        &self.exposures
+    }
+}
+
+fn assign(
+    experiment: &str,
+    weights: &[(&str, u32)],
+    subject: &str,
+) -> Result<String, SessionError> {
+    let total = weights
+        .iter()
+        .map(|(_, weight)| u64::from(*weight))
+        .sum::<u64>();
+    if total == 0 {
+        return Err(SessionError::InvalidExperimentError(experiment.to_string()));
+    }
+    let mut point = fnv1a(&[experiment.as_bytes(), b":", subject.as_bytes()]) % total;
+    for (name, weight) in weights {
+        let weight = u64::from(*weight);
+        if point < weight {
+            return Ok(name.to_string());
+        }
+        point -= weight;
+    }
+    unreachable!("point is always less than the total weight")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn experiment_returns_the_same_bucket_on_repeat_calls() {
+        let mut session = Session::default();
+        let weights = [("control", 50), ("treatment", 50)];
+        let first = session.experiment("checkout_v2", &weights).unwrap();
+        let second = session.experiment("checkout_v2", &weights).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(session.exposures().len(), 2);
+    }
+
+    #[test]
+    fn persisting_clears_recorded_exposures() {
+        let mut session = Session::default();
+        let weights = [("control", 50), ("treatment", 50)];
+        let bucket = session.experiment("checkout_v2", &weights).unwrap();
+        session.mark_persisted();
+        assert!(session.exposures().is_empty());
+
+        assert_eq!(session.experiment("checkout_v2", &weights).unwrap(), bucket);
+        assert_eq!(session.exposures().len(), 1);
+    }
+
+    #[test]
+    fn experiment_for_assigns_the_same_subject_identically_across_sessions() {
+        let weights = [("control", 1), ("a", 1), ("b", 1)];
+        let first = Session::default()
+            .experiment_for("checkout_v2", &weights, "user-42")
+            .unwrap();
+        let second = Session::default()
+            .experiment_for("checkout_v2", &weights, "user-42")
+            .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn experiment_never_assigns_zero_weight_buckets() {
+        let weights = [("control", 0), ("treatment", 1)];
+        for _ in 0..20 {
+            let bucket = Session::default()
+                .experiment("checkout_v2", &weights)
+                .unwrap();
+            assert_eq!(bucket, "treatment");
+        }
+    }
+
+    #[test]
+    fn experiment_rejects_weights_that_sum_to_zero() {
+        let result = Session::default().experiment("checkout_v2", &[("control", 0)]);
+        assert!(matches!(
+            result,
+            Err(SessionError::InvalidExperimentError(_))
+        ));
+    }
+}
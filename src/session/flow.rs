@@ -0,0 +1,205 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    storage::{Storage, StorageError, StorageGetError, StorageInsertError},
+    Session,
+};
+
+const FLOW_KEY_PREFIX: &str = "__flow:";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FlowError {
+    #[error(transparent)]
+    FlowStorageError(#[from] StorageError),
+    #[error("Flow \"{flow}\" does not allow a transition from \"{from}\" to \"{to}\"")]
+    InvalidTransitionError {
+        flow: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// Describes the steps of a multi-step form and which transitions are allowed.
+pub struct FlowDefinition {
+    name: String,
+    initial: String,
+    ttl: Duration,
+    transitions: HashMap<String, Vec<String>>,
+}
+
+impl FlowDefinition {
+    pub fn new(name: &str, initial: &str, ttl: Duration) -> Self {
+        Self {
+            name: name.to_string(),
+            initial: initial.to_string(),
+            ttl,
+            transitions: HashMap::new(),
+        }
+    }
+
+    pub fn transition(mut self, from: &str, to: &str) -> Self {
+        self.transitions
+            .entry(from.to_string())
+            .or_default()
+            .push(to.to_string());
+        self
+    }
+
+    fn allows(&self, from: &str, to: &str) -> bool {
+        self.transitions
+            .get(from)
+            .map(|targets| targets.iter().any(|target| target == to))
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FlowState {
+    step: String,
+    started_at: SystemTime,
+    inputs: HashMap<String, String>,
+}
+
+pub struct Flow<'a> {
+    session: &'a mut Session,
+    definition: &'a FlowDefinition,
+    state: FlowState,
+}
+
+impl<'a> Flow<'a> {
+    /// Resumes the flow stored in `session`, or starts it over when it is absent
+    /// or older than the definition's TTL.
+    pub fn load(
+        session: &'a mut Session,
+        definition: &'a FlowDefinition,
+    ) -> Result<Self, FlowError> {
+        let key = Self::key(definition);
+        let stored = session.get::<FlowState>(&key)?.filter(|state| {
+            let age = state.started_at.elapsed().unwrap_or_default();
+            age <= definition.ttl
+        });
+        let state = match stored {
+            Some(state) => state,
+            None => {
+                let state = FlowState {
+                    step: definition.initial.clone(),
+                    started_at: SystemTime::now(),
+                    inputs: HashMap::new(),
+                };
+                session.insert(&key, &state)?;
+                state
+            }
+        };
+        Ok(Self {
+            session,
+            definition,
+            state,
+        })
+    }
+
+    pub fn step(&self) -> &str {
+        &self.state.step
+    }
+
+    pub fn input<T: DeserializeOwned>(&self, step: &str) -> Result<Option<T>, StorageError> {
+        self.state
+            .inputs
+            .get(step)
+            .map(|v| ValueCodec::decode(v))
+            .transpose()
+            .map_err(|e| StorageGetError::DeserializeError(step.to_string(), e.to_string()))
+            .map_err(StorageError::from)
+    }
+
+    /// Records `payload` as the input of the current step and moves to `to`.
+    pub fn advance<T: Serialize>(&mut self, to: &str, payload: &T) -> Result<(), FlowError> {
+        if !self.definition.allows(&self.state.step, to) {
+            return Err(FlowError::InvalidTransitionError {
+                flow: self.definition.name.clone(),
+                from: self.state.step.clone(),
+                to: to.to_string(),
+            });
+        }
+        let step = self.state.step.clone();
+        let payload = ValueCodec::encode(payload)
+            .map_err(|e| StorageInsertError::SerializeError(step.clone(), e.to_string()))
+            .map_err(StorageError::from)?;
+        self.state.inputs.insert(step, payload);
+        self.state.step = to.to_string();
+        self.persist()
+    }
+
+    /// Removes the flow from the session.
+    pub fn finish(self) -> Result<(), FlowError> {
+        self.session
+            .remove::<FlowState>(&Self::key(self.definition))?;
+        Ok(())
+    }
+
+    fn persist(&mut self) -> Result<(), FlowError> {
+        self.session
+            .insert(&Self::key(self.definition), &self.state)?;
+        Ok(())
+    }
+
+    fn key(definition: &FlowDefinition) -> String {
+        format!("{}{}", FLOW_KEY_PREFIX, definition.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkout() -> FlowDefinition {
+        FlowDefinition::new("checkout", "shipping", Duration::from_secs(600))
+            .transition("shipping", "payment")
+            .transition("payment", "shipping")
+            .transition("payment", "review")
+    }
+
+    #[test]
+    fn advance_records_inputs_and_survives_reloading() {
+        let definition = checkout();
+        let mut session = Session::default();
+        let mut flow = Flow::load(&mut session, &definition).unwrap();
+        assert_eq!(flow.step(), "shipping");
+        flow.advance("payment", &"221B Baker Street").unwrap();
+
+        let flow = Flow::load(&mut session, &definition).unwrap();
+        assert_eq!(flow.step(), "payment");
+        let address = flow.input::<String>("shipping").unwrap().unwrap();
+        assert_eq!(address, "221B Baker Street");
+    }
+
+    #[test]
+    fn advance_rejects_transitions_that_are_not_allowed() {
+        let definition = checkout();
+        let mut session = Session::default();
+        let mut flow = Flow::load(&mut session, &definition).unwrap();
+        let result = flow.advance("review", &());
+        assert!(matches!(
+            result,
+            Err(FlowError::InvalidTransitionError { .. })
+        ));
+        assert_eq!(flow.step(), "shipping");
+    }
+
+    #[test]
+    fn load_restarts_expired_flows() {
+        let definition = FlowDefinition::new("checkout", "shipping", Duration::ZERO)
+            .transition("shipping", "payment");
+        let mut session = Session::default();
+        let mut flow = Flow::load(&mut session, &definition).unwrap();
+        flow.advance("payment", &()).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+
+        let flow = Flow::load(&mut session, &definition).unwrap();
+        assert_eq!(flow.step(), "shipping");
+    }
+}
@@ -0,0 +1,64 @@
+use serde::de::DeserializeOwned;
+
+use crate::{
+    session::Session,
+    storage::{Storage, StorageError},
+};
+
+/// A tuple of keys that [`Session::get_many`] can read in one call,
+/// producing a tuple of `Option`s in the same order.
+pub trait GetMany<Values> {
+    fn get_from(self, session: &Session) -> Result<Values, StorageError>;
+}
+
+macro_rules! impl_get_many {
+    ($(($key:ident, $value:ident)),+) => {
+        impl<$($key: AsRef<str>,)+ $($value: DeserializeOwned,)+> GetMany<($(Option<$value>,)+)>
+            for ($($key,)+)
+        {
+            #[allow(non_snake_case)]
+            fn get_from(self, session: &Session) -> Result<($(Option<$value>,)+), StorageError> {
+                let ($($key,)+) = self;
+                Ok(($(session.get::<$value>($key)?,)+))
+            }
+        }
+    };
+}
+
+impl_get_many!((K1, T1));
+impl_get_many!((K1, T1), (K2, T2));
+impl_get_many!((K1, T1), (K2, T2), (K3, T3));
+impl_get_many!((K1, T1), (K2, T2), (K3, T3), (K4, T4));
+impl_get_many!((K1, T1), (K2, T2), (K3, T3), (K4, T4), (K5, T5));
+impl_get_many!((K1, T1), (K2, T2), (K3, T3), (K4, T4), (K5, T5), (K6, T6));
+
+impl Session {
+    /// Reads several keys at once, each into its own type:
+    ///
+    /// ```ignore
+    /// let (user_id, cart): (Option<u64>, Option<Cart>) = session.get_many(("user_id", "cart"))?;
+    /// ```
+    ///
+    /// Fails on the first value that does not deserialize.
+    pub fn get_many<Values>(&self, keys: impl GetMany<Values>) -> Result<Values, StorageError> {
+        keys.get_from(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_many_reads_each_key_into_its_own_type() {
+        let mut session = Session::default();
+        session.insert("user_id", &7u64).unwrap();
+        session.insert("name", &"beavis").unwrap();
+
+        let (user_id, name, missing): (Option<u64>, Option<String>, Option<bool>) =
+            session.get_many(("user_id", "name", "missing")).unwrap();
+        assert_eq!(user_id, Some(7));
+        assert_eq!(name.as_deref(), Some("beavis"));
+        assert_eq!(missing, None);
+    }
+}
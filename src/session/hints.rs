@@ -0,0 +1,83 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    hash::fnv1a,
+    session::Session,
+    storage::{Storage, StorageError},
+};
+
+fn hint_key(name: &str) -> String {
+    format!("__hint:{name}")
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedHint<T> {
+    inputs: String,
+    value: T,
+}
+
+impl Session {
+    /// Data derived from request attributes, such as a geo lookup from the
+    /// IP or a device class from the user agent, cached under `name` for as
+    /// long as `inputs` stay the same. The inputs are kept only as a
+    /// fingerprint; a change in any of them runs `derive` again.
+    pub fn client_hint<T: Serialize + DeserializeOwned>(
+        &mut self,
+        name: &str,
+        inputs: &[&str],
+        derive: impl FnOnce() -> T,
+    ) -> Result<T, StorageError> {
+        // Separated so that `["ab", "c"]` and `["a", "bc"]` differ.
+        let parts = inputs
+            .iter()
+            .flat_map(|input| [input.as_bytes(), b"\0"])
+            .collect::<Vec<_>>();
+        // Only compares the inputs with the ones cached, so a fast unkeyed
+        // hash is enough.
+        let inputs = format!("{:016x}", fnv1a(&parts));
+        let key = hint_key(name);
+        if let Some(cached) = self.get::<CachedHint<T>>(&key)? {
+            if cached.inputs == inputs {
+                return Ok(cached.value);
+            }
+        }
+        let cached = CachedHint {
+            inputs,
+            value: derive(),
+        };
+        self.insert(&key, &cached)?;
+        Ok(cached.value)
+    }
+
+    /// Drops the cached hint `name`, so the next lookup derives it again.
+    pub fn forget_client_hint(&mut self, name: &str) {
+        self.remove_raw(&hint_key(name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_hint_is_derived_again_only_when_the_inputs_change() {
+        let mut session = Session::default();
+        let lookups = std::cell::Cell::new(0);
+        let country = |session: &mut Session, ip: &str| {
+            session
+                .client_hint("country", &[ip], || {
+                    lookups.set(lookups.get() + 1);
+                    format!("country-of-{ip}")
+                })
+                .unwrap()
+        };
+
+        assert_eq!(country(&mut session, "192.0.2.1"), "country-of-192.0.2.1");
+        assert_eq!(country(&mut session, "192.0.2.1"), "country-of-192.0.2.1");
+        assert_eq!(
+            country(&mut session, "198.51.100.7"),
+            "country-of-198.51.100.7"
+        );
+        assert_eq!(lookups.get(), 2);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{session::Session, session_state::SessionState, SessionKey};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JournalOperation {
+    Insert,
+    Remove,
+}
+
+/// A mutation made to a session since it was created or loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub key: String,
+    pub operation: JournalOperation,
+    pub source: Option<String>,
+}
+
+impl Session {
+    /// Mutations made since the session was created or loaded, oldest first.
+    pub fn journal(&self) -> &[JournalEntry] {
+        &self.journal
+    }
+
+    /// Tags subsequent mutations with `source` (e.g. the middleware making them).
+    pub fn set_journal_source(&mut self, source: Option<&str>) {
+        self.source = source.map(str::to_string);
+    }
+
+    /// This session holding `state` instead, with the same journal, for
+    /// stores that rewrite values on their way to the one they wrap.
+    pub(crate) fn with_state(&self, state: SessionState) -> Session {
+        let mut session = Session::new(self.id().clone(), state);
+        session.journal = self.journal.clone();
+        session.key_format = self.key_format;
+        session
+    }
+
+    /// A copy of this session's key, state and changes, for other handles
+    /// to read while the session itself is away being written.
+    #[cfg(any(
+        feature = "actix",
+        feature = "poem",
+        feature = "rocket",
+        feature = "tower",
+        feature = "warp"
+    ))]
+    pub(crate) fn read_copy(&self) -> Session {
+        let mut copy = self.with_state(self.state.clone());
+        copy.regenerated_from = self.regenerated_from().into();
+        copy.purged = self.purged;
+        copy.touched = self.touched;
+        copy
+    }
+
+    /// Keys inserted or removed since the session was created or loaded.
+    pub fn changed_keys(&self) -> impl Iterator<Item = &str> {
+        let mut keys = self
+            .journal
+            .iter()
+            .map(|entry| entry.key.as_str())
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys.dedup();
+        keys.into_iter()
+    }
+
+    /// Forgets the journal and recorded exposures once the session has been
+    /// written, so the next write only covers later mutations. A purged session starts over empty
+    /// under a fresh key.
+    pub fn mark_persisted(&mut self) {
+        if self.purged {
+            let defaults = self.take_defaults();
+            let key_format = self.key_format;
+            *self = Session::new(SessionKey::generate_in(key_format), Default::default());
+            self.key_format = key_format;
+            self.set_defaults(defaults);
+        } else {
+            self.journal.clear();
+            self.exposures.clear();
+            self.forget_autosaves();
+            self.touched = false;
+        }
+    }
+
+    pub(crate) fn record(&mut self, key: &str, operation: JournalOperation) {
+        self.journal.push(JournalEntry {
+            key: key.to_string(),
+            operation,
+            source: self.source.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+
+    #[test]
+    fn journal_lists_mutations_in_order_with_their_source() {
+        let mut session = Session::default();
+        session.set_journal_source(Some("csrf"));
+        session.insert("csrf", &"token").unwrap();
+        session.set_journal_source(Some("auth"));
+        session.insert("user_id", &"brandon").unwrap();
+        session.remove::<String>("csrf").unwrap();
+
+        let journal = session
+            .journal()
+            .iter()
+            .map(|entry| (entry.key.as_str(), entry.operation, entry.source.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            journal,
+            vec![
+                ("csrf", JournalOperation::Insert, Some("csrf")),
+                ("user_id", JournalOperation::Insert, Some("auth")),
+                ("csrf", JournalOperation::Remove, Some("auth")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    session::Session,
+    storage::{Storage, StorageError},
+};
+
+const CLASS_KEY_PREFIX: &str = "__key_class:";
+
+/// How long a value in a key class lives, as configured with
+/// [`SessionPolicyBuilder::with_key_class`](crate::SessionPolicyBuilder::with_key_class).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyLifetime {
+    /// Expires this long after it was written.
+    TimeToLive(Duration),
+    /// Expires once it has gone this long without being read or written.
+    TimeToIdle(Duration),
+}
+
+impl KeyLifetime {
+    pub fn duration(&self) -> Duration {
+        match self {
+            KeyLifetime::TimeToLive(duration) | KeyLifetime::TimeToIdle(duration) => *duration,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(crate) struct ClassStamp {
+    pub(crate) class: String,
+    /// When the value was written, or last used for a time-to-idle class.
+    pub(crate) at: u64,
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn class_key(key: &str) -> String {
+    format!("{CLASS_KEY_PREFIX}{key}")
+}
+
+impl Session {
+    /// Inserts `value` under `key` as a member of the key class `class`,
+    /// whose lifetime the session policy defines. Writing the key again
+    /// through `insert_in_class` restarts its lifetime.
+    pub fn insert_in_class<T: Serialize>(
+        &mut self,
+        key: &str,
+        value: &T,
+        class: &str,
+    ) -> Result<(), StorageError> {
+        self.insert(key, value)?;
+        self.stamp_class(key, class, now_millis())
+    }
+
+    /// The key class `key` was inserted into, if any.
+    pub fn key_class(&self, key: &str) -> Result<Option<String>, StorageError> {
+        let stamp = self.get::<ClassStamp>(class_key(key))?;
+        Ok(stamp.map(|stamp| stamp.class))
+    }
+
+    /// Every classed key with its stamp.
+    pub(crate) fn class_stamps(&self) -> Result<Vec<(String, ClassStamp)>, StorageError> {
+        let keys = self
+            .state()
+            .iter()
+            .filter_map(|(key, _)| key.strip_prefix(CLASS_KEY_PREFIX))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        keys.into_iter()
+            .filter_map(|key| match self.get::<ClassStamp>(class_key(&key)) {
+                Ok(stamp) => stamp.map(|stamp| Ok((key, stamp))),
+                Err(error) => Some(Err(error)),
+            })
+            .collect()
+    }
+
+    pub(crate) fn stamp_class(
+        &mut self,
+        key: &str,
+        class: &str,
+        at: u64,
+    ) -> Result<(), StorageError> {
+        let stamp = ClassStamp {
+            class: class.to_string(),
+            at,
+        };
+        self.insert(class_key(key), &stamp)
+    }
+
+    /// Removes a classed key along with its stamp.
+    pub(crate) fn expire_classed(&mut self, key: &str) {
+        self.remove_raw(key);
+        self.remove_raw(&class_key(key));
+    }
+}
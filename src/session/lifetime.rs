@@ -0,0 +1,58 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    session::{key_class::now_millis, Session},
+    storage::Storage,
+};
+
+const CREATED_AT_KEY: &str = "__created_at";
+
+impl Session {
+    /// When the session was first written to a store, for enforcing the
+    /// [absolute timeout](crate::SessionConfig::with_absolute_timeout).
+    /// `None` for sessions that were never written.
+    pub fn created_at(&self) -> Option<SystemTime> {
+        let millis = self.get::<u64>(CREATED_AT_KEY).ok().flatten()?;
+        Some(UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    /// Whether the session was created more than `absolute_timeout` ago.
+    pub fn outlived(&self, absolute_timeout: Duration) -> bool {
+        self.created_at()
+            .and_then(|created_at| created_at.elapsed().ok())
+            .is_some_and(|age| age > absolute_timeout)
+    }
+
+    /// Records the creation time of a session about to be written for the
+    /// first time. Regenerating keeps it, so logging in does not extend the
+    /// absolute timeout.
+    pub(crate) fn stamp_created(&mut self) {
+        if self.value(CREATED_AT_KEY).is_none() {
+            // Encoding a number cannot fail.
+            let _ = self.insert(CREATED_AT_KEY, &now_millis());
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn backdate_created(&mut self, age: Duration) {
+        let created_at = now_millis().saturating_sub(age.as_millis() as u64);
+        self.insert(CREATED_AT_KEY, &created_at).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamp_created_keeps_the_first_creation_time() {
+        let mut session = Session::default();
+        assert_eq!(session.created_at(), None);
+        assert!(!session.outlived(Duration::ZERO));
+
+        session.backdate_created(Duration::from_secs(120));
+        session.stamp_created();
+        assert!(session.outlived(Duration::from_secs(60)));
+        assert!(!session.outlived(Duration::from_secs(600)));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{
+    session::Session,
+    storage::{Storage, StorageError},
+};
+
+const LOCALE_KEY: &str = "__locale";
+const TIMEZONE_KEY: &str = "__timezone";
+
+impl Session {
+    pub fn locale(&self) -> Result<Option<String>, StorageError> {
+        self.get(LOCALE_KEY)
+    }
+
+    pub fn set_locale(&mut self, locale: &str) -> Result<(), StorageError> {
+        self.insert(LOCALE_KEY, &locale)
+    }
+
+    /// Returns the stored locale, negotiating one from `accept_language` first
+    /// if none is stored yet.
+    pub fn negotiate_locale(
+        &mut self,
+        accept_language: &str,
+        supported: &[&str],
+    ) -> Result<Option<String>, StorageError> {
+        if let Some(locale) = self.locale()? {
+            return Ok(Some(locale));
+        }
+        let negotiated = negotiate(accept_language, supported);
+        if let Some(locale) = &negotiated {
+            self.set_locale(locale)?;
+        }
+        Ok(negotiated)
+    }
+
+    pub fn timezone(&self) -> Result<Option<String>, StorageError> {
+        self.get(TIMEZONE_KEY)
+    }
+
+    pub fn set_timezone(&mut self, timezone: &str) -> Result<(), StorageError> {
+        self.insert(TIMEZONE_KEY, &timezone)
+    }
+}
+
+/// Picks the best `supported` locale for an `Accept-Language` header value.
+pub fn negotiate(accept_language: &str, supported: &[&str]) -> Option<String> {
+    let mut requested = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+        })
+        .collect::<Vec<_>>();
+    requested.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    let primary = |tag: &str| tag.split('-').next().unwrap_or(tag).to_ascii_lowercase();
+    requested.iter().find_map(|(tag, _)| {
+        if *tag == "*" {
+            return supported.first().map(|locale| locale.to_string());
+        }
+        supported
+            .iter()
+            .find(|locale| locale.eq_ignore_ascii_case(tag))
+            .or_else(|| {
+                supported
+                    .iter()
+                    .find(|locale| primary(locale) == primary(tag))
+            })
+            .map(|locale| locale.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_prefers_exact_matches_by_quality() {
+        let supported = ["en-US", "de-DE", "fr"];
+        assert_eq!(
+            negotiate("fr;q=0.5, de-DE;q=0.9, en;q=0.1", &supported),
+            Some("de-DE".to_string())
+        );
+    }
+
+    #[test]
+    fn negotiate_falls_back_to_the_primary_language() {
+        let supported = ["en-US", "de-DE"];
+        assert_eq!(negotiate("de-AT", &supported), Some("de-DE".to_string()));
+        assert_eq!(
+            negotiate("ja, *;q=0.1", &supported),
+            Some("en-US".to_string())
+        );
+        assert_eq!(negotiate("ja", &supported), None);
+    }
+
+    #[test]
+    fn negotiate_locale_keeps_an_explicitly_set_locale() {
+        let mut session = Session::default();
+        session.set_locale("fr").unwrap();
+        let locale = session
+            .negotiate_locale("de", &["de", "fr"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(locale, "fr");
+    }
+}
@@ -0,0 +1,77 @@
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    idempotency::{IdempotencyClaim, IdempotencyStore},
+    storage::{Storage, StorageError},
+    Session, SessionModel, SessionStore,
+};
+
+fn one_time_key(action: &str) -> String {
+    format!("__one_time:{action}")
+}
+
+impl Session {
+    /// Issues a single-use token for `action`, replacing any unconsumed one.
+    pub fn one_time(&mut self, action: &str) -> Result<String, StorageError> {
+        let token = (0..32)
+            .map(|_| char::from(OsRng.sample(Alphanumeric)))
+            .collect::<String>();
+        self.insert(one_time_key(action), &token)?;
+        Ok(token)
+    }
+
+    fn one_time_matches(&self, action: &str, token: &str) -> bool {
+        self.get::<String>(one_time_key(action))
+            .ok()
+            .flatten()
+            // Constant-time, so response timing reveals nothing of the
+            // issued token.
+            .is_some_and(|issued| bool::from(issued.as_bytes().ct_eq(token.as_bytes())))
+    }
+}
+
+impl<Store> SessionModel<Store>
+where
+    Store: SessionStore + IdempotencyStore<Error = <Store as SessionStore>::Error>,
+{
+    /// Consumes the token issued by [`Session::one_time`], returning whether it
+    /// was valid. The token is claimed in the store so that a replay against a
+    /// stale copy of the session on another node is also rejected.
+    pub async fn consume_one_time(
+        &mut self,
+        action: &str,
+        token: &str,
+    ) -> Result<bool, <Store as SessionStore>::Error> {
+        if !self.session().one_time_matches(action, token) {
+            return Ok(false);
+        }
+        let key = one_time_key(action);
+        let claim = self
+            .store()
+            .claim(self.id(), &format!("{key}:{token}"), token, self.timeout())
+            .await?;
+        if claim != IdempotencyClaim::Claimed {
+            return Ok(false);
+        }
+        self.session_mut().remove_raw(&key);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_time_replaces_the_previously_issued_token() {
+        let mut session = Session::default();
+        let first = session.one_time("confirm-payment").unwrap();
+        let second = session.one_time("confirm-payment").unwrap();
+
+        assert_ne!(first, second);
+        assert!(!session.one_time_matches("confirm-payment", &first));
+        assert!(session.one_time_matches("confirm-payment", &second));
+        assert!(!session.one_time_matches("delete-account", &second));
+    }
+}
@@ -0,0 +1,54 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use super::Session;
+use crate::SessionKey;
+
+pub(crate) type PostCommit = Box<dyn FnOnce(&SessionKey) + Send>;
+
+impl Session {
+    /// Queues `callback` to run once the session has been saved to the store.
+    pub fn after_commit(&mut self, callback: impl FnOnce(&SessionKey) + Send + 'static) {
+        self.post_commit
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Box::new(callback));
+    }
+
+    /// Runs and clears the queued callbacks, returning how many panicked.
+    ///
+    /// A panicking callback does not stop the others from running. Call this
+    /// only after a successful save; `SessionModel::save` does so itself.
+    pub fn run_post_commit(&self) -> usize {
+        let callbacks =
+            std::mem::take(&mut *self.post_commit.lock().unwrap_or_else(|e| e.into_inner()));
+        callbacks
+            .into_iter()
+            .map(|callback| panic::catch_unwind(AssertUnwindSafe(|| callback(&self.id))))
+            .filter(Result::is_err)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn run_post_commit_runs_each_callback_once_despite_panics() {
+        let ran = Arc::new(AtomicUsize::new(0));
+        let mut session = Session::default();
+        let counter = ran.clone();
+        session.after_commit(|_| panic!("welcome email failed"));
+        session.after_commit(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        assert_eq!(session.run_post_commit(), 1);
+        assert_eq!(session.run_post_commit(), 0);
+        assert_eq!(ran.load(Ordering::SeqCst), 1);
+    }
+}
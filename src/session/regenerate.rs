@@ -0,0 +1,71 @@
+use super::{JournalOperation, Session};
+use crate::{KeyFormat, SessionKey};
+
+impl Session {
+    /// Moves the session to a fresh key, keeping its state, to defend against
+    /// session fixation. The store copy under the old key must be destroyed;
+    /// `SessionModel::save` does so.
+    pub fn regenerate(&mut self) {
+        let fresh = self.id.regenerated_in(self.key_format);
+        let previous = std::mem::replace(&mut self.id, fresh);
+        self.regenerated_from
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert(previous);
+        let keys = self
+            .state
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in keys {
+            self.record(&key, JournalOperation::Insert);
+        }
+    }
+
+    /// Draws the fresh keys of [`regenerate`](Self::regenerate) and of a
+    /// purged session from `format`. The web integrations use their
+    /// config's [key format](crate::SessionConfig::with_key_format).
+    pub fn set_key_format(&mut self, format: KeyFormat) {
+        self.key_format = format;
+    }
+
+    /// The stored key this session was regenerated from and that has not
+    /// been destroyed yet.
+    pub fn regenerated_from(&self) -> Option<SessionKey> {
+        self.regenerated_from
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub(crate) fn take_regenerated_from(&self) -> Option<SessionKey> {
+        self.regenerated_from
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+
+    #[test]
+    fn regenerate_keeps_state_and_remembers_the_first_stored_key() {
+        let mut session = Session::default();
+        session.insert("cart", &vec!["socks"]).unwrap();
+        let stored = session.id().clone();
+
+        session.regenerate();
+        session.regenerate();
+        assert_ne!(session.id(), &stored);
+        assert_eq!(session.regenerated_from(), Some(stored));
+        assert_eq!(
+            session.get::<Vec<String>>("cart").unwrap().unwrap(),
+            vec!["socks"]
+        );
+        assert!(session.take_regenerated_from().is_some());
+        assert_eq!(session.regenerated_from(), None);
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
+
+use super::{Session, SessionError};
+use crate::{
+    codec::{Codec, ValueCodec},
+    session_state::{SessionState, StateDiff},
+    storage::{Storage, StorageError, StorageGetError, StorageInsertError, StorageRemoveError},
+    SessionKey,
+};
+
+/// An owned, `Send + 'static` copy of a session for background tasks.
+///
+/// Clones share the captured state until one of them is written to.
+#[derive(Clone, Debug)]
+pub struct SessionSnapshot {
+    id: SessionKey,
+    base: Arc<SessionState>,
+    state: Arc<SessionState>,
+}
+
+impl Session {
+    pub fn to_owned_snapshot(&self) -> SessionSnapshot {
+        let state = Arc::new(self.state.clone());
+        SessionSnapshot {
+            id: self.id.clone(),
+            base: state.clone(),
+            state,
+        }
+    }
+}
+
+impl SessionSnapshot {
+    pub fn id(&self) -> &SessionKey {
+        &self.id
+    }
+
+    pub fn state(&self) -> &SessionState {
+        &self.state
+    }
+
+    /// The changes made to this snapshot since it was taken.
+    pub fn diff(&self) -> StateDiff {
+        StateDiff::between(&self.base, &self.state)
+    }
+
+    /// Replays the keys changed on this snapshot onto `session`.
+    pub fn apply_back(&self, session: &mut Session) -> Result<(), SessionError> {
+        if session.id() != &self.id {
+            return Err(SessionError::SnapshotMismatchError);
+        }
+        let diff = self.diff();
+        for (key, value) in diff.inserted.into_iter().chain(diff.updated) {
+            session.insert_raw(&key, value);
+        }
+        for key in diff.removed {
+            session.remove_raw(&key);
+        }
+        Ok(())
+    }
+}
+
+impl<K: AsRef<str>> Storage<K> for SessionSnapshot {
+    type Error = StorageError;
+
+    fn insert<T: Serialize>(&mut self, key: K, value: &T) -> Result<(), Self::Error> {
+        let key = key.as_ref();
+        let insert = ValueCodec::encode(value)
+            .map_err(|e| StorageInsertError::SerializeError(key.to_string(), e.to_string()))
+            .map_err(StorageError::from)?;
+        Arc::make_mut(&mut self.state).insert(key, insert);
+        Ok(())
+    }
+
+    fn remove<T: DeserializeOwned>(&mut self, key: K) -> Result<Option<T>, Self::Error> {
+        let key = key.as_ref();
+        if self.state.get(key).is_none() {
+            return Ok(None);
+        }
+        Arc::make_mut(&mut self.state)
+            .remove(key)
+            .map(|v| ValueCodec::decode(&v))
+            .transpose()
+            .map_err(|e| StorageRemoveError::DeserializeError(key.to_string(), e.to_string()))
+            .map_err(StorageError::from)
+    }
+
+    fn get<T: DeserializeOwned>(&self, key: K) -> Result<Option<T>, Self::Error> {
+        let key = key.as_ref();
+        self.state
+            .get(key)
+            .map(|v| ValueCodec::decode(v))
+            .transpose()
+            .map_err(|e| StorageGetError::DeserializeError(key.to_string(), e.to_string()))
+            .map_err(StorageError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_are_readable_from_spawned_threads() {
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        let snapshot = session.to_owned_snapshot();
+
+        let user_id = std::thread::spawn(move || snapshot.get::<String>("user_id"))
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(user_id.as_deref(), Some("beavis"));
+    }
+
+    #[test]
+    fn apply_back_replays_only_the_snapshot_changes() {
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        session.insert("cart", &vec!["socks"]).unwrap();
+        let mut snapshot = session.to_owned_snapshot();
+        snapshot.insert("cart", &vec!["shoes"]).unwrap();
+        snapshot.remove::<String>("user_id").unwrap();
+        session.insert("theme", &"dark").unwrap();
+
+        snapshot.apply_back(&mut session).unwrap();
+        assert_eq!(
+            session.get::<Vec<String>>("cart").unwrap().unwrap(),
+            vec!["shoes"]
+        );
+        assert_eq!(session.get::<String>("user_id").unwrap(), None);
+        assert_eq!(
+            session.get::<String>("theme").unwrap().as_deref(),
+            Some("dark")
+        );
+    }
+
+    #[test]
+    fn apply_back_rejects_a_different_session() {
+        let snapshot = Session::default().to_owned_snapshot();
+        let result = snapshot.apply_back(&mut Session::default());
+        assert!(matches!(result, Err(SessionError::SnapshotMismatchError)));
+    }
+}
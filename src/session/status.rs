@@ -0,0 +1,67 @@
+use super::Session;
+
+/// What a request did to its session, and so what the middleware must do to
+/// the store and the cookie.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionStatus {
+    Unchanged,
+    Changed,
+    /// Moved to a fresh key by [`Session::regenerate`].
+    Renewed,
+    /// Cleared by [`Session::purge`]; the stored copy must be destroyed.
+    Purged,
+}
+
+impl Session {
+    pub fn status(&self) -> SessionStatus {
+        if self.purged {
+            SessionStatus::Purged
+        } else if self.regenerated_from().is_some() {
+            SessionStatus::Renewed
+        } else if self.journal.is_empty() && !self.touched && !self.has_unsettled_defaults() {
+            SessionStatus::Unchanged
+        } else {
+            SessionStatus::Changed
+        }
+    }
+
+    /// Marks the session changed without changing it, so the next write
+    /// extends its expiry.
+    pub fn touch(&mut self) {
+        self.touched = true;
+    }
+
+    /// Clears the session and marks it for destruction, e.g. on logout.
+    pub fn purge(&mut self) {
+        let keys = self
+            .state
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in keys {
+            self.remove_raw(&key);
+        }
+        self.purged = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+
+    #[test]
+    fn status_reflects_the_most_drastic_change() {
+        let mut session = Session::default();
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        session.touch();
+        assert_eq!(session.status(), SessionStatus::Changed);
+        session.insert("theme", &"dark").unwrap();
+        assert_eq!(session.status(), SessionStatus::Changed);
+        session.regenerate();
+        assert_eq!(session.status(), SessionStatus::Renewed);
+        session.purge();
+        assert_eq!(session.status(), SessionStatus::Purged);
+        assert!(session.state().is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    session::Session,
+    storage::{Storage, StorageError},
+};
+
+pub(crate) const TAGS_KEY: &str = "__tags";
+
+impl Session {
+    pub fn tags(&self) -> Result<BTreeSet<String>, StorageError> {
+        Ok(self.get(TAGS_KEY)?.unwrap_or_default())
+    }
+
+    pub fn has_tag(&self, tag: &str) -> Result<bool, StorageError> {
+        Ok(self.tags()?.contains(tag))
+    }
+
+    pub fn tag(&mut self, tag: &str) -> Result<(), StorageError> {
+        let mut tags = self.tags()?;
+        if tags.insert(tag.to_string()) {
+            self.insert(TAGS_KEY, &tags)?;
+        }
+        Ok(())
+    }
+
+    pub fn untag(&mut self, tag: &str) -> Result<(), StorageError> {
+        let mut tags = self.tags()?;
+        if tags.remove(tag) {
+            self.insert(TAGS_KEY, &tags)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_and_untag_update_the_stored_tags() {
+        let mut session = Session::default();
+        session.tag("kiosk").unwrap();
+        session.tag("beta-user").unwrap();
+        session.tag("kiosk").unwrap();
+        session.untag("kiosk").unwrap();
+
+        assert!(session.has_tag("beta-user").unwrap());
+        assert!(!session.has_tag("kiosk").unwrap());
+        assert_eq!(session.tags().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,108 @@
+use super::{Session, SessionError};
+
+impl Session {
+    /// Starts staging inserts and removes; reads through `Storage` see the
+    /// staged values, but `state()` and the stores do not until `commit`.
+    pub fn begin(&mut self) -> Result<(), SessionError> {
+        if self.staged.is_some() {
+            return Err(SessionError::TransactionActiveError);
+        }
+        self.staged = Some(Vec::new());
+        Ok(())
+    }
+
+    pub fn commit(&mut self) -> Result<(), SessionError> {
+        let staged = self.staged.take().ok_or(SessionError::NoTransactionError)?;
+        for (key, value) in staged {
+            match value {
+                Some(value) => self.insert_raw(&key, value),
+                None => {
+                    self.remove_raw(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn abort(&mut self) -> Result<(), SessionError> {
+        self.staged
+            .take()
+            .map(drop)
+            .ok_or(SessionError::NoTransactionError)
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.staged.is_some()
+    }
+
+    /// The latest staged value for `key`, if the open transaction touched it.
+    pub(crate) fn staged_value(&self, key: &str) -> Option<Option<&String>> {
+        self.staged
+            .as_ref()?
+            .iter()
+            .rev()
+            .find(|(staged, _)| staged == key)
+            .map(|(_, value)| value.as_ref())
+    }
+
+    /// Stages the mutation if a transaction is open, returning whether it did.
+    pub(crate) fn stage(&mut self, key: &str, value: Option<String>) -> bool {
+        match &mut self.staged {
+            Some(staged) => {
+                staged.push((key.to_string(), value));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec::{Codec, ValueCodec},
+        storage::Storage,
+    };
+
+    #[test]
+    fn commit_applies_staged_writes_and_abort_discards_them() {
+        let mut session = Session::default();
+        session.insert("cart", &vec!["socks"]).unwrap();
+
+        session.begin().unwrap();
+        session.insert("cart", &vec!["shoes"]).unwrap();
+        session.remove::<String>("coupon").unwrap();
+        assert_eq!(
+            session.get::<Vec<String>>("cart").unwrap().unwrap(),
+            vec!["shoes"]
+        );
+        let socks = ValueCodec::encode(&vec!["socks"]).unwrap();
+        assert_eq!(session.state().get("cart").unwrap(), &socks);
+        session.abort().unwrap();
+        assert_eq!(
+            session.get::<Vec<String>>("cart").unwrap().unwrap(),
+            vec!["socks"]
+        );
+        assert_eq!(session.changed_keys().collect::<Vec<_>>(), vec!["cart"]);
+
+        session.begin().unwrap();
+        session.remove::<Vec<String>>("cart").unwrap();
+        session.commit().unwrap();
+        assert_eq!(session.get::<Vec<String>>("cart").unwrap(), None);
+    }
+
+    #[test]
+    fn transactions_do_not_nest() {
+        let mut session = Session::default();
+        assert!(matches!(
+            session.commit(),
+            Err(SessionError::NoTransactionError)
+        ));
+        session.begin().unwrap();
+        assert!(matches!(
+            session.begin(),
+            Err(SessionError::TransactionActiveError)
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    storage::{Storage, StorageError, StorageGetError},
+    Session,
+};
+
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub classification: Option<&'static str>,
+    pub redact: bool,
+}
+
+/// A struct stored in the session under a fixed key with a schema version.
+///
+/// Usually derived with `#[derive(SessionData)]` (feature `derive`).
+pub trait SessionData: Serialize + DeserializeOwned {
+    const KEY: &'static str;
+    const VERSION: u32;
+
+    fn fields() -> &'static [FieldInfo];
+
+    /// Serializes `self` with every `#[session(redact)]` field masked.
+    fn redacted(&self) -> Result<serde_json::Value, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(object) = value.as_object_mut() {
+            for field in Self::fields().iter().filter(|field| field.redact) {
+                if let Some(entry) = object.get_mut(field.name) {
+                    *entry = serde_json::Value::String(REDACTED.to_string());
+                }
+            }
+        }
+        Ok(value)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Versioned<T> {
+    version: u32,
+    data: T,
+}
+
+impl Session {
+    pub fn insert_data<T: SessionData>(&mut self, data: &T) -> Result<(), StorageError> {
+        self.insert(
+            T::KEY,
+            &Versioned {
+                version: T::VERSION,
+                data,
+            },
+        )
+    }
+
+    pub fn get_data<T: SessionData>(&self) -> Result<Option<T>, StorageError> {
+        let version = match self.get::<Versioned<serde_json::Value>>(T::KEY)? {
+            Some(versioned) => versioned.version,
+            None => return Ok(None),
+        };
+        if version != T::VERSION {
+            return Err(StorageGetError::VersionMismatchError(
+                T::KEY.to_string(),
+                T::VERSION,
+                version,
+            )
+            .into());
+        }
+        let versioned = self.get::<Versioned<T>>(T::KEY)?;
+        Ok(versioned.map(|versioned| versioned.data))
+    }
+
+    pub fn remove_data<T: SessionData>(&mut self) -> Result<Option<T>, StorageError> {
+        let data = self.get_data::<T>()?;
+        self.remove::<serde_json::Value>(T::KEY)?;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Cart {
+        items: Vec<String>,
+        card: String,
+    }
+
+    impl SessionData for Cart {
+        const KEY: &'static str = "cart";
+        const VERSION: u32 = 2;
+
+        fn fields() -> &'static [FieldInfo] {
+            &[
+                FieldInfo {
+                    name: "items",
+                    classification: None,
+                    redact: false,
+                },
+                FieldInfo {
+                    name: "card",
+                    classification: Some("pci"),
+                    redact: true,
+                },
+            ]
+        }
+    }
+
+    #[test]
+    fn redacted_masks_redacted_fields() {
+        let cart = Cart {
+            items: vec!["book".to_string()],
+            card: "4111".to_string(),
+        };
+        let value = cart.redacted().unwrap();
+        assert_eq!(value["items"][0], "book");
+        assert_eq!(value["card"], REDACTED);
+    }
+
+    #[test]
+    fn get_data_rejects_a_stored_version_mismatch() {
+        let mut session = Session::default();
+        session
+            .insert(
+                Cart::KEY,
+                &Versioned {
+                    version: 1,
+                    data: serde_json::json!({ "items": [], "card": "" }),
+                },
+            )
+            .unwrap();
+        let error = session.get_data::<Cart>().err().unwrap();
+        assert!(matches!(
+            error,
+            StorageError::StorageGetError(StorageGetError::VersionMismatchError(_, 2, 1))
+        ));
+    }
+}
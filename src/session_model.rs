@@ -2,6 +2,9 @@ use serde::{de::DeserializeOwned, Serialize};
 use std::time::Duration;
 
 use crate::{
+    codec::CodecError,
+    idempotency::{Idempotency, IdempotencyStore},
+    policy::SessionPolicy,
     storage::{Storage, StorageError},
     Session, SessionKey, SessionStore,
 };
@@ -10,6 +13,7 @@ pub struct SessionModel<Store: SessionStore> {
     store: Store,
     session: Session,
     duration: Duration,
+    policy: Option<SessionPolicy>,
 }
 
 impl<Store: SessionStore> SessionModel<Store> {
@@ -18,9 +22,17 @@ impl<Store: SessionStore> SessionModel<Store> {
             store,
             duration,
             session: Default::default(),
+            policy: None,
         }
     }
 
+    /// Enforces the policy's regeneration rules on every write through the
+    /// model.
+    pub fn with_policy(mut self, policy: SessionPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
     pub async fn load(store: Store, id: &SessionKey) -> Result<Option<Self>, Store::Error> {
         let session = store.load(id).await?;
         let duration = store.ttl(id).await?;
@@ -28,6 +40,7 @@ impl<Store: SessionStore> SessionModel<Store> {
             store,
             session,
             duration,
+            policy: None,
         });
         Ok(model)
     }
@@ -36,14 +49,25 @@ impl<Store: SessionStore> SessionModel<Store> {
         self.session.id()
     }
 
-    pub async fn save(&self) -> Result<(), Store::Error> {
+    /// Writes the session and forgets its journal, so the next save, and
+    /// stores that only write changed keys, cover later mutations alone.
+    pub async fn save(&mut self) -> Result<(), Store::Error> {
+        if let Some(previous) = self.session.regenerated_from() {
+            self.store.destroy(&previous).await?;
+            self.session.take_regenerated_from();
+        }
+        self.session.stamp_created();
         let id = self.session.id();
+        let timeout = self.session.duration().ttl(self.duration);
         let exists = self.store.exists(id).await?;
         if exists {
-            self.store.update(&self.session, self.duration).await
+            self.store.update(&self.session, timeout).await?;
         } else {
-            self.store.save(&self.session, self.duration).await
+            self.store.save(&self.session, timeout).await?;
         }
+        self.session.run_post_commit();
+        self.session.mark_persisted();
+        Ok(())
     }
 
     pub async fn destroy(&self) -> Result<(), Store::Error> {
@@ -59,20 +83,51 @@ impl<Store: SessionStore> SessionModel<Store> {
     pub fn timeout(&self) -> Duration {
         self.duration
     }
+
+    fn enforce_regeneration(&mut self) {
+        if let Some(policy) = &self.policy {
+            policy.enforce_regeneration(&mut self.session);
+        }
+    }
+
+    pub(crate) fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub(crate) fn session_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
 }
 
-impl<Store: SessionStore> Storage<&str> for SessionModel<Store> {
+impl<Store> SessionModel<Store>
+where
+    Store: SessionStore + IdempotencyStore<Error = <Store as SessionStore>::Error>,
+    <Store as SessionStore>::Error: From<CodecError>,
+{
+    pub fn idempotency(&self, key: &str, ttl: Duration) -> Idempotency<'_, Store> {
+        Idempotency::new(self, key, ttl)
+    }
+}
+
+impl<Store: SessionStore, K: AsRef<str>> Storage<K> for SessionModel<Store> {
     type Error = StorageError;
 
-    fn insert<T: Serialize>(&mut self, key: &str, value: &T) -> Result<(), Self::Error> {
-        self.session.insert(key, value)
+    fn insert<T: Serialize>(&mut self, key: K, value: &T) -> Result<(), Self::Error> {
+        let key = key.as_ref();
+        self.session.insert(key, value)?;
+        self.enforce_regeneration();
+        Ok(())
     }
 
-    fn remove<T: DeserializeOwned>(&mut self, key: &str) -> Result<Option<T>, Self::Error> {
-        self.session.remove(key)
+    fn remove<T: DeserializeOwned>(&mut self, key: K) -> Result<Option<T>, Self::Error> {
+        let key = key.as_ref();
+        let removed = self.session.remove(key)?;
+        self.enforce_regeneration();
+        Ok(removed)
     }
 
-    fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Self::Error> {
+    fn get<T: DeserializeOwned>(&self, key: K) -> Result<Option<T>, Self::Error> {
+        let key = key.as_ref();
         self.session.get(key)
     }
 }
@@ -86,7 +141,45 @@ impl<Store: SessionStore> From<SessionModel<Store>> for Session {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{storage::Storage, RedisSessionStore};
+    use crate::{storage::Storage, MemorySessionStore, RedisSessionStore};
+
+    #[tokio::test]
+    async fn save_forgets_the_keys_it_wrote() {
+        let store = MemorySessionStore::new();
+        let mut model = SessionModel::new(&store, Duration::from_secs(10));
+        model.insert("user_id", &"beavis").unwrap();
+        model.save().await.expect("Unable to save session");
+        assert_eq!(model.session().changed_keys().count(), 0);
+
+        model.insert("theme", &"dark").unwrap();
+        let changed = model.session().changed_keys().collect::<Vec<_>>();
+        assert_eq!(changed, vec!["theme"]);
+    }
+
+    #[tokio::test]
+    async fn unrelated_writes_after_a_save_keep_the_regenerated_key() {
+        let store = MemorySessionStore::new();
+        let policy = SessionPolicy::builder()
+            .regenerate_on_change("user_id")
+            .build()
+            .unwrap();
+        let mut model = SessionModel::new(&store, Duration::from_secs(10)).with_policy(policy);
+        model.insert("theme", &"dark").unwrap();
+        model.save().await.unwrap();
+        let anonymous = model.id().clone();
+
+        model.insert("user_id", &"beavis").unwrap();
+        assert_ne!(model.id(), &anonymous);
+        model.save().await.unwrap();
+        assert!(!store.exists(&anonymous).await.unwrap());
+        let logged_in = model.id().clone();
+
+        model.insert("cart", &["book"]).unwrap();
+        assert_eq!(model.id(), &logged_in);
+        model.save().await.unwrap();
+        assert_eq!(model.id(), &logged_in);
+        assert!(store.exists(&logged_in).await.unwrap());
+    }
 
     #[tokio::test]
     async fn save_commits_new_sessions_to_the_store() {
@@ -203,4 +296,32 @@ mod tests {
             .expect("Unable to check exists");
         assert!(!exists);
     }
+
+    #[tokio::test]
+    async fn consume_one_time_accepts_each_token_once() {
+        let store = RedisSessionStore::new("redis://:password@localhost:6379/1")
+            .await
+            .expect("Unable to connect to Redis");
+        let mut model = SessionModel::new(&store, Duration::from_secs(10));
+        let token = model
+            .session_mut()
+            .one_time("confirm-payment")
+            .expect("Unable to issue token");
+        model.save().await.expect("Unable to save session");
+        let mut stale = SessionModel::load(&store, model.id())
+            .await
+            .expect("Unable to load session")
+            .expect("Unable to find saved session");
+
+        let consumed = model
+            .consume_one_time("confirm-payment", &token)
+            .await
+            .expect("Unable to consume token");
+        assert!(consumed);
+        let replayed = stale
+            .consume_one_time("confirm-payment", &token)
+            .await
+            .expect("Unable to consume token");
+        assert!(!replayed);
+    }
 }
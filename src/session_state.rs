@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Clone, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
 pub struct SessionState(HashMap<String, String>);
 
 impl SessionState {
@@ -16,4 +16,93 @@ impl SessionState {
     pub fn get(&self, key: &str) -> Option<&String> {
         self.0.get(key)
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StateDiff {
+    pub inserted: HashMap<String, String>,
+    pub updated: HashMap<String, String>,
+    pub removed: Vec<String>,
+}
+
+impl StateDiff {
+    pub fn between(old: &SessionState, new: &SessionState) -> Self {
+        let mut diff = Self::default();
+        for (key, value) in new.iter() {
+            match old.get(key) {
+                None => {
+                    diff.inserted.insert(key.clone(), value.clone());
+                }
+                Some(previous) if previous != value => {
+                    diff.updated.insert(key.clone(), value.clone());
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .iter()
+            .filter(|(key, _)| new.get(key).is_none())
+            .map(|(key, _)| key.clone())
+            .collect();
+        diff.removed.sort();
+        diff
+    }
+
+    pub fn apply(&self, state: &mut SessionState) {
+        for (key, value) in self.inserted.iter().chain(self.updated.iter()) {
+            state.insert(key, value.clone());
+        }
+        for key in &self.removed {
+            state.remove(key);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(entries: &[(&str, &str)]) -> SessionState {
+        let mut state = SessionState::default();
+        for (key, value) in entries {
+            state.insert(key, value.to_string());
+        }
+        state
+    }
+
+    #[test]
+    fn between_reports_inserted_updated_and_removed_keys() {
+        let old = state(&[("user_id", "beavis"), ("cart", "[]"), ("theme", "dark")]);
+        let new = state(&[("user_id", "butt-head"), ("theme", "dark"), ("csrf", "x")]);
+
+        let diff = StateDiff::between(&old, &new);
+        assert_eq!(diff.inserted, HashMap::from([("csrf".into(), "x".into())]));
+        assert_eq!(
+            diff.updated,
+            HashMap::from([("user_id".into(), "butt-head".into())])
+        );
+        assert_eq!(diff.removed, vec!["cart".to_string()]);
+    }
+
+    #[test]
+    fn apply_turns_the_old_state_into_the_new_state() {
+        let old = state(&[("user_id", "beavis"), ("cart", "[]")]);
+        let new = state(&[("user_id", "butt-head"), ("csrf", "x")]);
+
+        let mut applied = old.clone();
+        StateDiff::between(&old, &new).apply(&mut applied);
+        assert_eq!(applied, new);
+    }
 }
@@ -1,7 +1,99 @@
+mod archiving_session_store;
+mod cached_session_store;
+pub mod conformance;
+mod cookie_session_store;
+mod deadline_session_store;
+mod deferred_deletion_session_store;
+#[cfg(feature = "dynamodb")]
+mod dynamodb_session_store;
+mod encrypted_session_store;
+#[cfg(feature = "etcd")]
+mod etcd_session_store;
+mod event_sourced_session_store;
+mod file_session_store;
+mod history_session_store;
+#[cfg(feature = "json")]
+mod jwt_session_store;
+mod key_format;
+#[cfg(feature = "memcached")]
+mod memcached_session_store;
+mod memory_session_store;
+mod merging_session_store;
+mod metrics_session_store;
+#[cfg(feature = "mongodb")]
+mod mongo_session_store;
+#[cfg(feature = "mysql")]
+mod mysql_session_store;
+mod observed_session_store;
+#[cfg(feature = "postgres")]
+mod postgres_session_store;
+mod pre_expiry_session_store;
+mod priority_session_store;
+mod read_only_session_store;
 mod redis_session_store;
+mod replica_session_store;
 mod session_key;
+#[allow(clippy::module_inception)]
 mod session_store;
+mod shadow_session_store;
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+mod sql;
+#[cfg(feature = "sqlite")]
+mod sqlite_session_store;
+mod store_builder;
+#[cfg(test)]
+pub(crate) mod testing;
+mod watch;
+mod write_behind_session_store;
 
-pub use redis_session_store::{RedisSessionStore, StoreError as RedisSessionStoreError};
+pub use archiving_session_store::{ArchivingSessionStore, ArchivingStoreError};
+pub use cached_session_store::CachedSessionStore;
+pub use cookie_session_store::{CookieSessionStore, CookieStoreError};
+pub use deadline_session_store::{Deadline, DeadlineSessionStore, DeadlineStoreError};
+pub use deferred_deletion_session_store::{DeferredDeletionError, DeferredDeletionSessionStore};
+#[cfg(feature = "dynamodb")]
+pub use dynamodb_session_store::{DynamoDbSessionStore, DynamoDbStoreError};
+pub use encrypted_session_store::{EncryptedSessionStore, EncryptedStoreError};
+#[cfg(feature = "etcd")]
+pub use etcd_session_store::{EtcdSessionStore, EtcdStoreError};
+pub use event_sourced_session_store::{
+    EventLog, EventLogRecord, EventSourcedSessionStore, RedisEventLog, SessionMutation,
+};
+pub use file_session_store::{FileSessionStore, FileStoreError};
+pub use history_session_store::{HistorySessionStore, HistoryStoreError};
+#[cfg(feature = "json")]
+pub use jwt_session_store::{JwtAlgorithm, JwtSessionStore, JwtStoreError};
+#[cfg(feature = "cbor")]
+pub(crate) use key_format::{decode_base64_url, encode_base64_url};
+pub use key_format::{KeyEncoding, KeyFormat};
+#[cfg(feature = "memcached")]
+pub use memcached_session_store::{MemcachedSessionStore, MemcachedStoreError};
+pub use memory_session_store::{MemorySessionStore, MemoryStoreError};
+pub use merging_session_store::MergingSessionStore;
+pub use metrics_session_store::{
+    MetricsSessionStore, OperationMetrics, StoreMetrics, StoreOperation,
+};
+#[cfg(feature = "mongodb")]
+pub use mongo_session_store::{MongoSessionStore, MongoStoreError};
+#[cfg(feature = "mysql")]
+pub use mysql_session_store::{MySqlSessionStore, MySqlStoreError};
+pub use observed_session_store::{ObservedSessionStore, ObservedStoreError};
+#[cfg(feature = "postgres")]
+pub use postgres_session_store::{PostgresSessionStore, PostgresStoreError};
+pub use pre_expiry_session_store::PreExpirySessionStore;
+pub use priority_session_store::{Lane, PrioritySessionStore};
+pub use read_only_session_store::{
+    MaintenanceMode, ReadOnlyMode, ReadOnlySessionStore, ReadOnlyStoreError,
+};
+pub use redis_session_store::{
+    RedisOptions, RedisSessionStore, StoreError as RedisSessionStoreError,
+};
+pub use replica_session_store::{ReplicaSessionStore, ReplicaStoreError};
 pub use session_key::SessionKey;
-pub use session_store::SessionStore;
+pub use session_store::{SelfTestError, SessionSample, SessionStore};
+pub use shadow_session_store::{ShadowMismatch, ShadowSessionStore};
+#[cfg(feature = "sqlite")]
+pub use sqlite_session_store::{SqliteSessionStore, SqliteStoreError};
+pub use store_builder::{Layer, StoreBuilder, StoreBuilderError};
+pub use watch::SessionChange;
+pub use write_behind_session_store::WriteBehindSessionStore;
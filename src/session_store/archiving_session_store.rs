@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use crate::{
+    archive::{ArchiveError, ArchiveReason, Archiver, ObjectStorage},
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchivingStoreError<S, A> {
+    #[error("Session store error: {0}")]
+    StoreError(#[source] S),
+    #[error(transparent)]
+    ArchiveError(ArchiveError<A>),
+}
+
+/// Archives sessions before they are deleted from the wrapped store.
+pub struct ArchivingSessionStore<Store, Storage> {
+    store: Store,
+    archiver: Archiver<Storage>,
+}
+
+impl<Store, Storage> ArchivingSessionStore<Store, Storage>
+where
+    Store: SessionStore,
+    Storage: ObjectStorage,
+{
+    pub fn new(store: Store, archiver: Archiver<Storage>) -> Self {
+        Self { store, archiver }
+    }
+
+    /// Archives and deletes sessions the caller has determined to be expired.
+    pub async fn expire(
+        &self,
+        session_keys: &[SessionKey],
+    ) -> Result<(), ArchivingStoreError<Store::Error, Storage::Error>> {
+        self.archive_and_destroy(session_keys, ArchiveReason::Expired)
+            .await
+    }
+
+    async fn archive_and_destroy(
+        &self,
+        session_keys: &[SessionKey],
+        reason: ArchiveReason,
+    ) -> Result<(), ArchivingStoreError<Store::Error, Storage::Error>> {
+        let mut records = Vec::with_capacity(session_keys.len());
+        for session_key in session_keys {
+            let session = self
+                .store
+                .load(session_key)
+                .await
+                .map_err(ArchivingStoreError::StoreError)?;
+            if let Some(session) = session {
+                records.push(self.archiver.record(session_key, session.state(), reason));
+            }
+        }
+        self.archiver
+            .archive(&records)
+            .await
+            .map_err(ArchivingStoreError::ArchiveError)?;
+        for session_key in session_keys {
+            self.store
+                .destroy(session_key)
+                .await
+                .map_err(ArchivingStoreError::StoreError)?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store, Storage> SessionStore for ArchivingSessionStore<Store, Storage>
+where
+    Store: SessionStore,
+    Storage: ObjectStorage,
+{
+    type Error = ArchivingStoreError<Store::Error, Storage::Error>;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        self.store
+            .load(session_key)
+            .await
+            .map_err(ArchivingStoreError::StoreError)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store
+            .save(session, timeout)
+            .await
+            .map_err(ArchivingStoreError::StoreError)
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store
+            .update(session, timeout)
+            .await
+            .map_err(ArchivingStoreError::StoreError)
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.archive_and_destroy(std::slice::from_ref(session_key), ArchiveReason::Destroyed)
+            .await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.store
+            .exists(session_key)
+            .await
+            .map_err(ArchivingStoreError::StoreError)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.store
+            .ttl(session_key)
+            .await
+            .map_err(ArchivingStoreError::StoreError)
+    }
+}
@@ -0,0 +1,208 @@
+use futures::stream::{Stream, StreamExt, TryStreamExt};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+const DEFAULT_CAPACITY: usize = 10_000;
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(30);
+const PREWARM_CONCURRENCY: usize = 32;
+
+struct Cached {
+    state: SessionState,
+    expires_at: Instant,
+}
+
+/// Serves loads from an in-process cache in front of `store`, writing
+/// through to it.
+///
+/// Writes made through other nodes are not seen here until the cached copy
+/// is older than `max_age`, so keep it short where sessions are written
+/// from more than one instance. A fresh instance starts cold;
+/// [`prewarm`](Self::prewarm) fills the cache with the sessions most likely
+/// to be requested next, e.g. right after a rolling deploy.
+pub struct CachedSessionStore<Store> {
+    store: Store,
+    capacity: usize,
+    max_age: Duration,
+    cache: Mutex<HashMap<SessionKey, Cached>>,
+}
+
+impl<Store: SessionStore> CachedSessionStore<Store> {
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            capacity: DEFAULT_CAPACITY,
+            max_age: DEFAULT_MAX_AGE,
+            cache: Default::default(),
+        }
+    }
+
+    /// Caches at most `capacity` sessions.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Reloads a cached session from `store` once it is `max_age` old.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// The number of sessions cached, including any past their `max_age`.
+    pub fn len(&self) -> usize {
+        self.cache().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Loads the sessions in `keys`, e.g. those active just before a
+    /// deploy, into the cache, returning how many were cached. Loads run
+    /// concurrently and stop once the cache is full; missing sessions are
+    /// skipped and the first load error is returned.
+    pub async fn prewarm<Keys>(&self, keys: Keys) -> Result<usize, Store::Error>
+    where
+        Keys: Stream<Item = SessionKey>,
+    {
+        let loads = keys
+            .take_while(|_| futures::future::ready(self.len() < self.capacity))
+            .map(|session_key| async move { self.store.load(&session_key).await })
+            .buffer_unordered(PREWARM_CONCURRENCY);
+        loads
+            .try_fold(0, |cached, session| async move {
+                Ok(match session {
+                    Some(session) if self.cache_session(&session, self.max_age) => cached + 1,
+                    _ => cached,
+                })
+            })
+            .await
+    }
+
+    fn cache(&self) -> MutexGuard<'_, HashMap<SessionKey, Cached>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn cached(&self, session_key: &SessionKey) -> Option<SessionState> {
+        let mut cache = self.cache();
+        let cached = cache.get(session_key)?;
+        if cached.expires_at <= Instant::now() {
+            cache.remove(session_key);
+            return None;
+        }
+        Some(cached.state.clone())
+    }
+
+    /// Caches `session` for up to `timeout`, making room by dropping stale
+    /// entries. Returns whether it was cached.
+    fn cache_session(&self, session: &Session, timeout: Duration) -> bool {
+        let mut cache = self.cache();
+        if cache.len() >= self.capacity && !cache.contains_key(session.id()) {
+            let now = Instant::now();
+            cache.retain(|_, cached| cached.expires_at > now);
+            if cache.len() >= self.capacity {
+                return false;
+            }
+        }
+        let cached = Cached {
+            state: session.state().clone(),
+            expires_at: Instant::now() + timeout.min(self.max_age),
+        };
+        cache.insert(session.id().clone(), cached);
+        true
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store: SessionStore> SessionStore for CachedSessionStore<Store> {
+    type Error = Store::Error;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        if let Some(state) = self.cached(session_key) {
+            return Ok(Some(Session::new(session_key.clone(), state)));
+        }
+        let session = self.store.load(session_key).await?;
+        if let Some(session) = &session {
+            self.cache_session(session, self.max_age);
+        }
+        Ok(session)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store.save(session, timeout).await?;
+        self.cache_session(session, timeout);
+        Ok(())
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.cache().remove(session.id());
+        self.store.update(session, timeout).await?;
+        self.cache_session(session, timeout);
+        Ok(())
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.cache().remove(session_key);
+        self.store.destroy(session_key).await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        if self.cached(session_key).is_some() {
+            return Ok(true);
+        }
+        self.store.exists(session_key).await
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.store.ttl(session_key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::Storage, MemorySessionStore};
+
+    #[tokio::test]
+    async fn prewarm_caches_existing_sessions_up_to_capacity() {
+        let backend = MemorySessionStore::new();
+        let mut keys = Vec::new();
+        for user in ["beavis", "butthead", "daria"] {
+            let mut session = Session::default();
+            session.insert("user_id", &user).unwrap();
+            backend
+                .save(&session, Duration::from_secs(60))
+                .await
+                .unwrap();
+            keys.push(session.id().clone());
+        }
+        keys.insert(1, SessionKey::generate());
+        let store = CachedSessionStore::new(&backend).with_capacity(2);
+
+        let cached = store
+            .prewarm(futures::stream::iter(keys.clone()))
+            .await
+            .unwrap();
+        assert_eq!(cached, 2);
+        assert_eq!(store.len(), 2);
+
+        // Only the cached sessions outlive their removal from the backend.
+        let mut served = 0;
+        for session_key in &keys {
+            backend.destroy(session_key).await.unwrap();
+            if store.load(session_key).await.unwrap().is_some() {
+                served += 1;
+            }
+        }
+        assert_eq!(served, 2);
+    }
+}
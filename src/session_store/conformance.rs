@@ -0,0 +1,273 @@
+//! Checks that a [`SessionStore`] behaves the way the rest of this crate
+//! expects. Backend authors can call [`run`] from their own tests:
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn my_store_conforms() {
+//!     let store = MyStore::connect().await.unwrap();
+//!     lushus_session::conformance::run(&store).await.unwrap();
+//! }
+//! ```
+//!
+//! The expiry check sleeps for a little over two seconds. Each check destroys
+//! the sessions it saved, pass or fail, so a run leaves nothing behind in a
+//! shared backend.
+//!
+//! This crate runs the suite against every store it ships: the memory, file
+//! and SQLite stores always, Redis at `localhost`, and the other
+//! network-backed stores in `#[ignore]`d tests that read the server address
+//! from an environment variable, run with `cargo test -- --ignored`.
+//! [`CookieSessionStore`] and [`JwtSessionStore`] are not [`SessionStore`]s,
+//! as the client carries the session, so their own tests cover sealing and
+//! opening instead.
+//!
+//! [`CookieSessionStore`]: crate::CookieSessionStore
+//! [`JwtSessionStore`]: crate::JwtSessionStore
+
+use futures::future::join_all;
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+    storage::Storage,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConformanceError<S> {
+    #[error("Session store error: {0}")]
+    StoreError(S),
+    #[error("Conformance check \"{check}\" failed: {reason}")]
+    CheckError { check: &'static str, reason: String },
+}
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+const LARGE_VALUE_BYTES: usize = 1024 * 1024;
+
+/// Runs every check in turn, stopping at the first failure.
+pub async fn run<S: SessionStore>(store: &S) -> Result<(), ConformanceError<S::Error>> {
+    not_found(store).await?;
+    round_trip(store).await?;
+    update(store).await?;
+    destroy(store).await?;
+    large_value(store).await?;
+    concurrent_updates(store).await?;
+    expiry(store).await
+}
+
+fn check<S>(check: &'static str, passed: bool, reason: &str) -> Result<(), ConformanceError<S>> {
+    if passed {
+        Ok(())
+    } else {
+        Err(ConformanceError::CheckError {
+            check,
+            reason: reason.to_string(),
+        })
+    }
+}
+
+fn session(value: &str) -> Session {
+    let mut session = Session::default();
+    session
+        .insert("value", &value)
+        .expect("strings always serialize");
+    session
+}
+
+/// Destroys `session_key` once a check has run, reporting the check's own
+/// failure ahead of any failure to clean up.
+async fn cleaned_up<S: SessionStore>(
+    store: &S,
+    session_key: &SessionKey,
+    run: Result<(), ConformanceError<S::Error>>,
+) -> Result<(), ConformanceError<S::Error>> {
+    let destroyed = store
+        .destroy(session_key)
+        .await
+        .map_err(ConformanceError::StoreError);
+    run.and(destroyed)
+}
+
+async fn value<S: SessionStore>(
+    store: &S,
+    session_key: &SessionKey,
+) -> Result<Option<String>, ConformanceError<S::Error>> {
+    let loaded = store
+        .load(session_key)
+        .await
+        .map_err(ConformanceError::StoreError)?;
+    Ok(loaded.and_then(|session| session.get::<String>("value").ok().flatten()))
+}
+
+pub async fn not_found<S: SessionStore>(store: &S) -> Result<(), ConformanceError<S::Error>> {
+    let missing = SessionKey::generate();
+    let loaded = store
+        .load(&missing)
+        .await
+        .map_err(ConformanceError::StoreError)?;
+    check("not_found", loaded.is_none(), "load returned a session")?;
+    let exists = store
+        .exists(&missing)
+        .await
+        .map_err(ConformanceError::StoreError)?;
+    check("not_found", !exists, "exists returned true")
+}
+
+pub async fn round_trip<S: SessionStore>(store: &S) -> Result<(), ConformanceError<S::Error>> {
+    let session = session("round-trip");
+    let run = async {
+        store
+            .save(&session, TIMEOUT)
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        let loaded = value(store, session.id()).await?;
+        check(
+            "round_trip",
+            loaded.as_deref() == Some("round-trip"),
+            "loaded value differs from the saved value",
+        )?;
+        let ttl = store
+            .ttl(session.id())
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        check(
+            "round_trip",
+            !ttl.is_zero() && ttl <= TIMEOUT,
+            &format!("ttl {ttl:?} is outside (0, {TIMEOUT:?}]"),
+        )?;
+        let expires_at = store
+            .expires_at(session.id())
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        let now = SystemTime::now();
+        check(
+            "round_trip",
+            expires_at.is_some_and(|at| at > now && at <= now + TIMEOUT + Duration::from_secs(1)),
+            &format!("expires_at {expires_at:?} is not within the timeout from now"),
+        )
+    }
+    .await;
+    cleaned_up(store, session.id(), run).await
+}
+
+pub async fn update<S: SessionStore>(store: &S) -> Result<(), ConformanceError<S::Error>> {
+    let mut session = session("before");
+    let run = async {
+        store
+            .save(&session, TIMEOUT)
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        session
+            .insert("value", &"after")
+            .expect("strings always serialize");
+        store
+            .update(&session, TIMEOUT)
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        let loaded = value(store, session.id()).await?;
+        check(
+            "update",
+            loaded.as_deref() == Some("after"),
+            "update did not overwrite the stored value",
+        )
+    }
+    .await;
+    cleaned_up(store, session.id(), run).await
+}
+
+pub async fn destroy<S: SessionStore>(store: &S) -> Result<(), ConformanceError<S::Error>> {
+    let session = session("destroy");
+    let run = async {
+        store
+            .save(&session, TIMEOUT)
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        store
+            .destroy(session.id())
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        let exists = store
+            .exists(session.id())
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        check("destroy", !exists, "session exists after destroy")?;
+        store
+            .destroy(session.id())
+            .await
+            .map_err(ConformanceError::StoreError)
+    }
+    .await;
+    cleaned_up(store, session.id(), run).await
+}
+
+pub async fn large_value<S: SessionStore>(store: &S) -> Result<(), ConformanceError<S::Error>> {
+    let large = "x".repeat(LARGE_VALUE_BYTES);
+    let session = session(&large);
+    let run = async {
+        store
+            .save(&session, TIMEOUT)
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        let loaded = value(store, session.id()).await?;
+        check(
+            "large_value",
+            loaded.as_deref() == Some(large.as_str()),
+            "large value was truncated or corrupted",
+        )
+    }
+    .await;
+    cleaned_up(store, session.id(), run).await
+}
+
+pub async fn concurrent_updates<S: SessionStore>(
+    store: &S,
+) -> Result<(), ConformanceError<S::Error>> {
+    let mut session = session("0");
+    let run = async {
+        store
+            .save(&session, TIMEOUT)
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        let writes = (1..=8)
+            .map(|n| {
+                session
+                    .insert("value", &n.to_string())
+                    .expect("strings always serialize");
+                Session::new(session.id().clone(), session.state().clone())
+            })
+            .collect::<Vec<_>>();
+        let results = join_all(writes.iter().map(|write| store.update(write, TIMEOUT))).await;
+        for result in results {
+            result.map_err(ConformanceError::StoreError)?;
+        }
+        let loaded = value(store, session.id()).await?;
+        let written = loaded
+            .and_then(|value| value.parse::<u32>().ok())
+            .is_some_and(|n| (1..=8).contains(&n));
+        check(
+            "concurrent_updates",
+            written,
+            "session does not hold any one of the concurrent writes",
+        )
+    }
+    .await;
+    cleaned_up(store, session.id(), run).await
+}
+
+pub async fn expiry<S: SessionStore>(store: &S) -> Result<(), ConformanceError<S::Error>> {
+    let session = session("expiry");
+    let run = async {
+        store
+            .save(&session, Duration::from_secs(1))
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        let loaded = store
+            .load(session.id())
+            .await
+            .map_err(ConformanceError::StoreError)?;
+        check("expiry", loaded.is_none(), "session outlived its timeout")
+    }
+    .await;
+    cleaned_up(store, session.id(), run).await
+}
@@ -0,0 +1,230 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    revocation::RevocationFilter,
+    session::Session,
+    session_state::SessionState,
+    session_store::{
+        key_format::{decode_base64_url, encode_base64_url},
+        SessionKey,
+    },
+};
+
+const VERSION: &str = "v1";
+/// Binds sealed values to this format, so they cannot be replayed as
+/// ciphertexts of some other use of the same key.
+const ASSOCIATED_DATA: &[u8] = b"lushus-session/cookie/v1";
+const NONCE_LEN: usize = 24;
+/// Browsers keep cookies of up to about 4096 bytes, name included.
+const DEFAULT_MAX_SIZE: usize = 4000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CookieStoreError {
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] CodecError),
+    #[error("Sealed session is {size} bytes, over the {limit} byte limit")]
+    TooLargeError { size: usize, limit: usize },
+    #[error("Unable to encrypt session")]
+    EncryptionError,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Sealed {
+    id: SessionKey,
+    state: SessionState,
+    expires_at: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Keeps the whole session in the cookie, encrypted and authenticated with
+/// XChaCha20-Poly1305, so no server-side storage is needed.
+///
+/// The state travels with each request rather than living under a key, so
+/// this does not implement [`SessionStore`](crate::SessionStore):
+/// [`seal`](Self::seal) produces the cookie value and [`open`](Self::open)
+/// reads it back. The web integrations take it in place of a store and
+/// make the sealed session the cookie value. The expiry is sealed in with
+/// the state. There is nothing
+/// on the server to delete, so a sealed session can only be revoked before
+/// it expires through a [`RevocationFilter`], see
+/// [`with_revocations`](Self::with_revocations).
+pub struct CookieSessionStore {
+    cipher: XChaCha20Poly1305,
+    previous: Vec<XChaCha20Poly1305>,
+    max_size: usize,
+    revocations: Option<RevocationFilter>,
+}
+
+impl CookieSessionStore {
+    /// Seals with the 32-byte `key`, which must be kept secret.
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(key.into()),
+            previous: Vec::new(),
+            max_size: DEFAULT_MAX_SIZE,
+            revocations: None,
+        }
+    }
+
+    /// Also opens values sealed with `key`, so the sealing key can be
+    /// rotated without logging everyone out.
+    pub fn with_previous_key(mut self, key: &[u8; 32]) -> Self {
+        self.previous.push(XChaCha20Poly1305::new(key.into()));
+        self
+    }
+
+    /// Fails [`seal`](Self::seal) for values longer than `max_size` bytes.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Rejects sealed sessions that `revocations` might hold. There is no
+    /// store to confirm a hit against, so a false positive logs the user
+    /// out early.
+    pub fn with_revocations(mut self, revocations: RevocationFilter) -> Self {
+        self.revocations = Some(revocations);
+        self
+    }
+
+    /// The cookie value carrying `session` for `timeout`.
+    pub fn seal(&self, session: &Session, timeout: Duration) -> Result<String, CookieStoreError> {
+        let sealed = Sealed {
+            id: session.id().clone(),
+            state: session.state().clone(),
+            expires_at: now_secs() + timeout.as_secs().max(1),
+        };
+        let plaintext = ValueCodec::encode(&sealed)?;
+        let mut nonce = [0; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let payload = Payload {
+            msg: plaintext.as_bytes(),
+            aad: ASSOCIATED_DATA,
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| CookieStoreError::EncryptionError)?;
+        let value = format!(
+            "{VERSION}.{}",
+            encode_base64_url(&[&nonce[..], &ciphertext].concat())
+        );
+        if value.len() > self.max_size {
+            return Err(CookieStoreError::TooLargeError {
+                size: value.len(),
+                limit: self.max_size,
+            });
+        }
+        Ok(value)
+    }
+
+    /// The session sealed in `value`, or `None` if it is expired, revoked,
+    /// was not sealed with one of this store's keys, or has been tampered
+    /// with.
+    pub fn open(&self, value: &str) -> Result<Option<Session>, CookieStoreError> {
+        let Some(plaintext) = self
+            .decrypt(value)
+            .and_then(|plaintext| String::from_utf8(plaintext).ok())
+        else {
+            return Ok(None);
+        };
+        let sealed = ValueCodec::decode::<Sealed>(&plaintext)?;
+        let revoked = self
+            .revocations
+            .as_ref()
+            .is_some_and(|revocations| revocations.might_be_revoked(&sealed.id));
+        if sealed.expires_at <= now_secs() || revoked {
+            return Ok(None);
+        }
+        Ok(Some(Session::new(sealed.id, sealed.state)))
+    }
+
+    fn decrypt(&self, value: &str) -> Option<Vec<u8>> {
+        let encoded = value.strip_prefix(VERSION)?.strip_prefix('.')?;
+        if value.len() > self.max_size {
+            return None;
+        }
+        let bytes = decode_base64_url(encoded)?;
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        std::iter::once(&self.cipher)
+            .chain(&self.previous)
+            .find_map(|cipher| {
+                let payload = Payload {
+                    msg: ciphertext,
+                    aad: ASSOCIATED_DATA,
+                };
+                cipher.decrypt(XNonce::from_slice(nonce), payload).ok()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+
+    #[test]
+    fn sealed_sessions_open_only_with_a_known_key_and_untampered() {
+        let old = CookieSessionStore::new(&[1; 32]);
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        let sealed = old.seal(&session, Duration::from_secs(60)).unwrap();
+
+        let rotated = CookieSessionStore::new(&[2; 32]).with_previous_key(&[1; 32]);
+        let opened = rotated.open(&sealed).unwrap().unwrap();
+        assert_eq!(opened.id(), session.id());
+        assert_eq!(
+            opened.get::<String>("user_id").unwrap().as_deref(),
+            Some("beavis")
+        );
+
+        let mut tampered = sealed.clone().into_bytes();
+        let last = tampered.len() - 1;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+        let tampered = String::from_utf8(tampered).unwrap();
+        assert!(rotated.open(&tampered).unwrap().is_none());
+        assert!(CookieSessionStore::new(&[3; 32])
+            .open(&sealed)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn seal_enforces_the_size_limit() {
+        let store = CookieSessionStore::new(&[1; 32]).with_max_size(100);
+        let mut session = Session::default();
+        session.insert("bio", &"x".repeat(200)).unwrap();
+        let result = store.seal(&session, Duration::from_secs(60));
+        assert!(matches!(
+            result,
+            Err(CookieStoreError::TooLargeError { limit: 100, .. })
+        ));
+    }
+
+    #[test]
+    fn revoked_sessions_do_not_open() {
+        let revocations = RevocationFilter::new(100, 0.01);
+        let store = CookieSessionStore::new(&[1; 32]).with_revocations(revocations.clone());
+        let session = Session::default();
+        let sealed = store.seal(&session, Duration::from_secs(60)).unwrap();
+        assert!(store.open(&sealed).unwrap().is_some());
+
+        revocations.revoke(session.id());
+        assert!(store.open(&sealed).unwrap().is_none());
+    }
+}
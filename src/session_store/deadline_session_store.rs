@@ -0,0 +1,167 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    policy::SessionPolicy,
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeadlineStoreError<S> {
+    #[error("Session store error: {0}")]
+    StoreError(#[source] S),
+    #[error("Session store call exceeded the request deadline")]
+    DeadlineElapsedError,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    pub fn at(instant: Instant) -> Self {
+        Self(instant)
+    }
+
+    pub fn after(budget: Duration) -> Self {
+        Self(Instant::now() + budget)
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+/// Bounds every call to the wrapped store by a per-request [`Deadline`].
+///
+/// Meant to be built per request around a borrowed store, as the web
+/// integrations do for loads given a
+/// [store deadline](crate::SessionConfig::with_store_deadline). With
+/// [`fail_open`](Self::fail_open), a load that runs out of time returns no
+/// session instead of an error, so the request continues as anonymous.
+pub struct DeadlineSessionStore<Store> {
+    store: Store,
+    deadline: Deadline,
+    fail_open: bool,
+}
+
+impl<Store: SessionStore> DeadlineSessionStore<Store> {
+    pub fn new(store: Store, deadline: Deadline) -> Self {
+        Self {
+            store,
+            deadline,
+            fail_open: false,
+        }
+    }
+
+    pub fn fail_open(mut self) -> Self {
+        self.fail_open = true;
+        self
+    }
+
+    /// Applies the policy's fail-open behavior.
+    pub fn with_policy(mut self, policy: &SessionPolicy) -> Self {
+        self.fail_open = policy.fail_open();
+        self
+    }
+
+    pub fn deadline(&self) -> Deadline {
+        self.deadline
+    }
+
+    async fn within<T>(
+        &self,
+        call: impl Future<Output = Result<T, Store::Error>>,
+    ) -> Result<T, DeadlineStoreError<Store::Error>> {
+        if self.deadline.is_elapsed() {
+            return Err(DeadlineStoreError::DeadlineElapsedError);
+        }
+        tokio::time::timeout(self.deadline.remaining(), call)
+            .await
+            .map_err(|_| DeadlineStoreError::DeadlineElapsedError)?
+            .map_err(DeadlineStoreError::StoreError)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store: SessionStore> SessionStore for DeadlineSessionStore<Store> {
+    type Error = DeadlineStoreError<Store::Error>;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        match self.within(self.store.load(session_key)).await {
+            Err(DeadlineStoreError::DeadlineElapsedError) if self.fail_open => Ok(None),
+            result => result,
+        }
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.within(self.store.save(session, timeout)).await
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.within(self.store.update(session, timeout)).await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.within(self.store.destroy(session_key)).await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.within(self.store.exists(session_key)).await
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.within(self.store.ttl(session_key)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{session_state::SessionState, session_store::testing::FaultyStore};
+
+    #[test]
+    fn deadline_reports_the_remaining_budget() {
+        let deadline = Deadline::after(Duration::from_secs(60));
+        assert!(!deadline.is_elapsed());
+        assert!(deadline.remaining() > Duration::from_secs(59));
+
+        let elapsed = Deadline::at(Instant::now() - Duration::from_millis(1));
+        assert!(elapsed.is_elapsed());
+        assert_eq!(elapsed.remaining(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn slow_loads_fail_or_fail_open_at_the_deadline() {
+        let store = FaultyStore::new().with_delay(Duration::from_millis(200));
+        let session = Session::new(SessionKey::generate(), SessionState::default());
+        store
+            .inner()
+            .save(&session, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        let closed = DeadlineSessionStore::new(&store, Deadline::after(Duration::from_millis(20)));
+        assert!(matches!(
+            closed.load(session.id()).await,
+            Err(DeadlineStoreError::DeadlineElapsedError)
+        ));
+
+        let open = DeadlineSessionStore::new(&store, Deadline::after(Duration::from_millis(20)))
+            .fail_open();
+        assert!(open.load(session.id()).await.unwrap().is_none());
+        assert!(matches!(
+            open.exists(session.id()).await,
+            Err(DeadlineStoreError::DeadlineElapsedError)
+        ));
+
+        let patient = DeadlineSessionStore::new(&store, Deadline::after(Duration::from_secs(5)));
+        assert!(patient.load(session.id()).await.unwrap().is_some());
+    }
+}
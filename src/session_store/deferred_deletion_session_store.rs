@@ -0,0 +1,272 @@
+use std::{
+    collections::HashSet,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use crate::{
+    batch::BatchResult,
+    deletion_queue::DeletionQueue,
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeferredDeletionError<S, Q> {
+    #[error("Session store error: {0}")]
+    StoreError(#[source] S),
+    #[error("Deletion queue error: {0}")]
+    QueueError(Q),
+}
+
+/// Queues a session for deletion when `destroy` fails against the store, so
+/// a revoked session is deleted eventually instead of staying alive.
+///
+/// `destroy` succeeds once the key is queued, and this node treats queued
+/// sessions as gone. [`process`](Self::process) retries the queue and must
+/// be driven by the GC task; [`run`](Self::run) does so on an interval.
+/// Keys leave the queue only once their session is deleted.
+pub struct DeferredDeletionSessionStore<Store, Queue> {
+    store: Store,
+    queue: Queue,
+    pending: Mutex<HashSet<SessionKey>>,
+}
+
+impl<Store, Queue> DeferredDeletionSessionStore<Store, Queue>
+where
+    Store: SessionStore,
+    Queue: DeletionQueue,
+{
+    pub fn new(store: Store, queue: Queue) -> Self {
+        Self {
+            store,
+            queue,
+            pending: Default::default(),
+        }
+    }
+
+    fn is_pending(&self, session_key: &SessionKey) -> bool {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(session_key)
+    }
+
+    fn set_pending(&self, session_key: &SessionKey, pending: bool) {
+        let mut keys = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        if pending {
+            keys.insert(session_key.clone());
+        } else {
+            keys.remove(session_key);
+        }
+    }
+
+    /// Retries up to `limit` queued deletions and returns the deleted keys
+    /// and those that failed again, with their errors; the failed keys go
+    /// back to the end of the queue.
+    pub async fn process(
+        &self,
+        limit: usize,
+    ) -> Result<BatchResult<SessionKey, Store::Error>, Queue::Error> {
+        let mut result = BatchResult::default();
+        for key in self.queue.dequeue(limit).await? {
+            match self.store.destroy(&key).await {
+                Ok(()) => {
+                    self.queue.acknowledge(&key).await?;
+                    self.set_pending(&key, false);
+                    result.ok.push(key);
+                }
+                Err(error) => {
+                    self.queue.enqueue(&key).await?;
+                    self.queue.acknowledge(&key).await?;
+                    result.failed.push((key, error));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Recovers the claims of a previous run, then calls
+    /// [`process`](Self::process) every `interval` and hands each failed
+    /// deletion to `failed`, e.g. to log it. Returns on the first queue
+    /// error.
+    pub async fn run(
+        &self,
+        interval: Duration,
+        limit: usize,
+        mut failed: impl FnMut(SessionKey, Store::Error),
+    ) -> Result<(), Queue::Error> {
+        self.queue.recover().await?;
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            for (key, error) in self.process(limit).await?.failed {
+                failed(key, error);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store, Queue> SessionStore for DeferredDeletionSessionStore<Store, Queue>
+where
+    Store: SessionStore,
+    Queue: DeletionQueue,
+{
+    type Error = DeferredDeletionError<Store::Error, Queue::Error>;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        if self.is_pending(session_key) {
+            return Ok(None);
+        }
+        self.store
+            .load(session_key)
+            .await
+            .map_err(DeferredDeletionError::StoreError)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store
+            .save(session, timeout)
+            .await
+            .map_err(DeferredDeletionError::StoreError)
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store
+            .update(session, timeout)
+            .await
+            .map_err(DeferredDeletionError::StoreError)
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        if self.store.destroy(session_key).await.is_ok() {
+            return Ok(());
+        }
+        self.queue
+            .enqueue(session_key)
+            .await
+            .map_err(DeferredDeletionError::QueueError)?;
+        self.set_pending(session_key, true);
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        if self.is_pending(session_key) {
+            return Ok(false);
+        }
+        self.store
+            .exists(session_key)
+            .await
+            .map_err(DeferredDeletionError::StoreError)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        if self.is_pending(session_key) {
+            return Ok(Duration::ZERO);
+        }
+        self.store
+            .ttl(session_key)
+            .await
+            .map_err(DeferredDeletionError::StoreError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        collections::VecDeque,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+    use crate::session_store::{
+        testing::{FaultyStore, FaultyStoreError},
+        StoreOperation,
+    };
+
+    #[derive(Default)]
+    struct Queue {
+        queued: RefCell<VecDeque<SessionKey>>,
+        claimed: RefCell<Vec<SessionKey>>,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl DeletionQueue for Queue {
+        type Error = ();
+
+        async fn enqueue(&self, session_key: &SessionKey) -> Result<(), ()> {
+            self.queued.borrow_mut().push_back(session_key.clone());
+            Ok(())
+        }
+        async fn dequeue(&self, limit: usize) -> Result<Vec<SessionKey>, ()> {
+            let mut queued = self.queued.borrow_mut();
+            let count = limit.min(queued.len());
+            let keys = queued.drain(..count).collect::<Vec<_>>();
+            self.claimed.borrow_mut().extend(keys.iter().cloned());
+            Ok(keys)
+        }
+        async fn acknowledge(&self, session_key: &SessionKey) -> Result<(), ()> {
+            let mut claimed = self.claimed.borrow_mut();
+            if let Some(index) = claimed.iter().position(|key| key == session_key) {
+                claimed.remove(index);
+            }
+            Ok(())
+        }
+        async fn recover(&self) -> Result<usize, ()> {
+            let claimed = std::mem::take(&mut *self.claimed.borrow_mut());
+            let recovered = claimed.len();
+            self.queued.borrow_mut().extend(claimed);
+            Ok(recovered)
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_destroys_are_queued_and_retried() {
+        let down = Arc::new(AtomicBool::new(false));
+        let backend = FaultyStore::new().with_failures({
+            let down = down.clone();
+            move |operation, _| operation == StoreOperation::Destroy && down.load(Ordering::Relaxed)
+        });
+        let queue = Queue::default();
+        let store = DeferredDeletionSessionStore::new(&backend, &queue);
+        let session = Session::default();
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+
+        down.store(true, Ordering::Relaxed);
+        store.destroy(session.id()).await.unwrap();
+        assert!(store.load(session.id()).await.unwrap().is_none());
+        assert!(backend.exists(session.id()).await.unwrap());
+        let processed = store.process(10).await.unwrap();
+        assert!(processed.ok.is_empty());
+        assert!(matches!(
+            processed.failed[..],
+            [(_, FaultyStoreError::InjectedError(StoreOperation::Destroy))]
+        ));
+        assert_eq!(queue.queued.borrow().len(), 1);
+        assert!(queue.claimed.borrow().is_empty());
+
+        down.store(false, Ordering::Relaxed);
+        assert_eq!(store.process(10).await.unwrap().ok, [session.id().clone()]);
+        assert!(!backend.exists(session.id()).await.unwrap());
+        assert!(queue.queued.borrow().is_empty());
+        assert!(queue.claimed.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn claims_of_a_crashed_processor_are_recovered() {
+        let queue = Queue::default();
+        let session_key = SessionKey::generate();
+        queue.enqueue(&session_key).await.unwrap();
+        queue.dequeue(10).await.unwrap();
+        assert!(queue.queued.borrow().is_empty());
+
+        assert_eq!(queue.recover().await.unwrap(), 1);
+        let store = DeferredDeletionSessionStore::new(FaultyStore::new(), &queue);
+        assert_eq!(store.process(10).await.unwrap().ok, [session_key]);
+    }
+}
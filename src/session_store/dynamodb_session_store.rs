@@ -0,0 +1,285 @@
+use aws_sdk_dynamodb::{
+    error::DisplayErrorContext,
+    types::{AttributeValue, TimeToLiveSpecification},
+    Client,
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+const ID: &str = "id";
+const STATE: &str = "state";
+const EXPIRES_AT: &str = "expires_at";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DynamoDbStoreError {
+    #[error("Storage error: Backend error {0}")]
+    BackendError(String),
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] CodecError),
+    #[error("Session was created, updated or expired concurrently")]
+    ConditionFailedError,
+    #[error("DynamoDB error: {0}")]
+    ClientError(String),
+}
+
+fn client_error(error: impl std::error::Error) -> DynamoDbStoreError {
+    DynamoDbStoreError::ClientError(DisplayErrorContext(error).to_string())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn number(value: u64) -> AttributeValue {
+    AttributeValue::N(value.to_string())
+}
+
+/// Stores each session as an item keyed by `id`, with the expiry in a Unix
+/// seconds `expires_at` attribute for DynamoDB's TTL to delete. TTL deletion
+/// lags, so reads also ignore items past their expiry.
+///
+/// Saves only succeed for new or expired keys and updates only for live
+/// ones, so two requests cannot both create the same session or revive one
+/// a third just destroyed.
+pub struct DynamoDbSessionStore {
+    table: String,
+    client: Client,
+}
+
+impl DynamoDbSessionStore {
+    /// Uses `client`, built from the application's AWS configuration,
+    /// against the table called `table`, whose partition key is the string
+    /// attribute `id`.
+    pub fn from_client(client: Client, table: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            client,
+        }
+    }
+
+    /// Turns on DynamoDB TTL for the table's `expires_at` attribute.
+    pub async fn enable_ttl(&self) -> Result<(), DynamoDbStoreError> {
+        let specification = TimeToLiveSpecification::builder()
+            .enabled(true)
+            .attribute_name(EXPIRES_AT)
+            .build()
+            .map_err(client_error)?;
+        self.client
+            .update_time_to_live()
+            .table_name(&self.table)
+            .time_to_live_specification(specification)
+            .send()
+            .await
+            .map_err(client_error)?;
+        Ok(())
+    }
+
+    async fn item(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, AttributeValue>>, DynamoDbStoreError> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key(ID, AttributeValue::S(session_key.as_ref().to_string()))
+            .consistent_read(true)
+            .send()
+            .await
+            .map_err(client_error)?;
+        let live = output.item.filter(|item| expires_at(item) > now_secs());
+        Ok(live)
+    }
+
+    /// Writes the session if `condition` holds for the stored item.
+    async fn put(
+        &self,
+        session: &Session,
+        timeout: Duration,
+        condition: &str,
+    ) -> Result<(), DynamoDbStoreError> {
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(Self::attributes(session, timeout)?))
+            .condition_expression(condition)
+            .expression_attribute_names("#id", ID)
+            .expression_attribute_names("#expires_at", EXPIRES_AT)
+            .expression_attribute_values(":now", number(now_secs()))
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(error)
+                if error
+                    .as_service_error()
+                    .is_some_and(|error| error.is_conditional_check_failed_exception()) =>
+            {
+                Err(DynamoDbStoreError::ConditionFailedError)
+            }
+            Err(error) => Err(client_error(error)),
+        }
+    }
+
+    fn attributes(
+        session: &Session,
+        timeout: Duration,
+    ) -> Result<HashMap<String, AttributeValue>, DynamoDbStoreError> {
+        let state = ValueCodec::encode(session.state())?;
+        let expires_at = now_secs() + timeout.as_secs().max(1);
+        Ok(HashMap::from([
+            (
+                ID.to_string(),
+                AttributeValue::S(session.id().as_ref().to_string()),
+            ),
+            (STATE.to_string(), AttributeValue::S(state)),
+            (EXPIRES_AT.to_string(), number(expires_at)),
+        ]))
+    }
+}
+
+fn expires_at(item: &HashMap<String, AttributeValue>) -> u64 {
+    item.get(EXPIRES_AT)
+        .and_then(|value| value.as_n().ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for DynamoDbSessionStore {
+    type Error = DynamoDbStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let Some(item) = self.item(session_key).await? else {
+            return Ok(None);
+        };
+        let state = item
+            .get(STATE)
+            .and_then(|value| value.as_s().ok())
+            .ok_or_else(|| {
+                DynamoDbStoreError::BackendError("Item has no string state".to_string())
+            })?;
+        let state = ValueCodec::decode::<SessionState>(state)?;
+        Ok(Some(Session::new(session_key.clone(), state)))
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.put(
+            session,
+            timeout,
+            "attribute_not_exists(#id) OR #expires_at <= :now",
+        )
+        .await
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.put(
+            session,
+            timeout,
+            "attribute_exists(#id) AND #expires_at > :now",
+        )
+        .await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.client
+            .delete_item()
+            .table_name(&self.table)
+            .key(ID, AttributeValue::S(session_key.as_ref().to_string()))
+            .send()
+            .await
+            .map_err(client_error)?;
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        Ok(self.item(session_key).await?.is_some())
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        let remaining = self
+            .item(session_key)
+            .await?
+            .map(|item| expires_at(&item).saturating_sub(now_secs()))
+            .unwrap_or_default();
+        Ok(Duration::from_secs(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::{
+        config::{BehaviorVersion, Credentials, Region},
+        types::{AttributeDefinition, BillingMode, KeySchemaElement, KeyType, ScalarAttributeType},
+    };
+    use aws_smithy_http_client::tls::{rustls_provider::CryptoMode, Provider};
+
+    use super::*;
+    use crate::session_store::conformance;
+
+    /// A client for DynamoDB Local, which accepts any credentials.
+    fn client() -> Client {
+        let endpoint = std::env::var("LUSHUS_DYNAMODB_ENDPOINT")
+            .unwrap_or_else(|_| "http://localhost:8000".to_string());
+        let http_client = aws_smithy_http_client::Builder::new()
+            .tls_provider(Provider::Rustls(CryptoMode::Ring))
+            .build_https();
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url(endpoint)
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .http_client(http_client)
+            .build();
+        Client::from_conf(config)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DynamoDB Local at LUSHUS_DYNAMODB_ENDPOINT"]
+    async fn dynamodb_store_passes_the_conformance_checks() {
+        let client = client();
+        let table = format!("conformance-{:016x}", rand::random::<u64>());
+        client
+            .create_table()
+            .table_name(&table)
+            .attribute_definitions(
+                AttributeDefinition::builder()
+                    .attribute_name(ID)
+                    .attribute_type(ScalarAttributeType::S)
+                    .build()
+                    .unwrap(),
+            )
+            .key_schema(
+                KeySchemaElement::builder()
+                    .attribute_name(ID)
+                    .key_type(KeyType::Hash)
+                    .build()
+                    .unwrap(),
+            )
+            .billing_mode(BillingMode::PayPerRequest)
+            .send()
+            .await
+            .expect("Unable to create the table");
+        let store = DynamoDbSessionStore::from_client(client.clone(), &table);
+        conformance::run(&store).await.unwrap();
+        client
+            .delete_table()
+            .table_name(&table)
+            .send()
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use std::time::Duration;
+
+use crate::{
+    session::{Session, TAGS_KEY},
+    session_state::SessionState,
+    session_store::{
+        key_format::{decode_base64_url, encode_base64_url},
+        SessionKey, SessionStore,
+    },
+};
+
+const NONCE_LEN: usize = 24;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncryptedStoreError<S> {
+    #[error("Session store error: {0}")]
+    StoreError(#[source] S),
+    #[error("Unable to encrypt the value of \"{0}\"")]
+    EncryptionError(String),
+    #[error("Unable to decrypt the value of \"{0}\" with any of the keys")]
+    DecryptionError(String),
+}
+
+/// Encrypts every session value with XChaCha20-Poly1305 before it reaches
+/// `store`, so the backend and its backups only ever hold ciphertext.
+///
+/// Each value is sealed on its own, bound to its key name, so stores that
+/// write changed keys alone keep working and a value cannot be moved under
+/// another key. Key names stay readable, and so do a session's tags, which
+/// the backend indexes in the clear anyway.
+pub struct EncryptedSessionStore<Store> {
+    store: Store,
+    cipher: XChaCha20Poly1305,
+    previous: Vec<XChaCha20Poly1305>,
+}
+
+impl<Store: SessionStore> EncryptedSessionStore<Store> {
+    /// Encrypts with the 32-byte `key`, which must be kept secret.
+    pub fn new(store: Store, key: &[u8; 32]) -> Self {
+        Self {
+            store,
+            cipher: XChaCha20Poly1305::new(key.into()),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Also decrypts values encrypted with `key`, so the key can be rotated
+    /// without losing stored sessions; they are re-encrypted with the new
+    /// key when next written.
+    pub fn with_previous_key(mut self, key: &[u8; 32]) -> Self {
+        self.previous.push(XChaCha20Poly1305::new(key.into()));
+        self
+    }
+
+    fn encrypt(&self, session: &Session) -> Result<Session, EncryptedStoreError<Store::Error>> {
+        let mut state = SessionState::default();
+        for (key, value) in session.state().iter() {
+            if key == TAGS_KEY {
+                state.insert(key, value.clone());
+                continue;
+            }
+            let mut nonce = [0; NONCE_LEN];
+            OsRng.fill_bytes(&mut nonce);
+            let payload = Payload {
+                msg: value.as_bytes(),
+                aad: key.as_bytes(),
+            };
+            let ciphertext = self
+                .cipher
+                .encrypt(XNonce::from_slice(&nonce), payload)
+                .map_err(|_| EncryptedStoreError::EncryptionError(key.clone()))?;
+            state.insert(key, encode_base64_url(&[&nonce[..], &ciphertext].concat()));
+        }
+        Ok(session.with_state(state))
+    }
+
+    fn decrypt(&self, session: Session) -> Result<Session, EncryptedStoreError<Store::Error>> {
+        let mut state = SessionState::default();
+        for (key, value) in session.state().iter() {
+            if key == TAGS_KEY {
+                state.insert(key, value.clone());
+                continue;
+            }
+            let plaintext = self
+                .open(key, value)
+                .ok_or_else(|| EncryptedStoreError::DecryptionError(key.clone()))?;
+            state.insert(key, plaintext);
+        }
+        Ok(Session::new(session.id().clone(), state))
+    }
+
+    fn open(&self, key: &str, value: &str) -> Option<String> {
+        let bytes = decode_base64_url(value)?;
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let plaintext = std::iter::once(&self.cipher)
+            .chain(&self.previous)
+            .find_map(|cipher| {
+                let payload = Payload {
+                    msg: ciphertext,
+                    aad: key.as_bytes(),
+                };
+                cipher.decrypt(XNonce::from_slice(nonce), payload).ok()
+            })?;
+        String::from_utf8(plaintext).ok()
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store: SessionStore> SessionStore for EncryptedSessionStore<Store> {
+    type Error = EncryptedStoreError<Store::Error>;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let loaded = self
+            .store
+            .load(session_key)
+            .await
+            .map_err(EncryptedStoreError::StoreError)?;
+        loaded.map(|session| self.decrypt(session)).transpose()
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let encrypted = self.encrypt(session)?;
+        self.store
+            .save(&encrypted, timeout)
+            .await
+            .map_err(EncryptedStoreError::StoreError)
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let encrypted = self.encrypt(session)?;
+        self.store
+            .update(&encrypted, timeout)
+            .await
+            .map_err(EncryptedStoreError::StoreError)
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.store
+            .destroy(session_key)
+            .await
+            .map_err(EncryptedStoreError::StoreError)
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.store
+            .exists(session_key)
+            .await
+            .map_err(EncryptedStoreError::StoreError)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.store
+            .ttl(session_key)
+            .await
+            .map_err(EncryptedStoreError::StoreError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::Storage, MemorySessionStore};
+
+    #[tokio::test]
+    async fn values_are_stored_encrypted_and_survive_key_rotation() {
+        let inner = MemorySessionStore::new();
+        let store = EncryptedSessionStore::new(&inner, &[1; 32]);
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        session.tag("admin").unwrap();
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+
+        let stored = inner.load(session.id()).await.unwrap().unwrap();
+        assert_ne!(
+            stored.state().get("user_id"),
+            session.state().get("user_id")
+        );
+        assert!(stored.has_tag("admin").unwrap());
+
+        let rotated = EncryptedSessionStore::new(&inner, &[2; 32]).with_previous_key(&[1; 32]);
+        let loaded = rotated.load(session.id()).await.unwrap().unwrap();
+        assert_eq!(loaded.get::<String>("user_id").unwrap().unwrap(), "beavis");
+
+        let stranger = EncryptedSessionStore::new(&inner, &[3; 32]);
+        assert!(matches!(
+            stranger.load(session.id()).await,
+            Err(EncryptedStoreError::DecryptionError(_))
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use etcd_client::{Client, Compare, CompareOp, PutOptions, Txn, TxnOp};
+use std::time::Duration;
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EtcdStoreError {
+    #[error("Storage error: Backend error {0}")]
+    BackendError(String),
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] CodecError),
+    #[error("Etcd client error: {0}")]
+    ClientError(#[from] etcd_client::Error),
+}
+
+/// Stores each session under its own etcd lease so the TTL maps to lease expiry.
+pub struct EtcdSessionStore {
+    prefix: String,
+    client: Client,
+}
+
+impl EtcdSessionStore {
+    pub async fn new<E: AsRef<str>>(endpoints: &[E]) -> Result<Self, EtcdStoreError> {
+        let client = Client::connect(endpoints, None).await?;
+        Ok(Self::from_client(client))
+    }
+
+    pub fn from_client(client: Client) -> Self {
+        Self {
+            prefix: "sessions/".to_string(),
+            client,
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    fn cache_key(&self, session_key: &SessionKey) -> String {
+        format!("{}{}", self.prefix, session_key.as_ref())
+    }
+
+    async fn lease_of(&self, cache_key: &str) -> Result<Option<(i64, i64)>, EtcdStoreError> {
+        let response = self.client.clone().get(cache_key, None).await?;
+        let lease = response
+            .kvs()
+            .first()
+            .map(|kv| (kv.lease(), kv.mod_revision()));
+        Ok(lease)
+    }
+
+    async fn put_with_lease(
+        &self,
+        cache_key: String,
+        session: &Session,
+        timeout: Duration,
+        condition: Compare,
+    ) -> Result<bool, EtcdStoreError> {
+        let mut client = self.client.clone();
+        let body = ValueCodec::encode(session.state())?;
+        let lease = client
+            .lease_grant(timeout.as_secs().max(1) as i64, None)
+            .await?
+            .id();
+        let put = TxnOp::put(cache_key, body, Some(PutOptions::new().with_lease(lease)));
+        let txn = Txn::new().when([condition]).and_then([put]);
+        let succeeded = client.txn(txn).await?.succeeded();
+        if !succeeded {
+            client.lease_revoke(lease).await?;
+        }
+        Ok(succeeded)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for EtcdSessionStore {
+    type Error = EtcdStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let cache_key = self.cache_key(session_key);
+        let response = self.client.clone().get(cache_key, None).await?;
+        let state = response
+            .kvs()
+            .first()
+            .map(|kv| -> Result<SessionState, EtcdStoreError> {
+                Ok(ValueCodec::decode(kv.value_str()?)?)
+            })
+            .transpose()?;
+        let session = state.map(|state| Session::new(session_key.clone(), state));
+        Ok(session)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let cache_key = self.cache_key(session.id());
+        let absent = Compare::create_revision(cache_key.as_str(), CompareOp::Equal, 0);
+        self.put_with_lease(cache_key, session, timeout, absent)
+            .await?;
+        Ok(())
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let cache_key = self.cache_key(session.id());
+        let (old_lease, revision) = self.lease_of(&cache_key).await?.ok_or_else(|| {
+            EtcdStoreError::BackendError("Update returned nil response data".to_string())
+        })?;
+        let unchanged = Compare::mod_revision(cache_key.as_str(), CompareOp::Equal, revision);
+        let updated = self
+            .put_with_lease(cache_key, session, timeout, unchanged)
+            .await?;
+        if !updated {
+            return Err(EtcdStoreError::BackendError(
+                "Session was modified concurrently".to_string(),
+            ));
+        }
+        if old_lease != 0 {
+            self.client.clone().lease_revoke(old_lease).await?;
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        let cache_key = self.cache_key(session_key);
+        let lease = self.lease_of(&cache_key).await?;
+        self.client.clone().delete(cache_key, None).await?;
+        if let Some((lease, _)) = lease.filter(|(lease, _)| *lease != 0) {
+            self.client.clone().lease_revoke(lease).await?;
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        let cache_key = self.cache_key(session_key);
+        let lease = self.lease_of(&cache_key).await?;
+        Ok(lease.is_some())
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        let cache_key = self.cache_key(session_key);
+        let lease = match self.lease_of(&cache_key).await? {
+            Some((lease, _)) if lease != 0 => lease,
+            _ => return Ok(Duration::ZERO),
+        };
+        let ttl = self
+            .client
+            .clone()
+            .lease_time_to_live(lease, None)
+            .await?
+            .ttl();
+        Ok(Duration::from_secs(ttl.max(0) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::conformance;
+
+    #[tokio::test]
+    #[ignore = "needs etcd at LUSHUS_ETCD_ENDPOINT"]
+    async fn etcd_store_passes_the_conformance_checks() {
+        let endpoint =
+            std::env::var("LUSHUS_ETCD_ENDPOINT").unwrap_or_else(|_| "localhost:2379".to_string());
+        let store = EtcdSessionStore::new(&[endpoint])
+            .await
+            .expect("Unable to connect to etcd")
+            .with_prefix(&format!("conformance-{:016x}/", rand::random::<u64>()));
+        conformance::run(&store).await.unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+mod redis_event_log;
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+pub use redis_event_log::RedisEventLog;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SessionMutation {
+    Insert { key: String, value: String },
+    Remove { key: String },
+}
+
+impl SessionMutation {
+    pub fn apply(&self, state: &mut SessionState) {
+        match self {
+            Self::Insert { key, value } => state.insert(key, value.clone()),
+            Self::Remove { key } => {
+                state.remove(key);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EventLogRecord {
+    pub snapshot: SessionState,
+    pub events: Vec<SessionMutation>,
+}
+
+impl EventLogRecord {
+    pub fn fold(&self) -> SessionState {
+        let mut state = self.snapshot.clone();
+        for event in &self.events {
+            event.apply(&mut state);
+        }
+        state
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+pub trait EventLog {
+    type Error;
+
+    async fn read(&self, session_key: &SessionKey) -> Result<Option<EventLogRecord>, Self::Error>;
+    async fn append(
+        &self,
+        session_key: &SessionKey,
+        events: &[SessionMutation],
+        timeout: Duration,
+    ) -> Result<(), Self::Error>;
+    /// Replaces the snapshot with `snapshot` and drops the first `folded` events.
+    async fn compact(
+        &self,
+        session_key: &SessionKey,
+        snapshot: &SessionState,
+        folded: usize,
+    ) -> Result<(), Self::Error>;
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error>;
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error>;
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error>;
+}
+
+/// Stores sessions as an append-only log of per-key mutations.
+///
+/// Only the keys a session changed are appended, so concurrent saves that touch
+/// different keys merge instead of overwriting each other.
+pub struct EventSourcedSessionStore<Log> {
+    log: Log,
+    snapshot_every: Option<usize>,
+}
+
+impl<Log: EventLog> EventSourcedSessionStore<Log> {
+    pub fn new(log: Log) -> Self {
+        Self {
+            log,
+            snapshot_every: Some(100),
+        }
+    }
+
+    pub fn with_snapshot_every(mut self, snapshot_every: Option<usize>) -> Self {
+        self.snapshot_every = snapshot_every;
+        self
+    }
+
+    pub async fn record(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<EventLogRecord>, Log::Error> {
+        self.log.read(session_key).await
+    }
+
+    fn mutations(session: &Session) -> Vec<SessionMutation> {
+        session
+            .changed_keys()
+            .map(|key| match session.state().get(key) {
+                Some(value) => SessionMutation::Insert {
+                    key: key.to_string(),
+                    value: value.clone(),
+                },
+                None => SessionMutation::Remove {
+                    key: key.to_string(),
+                },
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Log: EventLog> SessionStore for EventSourcedSessionStore<Log> {
+    type Error = Log::Error;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let record = self.log.read(session_key).await?;
+        let session = record.map(|record| Session::new(session_key.clone(), record.fold()));
+        Ok(session)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let events = session
+            .state()
+            .iter()
+            .map(|(key, value)| SessionMutation::Insert {
+                key: key.clone(),
+                value: value.clone(),
+            })
+            .collect::<Vec<_>>();
+        self.log.append(session.id(), &events, timeout).await
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let events = Self::mutations(session);
+        self.log.append(session.id(), &events, timeout).await?;
+        if let Some(snapshot_every) = self.snapshot_every {
+            if let Some(record) = self.log.read(session.id()).await? {
+                if record.events.len() >= snapshot_every {
+                    self.log
+                        .compact(session.id(), &record.fold(), record.events.len())
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.log.destroy(session_key).await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.log.exists(session_key).await
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.log.ttl(session_key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fold_applies_events_on_top_of_the_snapshot() {
+        let mut snapshot = SessionState::default();
+        snapshot.insert("user_id", "\"beavis\"".to_string());
+        snapshot.insert("cart", "[]".to_string());
+        let record = EventLogRecord {
+            snapshot,
+            events: vec![
+                SessionMutation::Insert {
+                    key: "user_id".to_string(),
+                    value: "\"butt-head\"".to_string(),
+                },
+                SessionMutation::Remove {
+                    key: "cart".to_string(),
+                },
+            ],
+        };
+
+        let state = record.fold();
+        assert_eq!(state.get("user_id").unwrap(), "\"butt-head\"");
+        assert_eq!(state.get("cart"), None);
+    }
+}
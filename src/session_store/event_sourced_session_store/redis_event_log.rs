@@ -0,0 +1,151 @@
+use redis::aio::ConnectionManager;
+use std::time::Duration;
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    session_state::SessionState,
+    session_store::{
+        event_sourced_session_store::{EventLog, EventLogRecord, SessionMutation},
+        redis_session_store::RedisError,
+        RedisSessionStoreError, SessionKey,
+    },
+};
+
+pub struct RedisEventLog {
+    connection: ConnectionManager,
+}
+
+impl RedisEventLog {
+    pub async fn new(url: &str) -> Result<Self, RedisSessionStoreError> {
+        let client = redis::Client::open(url)
+            .map_err(|e| e.to_string())
+            .map_err(RedisError::ConnectionError)?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(RedisError::ConnectionError)?;
+        Ok(Self { connection })
+    }
+
+    fn events_key(session_key: &SessionKey) -> String {
+        format!("{}:events", session_key.as_ref())
+    }
+
+    fn snapshot_key(session_key: &SessionKey) -> String {
+        format!("{}:snapshot", session_key.as_ref())
+    }
+
+    async fn query<T: redis::FromRedisValue>(
+        &self,
+        pipeline: &redis::Pipeline,
+    ) -> Result<T, RedisSessionStoreError> {
+        pipeline
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(|e| e.to_string())
+            .map_err(RedisError::QueryError)
+            .map_err(RedisSessionStoreError::from)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl EventLog for RedisEventLog {
+    type Error = RedisSessionStoreError;
+
+    async fn read(&self, session_key: &SessionKey) -> Result<Option<EventLogRecord>, Self::Error> {
+        let mut pipeline = redis::pipe();
+        pipeline
+            .get(Self::snapshot_key(session_key))
+            .lrange(Self::events_key(session_key), 0, -1);
+        let (snapshot, events): (Option<String>, Vec<String>) = self.query(&pipeline).await?;
+        if snapshot.is_none() && events.is_empty() {
+            return Ok(None);
+        }
+        let snapshot = snapshot
+            .map(|v| ValueCodec::decode::<SessionState>(&v))
+            .transpose()?
+            .unwrap_or_default();
+        let events = events
+            .iter()
+            .map(|v| ValueCodec::decode::<SessionMutation>(v))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(EventLogRecord { snapshot, events }))
+    }
+
+    async fn append(
+        &self,
+        session_key: &SessionKey,
+        events: &[SessionMutation],
+        timeout: Duration,
+    ) -> Result<(), Self::Error> {
+        let events_key = Self::events_key(session_key);
+        let snapshot_key = Self::snapshot_key(session_key);
+        let events = events
+            .iter()
+            .map(ValueCodec::encode)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut pipeline = redis::pipe();
+        pipeline.atomic();
+        if events.is_empty() {
+            pipeline.set(&snapshot_key, "{}").arg("NX").ignore();
+        } else {
+            pipeline.rpush(&events_key, events).ignore();
+        }
+        pipeline
+            .expire(&events_key, timeout.as_secs() as i64)
+            .ignore()
+            .expire(&snapshot_key, timeout.as_secs() as i64)
+            .ignore();
+        self.query::<()>(&pipeline).await
+    }
+
+    async fn compact(
+        &self,
+        session_key: &SessionKey,
+        snapshot: &SessionState,
+        folded: usize,
+    ) -> Result<(), Self::Error> {
+        let events_key = Self::events_key(session_key);
+        let snapshot_key = Self::snapshot_key(session_key);
+        let body = ValueCodec::encode(snapshot)?;
+        let ttl = self.ttl(session_key).await?;
+        let mut pipeline = redis::pipe();
+        pipeline
+            .atomic()
+            .set_ex(&snapshot_key, body, ttl.as_secs().max(1))
+            .ignore()
+            .ltrim(&events_key, folded as isize, -1)
+            .ignore();
+        self.query::<()>(&pipeline).await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        let mut pipeline = redis::pipe();
+        pipeline
+            .del(&[
+                Self::events_key(session_key),
+                Self::snapshot_key(session_key),
+            ])
+            .ignore();
+        self.query::<()>(&pipeline).await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        let mut pipeline = redis::pipe();
+        pipeline.exists(&[
+            Self::events_key(session_key),
+            Self::snapshot_key(session_key),
+        ]);
+        let (exists,): (u64,) = self.query(&pipeline).await?;
+        Ok(exists > 0)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        let mut pipeline = redis::pipe();
+        pipeline
+            .ttl(Self::events_key(session_key))
+            .ttl(Self::snapshot_key(session_key));
+        let (events, snapshot): (i64, i64) = self.query(&pipeline).await?;
+        Ok(Duration::from_secs(events.max(snapshot).max(0) as u64))
+    }
+}
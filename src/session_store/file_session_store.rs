@@ -0,0 +1,236 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+#[cfg(feature = "json")]
+const EXTENSION: &str = "json";
+#[cfg(not(feature = "json"))]
+const EXTENSION: &str = "cbor";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FileStoreError {
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] CodecError),
+    #[error("Session file error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Session not found")]
+    MissingSessionError,
+}
+
+/// Stores each session as a JSON file under one directory, for CLI tools and
+/// small self-hosted deployments. A file's modification time is set to when it
+/// expires, and files are written beside their final name and renamed into
+/// place, so readers never see a partial write.
+///
+/// Reads delete the expired files they come across;
+/// [`purge_expired`](Self::purge_expired) sweeps the rest. File operations
+/// block the calling thread.
+#[derive(Clone, Debug)]
+pub struct FileSessionStore {
+    dir: PathBuf,
+}
+
+impl FileSessionStore {
+    /// Uses `dir`, creating it if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, FileStoreError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Deletes every expired session file, returning how many were deleted.
+    pub fn purge_expired(&self) -> Result<usize, FileStoreError> {
+        let now = SystemTime::now();
+        let mut purged = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_none_or(|extension| extension != EXTENSION)
+            {
+                continue;
+            }
+            let expired = expires_at(&path)?.is_some_and(|expires_at| expires_at <= now);
+            if expired && remove(&path)? {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    fn path(&self, session_key: &SessionKey) -> PathBuf {
+        self.dir
+            .join(format!("{}.{EXTENSION}", session_key.as_ref()))
+    }
+
+    /// When the session file expires, or `None` if it is missing or expired,
+    /// in which case it is deleted.
+    fn live(&self, session_key: &SessionKey) -> Result<Option<SystemTime>, FileStoreError> {
+        let path = self.path(session_key);
+        match expires_at(&path)? {
+            Some(expires_at) if expires_at > SystemTime::now() => Ok(Some(expires_at)),
+            Some(_) => {
+                remove(&path)?;
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn write(&self, session: &Session, timeout: Duration) -> Result<(), FileStoreError> {
+        let path = self.path(session.id());
+        let temporary = path.with_extension(format!("{EXTENSION}.{:016x}", rand::random::<u64>()));
+        let written = (|| -> Result<(), FileStoreError> {
+            let mut file = File::create(&temporary)?;
+            file.write_all(ValueCodec::encode(session.state())?.as_bytes())?;
+            file.flush()?;
+            file.set_modified(SystemTime::now() + timeout)?;
+            file.sync_all()?;
+            fs::rename(&temporary, &path)?;
+            Ok(())
+        })();
+        if written.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        written
+    }
+}
+
+fn expires_at(path: &Path) -> Result<Option<SystemTime>, FileStoreError> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(Some(metadata.modified()?)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Removes `path`, telling whether it was there.
+fn remove(path: &Path) -> Result<bool, FileStoreError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for FileSessionStore {
+    type Error = FileStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        if self.live(session_key)?.is_none() {
+            return Ok(None);
+        }
+        let body = match fs::read_to_string(self.path(session_key)) {
+            Ok(body) => body,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let state = ValueCodec::decode::<SessionState>(&body)?;
+        Ok(Some(Session::new(session_key.clone(), state)))
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.write(session, timeout)
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        if self.live(session.id())?.is_none() {
+            return Err(FileStoreError::MissingSessionError);
+        }
+        self.write(session, timeout)
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        remove(&self.path(session_key))?;
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        Ok(self.live(session_key)?.is_some())
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        let remaining = self
+            .live(session_key)?
+            .and_then(|expires_at| expires_at.duration_since(SystemTime::now()).ok())
+            .unwrap_or_default();
+        Ok(remaining)
+    }
+
+    async fn expires_at(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<SystemTime>, Self::Error> {
+        self.live(session_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{session_store::conformance, storage::Storage};
+
+    fn store() -> FileSessionStore {
+        let dir =
+            std::env::temp_dir().join(format!("lushus-session-{:016x}", rand::random::<u64>()));
+        FileSessionStore::new(dir).unwrap()
+    }
+
+    #[tokio::test]
+    async fn file_store_passes_the_conformance_checks() {
+        let store = store();
+        conformance::run(&store).await.unwrap();
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn purge_expired_deletes_only_expired_files() {
+        let store = store();
+        let mut expired = Session::default();
+        expired.insert("user_id", &"beavis").unwrap();
+        store
+            .save(&expired, Duration::from_millis(10))
+            .await
+            .unwrap();
+        let live = Session::default();
+        store.save(&live, Duration::from_secs(60)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(store.purge_expired().unwrap(), 1);
+        assert!(!store.path(expired.id()).exists());
+        assert!(store.exists(live.id()).await.unwrap());
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn files_hold_the_state_in_the_value_codec() {
+        let store = store();
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+
+        let body = fs::read_to_string(store.path(session.id())).unwrap();
+        let state = ValueCodec::decode::<SessionState>(&body).unwrap();
+        assert_eq!(&state, session.state());
+        #[cfg(not(feature = "json"))]
+        assert!(serde_json::from_str::<serde_json::Value>(&body).is_err());
+        let loaded = store.load(session.id()).await.unwrap().unwrap();
+        assert_eq!(loaded.get::<String>("user_id").unwrap().unwrap(), "beavis");
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use crate::{
+    history::{HistoryEntry, HistorySink},
+    redaction::{BoxedRedactor, Redactor},
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum HistoryStoreError<S, H> {
+    #[error("Session store error: {0}")]
+    StoreError(#[source] S),
+    #[error("History sink error: {0}")]
+    HistoryError(H),
+}
+
+/// Records every saved version of a session in a [`HistorySink`].
+pub struct HistorySessionStore<Store, Sink> {
+    store: Store,
+    sink: Sink,
+    redactor: Option<BoxedRedactor>,
+}
+
+impl<Store, Sink> HistorySessionStore<Store, Sink>
+where
+    Store: SessionStore,
+    Sink: HistorySink,
+{
+    pub fn new(store: Store, sink: Sink) -> Self {
+        Self {
+            store,
+            sink,
+            redactor: None,
+        }
+    }
+
+    /// Redacts each version before it is recorded; the store itself keeps
+    /// the full state.
+    pub fn with_redactor(mut self, redactor: impl Redactor + Send + Sync + 'static) -> Self {
+        self.redactor = Some(Box::new(redactor));
+        self
+    }
+
+    pub async fn history(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Vec<HistoryEntry>, HistoryStoreError<Store::Error, Sink::Error>> {
+        self.sink
+            .history(session_key)
+            .await
+            .map_err(HistoryStoreError::HistoryError)
+    }
+
+    async fn record(
+        &self,
+        session: &Session,
+    ) -> Result<(), HistoryStoreError<Store::Error, Sink::Error>> {
+        let mut state = session.state().clone();
+        if let Some(redactor) = &self.redactor {
+            redactor.redact(&mut state);
+        }
+        self.sink
+            .record(session.id(), &state)
+            .await
+            .map_err(HistoryStoreError::HistoryError)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store, Sink> SessionStore for HistorySessionStore<Store, Sink>
+where
+    Store: SessionStore,
+    Sink: HistorySink,
+{
+    type Error = HistoryStoreError<Store::Error, Sink::Error>;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        self.store
+            .load(session_key)
+            .await
+            .map_err(HistoryStoreError::StoreError)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store
+            .save(session, timeout)
+            .await
+            .map_err(HistoryStoreError::StoreError)?;
+        self.record(session).await
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store
+            .update(session, timeout)
+            .await
+            .map_err(HistoryStoreError::StoreError)?;
+        self.record(session).await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.store
+            .destroy(session_key)
+            .await
+            .map_err(HistoryStoreError::StoreError)
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.store
+            .exists(session_key)
+            .await
+            .map_err(HistoryStoreError::StoreError)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.store
+            .ttl(session_key)
+            .await
+            .map_err(HistoryStoreError::StoreError)
+    }
+}
@@ -0,0 +1,258 @@
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha384, Sha512};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    revocation::RevocationFilter,
+    session::Session,
+    session_state::SessionState,
+    session_store::{
+        key_format::{decode_base64_url, encode_base64_url},
+        SessionKey,
+    },
+};
+
+/// The HMAC algorithms a [`JwtSessionStore`] signs with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    #[default]
+    Hs256,
+    Hs384,
+    Hs512,
+}
+
+impl JwtAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            JwtAlgorithm::Hs256 => "HS256",
+            JwtAlgorithm::Hs384 => "HS384",
+            JwtAlgorithm::Hs512 => "HS512",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwtStoreError {
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] serde_json::Error),
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Header {
+    alg: String,
+    typ: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Claims {
+    sid: SessionKey,
+    iat: u64,
+    exp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iss: Option<String>,
+    state: SessionState,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Carries the whole session as the claims of a signed JWT, so no
+/// server-side storage is needed: the session key is the `sid` claim and
+/// the state the `state` claim.
+///
+/// Like [`CookieSessionStore`](crate::CookieSessionStore), this does not
+/// implement [`SessionStore`](crate::SessionStore);
+/// [`encode`](Self::encode) produces the token and [`decode`](Self::decode)
+/// reads it back, and the web integrations take it in place of a store,
+/// with the token as the cookie value. The claims are signed, not encrypted, so anything in the
+/// session is readable by whoever holds the token.
+pub struct JwtSessionStore {
+    algorithm: JwtAlgorithm,
+    secret: Vec<u8>,
+    issuer: Option<String>,
+    leeway: Duration,
+    revocations: Option<RevocationFilter>,
+}
+
+impl JwtSessionStore {
+    pub fn new(algorithm: JwtAlgorithm, secret: &[u8]) -> Self {
+        Self {
+            algorithm,
+            secret: secret.to_vec(),
+            issuer: None,
+            leeway: Duration::ZERO,
+            revocations: None,
+        }
+    }
+
+    /// Writes `issuer` as the `iss` claim and rejects tokens without it.
+    pub fn with_issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    /// Accepts tokens up to `leeway` past their `exp`, for clock skew
+    /// between the nodes issuing and checking them.
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Rejects tokens whose session `revocations` might hold. There is no
+    /// store to confirm a hit against, so a false positive logs the user
+    /// out early.
+    pub fn with_revocations(mut self, revocations: RevocationFilter) -> Self {
+        self.revocations = Some(revocations);
+        self
+    }
+
+    /// A token carrying `session` that expires after `timeout`.
+    pub fn encode(&self, session: &Session, timeout: Duration) -> Result<String, JwtStoreError> {
+        let header = Header {
+            alg: self.algorithm.name().to_string(),
+            typ: "JWT".to_string(),
+        };
+        let iat = now_secs();
+        let claims = Claims {
+            sid: session.id().clone(),
+            iat,
+            exp: iat + timeout.as_secs().max(1),
+            iss: self.issuer.clone(),
+            state: session.state().clone(),
+        };
+        let signing_input = format!(
+            "{}.{}",
+            encode_base64_url(&serde_json::to_vec(&header)?),
+            encode_base64_url(&serde_json::to_vec(&claims)?)
+        );
+        let signature = encode_base64_url(&self.sign(&signing_input));
+        Ok(format!("{signing_input}.{signature}"))
+    }
+
+    /// The session carried by `token`, or `None` if it is expired, for
+    /// another issuer, revoked, or not signed by this store's secret and
+    /// algorithm.
+    pub fn decode(&self, token: &str) -> Result<Option<Session>, JwtStoreError> {
+        let Some(claims) = self.verify(token) else {
+            return Ok(None);
+        };
+        let claims = serde_json::from_slice::<Claims>(&claims)?;
+        let expired = claims.exp + self.leeway.as_secs() <= now_secs();
+        let revoked = self
+            .revocations
+            .as_ref()
+            .is_some_and(|revocations| revocations.might_be_revoked(&claims.sid));
+        if expired || revoked || claims.iss != self.issuer {
+            return Ok(None);
+        }
+        Ok(Some(Session::new(claims.sid, claims.state)))
+    }
+
+    /// The claims of a token signed with this store's algorithm and secret.
+    fn verify(&self, token: &str) -> Option<Vec<u8>> {
+        let (signing_input, signature) = token.rsplit_once('.')?;
+        let (header, claims) = signing_input.split_once('.')?;
+        // Checked first, so a token cannot pick a weaker algorithm or none.
+        let header = serde_json::from_slice::<Header>(&decode_base64_url(header)?).ok()?;
+        if header.alg != self.algorithm.name() {
+            return None;
+        }
+        let signature = decode_base64_url(signature)?;
+        let verified = match self.algorithm {
+            JwtAlgorithm::Hs256 => mac::<Hmac<Sha256>>(&self.secret, signing_input)
+                .verify_slice(&signature)
+                .is_ok(),
+            JwtAlgorithm::Hs384 => mac::<Hmac<Sha384>>(&self.secret, signing_input)
+                .verify_slice(&signature)
+                .is_ok(),
+            JwtAlgorithm::Hs512 => mac::<Hmac<Sha512>>(&self.secret, signing_input)
+                .verify_slice(&signature)
+                .is_ok(),
+        };
+        verified.then(|| decode_base64_url(claims)).flatten()
+    }
+
+    fn sign(&self, signing_input: &str) -> Vec<u8> {
+        match self.algorithm {
+            JwtAlgorithm::Hs256 => mac::<Hmac<Sha256>>(&self.secret, signing_input)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+            JwtAlgorithm::Hs384 => mac::<Hmac<Sha384>>(&self.secret, signing_input)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+            JwtAlgorithm::Hs512 => mac::<Hmac<Sha512>>(&self.secret, signing_input)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+        }
+    }
+}
+
+fn mac<M: Mac + hmac::digest::KeyInit>(secret: &[u8], signing_input: &str) -> M {
+    let mut mac = <M as Mac>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(signing_input.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+
+    #[test]
+    fn tokens_decode_only_with_the_same_algorithm_and_secret() {
+        let store = JwtSessionStore::new(JwtAlgorithm::Hs384, b"secret").with_issuer("edge");
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        let token = store.encode(&session, Duration::from_secs(60)).unwrap();
+
+        let decoded = store.decode(&token).unwrap().unwrap();
+        assert_eq!(decoded.id(), session.id());
+        assert_eq!(
+            decoded.get::<String>("user_id").unwrap().as_deref(),
+            Some("beavis")
+        );
+
+        let other_secret = JwtSessionStore::new(JwtAlgorithm::Hs384, b"other").with_issuer("edge");
+        let other_algorithm =
+            JwtSessionStore::new(JwtAlgorithm::Hs256, b"secret").with_issuer("edge");
+        let other_issuer = JwtSessionStore::new(JwtAlgorithm::Hs384, b"secret");
+        assert!(other_secret.decode(&token).unwrap().is_none());
+        assert!(other_algorithm.decode(&token).unwrap().is_none());
+        assert!(other_issuer.decode(&token).unwrap().is_none());
+    }
+
+    #[test]
+    fn unsigned_tokens_are_rejected() {
+        let store = JwtSessionStore::new(JwtAlgorithm::Hs256, b"secret");
+        let token = store
+            .encode(&Session::default(), Duration::from_secs(60))
+            .unwrap();
+        let (_, rest) = token.split_once('.').unwrap();
+        let (claims, _) = rest.split_once('.').unwrap();
+        let header = encode_base64_url(br#"{"alg":"none","typ":"JWT"}"#);
+        assert!(store
+            .decode(&format!("{header}.{claims}."))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn revoked_sessions_are_rejected() {
+        let revocations = RevocationFilter::new(100, 0.01);
+        let store = JwtSessionStore::new(JwtAlgorithm::Hs256, b"secret")
+            .with_revocations(revocations.clone());
+        let session = Session::default();
+        let token = store.encode(&session, Duration::from_secs(60)).unwrap();
+        assert!(store.decode(&token).unwrap().is_some());
+
+        revocations.revoke(session.id());
+        assert!(store.decode(&token).unwrap().is_none());
+    }
+}
@@ -0,0 +1,189 @@
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng, RngCore};
+
+const BASE64_URL: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const HEX: &[u8] = b"0123456789abcdef";
+const CROCKFORD: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// The character set session keys are written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyEncoding {
+    /// `[A-Za-z0-9]`, the original format.
+    Alphanumeric,
+    /// Unpadded base64url, `[A-Za-z0-9_-]`.
+    Base64Url,
+    /// Lowercase hex.
+    Hex,
+    /// Crockford base32: uppercase, without the easily confused I, L, O
+    /// and U.
+    Crockford,
+}
+
+/// How session keys are generated and which cookie values are accepted as
+/// keys: an encoding and the number of random bytes behind each key.
+///
+/// Set it per app with [`SessionConfig::with_key_format`](crate::SessionConfig::with_key_format)
+/// and per Redis store with
+/// [`RedisOptions::with_key_format`](crate::RedisOptions::with_key_format);
+/// both default to 48 alphanumeric bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyFormat {
+    encoding: KeyEncoding,
+    bytes: usize,
+}
+
+impl Default for KeyFormat {
+    fn default() -> Self {
+        Self {
+            encoding: KeyEncoding::Alphanumeric,
+            bytes: 48,
+        }
+    }
+}
+
+impl KeyFormat {
+    /// Formats with fewer than 16 random bytes are raised to 16.
+    pub fn new(encoding: KeyEncoding, bytes: usize) -> Self {
+        Self {
+            encoding,
+            bytes: bytes.max(16),
+        }
+    }
+
+    pub fn encoding(&self) -> KeyEncoding {
+        self.encoding
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// The length of an encoded key in characters.
+    pub fn encoded_len(&self) -> usize {
+        match self.encoding {
+            KeyEncoding::Alphanumeric | KeyEncoding::Base64Url => (self.bytes * 4).div_ceil(3),
+            KeyEncoding::Hex => self.bytes * 2,
+            KeyEncoding::Crockford => (self.bytes * 8).div_ceil(5),
+        }
+    }
+
+    /// The randomness behind each key, rounded down.
+    pub fn entropy_bits(&self) -> usize {
+        match self.encoding {
+            // Each of the 62 characters carries just under 6 bits.
+            KeyEncoding::Alphanumeric => self.encoded_len() * 5,
+            _ => self.bytes * 8,
+        }
+    }
+
+    pub(crate) fn generate(&self) -> String {
+        if self.encoding == KeyEncoding::Alphanumeric {
+            return (0..self.encoded_len())
+                .map(|_| char::from(OsRng.sample(Alphanumeric)))
+                .collect();
+        }
+        let mut bytes = vec![0; self.bytes];
+        OsRng.fill_bytes(&mut bytes);
+        match self.encoding {
+            KeyEncoding::Base64Url => encode_bits(&bytes, 6, BASE64_URL),
+            KeyEncoding::Hex => encode_bits(&bytes, 4, HEX),
+            KeyEncoding::Crockford => encode_bits(&bytes, 5, CROCKFORD),
+            KeyEncoding::Alphanumeric => unreachable!(),
+        }
+    }
+
+    pub(crate) fn accepts(&self, value: &str) -> bool {
+        let alphabet: &dyn Fn(u8) -> bool = match self.encoding {
+            KeyEncoding::Alphanumeric => &|c: u8| c.is_ascii_alphanumeric(),
+            KeyEncoding::Base64Url => &|c: u8| BASE64_URL.contains(&c),
+            KeyEncoding::Hex => &|c: u8| HEX.contains(&c),
+            KeyEncoding::Crockford => &|c: u8| CROCKFORD.contains(&c),
+        };
+        value.len() == self.encoded_len() && value.bytes().all(alphabet)
+    }
+}
+
+/// Writes `bytes` `width` bits per character, most significant bit first.
+fn encode_bits(bytes: &[u8], width: u32, alphabet: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= width {
+            bits -= width;
+            encoded.push(char::from(
+                alphabet[((buffer >> bits) & ((1 << width) - 1)) as usize],
+            ));
+        }
+    }
+    if bits > 0 {
+        encoded.push(char::from(
+            alphabet[((buffer << (width - bits)) & ((1 << width) - 1)) as usize],
+        ));
+    }
+    encoded
+}
+
+pub(crate) fn encode_base64_url(bytes: &[u8]) -> String {
+    encode_bits(bytes, 6, BASE64_URL)
+}
+
+/// Reads unpadded base64url, rejecting stray characters and trailing bits.
+pub(crate) fn decode_base64_url(value: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(value.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in value.bytes() {
+        let sextet = BASE64_URL.iter().position(|&a| a == c)?;
+        buffer = (buffer << 6) | sextet as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    (buffer & ((1 << bits) - 1) == 0 && bits < 6).then_some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_match_their_format() {
+        for encoding in [
+            KeyEncoding::Alphanumeric,
+            KeyEncoding::Base64Url,
+            KeyEncoding::Hex,
+            KeyEncoding::Crockford,
+        ] {
+            let format = KeyFormat::new(encoding, 32);
+            let key = format.generate();
+            assert_eq!(key.len(), format.encoded_len(), "{encoding:?}");
+            assert!(format.accepts(&key), "{encoding:?} rejected {key}");
+        }
+        assert_eq!(KeyFormat::default().encoded_len(), 64);
+        assert_eq!(KeyFormat::default().entropy_bits(), 320);
+        assert_eq!(KeyFormat::new(KeyEncoding::Hex, 32).entropy_bits(), 256);
+        assert!(!KeyFormat::new(KeyEncoding::Crockford, 32).accepts(&"I".repeat(52)));
+        assert!(!KeyFormat::new(KeyEncoding::Hex, 32).accepts(&"A".repeat(64)));
+    }
+
+    #[test]
+    fn encode_bits_matches_known_vectors() {
+        assert_eq!(encode_bits(b"\xde\xad\xbe\xef", 4, HEX), "deadbeef");
+        assert_eq!(encode_bits(b"foobar", 6, BASE64_URL), "Zm9vYmFy");
+        assert_eq!(encode_bits(b"f", 5, CROCKFORD), "CR");
+    }
+
+    #[test]
+    fn base64_url_round_trips() {
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"\xff\xfe\xfd\xfc"] {
+            let encoded = encode_base64_url(bytes);
+            assert_eq!(decode_base64_url(&encoded).as_deref(), Some(bytes));
+        }
+        assert_eq!(decode_base64_url("Zm9v+"), None);
+        assert_eq!(decode_base64_url("Zh"), None);
+    }
+}
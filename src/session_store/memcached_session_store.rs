@@ -0,0 +1,172 @@
+use memcache::Client;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+/// memcached reads expirations longer than this as absolute Unix times.
+const MAX_RELATIVE_EXPIRATION: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MemcachedStoreError {
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] CodecError),
+    #[error("Memcached error: {0}")]
+    ClientError(#[from] memcache::MemcacheError),
+}
+
+/// What is stored per session; memcached cannot report a key's remaining TTL,
+/// so the expiry travels with the state.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    state: SessionState,
+    expires_at: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Stores each session under its own memcached key, expired by memcached.
+/// The client is blocking, so every call runs on Tokio's blocking pool.
+pub struct MemcachedSessionStore {
+    prefix: String,
+    client: Client,
+}
+
+impl MemcachedSessionStore {
+    /// Connects to `url`, e.g. `memcache://127.0.0.1:11211`.
+    pub fn new(url: &str) -> Result<Self, MemcachedStoreError> {
+        let client = Client::connect(url)?;
+        Ok(Self::from_client(client))
+    }
+
+    pub fn from_client(client: Client) -> Self {
+        Self {
+            prefix: "session:".to_string(),
+            client,
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    fn cache_key(&self, session_key: &SessionKey) -> String {
+        format!("{}{}", self.prefix, session_key.as_ref())
+    }
+
+    async fn blocking<T, F>(&self, op: F) -> Result<T, MemcachedStoreError>
+    where
+        T: Send + 'static,
+        F: FnOnce(Client) -> Result<T, memcache::MemcacheError> + Send + 'static,
+    {
+        let client = self.client.clone();
+        tokio::task::spawn_blocking(move || op(client))
+            .await
+            .unwrap_or_else(|error| std::panic::resume_unwind(error.into_panic()))
+            .map_err(MemcachedStoreError::from)
+    }
+
+    async fn entry(&self, session_key: &SessionKey) -> Result<Option<Entry>, MemcachedStoreError> {
+        let cache_key = self.cache_key(session_key);
+        let body = self
+            .blocking(move |client| client.get::<String>(&cache_key))
+            .await?;
+        let entry = body
+            .map(|body| ValueCodec::decode::<Entry>(&body))
+            .transpose()?;
+        Ok(entry.filter(|entry| entry.expires_at > now_secs()))
+    }
+
+    /// The entry body and the expiration argument memcached expects.
+    fn encode(
+        &self,
+        session: &Session,
+        timeout: Duration,
+    ) -> Result<(String, String, u32), MemcachedStoreError> {
+        let seconds = timeout.as_secs().max(1);
+        let expires_at = now_secs() + seconds;
+        let entry = Entry {
+            state: session.state().clone(),
+            expires_at,
+        };
+        let expiration = if seconds > MAX_RELATIVE_EXPIRATION {
+            expires_at
+        } else {
+            seconds
+        };
+        let body = ValueCodec::encode(&entry)?;
+        Ok((
+            self.cache_key(session.id()),
+            body,
+            expiration.try_into().unwrap_or(u32::MAX),
+        ))
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for MemcachedSessionStore {
+    type Error = MemcachedStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let entry = self.entry(session_key).await?;
+        Ok(entry.map(|entry| Session::new(session_key.clone(), entry.state)))
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let (cache_key, body, expiration) = self.encode(session, timeout)?;
+        self.blocking(move |client| client.set(&cache_key, body.as_str(), expiration))
+            .await
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let (cache_key, body, expiration) = self.encode(session, timeout)?;
+        self.blocking(move |client| client.replace(&cache_key, body.as_str(), expiration))
+            .await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        let cache_key = self.cache_key(session_key);
+        self.blocking(move |client| client.delete(&cache_key).map(|_| ()))
+            .await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        Ok(self.entry(session_key).await?.is_some())
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        let remaining = self
+            .entry(session_key)
+            .await?
+            .map(|entry| entry.expires_at.saturating_sub(now_secs()))
+            .unwrap_or_default();
+        Ok(Duration::from_secs(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::conformance;
+
+    #[tokio::test]
+    #[ignore = "needs memcached at LUSHUS_MEMCACHED_URL"]
+    async fn memcached_store_passes_the_conformance_checks() {
+        let url = std::env::var("LUSHUS_MEMCACHED_URL")
+            .unwrap_or_else(|_| "memcache://localhost:11211".to_string());
+        let store = MemcachedSessionStore::new(&url)
+            .expect("Unable to connect to memcached")
+            .with_prefix(&format!("conformance-{:016x}:", rand::random::<u64>()));
+        conformance::run(&store).await.unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+use rand::seq::IteratorRandom;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    session::Session,
+    session_store::{SessionKey, SessionSample, SessionStore},
+    SessionState,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MemoryStoreError {
+    #[error("Session does not exist or has expired")]
+    MissingSessionError,
+}
+
+struct Entry {
+    state: SessionState,
+    expires_at: Instant,
+}
+
+impl Entry {
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        let remaining = self.expires_at.saturating_duration_since(now);
+        (!remaining.is_zero()).then_some(remaining)
+    }
+}
+
+/// Keeps sessions in process memory, for tests, local development and
+/// single-node deployments without Redis. Clones share the same sessions.
+///
+/// Expired sessions are dropped when next accessed; call
+/// [`purge_expired`](Self::purge_expired) periodically to reclaim the
+/// memory of sessions nobody comes back for.
+#[derive(Clone, Default)]
+pub struct MemorySessionStore {
+    sessions: Arc<Mutex<HashMap<SessionKey, Entry>>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of sessions held, including any that have expired but
+    /// not yet been dropped.
+    pub fn len(&self) -> usize {
+        self.sessions().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every expired session, returning how many were dropped.
+    pub fn purge_expired(&self) -> usize {
+        let now = Instant::now();
+        let mut sessions = self.sessions();
+        let before = sessions.len();
+        sessions.retain(|_, entry| entry.remaining(now).is_some());
+        before - sessions.len()
+    }
+
+    fn sessions(&self) -> MutexGuard<'_, HashMap<SessionKey, Entry>> {
+        // A panic while holding the lock cannot leave an entry half-written.
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Applies `f` to the live entry for `session_key`, dropping it first
+    /// if it has expired.
+    fn live<R>(
+        &self,
+        session_key: &SessionKey,
+        f: impl FnOnce(&Entry, Duration) -> R,
+    ) -> Option<R> {
+        let now = Instant::now();
+        let mut sessions = self.sessions();
+        let remaining = sessions.get(session_key)?.remaining(now);
+        match remaining {
+            Some(remaining) => Some(f(&sessions[session_key], remaining)),
+            None => {
+                sessions.remove(session_key);
+                None
+            }
+        }
+    }
+
+    fn entry(session: &Session, timeout: Duration) -> Entry {
+        Entry {
+            state: session.state().clone(),
+            expires_at: Instant::now() + timeout,
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for MemorySessionStore {
+    type Error = MemoryStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        Ok(self.live(session_key, |entry, _| {
+            Session::new(session_key.clone(), entry.state.clone())
+        }))
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.sessions()
+            .insert(session.id().clone(), Self::entry(session, timeout));
+        Ok(())
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        if self.live(session.id(), |_, _| ()).is_none() {
+            return Err(MemoryStoreError::MissingSessionError);
+        }
+        self.save(session, timeout).await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.sessions().remove(session_key);
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        Ok(self.live(session_key, |_, _| ()).is_some())
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        Ok(self
+            .live(session_key, |_, remaining| remaining)
+            .unwrap_or(Duration::ZERO))
+    }
+
+    async fn sample(&self, n: usize) -> Result<Vec<SessionSample>, Self::Error> {
+        let now = Instant::now();
+        let sessions = self.sessions();
+        let live = sessions
+            .iter()
+            .filter_map(|(session_key, entry)| Some((session_key, entry, entry.remaining(now)?)));
+        let sample = live
+            .choose_multiple(&mut rand::thread_rng(), n)
+            .into_iter()
+            .map(|(session_key, entry, ttl)| SessionSample {
+                session_key: session_key.clone(),
+                size: ValueCodec::encode(&entry.state).map_or(0, |body| body.len()),
+                ttl,
+            })
+            .collect();
+        Ok(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{session_store::conformance, storage::Storage};
+
+    #[tokio::test]
+    async fn memory_store_passes_the_conformance_checks() {
+        let store = MemorySessionStore::new();
+        conformance::run(&store).await.unwrap();
+        assert!(store.sessions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn memory_store_passes_the_self_test() {
+        let store = MemorySessionStore::new();
+        store.self_test().await.unwrap();
+        assert!(store.sessions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_fails_once_the_session_has_expired() {
+        let store = MemorySessionStore::new();
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        store
+            .save(&session, Duration::from_millis(10))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let result = store.update(&session, Duration::from_secs(60)).await;
+        assert!(matches!(result, Err(MemoryStoreError::MissingSessionError)));
+        assert_eq!(store.ttl(session.id()).await.unwrap(), Duration::ZERO);
+        assert!(store.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sample_picks_distinct_live_sessions() {
+        let store = MemorySessionStore::new();
+        for _ in 0..10 {
+            store
+                .save(&Session::default(), Duration::from_secs(60))
+                .await
+                .unwrap();
+        }
+        store
+            .save(&Session::default(), Duration::ZERO)
+            .await
+            .unwrap();
+
+        let sample = store.sample(4).await.unwrap();
+        assert_eq!(sample.len(), 4);
+        let keys = sample
+            .iter()
+            .map(|sample| &sample.session_key)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(keys.len(), 4);
+        assert!(sample.iter().all(|sample| !sample.ttl.is_zero()));
+        assert_eq!(store.sample(20).await.unwrap().len(), 10);
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use crate::{
+    conflict::ConflictResolver,
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+/// Resolves concurrent saves against the currently stored state before updating.
+///
+/// Keys the session changed overwrite the stored values, untouched keys keep
+/// whatever a concurrent writer stored, and the resolver merges the rest.
+pub struct MergingSessionStore<Store, Resolver> {
+    store: Store,
+    resolver: Resolver,
+}
+
+impl<Store, Resolver> MergingSessionStore<Store, Resolver>
+where
+    Store: SessionStore,
+    Resolver: ConflictResolver,
+{
+    pub fn new(store: Store, resolver: Resolver) -> Self {
+        Self { store, resolver }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store, Resolver> SessionStore for MergingSessionStore<Store, Resolver>
+where
+    Store: SessionStore,
+    Resolver: ConflictResolver,
+{
+    type Error = Store::Error;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        self.store.load(session_key).await
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store.save(session, timeout).await
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let stored = match self.store.load(session.id()).await? {
+            Some(stored) => stored,
+            None => return self.store.update(session, timeout).await,
+        };
+        let mut local = stored.state().clone();
+        for key in session.changed_keys() {
+            match session.state().get(key) {
+                Some(value) => local.insert(key, value.clone()),
+                None => {
+                    local.remove(key);
+                }
+            }
+        }
+        let resolved = self.resolver.resolve(&local, stored.state());
+        let merged = Session::new(session.id().clone(), resolved);
+        self.store.update(&merged, timeout).await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.store.destroy(session_key).await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.store.exists(session_key).await
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.store.ttl(session_key).await
+    }
+}
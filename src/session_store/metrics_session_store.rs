@@ -0,0 +1,146 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+/// A [`SessionStore`] method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StoreOperation {
+    Load,
+    Save,
+    Update,
+    Destroy,
+    Exists,
+    Ttl,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OperationMetrics {
+    pub calls: u64,
+    pub errors: u64,
+    pub total_time: Duration,
+    pub max_time: Duration,
+}
+
+/// Call counts, errors and latency of the stores recording into it; clones
+/// share the same counters, so one can be kept to read them while another
+/// is handed to a [`MetricsSessionStore`].
+#[derive(Clone, Default)]
+pub struct StoreMetrics(Arc<Mutex<BTreeMap<StoreOperation, OperationMetrics>>>);
+
+impl StoreMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<StoreOperation, OperationMetrics> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn record(&self, operation: StoreOperation, elapsed: Duration, failed: bool) {
+        let mut metrics = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let metrics = metrics.entry(operation).or_default();
+        metrics.calls += 1;
+        metrics.errors += u64::from(failed);
+        metrics.total_time += elapsed;
+        metrics.max_time = metrics.max_time.max(elapsed);
+    }
+}
+
+/// Records every call to `store` in a [`StoreMetrics`].
+pub struct MetricsSessionStore<Store> {
+    store: Store,
+    metrics: StoreMetrics,
+}
+
+impl<Store: SessionStore> MetricsSessionStore<Store> {
+    pub fn new(store: Store, metrics: StoreMetrics) -> Self {
+        Self { store, metrics }
+    }
+
+    pub fn metrics(&self) -> &StoreMetrics {
+        &self.metrics
+    }
+
+    async fn timed<T>(
+        &self,
+        operation: StoreOperation,
+        call: impl Future<Output = Result<T, Store::Error>>,
+    ) -> Result<T, Store::Error> {
+        let started = Instant::now();
+        let result = call.await;
+        self.metrics
+            .record(operation, started.elapsed(), result.is_err());
+        result
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store: SessionStore> SessionStore for MetricsSessionStore<Store> {
+    type Error = Store::Error;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        self.timed(StoreOperation::Load, self.store.load(session_key))
+            .await
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.timed(StoreOperation::Save, self.store.save(session, timeout))
+            .await
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.timed(StoreOperation::Update, self.store.update(session, timeout))
+            .await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.timed(StoreOperation::Destroy, self.store.destroy(session_key))
+            .await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.timed(StoreOperation::Exists, self.store.exists(session_key))
+            .await
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.timed(StoreOperation::Ttl, self.store.ttl(session_key))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::testing::FaultyStore;
+
+    #[tokio::test]
+    async fn every_call_is_counted_with_its_outcome() {
+        let metrics = StoreMetrics::new();
+        let inner =
+            FaultyStore::new().with_failures(|operation, _| operation == StoreOperation::Ttl);
+        let store = MetricsSessionStore::new(inner, metrics.clone());
+        let session = Session::default();
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+        store.load(session.id()).await.unwrap();
+        store.load(session.id()).await.unwrap();
+        assert!(store.ttl(session.id()).await.is_err());
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot[&StoreOperation::Load].calls, 2);
+        assert_eq!(snapshot[&StoreOperation::Save].errors, 0);
+        assert_eq!(snapshot[&StoreOperation::Ttl].errors, 1);
+        assert!(!snapshot.contains_key(&StoreOperation::Destroy));
+    }
+}
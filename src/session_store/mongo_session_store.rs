@@ -0,0 +1,197 @@
+use mongodb::{
+    bson::{doc, DateTime, Document},
+    options::IndexOptions,
+    Client, Collection, Database, IndexModel,
+};
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+const STATE: &str = "state";
+const EXPIRES_AT: &str = "expires_at";
+
+#[derive(Debug, thiserror::Error)]
+pub enum MongoStoreError {
+    #[error("Storage error: Backend error {0}")]
+    BackendError(String),
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] CodecError),
+    #[error("MongoDB error: {0}")]
+    DatabaseError(#[from] mongodb::error::Error),
+}
+
+fn expires_after(timeout: Duration) -> DateTime {
+    DateTime::from_system_time(SystemTime::now() + timeout)
+}
+
+/// Stores each session as a document keyed by `_id`, with the expiry in a
+/// BSON date `expires_at` field that a TTL index deletes on. The TTL monitor
+/// only runs about once a minute, so reads also ignore documents past their
+/// expiry.
+pub struct MongoSessionStore {
+    database: Database,
+    collection: Collection<Document>,
+}
+
+impl MongoSessionStore {
+    /// Connects to `uri`, e.g. `mongodb://127.0.0.1:27017`, using the
+    /// database called `database`.
+    pub async fn new(uri: &str, database: &str) -> Result<Self, MongoStoreError> {
+        let client = Client::with_uri_str(uri).await?;
+        Ok(Self::from_database(client.database(database)))
+    }
+
+    pub fn from_database(database: Database) -> Self {
+        Self {
+            collection: database.collection("sessions"),
+            database,
+        }
+    }
+
+    pub fn with_collection(mut self, collection: &str) -> Self {
+        self.collection = self.database.collection(collection);
+        self
+    }
+
+    /// Creates the TTL index on `expires_at`, if it does not exist.
+    pub async fn migrate(&self) -> Result<(), MongoStoreError> {
+        let index = IndexModel::builder()
+            .keys(doc! { EXPIRES_AT: 1 })
+            .options(IndexOptions::builder().expire_after(Duration::ZERO).build())
+            .build();
+        self.collection.create_index(index).await?;
+        Ok(())
+    }
+
+    async fn document(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<Document>, MongoStoreError> {
+        let document = self
+            .collection
+            .find_one(doc! {
+                "_id": session_key.as_ref(),
+                EXPIRES_AT: { "$gt": DateTime::now() },
+            })
+            .await?;
+        Ok(document)
+    }
+
+    fn encode(session: &Session, timeout: Duration) -> Result<Document, MongoStoreError> {
+        let state = ValueCodec::encode(session.state())?;
+        Ok(doc! {
+            "_id": session.id().as_ref(),
+            STATE: state,
+            EXPIRES_AT: expires_after(timeout),
+        })
+    }
+}
+
+fn expires_at(document: &Document) -> Result<SystemTime, MongoStoreError> {
+    let expires_at = document
+        .get_datetime(EXPIRES_AT)
+        .map_err(|error| MongoStoreError::BackendError(error.to_string()))?;
+    Ok(expires_at.to_system_time())
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for MongoSessionStore {
+    type Error = MongoStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let Some(document) = self.document(session_key).await? else {
+            return Ok(None);
+        };
+        let state = document
+            .get_str(STATE)
+            .map_err(|error| MongoStoreError::BackendError(error.to_string()))?;
+        let state = ValueCodec::decode::<SessionState>(state)?;
+        Ok(Some(Session::new(session_key.clone(), state)))
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.collection
+            .replace_one(
+                doc! { "_id": session.id().as_ref() },
+                Self::encode(session, timeout)?,
+            )
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let updated = self
+            .collection
+            .replace_one(
+                doc! {
+                    "_id": session.id().as_ref(),
+                    EXPIRES_AT: { "$gt": DateTime::now() },
+                },
+                Self::encode(session, timeout)?,
+            )
+            .await?;
+        if updated.matched_count == 0 {
+            return Err(MongoStoreError::BackendError(
+                "Update matched no live session".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.collection
+            .delete_one(doc! { "_id": session_key.as_ref() })
+            .await?;
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        Ok(self.document(session_key).await?.is_some())
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        let remaining = self
+            .expires_at(session_key)
+            .await?
+            .and_then(|expires_at| expires_at.duration_since(SystemTime::now()).ok())
+            .unwrap_or_default();
+        Ok(remaining)
+    }
+
+    async fn expires_at(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<SystemTime>, Self::Error> {
+        self.document(session_key)
+            .await?
+            .map(|document| expires_at(&document))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::conformance;
+
+    #[tokio::test]
+    #[ignore = "needs MongoDB at LUSHUS_MONGO_URI"]
+    async fn mongo_store_passes_the_conformance_checks() {
+        let uri = std::env::var("LUSHUS_MONGO_URI")
+            .unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+        let collection = format!("conformance-{:016x}", rand::random::<u64>());
+        let store = MongoSessionStore::new(&uri, "lushus")
+            .await
+            .expect("Unable to connect to MongoDB")
+            .with_collection(&collection);
+        store.migrate().await.expect("Unable to create the index");
+        conformance::run(&store).await.unwrap();
+        store.collection.drop().await.unwrap();
+    }
+}
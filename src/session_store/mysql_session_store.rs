@@ -0,0 +1,194 @@
+use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
+use std::time::Duration;
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    session::Session,
+    session_state::SessionState,
+    session_store::{sql, SessionKey, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MySqlStoreError {
+    #[error("Storage error: Backend error {0}")]
+    BackendError(String),
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] CodecError),
+    #[error("MySQL error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+fn micros(timeout: Duration) -> i64 {
+    timeout.as_micros().try_into().unwrap_or(i64::MAX)
+}
+
+/// Stores each session as a row in MySQL or MariaDB with an `expires_at`
+/// column that reads filter on, so it behaves like the Redis store.
+/// Expired rows stay until [`delete_expired`](Self::delete_expired) removes
+/// them.
+pub struct MySqlSessionStore {
+    table: String,
+    pool: MySqlPool,
+}
+
+impl MySqlSessionStore {
+    pub async fn new(url: &str) -> Result<Self, MySqlStoreError> {
+        let pool = MySqlPoolOptions::new().connect(url).await?;
+        Ok(Self::from_pool(pool))
+    }
+
+    pub fn from_pool(pool: MySqlPool) -> Self {
+        Self {
+            table: "sessions".to_string(),
+            pool,
+        }
+    }
+
+    /// Panics unless `table` is a plain, optionally database-qualified, SQL
+    /// identifier, since it is spliced into every query.
+    pub fn with_table(mut self, table: &str) -> Self {
+        sql::assert_table_name(table);
+        self.table = table.to_string();
+        self
+    }
+
+    /// Creates the sessions table, with its expiry index, if it does not
+    /// exist.
+    pub async fn migrate(&self) -> Result<(), MySqlStoreError> {
+        let table = &self.table;
+        let index = sql::expiry_index(table);
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                id VARCHAR(255) NOT NULL PRIMARY KEY,
+                state LONGTEXT NOT NULL,
+                expires_at DATETIME(6) NOT NULL,
+                INDEX {index} (expires_at)
+            )"
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes every expired row, returning how many were deleted.
+    pub async fn delete_expired(&self) -> Result<u64, MySqlStoreError> {
+        let deleted = sqlx::query(&format!(
+            "DELETE FROM {} WHERE expires_at <= NOW(6)",
+            self.table
+        ))
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(deleted)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for MySqlSessionStore {
+    type Error = MySqlStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let state = sqlx::query_scalar::<_, String>(&format!(
+            "SELECT state FROM {} WHERE id = ? AND expires_at > NOW(6)",
+            self.table
+        ))
+        .bind(session_key.as_ref())
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|state| ValueCodec::decode::<SessionState>(&state))
+        .transpose()?;
+        Ok(state.map(|state| Session::new(session_key.clone(), state)))
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let state = ValueCodec::encode(session.state())?;
+        sqlx::query(&format!(
+            "INSERT INTO {} (id, state, expires_at)
+             VALUES (?, ?, NOW(6) + INTERVAL ? MICROSECOND)
+             ON DUPLICATE KEY UPDATE state = VALUES(state), expires_at = VALUES(expires_at)",
+            self.table
+        ))
+        .bind(session.id().as_ref())
+        .bind(state)
+        .bind(micros(timeout))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let state = ValueCodec::encode(session.state())?;
+        let updated = sqlx::query(&format!(
+            "UPDATE {}
+             SET state = ?, expires_at = NOW(6) + INTERVAL ? MICROSECOND
+             WHERE id = ? AND expires_at > NOW(6)",
+            self.table
+        ))
+        .bind(state)
+        .bind(micros(timeout))
+        .bind(session.id().as_ref())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Err(MySqlStoreError::BackendError(
+                "Update matched no live session".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        sqlx::query(&format!("DELETE FROM {} WHERE id = ?", self.table))
+            .bind(session_key.as_ref())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        let count = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT COUNT(*) FROM {} WHERE id = ? AND expires_at > NOW(6)",
+            self.table
+        ))
+        .bind(session_key.as_ref())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count > 0)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        let remaining = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT TIMESTAMPDIFF(MICROSECOND, NOW(6), expires_at) FROM {} WHERE id = ?",
+            self.table
+        ))
+        .bind(session_key.as_ref())
+        .fetch_optional(&self.pool)
+        .await?
+        .unwrap_or_default();
+        Ok(Duration::from_micros(remaining.max(0) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::conformance;
+
+    #[tokio::test]
+    #[ignore = "needs MySQL at LUSHUS_MYSQL_URL"]
+    async fn mysql_store_passes_the_conformance_checks() {
+        let url = std::env::var("LUSHUS_MYSQL_URL")
+            .unwrap_or_else(|_| "mysql://root@localhost/sessions".to_string());
+        let store = MySqlSessionStore::new(&url)
+            .await
+            .expect("Unable to connect to MySQL")
+            .with_table(&format!("conformance_{:016x}", rand::random::<u64>()));
+        store.migrate().await.expect("Unable to create the table");
+        conformance::run(&store).await.unwrap();
+        sqlx::query(&format!("DROP TABLE {}", store.table))
+            .execute(&store.pool)
+            .await
+            .unwrap();
+    }
+}
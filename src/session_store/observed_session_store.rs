@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use crate::{
+    observer::{SessionEvent, SessionEventKind, SessionObserver},
+    redaction::{BoxedRedactor, Redactor},
+    session::Session,
+    session_state::{SessionState, StateDiff},
+    session_store::{SessionKey, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ObservedStoreError<S, O> {
+    #[error("Session store error: {0}")]
+    StoreError(#[source] S),
+    #[error("Session observer error: {0}")]
+    ObserverError(O),
+}
+
+/// Emits a [`SessionEvent`] to the observer after every successful mutation.
+pub struct ObservedSessionStore<Store, Observer> {
+    store: Store,
+    observer: Observer,
+    redactor: Option<BoxedRedactor>,
+}
+
+impl<Store, Observer> ObservedSessionStore<Store, Observer>
+where
+    Store: SessionStore,
+    Observer: SessionObserver,
+{
+    pub fn new(store: Store, observer: Observer) -> Self {
+        Self {
+            store,
+            observer,
+            redactor: None,
+        }
+    }
+
+    /// Redacts the diffs carried by `Created` and `Updated` events before the
+    /// observer sees them.
+    pub fn with_redactor(mut self, redactor: impl Redactor + Send + Sync + 'static) -> Self {
+        self.redactor = Some(Box::new(redactor));
+        self
+    }
+
+    async fn notify(
+        &self,
+        session_key: &SessionKey,
+        mut kind: SessionEventKind,
+    ) -> Result<(), ObservedStoreError<Store::Error, Observer::Error>> {
+        if let (
+            Some(redactor),
+            SessionEventKind::Created { diff, .. } | SessionEventKind::Updated { diff, .. },
+        ) = (&self.redactor, &mut kind)
+        {
+            redactor.redact_diff(diff);
+        }
+        let event = SessionEvent::new(session_key.clone(), kind);
+        self.observer
+            .notify(&event)
+            .await
+            .map_err(ObservedStoreError::ObserverError)
+    }
+
+    async fn notify_exposures(
+        &self,
+        session: &Session,
+    ) -> Result<(), ObservedStoreError<Store::Error, Observer::Error>> {
+        for exposure in session.exposures() {
+            let exposure = exposure.clone();
+            self.notify(session.id(), SessionEventKind::Exposed { exposure })
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store, Observer> SessionStore for ObservedSessionStore<Store, Observer>
+where
+    Store: SessionStore,
+    Observer: SessionObserver,
+{
+    type Error = ObservedStoreError<Store::Error, Observer::Error>;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        self.store
+            .load(session_key)
+            .await
+            .map_err(ObservedStoreError::StoreError)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store
+            .save(session, timeout)
+            .await
+            .map_err(ObservedStoreError::StoreError)?;
+        let diff = StateDiff::between(&SessionState::default(), session.state());
+        self.notify(session.id(), SessionEventKind::Created { diff, timeout })
+            .await?;
+        self.notify_exposures(session).await
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let previous = self
+            .store
+            .load(session.id())
+            .await
+            .map_err(ObservedStoreError::StoreError)?
+            .map(SessionState::from)
+            .unwrap_or_default();
+        self.store
+            .update(session, timeout)
+            .await
+            .map_err(ObservedStoreError::StoreError)?;
+        let diff = StateDiff::between(&previous, session.state());
+        self.notify(session.id(), SessionEventKind::Updated { diff, timeout })
+            .await?;
+        self.notify_exposures(session).await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.store
+            .destroy(session_key)
+            .await
+            .map_err(ObservedStoreError::StoreError)?;
+        self.notify(session_key, SessionEventKind::Destroyed).await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.store
+            .exists(session_key)
+            .await
+            .map_err(ObservedStoreError::StoreError)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.store
+            .ttl(session_key)
+            .await
+            .map_err(ObservedStoreError::StoreError)
+    }
+}
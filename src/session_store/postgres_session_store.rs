@@ -0,0 +1,272 @@
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    session::Session,
+    session_state::SessionState,
+    session_store::{sql, SessionKey, SessionSample, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PostgresStoreError {
+    #[error("Storage error: Backend error {0}")]
+    BackendError(String),
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] serde_json::Error),
+    #[error("Postgres error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Stores each session as a row keyed by session key, with an `expires_at`
+/// column that reads filter on. Expired rows stay until
+/// [`delete_expired`](Self::delete_expired) removes them.
+pub struct PostgresSessionStore {
+    table: String,
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub async fn new(url: &str) -> Result<Self, PostgresStoreError> {
+        let pool = PgPoolOptions::new().connect(url).await?;
+        Ok(Self::from_pool(pool))
+    }
+
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self {
+            table: "sessions".to_string(),
+            pool,
+        }
+    }
+
+    /// Panics unless `table` is a plain, optionally schema-qualified, SQL
+    /// identifier, since it is spliced into every query.
+    pub fn with_table(mut self, table: &str) -> Self {
+        sql::assert_table_name(table);
+        self.table = table.to_string();
+        self
+    }
+
+    /// Creates the sessions table and its expiry index if they do not exist.
+    pub async fn migrate(&self) -> Result<(), PostgresStoreError> {
+        let table = &self.table;
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                id TEXT PRIMARY KEY,
+                state JSONB NOT NULL,
+                expires_at TIMESTAMPTZ NOT NULL
+            )"
+        ))
+        .execute(&self.pool)
+        .await?;
+        let index = sql::expiry_index(table);
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {index} ON {table} (expires_at)"
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes every expired row, returning how many were deleted.
+    pub async fn delete_expired(&self) -> Result<u64, PostgresStoreError> {
+        let deleted = sqlx::query(&format!(
+            "DELETE FROM {} WHERE expires_at <= now()",
+            self.table
+        ))
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(deleted)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for PostgresSessionStore {
+    type Error = PostgresStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let state = sqlx::query_scalar::<_, String>(&format!(
+            "SELECT state::text FROM {} WHERE id = $1 AND expires_at > now()",
+            self.table
+        ))
+        .bind(session_key.as_ref())
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|state| serde_json::from_str::<SessionState>(&state))
+        .transpose()?;
+        Ok(state.map(|state| Session::new(session_key.clone(), state)))
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let state = serde_json::to_string(session.state())?;
+        sqlx::query(&format!(
+            "INSERT INTO {} (id, state, expires_at)
+             VALUES ($1, $2::jsonb, now() + make_interval(secs => $3))
+             ON CONFLICT (id) DO UPDATE
+             SET state = EXCLUDED.state, expires_at = EXCLUDED.expires_at",
+            self.table
+        ))
+        .bind(session.id().as_ref())
+        .bind(state)
+        .bind(timeout.as_secs_f64())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let state = serde_json::to_string(session.state())?;
+        let updated = sqlx::query(&format!(
+            "UPDATE {}
+             SET state = $2::jsonb, expires_at = now() + make_interval(secs => $3)
+             WHERE id = $1 AND expires_at > now()",
+            self.table
+        ))
+        .bind(session.id().as_ref())
+        .bind(state)
+        .bind(timeout.as_secs_f64())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Err(PostgresStoreError::BackendError(
+                "Update matched no live session".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        sqlx::query(&format!("DELETE FROM {} WHERE id = $1", self.table))
+            .bind(session_key.as_ref())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        let exists = sqlx::query_scalar::<_, bool>(&format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE id = $1 AND expires_at > now())",
+            self.table
+        ))
+        .bind(session_key.as_ref())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        let seconds = sqlx::query_scalar::<_, f64>(&format!(
+            "SELECT EXTRACT(EPOCH FROM expires_at - now())::float8 FROM {} WHERE id = $1",
+            self.table
+        ))
+        .bind(session_key.as_ref())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(Duration::from_secs_f64(seconds.unwrap_or(0.0).max(0.0)))
+    }
+
+    async fn expires_at(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<SystemTime>, Self::Error> {
+        let epoch = sqlx::query_scalar::<_, f64>(&format!(
+            "SELECT EXTRACT(EPOCH FROM expires_at)::float8 FROM {}
+             WHERE id = $1 AND expires_at > now()",
+            self.table
+        ))
+        .bind(session_key.as_ref())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(epoch.map(|epoch| UNIX_EPOCH + Duration::from_secs_f64(epoch.max(0.0))))
+    }
+
+    /// Reads a block sample sized from the planner's row estimate, so only
+    /// a fraction of the table is scanned.
+    async fn sample(&self, n: usize) -> Result<Vec<SessionSample>, Self::Error> {
+        let rows =
+            sqlx::query_scalar::<_, f32>("SELECT reltuples FROM pg_class WHERE oid = $1::regclass")
+                .bind(&self.table)
+                .fetch_optional(&self.pool)
+                .await?
+                .unwrap_or_default();
+        // Oversampled, as blocks hold expired rows too; a table never
+        // analysed estimates -1 rows and is read in full.
+        let percent = if rows > 0.0 {
+            (400.0 * n as f64 / f64::from(rows)).min(100.0)
+        } else {
+            100.0
+        };
+        let sampled = sqlx::query_as::<_, (String, i32, f64)>(&format!(
+            "SELECT id, octet_length(state::text), EXTRACT(EPOCH FROM expires_at - now())::float8
+             FROM {} TABLESAMPLE SYSTEM ($1)
+             WHERE expires_at > now()
+             ORDER BY random()
+             LIMIT $2",
+            self.table
+        ))
+        .bind(percent)
+        .bind(i64::try_from(n).unwrap_or(i64::MAX))
+        .fetch_all(&self.pool)
+        .await?;
+        let sample = sampled
+            .into_iter()
+            .map(|(id, size, ttl)| SessionSample {
+                session_key: SessionKey::from_raw(id),
+                size: size.max(0) as usize,
+                ttl: Duration::from_secs_f64(ttl.max(0.0)),
+            })
+            .collect();
+        Ok(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::conformance;
+
+    async fn store() -> PostgresSessionStore {
+        let url = std::env::var("LUSHUS_POSTGRES_URL")
+            .unwrap_or_else(|_| "postgres://postgres@localhost/postgres".to_string());
+        let store = PostgresSessionStore::new(&url)
+            .await
+            .expect("Unable to connect to Postgres")
+            .with_table(&format!("conformance_{:016x}", rand::random::<u64>()));
+        store.migrate().await.expect("Unable to create the table");
+        store
+    }
+
+    async fn drop_table(store: PostgresSessionStore) {
+        sqlx::query(&format!("DROP TABLE {}", store.table))
+            .execute(&store.pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at LUSHUS_POSTGRES_URL"]
+    async fn postgres_store_passes_the_conformance_checks() {
+        let store = store().await;
+        conformance::run(&store).await.unwrap();
+        drop_table(store).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at LUSHUS_POSTGRES_URL"]
+    async fn sample_reads_a_partial_block_sample_of_an_analysed_table() {
+        let store = store().await;
+        for _ in 0..10 {
+            let session = Session::default();
+            store.save(&session, Duration::from_secs(60)).await.unwrap();
+        }
+        assert_eq!(store.sample(10).await.unwrap().len(), 10);
+
+        sqlx::query(&format!("ANALYZE {}", store.table))
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        assert!(store.sample(1).await.unwrap().len() <= 1);
+        drop_table(store).await;
+    }
+}
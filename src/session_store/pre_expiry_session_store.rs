@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+/// Calls `notify` when a session seen through this store comes within `lead`
+/// of expiring, so the app can warn the user first, and destroys sessions
+/// past their absolute timeout.
+///
+/// A session expires at its stored expiry or, with
+/// [`with_absolute_timeout`](Self::with_absolute_timeout), at its creation
+/// time plus that timeout if sooner. Both are read from the store on every
+/// [`tick`](Self::tick), so writes from other nodes count too; a session is
+/// announced again only once its expiry has moved by more than `lead`.
+/// [`run`](Self::run) is the collection loop for absolute expiry; schedule
+/// it on one task per node. Sessions are picked up when they are saved or
+/// loaded through this store.
+pub struct PreExpirySessionStore<Store, Notify> {
+    store: Store,
+    notify: Notify,
+    lead: Duration,
+    absolute_timeout: Option<Duration>,
+    /// Every session seen, with the expiry it was last notified about.
+    seen: Mutex<HashMap<SessionKey, Option<SystemTime>>>,
+}
+
+impl<Store, Notify> PreExpirySessionStore<Store, Notify>
+where
+    Store: SessionStore,
+    Notify: Fn(&SessionKey, Duration),
+{
+    pub fn new(store: Store, lead: Duration, notify: Notify) -> Self {
+        Self {
+            store,
+            notify,
+            lead,
+            absolute_timeout: None,
+            seen: Default::default(),
+        }
+    }
+
+    /// Expires sessions this long after they were created, however active,
+    /// matching [`SessionConfig::with_absolute_timeout`].
+    ///
+    /// [`SessionConfig::with_absolute_timeout`]: crate::SessionConfig::with_absolute_timeout
+    pub fn with_absolute_timeout(mut self, absolute_timeout: Duration) -> Self {
+        self.absolute_timeout = Some(absolute_timeout);
+        self
+    }
+
+    fn seen(&self) -> std::sync::MutexGuard<'_, HashMap<SessionKey, Option<SystemTime>>> {
+        self.seen.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn track(&self, session_key: &SessionKey) {
+        self.seen().entry(session_key.clone()).or_default();
+    }
+
+    fn untrack(&self, session_key: &SessionKey) {
+        self.seen().remove(session_key);
+    }
+
+    /// Records `expiry` as announced unless an expiry within `lead` of it
+    /// already was. Stores that derive the expiry from a TTL report it a
+    /// little differently on every read.
+    fn announce(&self, session_key: &SessionKey, expiry: SystemTime) -> bool {
+        let mut seen = self.seen();
+        let Some(announced) = seen.get_mut(session_key) else {
+            return false;
+        };
+        if announced.is_some_and(|announced| expiry <= announced + self.lead) {
+            return false;
+        }
+        *announced = Some(expiry);
+        true
+    }
+
+    /// When `session_key` expires, or `None` once it is gone.
+    async fn expiry(&self, session_key: &SessionKey) -> Result<Option<SystemTime>, Store::Error> {
+        let Some(expires_at) = self.store.expires_at(session_key).await? else {
+            return Ok(None);
+        };
+        let Some(absolute_timeout) = self.absolute_timeout else {
+            return Ok(Some(expires_at));
+        };
+        let created_at = self
+            .store
+            .load(session_key)
+            .await?
+            .and_then(|session| session.created_at());
+        Ok(Some(created_at.map_or(expires_at, |created_at| {
+            expires_at.min(created_at + absolute_timeout)
+        })))
+    }
+
+    /// Destroys every seen session past its absolute timeout, notifies each
+    /// one newly within `lead` of expiring and returns how many were
+    /// notified.
+    pub async fn tick(&self) -> Result<usize, Store::Error> {
+        let mut notified = 0;
+        let seen = self.seen().keys().cloned().collect::<Vec<_>>();
+        for key in seen {
+            let Some(expiry) = self.expiry(&key).await? else {
+                self.untrack(&key);
+                continue;
+            };
+            let remaining = expiry.duration_since(SystemTime::now()).unwrap_or_default();
+            if remaining.is_zero() {
+                self.store.destroy(&key).await?;
+                self.untrack(&key);
+            } else if remaining <= self.lead && self.announce(&key, expiry) {
+                (self.notify)(&key, remaining);
+                notified += 1;
+            }
+        }
+        Ok(notified)
+    }
+
+    /// Calls [`tick`](Self::tick) every `interval`, returning on the first
+    /// store error.
+    pub async fn run(&self, interval: Duration) -> Result<(), Store::Error> {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            self.tick().await?;
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store, Notify> SessionStore for PreExpirySessionStore<Store, Notify>
+where
+    Store: SessionStore,
+    Notify: Fn(&SessionKey, Duration),
+{
+    type Error = Store::Error;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let loaded = self.store.load(session_key).await?;
+        if loaded.is_some() {
+            self.track(session_key);
+        }
+        Ok(loaded)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store.save(session, timeout).await?;
+        self.track(session.id());
+        Ok(())
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store.update(session, timeout).await?;
+        self.track(session.id());
+        Ok(())
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.store.destroy(session_key).await?;
+        self.untrack(session_key);
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.store.exists(session_key).await
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.store.ttl(session_key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::MemorySessionStore;
+
+    #[tokio::test]
+    async fn tick_notifies_each_stored_expiry_once() {
+        let notified = RefCell::new(Vec::new());
+        let store = PreExpirySessionStore::new(
+            MemorySessionStore::new(),
+            Duration::from_secs(60),
+            |session_key: &SessionKey, _| notified.borrow_mut().push(session_key.clone()),
+        );
+        let soon = Session::default();
+        store.save(&soon, Duration::from_secs(30)).await.unwrap();
+        let later = Session::default();
+        store.save(&later, Duration::from_secs(600)).await.unwrap();
+
+        assert_eq!(store.tick().await.unwrap(), 1);
+        assert_eq!(store.tick().await.unwrap(), 0);
+        assert_eq!(*notified.borrow(), vec![soon.id().clone()]);
+    }
+
+    #[tokio::test]
+    async fn tick_destroys_sessions_past_the_absolute_timeout() {
+        let inner = MemorySessionStore::new();
+        let mut outlived = Session::default();
+        outlived.backdate_created(Duration::from_secs(7200));
+        inner
+            .save(&outlived, Duration::from_secs(600))
+            .await
+            .unwrap();
+        let mut ending = Session::default();
+        ending.backdate_created(Duration::from_secs(3590));
+        inner.save(&ending, Duration::from_secs(600)).await.unwrap();
+        let store =
+            PreExpirySessionStore::new(&inner, Duration::from_secs(60), |_: &SessionKey, _| {})
+                .with_absolute_timeout(Duration::from_secs(3600));
+        store.load(outlived.id()).await.unwrap();
+        store.load(ending.id()).await.unwrap();
+
+        assert_eq!(store.tick().await.unwrap(), 1);
+        assert!(!inner.exists(outlived.id()).await.unwrap());
+        assert!(inner.exists(ending.id()).await.unwrap());
+    }
+}
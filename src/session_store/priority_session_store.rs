@@ -0,0 +1,117 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::{
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Lane {
+    /// Calls serving a user request, e.g. the per-request session load.
+    #[default]
+    Interactive,
+    /// Maintenance traffic such as GC, archival or replication.
+    Background,
+}
+
+struct Admission {
+    total: Semaphore,
+    background: Semaphore,
+}
+
+impl Admission {
+    fn new(capacity: usize, reserved: usize) -> Self {
+        Self {
+            total: Semaphore::new(capacity),
+            background: Semaphore::new(capacity.saturating_sub(reserved)),
+        }
+    }
+
+    async fn admit(&self, lane: Lane) -> (Option<SemaphorePermit<'_>>, SemaphorePermit<'_>) {
+        let background = match lane {
+            Lane::Interactive => None,
+            Lane::Background => Some(self.background.acquire().await.expect("semaphore closed")),
+        };
+        let total = self.total.acquire().await.expect("semaphore closed");
+        (background, total)
+    }
+}
+
+/// Caps in-flight calls to the wrapped store at `capacity`, of which
+/// `reserved` slots can only be taken by [`Lane::Interactive`] calls, so
+/// background traffic cannot starve request handling when saturated.
+pub struct PrioritySessionStore<Store> {
+    store: Store,
+    admission: Arc<Admission>,
+    lane: Lane,
+}
+
+impl<Store: SessionStore> PrioritySessionStore<Store> {
+    pub fn new(store: Store, capacity: usize, reserved: usize) -> Self {
+        Self {
+            store,
+            admission: Arc::new(Admission::new(capacity, reserved)),
+            lane: Lane::Interactive,
+        }
+    }
+
+    /// A handle to the same store and admission limits in another lane.
+    pub fn lane(&self, lane: Lane) -> PrioritySessionStore<&Store> {
+        PrioritySessionStore {
+            store: &self.store,
+            admission: self.admission.clone(),
+            lane,
+        }
+    }
+
+    async fn admitted<T>(&self, call: impl Future<Output = T>) -> T {
+        let _permits = self.admission.admit(self.lane).await;
+        call.await
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store: SessionStore> SessionStore for PrioritySessionStore<Store> {
+    type Error = Store::Error;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        self.admitted(self.store.load(session_key)).await
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.admitted(self.store.save(session, timeout)).await
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.admitted(self.store.update(session, timeout)).await
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.admitted(self.store.destroy(session_key)).await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.admitted(self.store.exists(session_key)).await
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.admitted(self.store.ttl(session_key)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[tokio::test]
+    async fn background_calls_cannot_take_reserved_slots() {
+        let admission = Admission::new(2, 1);
+        let _held = admission.admit(Lane::Background).await;
+
+        assert!(admission.admit(Lane::Background).now_or_never().is_none());
+        assert!(admission.admit(Lane::Interactive).now_or_never().is_some());
+    }
+}
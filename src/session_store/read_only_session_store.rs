@@ -0,0 +1,190 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+/// Returned for writes while maintenance mode is active. `until` is the end
+/// of the window, if it was time-boxed, so callers can tell users when to
+/// retry.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[error("Sessions are read-only for maintenance")]
+pub struct ReadOnlyMode {
+    pub until: Option<Instant>,
+}
+
+impl ReadOnlyMode {
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReadOnlyStoreError<S> {
+    #[error("Session store error: {0}")]
+    StoreError(#[source] S),
+    #[error("{0}")]
+    ReadOnlyError(#[source] ReadOnlyMode),
+}
+
+#[derive(Clone, Copy)]
+enum Window {
+    Inactive,
+    Until(Option<Instant>),
+}
+
+/// A switch shared by every store and handler that should honour the same
+/// maintenance window. Clones observe the same state.
+#[derive(Clone)]
+pub struct MaintenanceMode(Arc<Mutex<Window>>);
+
+impl Default for MaintenanceMode {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(Window::Inactive)))
+    }
+}
+
+impl MaintenanceMode {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes sessions read-only until [`MaintenanceMode::disable`] is called.
+    pub fn enable(&self) {
+        self.set(Window::Until(None));
+    }
+
+    /// Makes sessions read-only for `duration`, after which writes resume on
+    /// their own.
+    pub fn enable_for(&self, duration: Duration) {
+        self.set(Window::Until(Some(Instant::now() + duration)));
+    }
+
+    pub fn disable(&self) {
+        self.set(Window::Inactive);
+    }
+
+    /// The active window, if any.
+    pub fn active(&self) -> Option<ReadOnlyMode> {
+        let mut window = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match *window {
+            Window::Until(Some(until)) if until <= Instant::now() => {
+                *window = Window::Inactive;
+                None
+            }
+            Window::Until(until) => Some(ReadOnlyMode { until }),
+            Window::Inactive => None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active().is_some()
+    }
+
+    fn set(&self, window: Window) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = window;
+    }
+}
+
+/// Rejects saves, updates and destroys with [`ReadOnlyMode`] while the
+/// maintenance switch is on; reads pass through.
+pub struct ReadOnlySessionStore<Store> {
+    store: Store,
+    mode: MaintenanceMode,
+}
+
+impl<Store: SessionStore> ReadOnlySessionStore<Store> {
+    pub fn new(store: Store, mode: MaintenanceMode) -> Self {
+        Self { store, mode }
+    }
+
+    pub fn mode(&self) -> &MaintenanceMode {
+        &self.mode
+    }
+
+    fn check(&self) -> Result<(), ReadOnlyStoreError<Store::Error>> {
+        match self.mode.active() {
+            Some(mode) => Err(ReadOnlyStoreError::ReadOnlyError(mode)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store: SessionStore> SessionStore for ReadOnlySessionStore<Store> {
+    type Error = ReadOnlyStoreError<Store::Error>;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        self.store
+            .load(session_key)
+            .await
+            .map_err(ReadOnlyStoreError::StoreError)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.check()?;
+        self.store
+            .save(session, timeout)
+            .await
+            .map_err(ReadOnlyStoreError::StoreError)
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.check()?;
+        self.store
+            .update(session, timeout)
+            .await
+            .map_err(ReadOnlyStoreError::StoreError)
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.check()?;
+        self.store
+            .destroy(session_key)
+            .await
+            .map_err(ReadOnlyStoreError::StoreError)
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.store
+            .exists(session_key)
+            .await
+            .map_err(ReadOnlyStoreError::StoreError)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.store
+            .ttl(session_key)
+            .await
+            .map_err(ReadOnlyStoreError::StoreError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maintenance_mode_is_shared_between_clones_and_ends_with_its_window() {
+        let mode = MaintenanceMode::new();
+        let handle = mode.clone();
+        assert!(!mode.is_active());
+
+        handle.enable();
+        assert_eq!(mode.active(), Some(ReadOnlyMode { until: None }));
+        handle.disable();
+        assert!(!mode.is_active());
+
+        handle.enable_for(Duration::from_secs(60));
+        let retry_after = mode.active().and_then(|mode| mode.retry_after()).unwrap();
+        assert!(retry_after <= Duration::from_secs(60));
+
+        handle.enable_for(Duration::ZERO);
+        assert!(!mode.is_active());
+    }
+}
@@ -1,33 +1,172 @@
+#[cfg(feature = "cluster")]
+mod cluster;
 mod commands;
+mod sentinel;
 
+use futures::stream::{self, LocalBoxStream, StreamExt};
 use redis::aio::ConnectionManager;
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 
+use crate::session_store::watch;
 use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    deletion_queue::DeletionQueue,
+    eviction::{EvictionCandidate, EvictionSource},
+    idempotency::{IdempotencyClaim, IdempotencyRecord, IdempotencyStore},
     session::Session,
     session_state::SessionState,
-    session_store::{SessionKey, SessionStore},
+    session_store::{KeyFormat, SessionChange, SessionKey, SessionSample, SessionStore},
+    tags::TagStore,
 };
 use commands::Command;
 
+const DELETE_BATCH: usize = 512;
+const FOREIGN_KEY_SAMPLE: usize = 100;
+/// The deletion queue and the keys claimed from it share a hash slot, so a
+/// cluster can move keys between them atomically.
+const DELETION_QUEUE_KEY: &str = "{deletion-queue}";
+const DELETION_CLAIMS_KEY: &str = "{deletion-queue}:claimed";
+
 struct Configuration {
     key_gen: Box<dyn Fn(&SessionKey) -> String + Send + Sync>,
+    key_format: KeyFormat,
 }
 
 impl Default for Configuration {
     fn default() -> Self {
         Self {
             key_gen: Box::new(|v| v.as_ref().to_owned()),
+            key_format: KeyFormat::default(),
         }
     }
 }
 
+impl Configuration {
+    /// The session a Redis key stores, if `key_gen` produced it. `key_gen`
+    /// is inverted by probing where it puts a key, so keys it hashes or
+    /// otherwise rewrites are never recognized.
+    fn session_key_of(&self, key: &str) -> Option<SessionKey> {
+        let probe = SessionKey::generate_in(self.key_format);
+        let generated = (self.key_gen)(&probe);
+        let (prefix, suffix) = generated.split_once(probe.as_ref())?;
+        let session_key = key.strip_prefix(prefix)?.strip_suffix(suffix)?;
+        let session_key = SessionKey::parse_in(session_key, self.key_format)?;
+        ((self.key_gen)(&session_key) == key).then_some(session_key)
+    }
+
+    /// Whether the store wrote `key`: a session, one of its idempotency,
+    /// event or snapshot keys, a tag set or the deletion queue.
+    fn is_own_key(&self, key: &str) -> bool {
+        if key.starts_with("tag:") || key == DELETION_QUEUE_KEY || key == DELETION_CLAIMS_KEY {
+            return true;
+        }
+        let session = [":events", ":snapshot"]
+            .iter()
+            .find_map(|suffix| key.strip_suffix(suffix))
+            .or_else(|| key.split_once(":idempotency:").map(|(session, _)| session));
+        self.session_key_of(key).is_some()
+            || session.is_some_and(|session| self.session_key_of(session).is_some())
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum RedisError {
     #[error("Redis connection error: {0}")]
     ConnectionError(String),
+    #[error("Redis rejected the credentials: {0}; check the username and password")]
+    AuthenticationError(String),
     #[error("Redis query error: {0}")]
     QueryError(String),
+    #[error("Redis connection uses database {actual}, expected database {expected}")]
+    WrongDatabase { expected: i64, actual: i64 },
+    #[error("Redis database contains keys not written by this store, e.g. \"{0}\"")]
+    ForeignKeys(String),
+}
+
+impl RedisError {
+    pub(super) fn connection(error: redis::RedisError) -> Self {
+        if is_authentication_failure(&error) {
+            return RedisError::AuthenticationError(error.to_string());
+        }
+        RedisError::ConnectionError(error.to_string())
+    }
+
+    pub(super) fn query(error: redis::RedisError) -> Self {
+        if is_authentication_failure(&error) {
+            return RedisError::AuthenticationError(error.to_string());
+        }
+        RedisError::QueryError(error.to_string())
+    }
+}
+
+/// A failed `AUTH`, or a command refused because the connection never
+/// authenticated.
+fn is_authentication_failure(error: &redis::RedisError) -> bool {
+    error.kind() == redis::ErrorKind::AuthenticationFailed
+        || matches!(error.code(), Some("NOAUTH" | "WRONGPASS"))
+}
+
+/// Connection options for [`RedisSessionStore::connect`] and
+/// [`RedisSessionStore::sentinel`].
+#[derive(Clone, Debug, Default)]
+pub struct RedisOptions {
+    database: Option<i64>,
+    username: Option<String>,
+    password: Option<String>,
+    #[cfg(feature = "tls")]
+    root_certificate: Option<Vec<u8>>,
+    force: bool,
+    key_format: KeyFormat,
+}
+
+impl RedisOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Selects the logical database, overriding any database in the URL.
+    pub fn with_database(mut self, database: i64) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// Authenticates as the Redis 6 ACL user `username`, overriding any
+    /// username in the URL. Without one, `default` is used.
+    pub fn with_username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    /// Authenticates with `password`, overriding any password in the URL.
+    /// Sentinel URLs keep their own; this one is for the master.
+    pub fn with_password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// Trusts the PEM-encoded CA certificate `pem` for `rediss://` URLs
+    /// instead of the bundled web PKI roots, e.g. for a private CA.
+    #[cfg(feature = "tls")]
+    pub fn with_root_certificate(mut self, pem: &[u8]) -> Self {
+        self.root_certificate = Some(pem.to_vec());
+        self
+    }
+
+    /// The format of the session keys stored, as the app's
+    /// [`SessionConfig::with_key_format`](crate::SessionConfig::with_key_format)
+    /// generates them; keys in another format are not recognized as this
+    /// store's own.
+    pub fn with_key_format(mut self, key_format: KeyFormat) -> Self {
+        self.key_format = key_format;
+        self
+    }
+
+    /// Connects even if the database already holds keys this store did not
+    /// write.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -35,41 +174,367 @@ pub enum StoreError {
     #[error("Storage error: Backend error {0}")]
     BackendError(String),
     #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
-    SerializationError(#[from] serde_json::Error),
+    SerializationError(#[from] CodecError),
     #[error(transparent)]
     RedisError(#[from] RedisError),
 }
 
+/// Where a [`watch`](SessionStore::watch) on keyspace notifications is.
+enum KeyspaceWatch {
+    Subscribe,
+    /// The subscription ended with its connection; `wait` after a failed
+    /// attempt.
+    Resubscribe {
+        wait: bool,
+    },
+    Listen(LocalBoxStream<'static, String>),
+    Done,
+}
+
+enum Connection {
+    Node {
+        client: redis::Client,
+        manager: ConnectionManager,
+    },
+    #[cfg(feature = "cluster")]
+    Cluster(cluster::Cluster),
+    Sentinel(sentinel::Sentinel),
+}
+
 pub struct RedisSessionStore {
     config: Configuration,
-    connection: ConnectionManager,
+    database: i64,
+    connection: Connection,
 }
 
 impl RedisSessionStore {
     pub async fn new(url: &str) -> Result<Self, RedisError> {
         let config = Default::default();
-        let client = redis::Client::open(url)
-            .map_err(|e| e.to_string())
-            .map_err(RedisError::ConnectionError)?;
-        let connection = ConnectionManager::new(client)
+        let client = redis::Client::open(url).map_err(RedisError::connection)?;
+        let database = client.get_connection_info().redis.db;
+        let manager = ConnectionManager::new(client.clone())
             .await
-            .map_err(|e| e.to_string())
-            .map_err(RedisError::ConnectionError)?;
-        Ok(Self { config, connection })
+            .map_err(RedisError::connection)?;
+        Ok(Self {
+            config,
+            database,
+            connection: Connection::Node { client, manager },
+        })
+    }
+
+    /// Connects, verifies the selected database and, unless forced, refuses
+    /// a database that holds keys belonging to something else.
+    ///
+    /// With the `tls` feature, `rediss://` URLs connect over TLS using
+    /// rustls, as managed offerings with in-transit encryption require.
+    /// `redis+unix:///path/to/redis.sock` URLs connect through a Unix domain
+    /// socket, e.g. to a colocated Redis; select the database with `?db=`.
+    pub async fn connect(url: &str, options: RedisOptions) -> Result<Self, RedisError> {
+        let info = connection_info(url, &options)?;
+        let expected = info.redis.db;
+        #[cfg(feature = "tls")]
+        let client = match options.root_certificate {
+            Some(root_cert) => {
+                let certificates = redis::TlsCertificates {
+                    client_tls: None,
+                    root_cert: Some(root_cert),
+                };
+                redis::Client::build_with_tls(info, certificates)
+            }
+            None => redis::Client::open(info),
+        };
+        #[cfg(not(feature = "tls"))]
+        let client = redis::Client::open(info);
+        let client = client.map_err(RedisError::connection)?;
+        let manager = ConnectionManager::new(client.clone())
+            .await
+            .map_err(RedisError::connection)?;
+        let store = Self {
+            config: Configuration {
+                key_format: options.key_format,
+                ..Default::default()
+            },
+            database: expected,
+            connection: Connection::Node { client, manager },
+        };
+        store.verify_database(expected).await?;
+        if !options.force {
+            store.refuse_foreign_keys().await?;
+        }
+        Ok(store)
+    }
+
+    /// Connects to the master the Redis Sentinels at `sentinels`, e.g.
+    /// `["redis://10.0.0.1:26379"]`, know as `master_name`, checking it as
+    /// [`connect`](Self::connect) does.
+    ///
+    /// After a failover, the first query to reach the old master fails over
+    /// with it: the sentinels are asked for the new master and the query
+    /// is retried there, so no restart is needed.
+    pub async fn sentinel(
+        sentinels: &[&str],
+        master_name: &str,
+        options: RedisOptions,
+    ) -> Result<Self, RedisError> {
+        let redis = redis::RedisConnectionInfo {
+            db: options.database.unwrap_or_default(),
+            username: options.username,
+            password: options.password,
+        };
+        let expected = redis.db;
+        let store = Self {
+            config: Configuration {
+                key_format: options.key_format,
+                ..Default::default()
+            },
+            database: expected,
+            connection: Connection::Sentinel(
+                sentinel::Sentinel::open(sentinels, master_name, redis).await?,
+            ),
+        };
+        store.verify_database(expected).await?;
+        if !options.force {
+            store.refuse_foreign_keys().await?;
+        }
+        Ok(store)
+    }
+
+    /// Connects to the Redis Cluster that `nodes`, e.g.
+    /// `["redis://10.0.0.1:6379", "redis://10.0.0.2:6379"]`, belong to,
+    /// routing each session to the node serving its hash slot. `rediss://`
+    /// nodes connect over TLS. `options` apply as for
+    /// [`connect`](Self::connect), except that a cluster only has database
+    /// 0 and foreign keys are looked for on a single node.
+    ///
+    /// Multi-key commands are split per key, so a session's tag index
+    /// entries are written after, rather than atomically with, the session.
+    /// Eviction sampling scans a single node and is unavailable, and
+    /// [`watch`](SessionStore::watch) polls, since keyspace notifications
+    /// are only published on the node holding the key.
+    #[cfg(feature = "cluster")]
+    pub async fn cluster(nodes: &[&str], options: RedisOptions) -> Result<Self, RedisError> {
+        if let Some(expected) = options.database.filter(|database| *database != 0) {
+            return Err(RedisError::WrongDatabase {
+                expected,
+                actual: 0,
+            });
+        }
+        let store = Self {
+            config: Configuration {
+                key_format: options.key_format,
+                ..Default::default()
+            },
+            database: 0,
+            connection: Connection::Cluster(cluster::Cluster::open(nodes, &options)?),
+        };
+        if !options.force {
+            store.refuse_foreign_keys().await?;
+        }
+        Ok(store)
+    }
+
+    async fn verify_database(&self, expected: i64) -> Result<(), RedisError> {
+        // CLIENT INFO needs Redis 6.2; older servers skip the check.
+        let Ok(info) = self.execute_command::<String>(Command::client_info()).await else {
+            return Ok(());
+        };
+        let actual = info
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("db="))
+            .and_then(|db| db.parse::<i64>().ok());
+        match actual {
+            Some(actual) if actual != expected => {
+                Err(RedisError::WrongDatabase { expected, actual })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn refuse_foreign_keys(&self) -> Result<(), RedisError> {
+        let (_, keys) = self
+            .execute_command::<(u64, Vec<String>)>(Command::scan(0, FOREIGN_KEY_SAMPLE))
+            .await?;
+        match keys.into_iter().find(|key| !self.config.is_own_key(key)) {
+            Some(key) => Err(RedisError::ForeignKeys(key)),
+            None => Ok(()),
+        }
     }
 
     async fn execute_command<T: redis::FromRedisValue>(
         &self,
         command: Command,
     ) -> Result<T, RedisError> {
+        let manager = match &self.connection {
+            Connection::Node { manager, .. } => manager,
+            #[cfg(feature = "cluster")]
+            Connection::Cluster(cluster) => {
+                let replies = cluster.query(vec![command]).await?;
+                return from_reply(replies.first().unwrap_or(&redis::Value::Nil));
+            }
+            Connection::Sentinel(sentinel) => {
+                let redis_command: redis::Cmd = command.into();
+                return sentinel
+                    .query(|mut manager| {
+                        let redis_command = redis_command.clone();
+                        async move { redis_command.query_async(&mut manager).await }
+                    })
+                    .await;
+            }
+        };
         let redis_command: redis::Cmd = command.into();
         let result = redis_command
-            .query_async(&mut self.connection.clone())
+            .query_async(&mut manager.clone())
+            .await
+            .map_err(RedisError::query)?;
+        Ok(result)
+    }
+
+    async fn execute_pipeline<T: redis::FromRedisValue>(
+        &self,
+        commands: Vec<Command>,
+    ) -> Result<Vec<T>, RedisError> {
+        if commands.is_empty() {
+            return Ok(Vec::new());
+        }
+        let manager = match &self.connection {
+            Connection::Node { manager, .. } => manager,
+            #[cfg(feature = "cluster")]
+            Connection::Cluster(cluster) => {
+                let replies = cluster.query(commands).await?;
+                return replies.iter().map(from_reply).collect();
+            }
+            Connection::Sentinel(sentinel) => {
+                let pipeline = pipeline(commands);
+                return sentinel
+                    .query(|mut manager| {
+                        let pipeline = pipeline.clone();
+                        async move { pipeline.query_async(&mut manager).await }
+                    })
+                    .await;
+            }
+        };
+        let result = pipeline(commands)
+            .query_async(&mut manager.clone())
             .await
-            .map_err(|e| e.to_string())
-            .map_err(RedisError::QueryError)?;
+            .map_err(RedisError::query)?;
         Ok(result)
     }
+
+    /// Unlinks the given sessions in batches; Redis frees their memory off
+    /// the main thread.
+    pub async fn destroy_many(&self, session_keys: &[SessionKey]) -> Result<(), StoreError> {
+        let commands = session_keys
+            .chunks(DELETE_BATCH)
+            .map(|chunk| chunk.iter().map(|key| (self.config.key_gen)(key)).collect())
+            .map(Command::delete_many)
+            .collect();
+        self.execute_pipeline::<redis::Value>(commands)
+            .await
+            .map_err(StoreError::from)?;
+        Ok(())
+    }
+
+    /// The keyspace events for `session_key`, on a dedicated connection to
+    /// the master, which is first resolved again through Sentinel if
+    /// `failed_over`.
+    async fn keyspace_events(
+        &self,
+        session_key: &SessionKey,
+        failed_over: bool,
+    ) -> Result<LocalBoxStream<'static, String>, RedisError> {
+        let client = match &self.connection {
+            Connection::Node { client, .. } => client.clone(),
+            Connection::Sentinel(sentinel) if failed_over => sentinel.resolve().await?.client,
+            Connection::Sentinel(sentinel) => sentinel.master().client,
+            #[cfg(feature = "cluster")]
+            Connection::Cluster(_) => unreachable!("cluster watches poll"),
+        };
+        let cache_key = (self.config.key_gen)(session_key);
+        let channel = format!("__keyspace@{}__:{cache_key}", self.database);
+        let mut pubsub = client
+            .get_async_pubsub()
+            .await
+            .map_err(RedisError::connection)?;
+        pubsub.subscribe(channel).await.map_err(RedisError::query)?;
+        let events = pubsub
+            .into_on_message()
+            .filter_map(|message| async move { message.get_payload::<String>().ok() });
+        Ok(events.boxed_local())
+    }
+
+    /// The session as it is now, as the change a keyspace event announced.
+    async fn reload(
+        &self,
+        session_key: &SessionKey,
+        events: LocalBoxStream<'static, String>,
+    ) -> (Result<SessionChange, StoreError>, KeyspaceWatch) {
+        match self.load(session_key).await {
+            Ok(Some(session)) => (
+                Ok(SessionChange::Updated(Box::new(session))),
+                KeyspaceWatch::Listen(events),
+            ),
+            Ok(None) => (Ok(SessionChange::Destroyed), KeyspaceWatch::Done),
+            Err(error) => (Err(error), KeyspaceWatch::Listen(events)),
+        }
+    }
+
+    fn idempotency_key(&self, session_key: &SessionKey, key: &str) -> String {
+        format!("{}:idempotency:{}", (self.config.key_gen)(session_key), key)
+    }
+
+    fn tag_key(&self, tag: &str) -> String {
+        format!("tag:{tag}")
+    }
+
+    fn write(
+        &self,
+        session: &Session,
+        timeout: Duration,
+        existing: bool,
+    ) -> Result<Command, StoreError> {
+        let cache_key = (self.config.key_gen)(session.id());
+        let state: SessionState = session.into();
+        let body = ValueCodec::encode(&state).map_err(StoreError::SerializationError)?;
+        let index_keys = session
+            .tags()
+            .map_err(|e| StoreError::BackendError(e.to_string()))?
+            .iter()
+            .map(|tag| self.tag_key(tag))
+            .collect();
+        let member = session.id().as_ref().to_string();
+        Ok(Command::write(
+            cache_key, body, timeout, existing, index_keys, member,
+        ))
+    }
+}
+
+/// Parses `url`, for any scheme redis-rs supports, with `options` applied.
+fn connection_info(url: &str, options: &RedisOptions) -> Result<redis::ConnectionInfo, RedisError> {
+    let mut info =
+        redis::IntoConnectionInfo::into_connection_info(url).map_err(RedisError::connection)?;
+    if let Some(database) = options.database {
+        info.redis.db = database;
+    }
+    if let Some(username) = &options.username {
+        info.redis.username = Some(username.clone());
+    }
+    if let Some(password) = &options.password {
+        info.redis.password = Some(password.clone());
+    }
+    Ok(info)
+}
+
+fn pipeline(commands: Vec<Command>) -> redis::Pipeline {
+    let mut pipeline = redis::pipe();
+    for command in commands {
+        pipeline.add_command(command.into());
+    }
+    pipeline
+}
+
+#[cfg(feature = "cluster")]
+fn from_reply<T: redis::FromRedisValue>(reply: &redis::Value) -> Result<T, RedisError> {
+    T::from_redis_value(reply).map_err(RedisError::query)
 }
 
 #[async_trait::async_trait(?Send)]
@@ -77,13 +542,13 @@ impl SessionStore for RedisSessionStore {
     type Error = StoreError;
 
     async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
-        let cache_key = (&self.config.key_gen)(session_key);
+        let cache_key = (self.config.key_gen)(session_key);
         let value = self
             .execute_command::<Option<String>>(Command::get(cache_key))
             .await
             .map_err(StoreError::from)?;
         let state = value
-            .map(|v| serde_json::from_str::<SessionState>(&v))
+            .map(|v| ValueCodec::decode::<SessionState>(&v))
             .transpose()
             .map_err(StoreError::SerializationError)?;
         let session = state.map(|state| Session::new(session_key.clone(), state));
@@ -91,25 +556,17 @@ impl SessionStore for RedisSessionStore {
     }
 
     async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
-        let session_id = session.id();
-        let cache_key = (&self.config.key_gen)(&session_id);
-        let state: SessionState = session.into();
-        let body = serde_json::to_string(&state).map_err(StoreError::SerializationError)?;
-        self.execute_command(Command::set(cache_key, body, timeout))
+        let command = self.write(session, timeout, false)?;
+        self.execute_command::<()>(command)
             .await
-            .map_err(StoreError::from)?;
-        Ok(())
+            .map_err(StoreError::from)
     }
 
     async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
-        let session_id = session.id();
-        let cache_key = (&self.config.key_gen)(&session_id);
-        let state: SessionState = session.into();
-        let body = serde_json::to_string(&state).map_err(StoreError::SerializationError)?;
+        let command = self.write(session, timeout, true)?;
         let value = self
-            .execute_command(Command::update(cache_key, body, timeout))
+            .execute_command::<redis::Value>(command)
             .await
-            .map(redis::Value::from)
             .map_err(StoreError::from)?;
         match value {
             redis::Value::Okay => Ok(()),
@@ -123,14 +580,14 @@ impl SessionStore for RedisSessionStore {
     }
 
     async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
-        let cache_key = (&self.config.key_gen)(&session_key);
-        self.execute_command(Command::delete(cache_key))
+        let cache_key = (self.config.key_gen)(session_key);
+        self.execute_command::<()>(Command::delete(cache_key))
             .await
             .map_err(StoreError::from)
     }
 
     async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
-        let cache_key = (&self.config.key_gen)(&session_key);
+        let cache_key = (self.config.key_gen)(session_key);
         let exists = self
             .execute_command::<u64>(Command::exists(cache_key))
             .await
@@ -139,20 +596,452 @@ impl SessionStore for RedisSessionStore {
     }
 
     async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
-        let cache_key = (&self.config.key_gen)(&session_key);
+        let cache_key = (self.config.key_gen)(session_key);
+        // PTTL answers -2 for a missing key and -1 for one without expiry.
         let ttl = self
-            .execute_command::<u64>(Command::ttl(cache_key))
+            .execute_command::<i64>(Command::ttl(cache_key))
             .await
             .map_err(StoreError::from)?;
-        Ok(Duration::from_secs(ttl))
+        Ok(Duration::from_millis(ttl.max(0) as u64))
+    }
+
+    /// Draws with RANDOMKEY, which may repeat keys and land on keys that
+    /// are not sessions, so fewer than `n` sessions can come back.
+    async fn sample(&self, n: usize) -> Result<Vec<SessionSample>, Self::Error> {
+        let draws = (0..n * 2).map(|_| Command::random_key()).collect();
+        let mut seen = HashSet::new();
+        // Tag sets, the deletion queue and per-session auxiliary keys share
+        // the keyspace; only session keys are sampled.
+        let (keys, session_keys): (Vec<_>, Vec<_>) = self
+            .execute_pipeline::<Option<String>>(draws)
+            .await
+            .map_err(StoreError::from)?
+            .into_iter()
+            .flatten()
+            .filter(|key| seen.insert(key.clone()))
+            .filter_map(|key| {
+                let session_key = self.config.session_key_of(&key)?;
+                Some((key, session_key))
+            })
+            .unzip();
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let values = self
+            .execute_command::<Vec<Option<String>>>(Command::get_many(keys.clone()))
+            .await
+            .map_err(StoreError::from)?;
+        let sessions = keys
+            .into_iter()
+            .zip(session_keys)
+            .zip(values)
+            .filter_map(|((key, session_key), value)| {
+                let value = value?;
+                ValueCodec::decode::<SessionState>(&value).ok()?;
+                Some((key, session_key, value.len()))
+            })
+            .take(n)
+            .collect::<Vec<_>>();
+        let ttls = self
+            .execute_pipeline::<i64>(
+                sessions
+                    .iter()
+                    .map(|(key, ..)| Command::ttl(key.clone()))
+                    .collect(),
+            )
+            .await
+            .map_err(StoreError::from)?;
+        let sample = sessions
+            .into_iter()
+            .zip(ttls)
+            .filter(|(_, ttl)| *ttl > 0)
+            .map(|((_, session_key, size), ttl)| SessionSample {
+                session_key,
+                size,
+                ttl: Duration::from_millis(ttl as u64),
+            })
+            .collect();
+        Ok(sample)
+    }
+
+    /// Built on keyspace notifications, which the server must publish for
+    /// string, generic and expired events, e.g. with
+    /// `CONFIG SET notify-keyspace-events K$gx`.
+    ///
+    /// When the subscription's connection drops, e.g. on a Sentinel
+    /// failover, the master is resolved again and the session reported as
+    /// it is once resubscribed, since changes in between were missed.
+    fn watch<'a>(
+        &'a self,
+        session_key: &SessionKey,
+    ) -> LocalBoxStream<'a, Result<SessionChange, Self::Error>>
+    where
+        Self::Error: 'a,
+    {
+        #[cfg(feature = "cluster")]
+        if let Connection::Cluster(_) = &self.connection {
+            return watch::poll(self, session_key.clone(), watch::POLL_INTERVAL);
+        }
+        let session_key = session_key.clone();
+        stream::unfold(KeyspaceWatch::Subscribe, move |mut state| {
+            let session_key = session_key.clone();
+            async move {
+                loop {
+                    let mut events = match state {
+                        KeyspaceWatch::Done => return None,
+                        KeyspaceWatch::Listen(events) => events,
+                        KeyspaceWatch::Subscribe => {
+                            match self.keyspace_events(&session_key, false).await {
+                                Ok(events) => events,
+                                Err(error) => {
+                                    return Some((Err(error.into()), KeyspaceWatch::Done))
+                                }
+                            }
+                        }
+                        KeyspaceWatch::Resubscribe { wait } => {
+                            if wait {
+                                tokio::time::sleep(watch::POLL_INTERVAL).await;
+                            }
+                            let retry = KeyspaceWatch::Resubscribe { wait: true };
+                            return match self.keyspace_events(&session_key, true).await {
+                                Ok(events) => Some(self.reload(&session_key, events).await),
+                                Err(error) => Some((Err(error.into()), retry)),
+                            };
+                        }
+                    };
+                    while let Some(event) = events.next().await {
+                        match event.as_str() {
+                            "set" => return Some(self.reload(&session_key, events).await),
+                            "del" | "expired" | "evicted" => {
+                                return Some((Ok(SessionChange::Destroyed), KeyspaceWatch::Done))
+                            }
+                            _ => {}
+                        }
+                    }
+                    state = KeyspaceWatch::Resubscribe { wait: false };
+                }
+            }
+        })
+        .boxed_local()
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl IdempotencyStore for RedisSessionStore {
+    type Error = StoreError;
+
+    async fn claim(
+        &self,
+        session_key: &SessionKey,
+        key: &str,
+        fingerprint: &str,
+        ttl: Duration,
+    ) -> Result<IdempotencyClaim<String>, Self::Error> {
+        let cache_key = self.idempotency_key(session_key, key);
+        let record = IdempotencyRecord {
+            fingerprint: fingerprint.to_string(),
+            response: None,
+        };
+        let body = ValueCodec::encode(&record).map_err(StoreError::SerializationError)?;
+        let claimed = self
+            .execute_command::<redis::Value>(Command::set(cache_key.clone(), body, ttl))
+            .await
+            .map_err(StoreError::from)?;
+        if claimed == redis::Value::Okay {
+            return Ok(IdempotencyClaim::Claimed);
+        }
+        let existing = self
+            .execute_command::<Option<String>>(Command::get(cache_key))
+            .await
+            .map_err(StoreError::from)?
+            .map(|v| ValueCodec::decode::<IdempotencyRecord>(&v))
+            .transpose()
+            .map_err(StoreError::SerializationError)?;
+        let claim = match existing {
+            None => IdempotencyClaim::InProgress,
+            Some(existing) if existing.fingerprint != fingerprint => {
+                IdempotencyClaim::FingerprintMismatch
+            }
+            Some(IdempotencyRecord {
+                response: Some(response),
+                ..
+            }) => IdempotencyClaim::Completed(response),
+            Some(_) => IdempotencyClaim::InProgress,
+        };
+        Ok(claim)
+    }
+
+    async fn complete(
+        &self,
+        session_key: &SessionKey,
+        key: &str,
+        record: &IdempotencyRecord,
+        ttl: Duration,
+    ) -> Result<(), Self::Error> {
+        let cache_key = self.idempotency_key(session_key, key);
+        let body = ValueCodec::encode(record).map_err(StoreError::SerializationError)?;
+        self.execute_command::<()>(Command::update(cache_key, body, ttl))
+            .await
+            .map_err(StoreError::from)
+    }
+
+    async fn release(&self, session_key: &SessionKey, key: &str) -> Result<(), Self::Error> {
+        let cache_key = self.idempotency_key(session_key, key);
+        self.execute_command::<()>(Command::delete(cache_key))
+            .await
+            .map_err(StoreError::from)
     }
 }
 
+#[async_trait::async_trait(?Send)]
+impl DeletionQueue for RedisSessionStore {
+    type Error = StoreError;
+
+    async fn enqueue(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.execute_command::<redis::Value>(Command::list_push(
+            DELETION_QUEUE_KEY.to_string(),
+            session_key.as_ref().to_string(),
+        ))
+        .await
+        .map_err(StoreError::from)?;
+        Ok(())
+    }
+
+    async fn dequeue(&self, limit: usize) -> Result<Vec<SessionKey>, Self::Error> {
+        let mut claimed = Vec::new();
+        while claimed.len() < limit.max(1) {
+            let member = self
+                .execute_command::<Option<String>>(Command::list_move(
+                    DELETION_QUEUE_KEY.to_string(),
+                    DELETION_CLAIMS_KEY.to_string(),
+                ))
+                .await
+                .map_err(StoreError::from)?;
+            match member {
+                Some(member) => claimed.push(SessionKey::from_raw(member)),
+                None => break,
+            }
+        }
+        Ok(claimed)
+    }
+
+    async fn acknowledge(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.execute_command::<redis::Value>(Command::list_remove(
+            DELETION_CLAIMS_KEY.to_string(),
+            session_key.as_ref().to_string(),
+        ))
+        .await
+        .map_err(StoreError::from)?;
+        Ok(())
+    }
+
+    async fn recover(&self) -> Result<usize, Self::Error> {
+        let mut recovered = 0;
+        loop {
+            let member = self
+                .execute_command::<Option<String>>(Command::list_move(
+                    DELETION_CLAIMS_KEY.to_string(),
+                    DELETION_QUEUE_KEY.to_string(),
+                ))
+                .await
+                .map_err(StoreError::from)?;
+            if member.is_none() {
+                return Ok(recovered);
+            }
+            recovered += 1;
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl EvictionSource for RedisSessionStore {
+    type Error = StoreError;
+
+    async fn pressure(&self) -> Result<f64, Self::Error> {
+        let info = self
+            .execute_command::<String>(Command::info("memory".to_string()))
+            .await
+            .map_err(StoreError::from)?;
+        Ok(memory_pressure(&info))
+    }
+
+    async fn candidates(&self, limit: usize) -> Result<Vec<EvictionCandidate>, Self::Error> {
+        let mut candidates = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next, keys) = self
+                .execute_command::<(u64, Vec<String>)>(Command::scan(cursor, limit))
+                .await
+                .map_err(StoreError::from)?;
+            // Tag sets, the deletion queue and per-session auxiliary keys
+            // share the keyspace; only session keys are candidates.
+            let (keys, session_keys): (Vec<_>, Vec<_>) = keys
+                .into_iter()
+                .filter_map(|key| {
+                    let session_key = self.config.session_key_of(&key)?;
+                    Some((key, session_key))
+                })
+                .take(limit - candidates.len())
+                .unzip();
+            if !keys.is_empty() {
+                let values = self
+                    .execute_command::<Vec<Option<String>>>(Command::get_many(keys.clone()))
+                    .await
+                    .map_err(StoreError::from)?;
+                let sessions = keys
+                    .into_iter()
+                    .zip(session_keys)
+                    .zip(values)
+                    .filter_map(|((key, session_key), value)| {
+                        let state = ValueCodec::decode::<SessionState>(&value?).ok()?;
+                        Some((key, session_key, state))
+                    })
+                    .collect::<Vec<_>>();
+                let idle_times = self
+                    .execute_pipeline::<u64>(
+                        sessions
+                            .iter()
+                            .map(|(key, _, _)| Command::idle_time(key.clone()))
+                            .collect(),
+                    )
+                    .await
+                    .map_err(StoreError::from)?;
+                for ((_, session_key, state), idle) in sessions.into_iter().zip(idle_times) {
+                    candidates.push(EvictionCandidate {
+                        key: session_key,
+                        idle: Duration::from_secs(idle),
+                        state,
+                    });
+                }
+            }
+            if candidates.len() >= limit {
+                return Ok(candidates);
+            }
+            cursor = next;
+            if cursor == 0 {
+                return Ok(candidates);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl TagStore for RedisSessionStore {
+    async fn find_by_tag(&self, tag: &str) -> Result<Vec<SessionKey>, Self::Error> {
+        let tag_key = self.tag_key(tag);
+        let members = self
+            .execute_command::<Vec<String>>(Command::set_members(tag_key.clone()))
+            .await
+            .map_err(StoreError::from)?;
+        if members.is_empty() {
+            return Ok(Vec::new());
+        }
+        let values = self
+            .execute_command::<Vec<Option<String>>>(Command::get_many(
+                members
+                    .iter()
+                    .map(|member| (self.config.key_gen)(&SessionKey::from_raw(member.clone())))
+                    .collect(),
+            ))
+            .await
+            .map_err(StoreError::from)?;
+        let mut keys = Vec::new();
+        let mut stale = Vec::new();
+        for (member, value) in members.into_iter().zip(values) {
+            let tagged = value
+                .and_then(|value| ValueCodec::decode::<SessionState>(&value).ok())
+                .map(|state| Session::new(SessionKey::from_raw(member.clone()), state))
+                .is_some_and(|session| session.has_tag(tag).unwrap_or_default());
+            if tagged {
+                keys.push(SessionKey::from_raw(member));
+            } else {
+                stale.push(Command::set_remove(tag_key.clone(), member));
+            }
+        }
+        self.execute_pipeline::<redis::Value>(stale)
+            .await
+            .map_err(StoreError::from)?;
+        Ok(keys)
+    }
+
+    async fn destroy_by_tag(&self, tag: &str) -> Result<usize, Self::Error> {
+        let keys = self.find_by_tag(tag).await?;
+        self.destroy_many(&keys).await?;
+        Ok(keys.len())
+    }
+}
+
+fn memory_pressure(info: &str) -> f64 {
+    let field = |name: &str| {
+        info.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .unwrap_or_default()
+    };
+    let max = field("maxmemory");
+    if max == 0.0 {
+        return 0.0;
+    }
+    field("used_memory") / max
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{session::Session, storage::Storage};
 
+    #[cfg(unix)]
+    #[test]
+    fn connection_info_accepts_unix_socket_urls() {
+        let info = connection_info("redis+unix:///run/redis.sock?db=2", &RedisOptions::new())
+            .expect("Unable to parse the socket URL");
+        assert!(matches!(
+            info.addr,
+            redis::ConnectionAddr::Unix(ref path) if path.as_os_str() == "/run/redis.sock"
+        ));
+        assert_eq!(info.redis.db, 2);
+
+        let options = RedisOptions::new()
+            .with_database(5)
+            .with_username("sessions")
+            .with_password("secret");
+        let info = connection_info("unix:///run/redis.sock", &options).unwrap();
+        assert_eq!(info.redis.db, 5);
+        assert_eq!(info.redis.username.as_deref(), Some("sessions"));
+        assert_eq!(info.redis.password.as_deref(), Some("secret"));
+    }
+
+    #[cfg(feature = "cluster")]
+    #[tokio::test]
+    async fn clusters_refuse_databases_other_than_zero() {
+        let options = RedisOptions::new().with_database(2);
+        let cluster = RedisSessionStore::cluster(&["redis://localhost:7000"], options).await;
+        assert!(matches!(
+            cluster,
+            Err(RedisError::WrongDatabase {
+                expected: 2,
+                actual: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn authentication_failures_get_their_own_variant() {
+        let refused = redis::RedisError::from((
+            redis::ErrorKind::AuthenticationFailed,
+            "Password authentication failed",
+        ));
+        assert!(matches!(
+            RedisError::connection(refused),
+            RedisError::AuthenticationError(_)
+        ));
+        let io = redis::RedisError::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        assert!(matches!(
+            RedisError::connection(io),
+            RedisError::ConnectionError(_)
+        ));
+    }
+
     #[tokio::test]
     async fn test_it() {
         RedisSessionStore::new("redis://localhost:6379")
@@ -257,4 +1146,202 @@ mod tests {
             .expect("Unable to check exists");
         assert!(!exists)
     }
+
+    #[tokio::test]
+    async fn claim_is_single_use_and_replays_the_completed_response() {
+        let store = RedisSessionStore::new("redis://:password@localhost:6379/1")
+            .await
+            .expect("Unable to connect to Redis");
+        let session = Session::default();
+        let ttl = Duration::new(5, 0);
+
+        let claim = store
+            .claim(session.id(), "order-1", "abc", ttl)
+            .await
+            .expect("Unable to claim");
+        assert_eq!(claim, IdempotencyClaim::Claimed);
+        let claim = store
+            .claim(session.id(), "order-1", "abc", ttl)
+            .await
+            .expect("Unable to claim");
+        assert_eq!(claim, IdempotencyClaim::InProgress);
+
+        let record = IdempotencyRecord {
+            fingerprint: "abc".to_string(),
+            response: Some("201".to_string()),
+        };
+        store
+            .complete(session.id(), "order-1", &record, ttl)
+            .await
+            .expect("Unable to complete");
+        let claim = store
+            .claim(session.id(), "order-1", "abc", ttl)
+            .await
+            .expect("Unable to claim");
+        assert_eq!(claim, IdempotencyClaim::Completed("201".to_string()));
+        let claim = store
+            .claim(session.id(), "order-1", "xyz", ttl)
+            .await
+            .expect("Unable to claim");
+        assert_eq!(claim, IdempotencyClaim::FingerprintMismatch);
+    }
+
+    #[test]
+    fn is_own_key_recognizes_keys_written_by_the_store() {
+        let config = Configuration::default();
+        let session_key = SessionKey::generate();
+        let session_key = session_key.as_ref();
+        assert!(config.is_own_key(session_key));
+        assert!(config.is_own_key(&format!("{session_key}:idempotency:order-1")));
+        assert!(config.is_own_key(&format!("{session_key}:events")));
+        assert!(config.is_own_key("tag:kiosk"));
+        assert!(config.is_own_key(DELETION_QUEUE_KEY));
+        assert!(config.is_own_key(DELETION_CLAIMS_KEY));
+        assert!(!config.is_own_key("cache:users:42"));
+        assert!(!config.is_own_key(&format!("{session_key}:other")));
+    }
+
+    #[test]
+    fn session_key_of_inverts_a_custom_key_gen() {
+        let config = Configuration {
+            key_gen: Box::new(|key| format!("app:{}:session", key.as_ref())),
+            ..Default::default()
+        };
+        let session_key = SessionKey::generate();
+        let redis_key = format!("app:{}:session", session_key.as_ref());
+        assert_eq!(config.session_key_of(&redis_key), Some(session_key.clone()));
+        assert!(config.is_own_key(&format!("{redis_key}:idempotency:order-1")));
+        assert_eq!(config.session_key_of(session_key.as_ref()), None);
+        assert_eq!(config.session_key_of("tag:kiosk"), None);
+        assert_eq!(config.session_key_of(DELETION_QUEUE_KEY), None);
+        assert!(!config.is_own_key(session_key.as_ref()));
+    }
+
+    #[test]
+    fn own_keys_are_recognized_in_the_configured_format() {
+        let key_format = KeyFormat::new(crate::KeyEncoding::Hex, 16);
+        let config = Configuration {
+            key_format,
+            ..Default::default()
+        };
+        let session_key = SessionKey::generate_in(key_format);
+        assert_eq!(
+            config.session_key_of(session_key.as_ref()),
+            Some(session_key)
+        );
+        assert!(!config.is_own_key(SessionKey::generate().as_ref()));
+    }
+
+    #[test]
+    fn memory_pressure_reads_used_and_max_memory() {
+        let info = "# Memory\r\nused_memory:750\r\nused_memory_human:750B\r\nmaxmemory:1000\r\n";
+        assert_eq!(memory_pressure(info), 0.75);
+        assert_eq!(memory_pressure("used_memory:750\r\nmaxmemory:0\r\n"), 0.0);
+    }
+
+    #[tokio::test]
+    async fn find_by_tag_returns_only_sessions_still_carrying_the_tag() {
+        let store = RedisSessionStore::new("redis://:password@localhost:6379/1")
+            .await
+            .expect("Unable to connect to Redis");
+        let timeout = Duration::new(5, 0);
+        let mut kiosk = Session::default();
+        kiosk.tag("kiosk").expect("Unable to tag session");
+        store
+            .save(&kiosk, timeout)
+            .await
+            .expect("Unable to save session");
+        let mut untagged = Session::default();
+        untagged.tag("kiosk").expect("Unable to tag session");
+        store
+            .save(&untagged, timeout)
+            .await
+            .expect("Unable to save session");
+        untagged.untag("kiosk").expect("Unable to untag session");
+        store
+            .update(&untagged, timeout)
+            .await
+            .expect("Unable to update session");
+
+        let keys = store
+            .find_by_tag("kiosk")
+            .await
+            .expect("Unable to find sessions by tag");
+        assert!(keys.contains(kiosk.id()));
+        assert!(!keys.contains(untagged.id()));
+    }
+
+    #[tokio::test]
+    async fn destroy_many_removes_every_given_session() {
+        let store = RedisSessionStore::new("redis://:password@localhost:6379/1")
+            .await
+            .expect("Unable to connect to Redis");
+        let timeout = Duration::new(5, 0);
+        let sessions = [Session::default(), Session::default()];
+        for session in &sessions {
+            store
+                .save(session, timeout)
+                .await
+                .expect("Unable to save session");
+        }
+
+        let keys = sessions
+            .iter()
+            .map(|session| session.id().clone())
+            .collect::<Vec<_>>();
+        store
+            .destroy_many(&keys)
+            .await
+            .expect("Unable to destroy sessions");
+        for key in &keys {
+            let exists = store.exists(key).await.expect("Unable to check exists");
+            assert!(!exists);
+        }
+    }
+
+    #[tokio::test]
+    async fn sample_reports_sessions_by_their_key_and_skips_other_keys() {
+        let mut store = RedisSessionStore::new("redis://:password@localhost:6379/2")
+            .await
+            .expect("Unable to connect to Redis");
+        store.config.key_gen = Box::new(|key| format!("app:{}:session", key.as_ref()));
+        let timeout = Duration::new(5, 0);
+        let session = Session::default();
+        store
+            .save(&session, timeout)
+            .await
+            .expect("Unable to save session");
+        // Decodes as a session state, but is not a session.
+        let snapshot = format!("app:{}:session:snapshot", session.id().as_ref());
+        store
+            .execute_command::<redis::Value>(Command::set(snapshot, "{}".to_string(), timeout))
+            .await
+            .expect("Unable to write the snapshot key");
+
+        let sample = store.sample(10).await.expect("Unable to sample");
+        assert!(sample
+            .iter()
+            .any(|sampled| &sampled.session_key == session.id()));
+        for sampled in &sample {
+            assert!(store.exists(&sampled.session_key).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn self_test_passes_against_a_writable_redis() {
+        let store = RedisSessionStore::new("redis://:password@localhost:6379/1")
+            .await
+            .expect("Unable to connect to Redis");
+        store.self_test().await.expect("Self test failed");
+    }
+
+    #[tokio::test]
+    async fn redis_session_store_passes_the_conformance_suite() {
+        let store = RedisSessionStore::new("redis://:password@localhost:6379/1")
+            .await
+            .expect("Unable to connect to Redis");
+        crate::conformance::run(&store)
+            .await
+            .expect("Conformance check failed");
+    }
 }
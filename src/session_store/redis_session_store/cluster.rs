@@ -0,0 +1,116 @@
+use redis::{
+    cluster::{ClusterClient, ClusterClientBuilder, ClusterConnection},
+    ConnectionLike, Value,
+};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::{commands::Command, RedisError, RedisOptions};
+
+/// A Redis Cluster, reached through redis-rs's blocking, hash-slot aware
+/// client on Tokio's blocking pool. Connections are kept for reuse, as each
+/// one holds a connection to every node.
+#[derive(Clone)]
+pub(super) struct Cluster {
+    client: Arc<ClusterClient>,
+    idle: Arc<Mutex<Vec<ClusterConnection>>>,
+}
+
+impl Cluster {
+    pub(super) fn open(nodes: &[&str], options: &RedisOptions) -> Result<Self, RedisError> {
+        let mut builder = ClusterClientBuilder::new(nodes.to_vec());
+        if let Some(username) = &options.username {
+            builder = builder.username(username.clone());
+        }
+        if let Some(password) = &options.password {
+            builder = builder.password(password.clone());
+        }
+        #[cfg(feature = "tls")]
+        if let Some(root_cert) = &options.root_certificate {
+            builder = builder.certs(redis::TlsCertificates {
+                client_tls: None,
+                root_cert: Some(root_cert.clone()),
+            });
+        }
+        let client = builder.build().map_err(RedisError::connection)?;
+        Ok(Self {
+            client: Arc::new(client),
+            idle: Default::default(),
+        })
+    }
+
+    /// Runs `commands` in order on one connection. A cluster only runs a
+    /// multi-key command when every key is in the same hash slot, so those
+    /// are split per key and their replies put back together.
+    pub(super) async fn query(&self, commands: Vec<Command>) -> Result<Vec<Value>, RedisError> {
+        let cluster = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = cluster.connection()?;
+            let replies = commands
+                .into_iter()
+                .map(|command| run(&mut connection, command))
+                .collect::<Result<Vec<_>, _>>();
+            if replies.is_ok() {
+                cluster.release(connection);
+            }
+            replies
+        })
+        .await
+        .unwrap_or_else(|error| std::panic::resume_unwind(error.into_panic()))
+    }
+
+    fn connection(&self) -> Result<ClusterConnection, RedisError> {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        match idle {
+            Some(connection) => Ok(connection),
+            None => self.client.get_connection().map_err(RedisError::connection),
+        }
+    }
+
+    fn release(&self, connection: ClusterConnection) {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(connection);
+    }
+}
+
+fn run(connection: &mut ClusterConnection, command: Command) -> Result<Value, RedisError> {
+    let mut query = |command: Command| {
+        connection
+            .req_command(&command.into())
+            .map_err(RedisError::query)
+    };
+    match command {
+        Command::Delete { keys } => {
+            let mut deleted = 0;
+            for key in keys {
+                if let Value::Int(count) = query(Command::delete(key))? {
+                    deleted += count;
+                }
+            }
+            Ok(Value::Int(deleted))
+        }
+        Command::GetMany { keys } => {
+            let values = keys
+                .into_iter()
+                .map(|key| query(Command::get(key)))
+                .collect::<Result<_, _>>()?;
+            Ok(Value::Bulk(values))
+        }
+        command @ Command::Write { .. } => {
+            let (write, index) = command.split_index();
+            let written = query(write)?;
+            if written != Value::Nil {
+                for command in index {
+                    query(command)?;
+                }
+            }
+            Ok(written)
+        }
+        command => query(command),
+    }
+}
@@ -1,8 +1,24 @@
 use std::time::Duration;
 
+/// Writes the session, its expiry and its tag index entries in one step.
+/// Every key is passed in `KEYS`, so a cluster rejects the script when they
+/// span hash slots instead of running it half-routed; see
+/// [`Command::split_index`].
+const WRITE_SCRIPT: &str = r#"
+local written = redis.call('SET', KEYS[1], ARGV[1], ARGV[2], 'EX', ARGV[3])
+if not written then
+    return false
+end
+for i = 2, #KEYS do
+    redis.call('SADD', KEYS[i], ARGV[4])
+end
+return written
+"#;
+
 pub enum Command {
+    ClientInfo,
     Delete {
-        key: String,
+        keys: Vec<String>,
     },
     Exists {
         key: String,
@@ -10,12 +26,52 @@ pub enum Command {
     Get {
         key: String,
     },
+    GetMany {
+        keys: Vec<String>,
+    },
+    IdleTime {
+        key: String,
+    },
+    Info {
+        section: String,
+    },
+    ListMove {
+        source: String,
+        destination: String,
+    },
+    ListPush {
+        key: String,
+        member: String,
+    },
+    ListRemove {
+        key: String,
+        member: String,
+    },
+    RandomKey,
+    Scan {
+        cursor: u64,
+        count: usize,
+    },
+    SentinelMaster {
+        name: String,
+    },
     Set {
         key: String,
         value: String,
         ttl: Duration,
     },
-    TTL {
+    SetAdd {
+        key: String,
+        member: String,
+    },
+    SetMembers {
+        key: String,
+    },
+    SetRemove {
+        key: String,
+        member: String,
+    },
+    Ttl {
         key: String,
     },
     Update {
@@ -23,11 +79,25 @@ pub enum Command {
         value: String,
         ttl: Duration,
     },
+    Write {
+        key: String,
+        value: String,
+        ttl: Duration,
+        existing: bool,
+        index_keys: Vec<String>,
+        member: String,
+    },
 }
 
 impl Command {
+    pub fn client_info() -> Self {
+        Self::ClientInfo
+    }
     pub fn delete(key: String) -> Self {
-        Self::Delete { key }
+        Self::Delete { keys: vec![key] }
+    }
+    pub fn delete_many(keys: Vec<String>) -> Self {
+        Self::Delete { keys }
     }
     pub fn exists(key: String) -> Self {
         Self::Exists { key }
@@ -35,23 +105,139 @@ impl Command {
     pub fn get(key: String) -> Self {
         Self::Get { key }
     }
+    pub fn get_many(keys: Vec<String>) -> Self {
+        Self::GetMany { keys }
+    }
+    pub fn idle_time(key: String) -> Self {
+        Self::IdleTime { key }
+    }
+    pub fn info(section: String) -> Self {
+        Self::Info { section }
+    }
+    pub fn list_move(source: String, destination: String) -> Self {
+        Self::ListMove {
+            source,
+            destination,
+        }
+    }
+    pub fn list_push(key: String, member: String) -> Self {
+        Self::ListPush { key, member }
+    }
+    pub fn list_remove(key: String, member: String) -> Self {
+        Self::ListRemove { key, member }
+    }
+    pub fn random_key() -> Self {
+        Self::RandomKey
+    }
+    pub fn scan(cursor: u64, count: usize) -> Self {
+        Self::Scan { cursor, count }
+    }
+    pub fn sentinel_master(name: String) -> Self {
+        Self::SentinelMaster { name }
+    }
     pub fn set(key: String, value: String, ttl: Duration) -> Self {
         Self::Set { key, value, ttl }
     }
+    pub fn set_add(key: String, member: String) -> Self {
+        Self::SetAdd { key, member }
+    }
+    pub fn set_members(key: String) -> Self {
+        Self::SetMembers { key }
+    }
+    pub fn set_remove(key: String, member: String) -> Self {
+        Self::SetRemove { key, member }
+    }
     pub fn ttl(key: String) -> Self {
-        Self::TTL { key }
+        Self::Ttl { key }
     }
     pub fn update(key: String, value: String, ttl: Duration) -> Self {
         Self::Update { key, value, ttl }
     }
+    pub fn write(
+        key: String,
+        value: String,
+        ttl: Duration,
+        existing: bool,
+        index_keys: Vec<String>,
+        member: String,
+    ) -> Self {
+        Self::Write {
+            key,
+            value,
+            ttl,
+            existing,
+            index_keys,
+            member,
+        }
+    }
+}
+
+impl Command {
+    /// Splits a write into the script for the session key alone and one
+    /// `SADD` per tag set, for a cluster, where the tag sets live in other
+    /// hash slots. The tag sets are then updated after the write rather
+    /// than with it, which tag lookups tolerate as they already skip and
+    /// prune members whose session no longer carries the tag. Other
+    /// commands are returned unchanged.
+    pub fn split_index(self) -> (Self, Vec<Self>) {
+        match self {
+            Self::Write {
+                key,
+                value,
+                ttl,
+                existing,
+                index_keys,
+                member,
+            } => {
+                let index = index_keys
+                    .into_iter()
+                    .map(|index_key| Self::set_add(index_key, member.clone()))
+                    .collect();
+                let write = Self::Write {
+                    key,
+                    value,
+                    ttl,
+                    existing,
+                    index_keys: Vec::new(),
+                    member,
+                };
+                (write, index)
+            }
+            command => (command, Vec::new()),
+        }
+    }
 }
 
 impl From<Command> for redis::Cmd {
     fn from(command: Command) -> Self {
         match command {
-            Command::Delete { key } => redis::cmd("DEL").arg(&[&key]).clone(),
+            Command::ClientInfo => redis::cmd("CLIENT").arg("INFO").clone(),
+            Command::Delete { keys } => redis::cmd("UNLINK").arg(&keys).clone(),
             Command::Exists { key } => redis::cmd("EXISTS").arg(&[&key]).clone(),
             Command::Get { key } => redis::cmd("GET").arg(&[&key]).clone(),
+            Command::GetMany { keys } => redis::cmd("MGET").arg(&keys).clone(),
+            Command::IdleTime { key } => redis::cmd("OBJECT").arg("IDLETIME").arg(&key).clone(),
+            Command::Info { section } => redis::cmd("INFO").arg(&section).clone(),
+            Command::ListMove {
+                source,
+                destination,
+            } => redis::cmd("LMOVE")
+                .arg(&[&source, &destination, "LEFT", "RIGHT"])
+                .clone(),
+            Command::ListPush { key, member } => redis::cmd("RPUSH").arg(&[&key, &member]).clone(),
+            Command::ListRemove { key, member } => {
+                redis::cmd("LREM").arg(&key).arg(1).arg(&member).clone()
+            }
+            Command::RandomKey => redis::cmd("RANDOMKEY"),
+            Command::Scan { cursor, count } => redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(count)
+                .clone(),
+            Command::SentinelMaster { name } => redis::cmd("SENTINEL")
+                .arg("get-master-addr-by-name")
+                .arg(name)
+                .clone(),
             Command::Set { key, value, ttl } => redis::cmd("SET")
                 .arg(&[
                     &key,
@@ -61,7 +247,10 @@ impl From<Command> for redis::Cmd {
                     format!("{}", ttl.as_secs()).as_ref(),
                 ])
                 .clone(),
-            Command::TTL { key } => redis::cmd("TTL").arg(&[&key]).clone(),
+            Command::SetAdd { key, member } => redis::cmd("SADD").arg(&[&key, &member]).clone(),
+            Command::SetMembers { key } => redis::cmd("SMEMBERS").arg(&[&key]).clone(),
+            Command::SetRemove { key, member } => redis::cmd("SREM").arg(&[&key, &member]).clone(),
+            Command::Ttl { key } => redis::cmd("PTTL").arg(&[&key]).clone(),
             Command::Update { key, value, ttl } => redis::cmd("SET")
                 .arg(&[
                     &key,
@@ -71,6 +260,95 @@ impl From<Command> for redis::Cmd {
                     format!("{}", ttl.as_secs()).as_ref(),
                 ])
                 .clone(),
+            Command::Write {
+                key,
+                value,
+                ttl,
+                existing,
+                index_keys,
+                member,
+            } => redis::cmd("EVAL")
+                .arg(WRITE_SCRIPT)
+                .arg(1 + index_keys.len())
+                .arg(&key)
+                .arg(&index_keys)
+                .arg(&value)
+                .arg(if existing { "XX" } else { "NX" })
+                .arg(ttl.as_secs())
+                .arg(&member)
+                .clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: Command) -> Vec<String> {
+        let command: redis::Cmd = command.into();
+        command
+            .args_iter()
+            .map(|arg| match arg {
+                redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                redis::Arg::Cursor => "<cursor>".to_string(),
+            })
+            .collect()
+    }
+
+    fn write(index_keys: &[&str]) -> Command {
+        Command::write(
+            "session".to_string(),
+            "{}".to_string(),
+            Duration::from_secs(60),
+            false,
+            index_keys.iter().map(|key| key.to_string()).collect(),
+            "session".to_string(),
+        )
+    }
+
+    #[test]
+    fn write_scripts_declare_every_key_they_touch() {
+        let args = args(write(&["tag:kiosk", "tag:beta"]));
+        assert_eq!(args[0], "EVAL");
+        assert_eq!(
+            &args[2..],
+            [
+                "3",
+                "session",
+                "tag:kiosk",
+                "tag:beta",
+                "{}",
+                "NX",
+                "60",
+                "session"
+            ]
+        );
+    }
+
+    #[test]
+    fn split_index_keeps_each_command_in_one_hash_slot() {
+        let (write, index) = write(&["tag:kiosk", "tag:beta"]).split_index();
+        assert_eq!(&args(write)[2..4], ["1", "session"]);
+        let index = index.into_iter().map(args).collect::<Vec<_>>();
+        assert_eq!(
+            index,
+            [
+                ["SADD", "tag:kiosk", "session"],
+                ["SADD", "tag:beta", "session"]
+            ]
+        );
+
+        let (get, index) = Command::get("session".to_string()).split_index();
+        assert_eq!(args(get), ["GET", "session"]);
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn claims_move_keys_from_the_head_of_the_queue() {
+        let claim = Command::list_move("queue".to_string(), "claimed".to_string());
+        assert_eq!(args(claim), ["LMOVE", "queue", "claimed", "LEFT", "RIGHT"]);
+        let acknowledge = Command::list_remove("claimed".to_string(), "session".to_string());
+        assert_eq!(args(acknowledge), ["LREM", "claimed", "1", "session"]);
+    }
+}
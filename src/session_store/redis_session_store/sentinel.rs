@@ -0,0 +1,127 @@
+use redis::{aio::ConnectionManager, ConnectionAddr, ConnectionInfo, RedisConnectionInfo};
+use std::{
+    future::Future,
+    sync::{Mutex, PoisonError},
+};
+
+use super::{commands::Command, RedisError};
+
+#[derive(Clone)]
+pub(super) struct Master {
+    pub(super) client: redis::Client,
+    pub(super) manager: ConnectionManager,
+    addr: (String, u16),
+}
+
+/// A master discovered through Redis Sentinel. Queries go to the last known
+/// master; when one fails in a way that suggests a failover, the master is
+/// resolved again and the query retried once.
+pub(super) struct Sentinel {
+    sentinels: Vec<redis::Client>,
+    master_name: String,
+    redis: RedisConnectionInfo,
+    master: Mutex<Master>,
+}
+
+impl Sentinel {
+    pub(super) async fn open(
+        sentinels: &[&str],
+        master_name: &str,
+        redis: RedisConnectionInfo,
+    ) -> Result<Self, RedisError> {
+        let sentinels = sentinels
+            .iter()
+            .map(|url| redis::Client::open(*url))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(RedisError::connection)?;
+        let addr = master_addr(&sentinels, master_name).await?;
+        let master = connect(addr, &redis).await?;
+        Ok(Self {
+            sentinels,
+            master_name: master_name.to_string(),
+            redis,
+            master: Mutex::new(master),
+        })
+    }
+
+    pub(super) fn master(&self) -> Master {
+        self.master
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Runs `query` on the master, following a failover at most once.
+    pub(super) async fn query<T, F, Fut>(&self, query: F) -> Result<T, RedisError>
+    where
+        F: Fn(ConnectionManager) -> Fut,
+        Fut: Future<Output = redis::RedisResult<T>>,
+    {
+        match query(self.master().manager).await {
+            Err(error) if is_failover(&error) => {}
+            result => return result.map_err(RedisError::query),
+        }
+        let master = self.resolve().await?;
+        query(master.manager).await.map_err(RedisError::query)
+    }
+
+    /// Asks the sentinels for the master again, connecting to it if it
+    /// moved.
+    pub(super) async fn resolve(&self) -> Result<Master, RedisError> {
+        let current = self.master();
+        let addr = master_addr(&self.sentinels, &self.master_name).await?;
+        if addr == current.addr {
+            return Ok(current);
+        }
+        let master = connect(addr, &self.redis).await?;
+        *self.master.lock().unwrap_or_else(PoisonError::into_inner) = master.clone();
+        Ok(master)
+    }
+}
+
+/// Errors from a master that went away or was demoted to a replica.
+fn is_failover(error: &redis::RedisError) -> bool {
+    error.is_io_error()
+        || error.is_connection_refusal()
+        || error.is_connection_dropped()
+        || error.kind() == redis::ErrorKind::ReadOnly
+}
+
+/// Asks each sentinel in turn for the master's address.
+async fn master_addr(
+    sentinels: &[redis::Client],
+    master_name: &str,
+) -> Result<(String, u16), RedisError> {
+    let mut failure = format!("no sentinel knows a master named \"{master_name}\"");
+    for sentinel in sentinels {
+        let addr = async {
+            let mut connection = sentinel.get_multiplexed_async_connection().await?;
+            let command: redis::Cmd = Command::sentinel_master(master_name.to_string()).into();
+            command
+                .query_async::<_, Option<(String, u16)>>(&mut connection)
+                .await
+        };
+        match addr.await {
+            Ok(Some(addr)) => return Ok(addr),
+            Ok(None) => {}
+            Err(error) => failure = error.to_string(),
+        }
+    }
+    Err(RedisError::ConnectionError(failure))
+}
+
+async fn connect(addr: (String, u16), redis: &RedisConnectionInfo) -> Result<Master, RedisError> {
+    let info = ConnectionInfo {
+        addr: ConnectionAddr::Tcp(addr.0.clone(), addr.1),
+        redis: redis.clone(),
+    };
+    let client = redis::Client::open(info).map_err(RedisError::connection)?;
+    let manager = ConnectionManager::new(client.clone())
+        .await
+        .map_err(RedisError::connection)?;
+    Ok(Master {
+        client,
+        manager,
+        addr,
+    })
+}
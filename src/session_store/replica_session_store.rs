@@ -0,0 +1,209 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    session::Session,
+    session_store::{SessionKey, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplicaStoreError<P, R> {
+    #[error(transparent)]
+    PrimaryError(P),
+    #[error(transparent)]
+    ReplicaError(R),
+}
+
+/// Sends writes to `primary` and reads to `replica`, except that reads of a
+/// session written through this store within `window` also go to `primary`
+/// so a client never reads an older state than it just wrote.
+///
+/// Recent writes are remembered in this process only, so the guarantee
+/// holds per node: a client whose next request lands on another node may
+/// read the replica. Route each session to one node, or keep the window
+/// short enough that replication lag rarely exceeds it.
+pub struct ReplicaSessionStore<Primary, Replica> {
+    primary: Primary,
+    replica: Replica,
+    watermarks: Watermarks,
+}
+
+impl<Primary, Replica> ReplicaSessionStore<Primary, Replica>
+where
+    Primary: SessionStore,
+    Replica: SessionStore,
+{
+    pub fn new(primary: Primary, replica: Replica) -> Self {
+        Self {
+            primary,
+            replica,
+            watermarks: Watermarks::new(Duration::from_secs(5)),
+        }
+    }
+
+    /// How long after a write reads stay on the primary; should exceed the
+    /// worst expected replication lag.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.watermarks = Watermarks::new(window);
+        self
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Primary, Replica> SessionStore for ReplicaSessionStore<Primary, Replica>
+where
+    Primary: SessionStore,
+    Replica: SessionStore,
+{
+    type Error = ReplicaStoreError<Primary::Error, Replica::Error>;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        if self.watermarks.pinned(session_key) {
+            self.primary
+                .load(session_key)
+                .await
+                .map_err(ReplicaStoreError::PrimaryError)
+        } else {
+            self.replica
+                .load(session_key)
+                .await
+                .map_err(ReplicaStoreError::ReplicaError)
+        }
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.watermarks.mark(session.id());
+        self.primary
+            .save(session, timeout)
+            .await
+            .map_err(ReplicaStoreError::PrimaryError)
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.watermarks.mark(session.id());
+        self.primary
+            .update(session, timeout)
+            .await
+            .map_err(ReplicaStoreError::PrimaryError)
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.watermarks.mark(session_key);
+        self.primary
+            .destroy(session_key)
+            .await
+            .map_err(ReplicaStoreError::PrimaryError)
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        if self.watermarks.pinned(session_key) {
+            self.primary
+                .exists(session_key)
+                .await
+                .map_err(ReplicaStoreError::PrimaryError)
+        } else {
+            self.replica
+                .exists(session_key)
+                .await
+                .map_err(ReplicaStoreError::ReplicaError)
+        }
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        if self.watermarks.pinned(session_key) {
+            self.primary
+                .ttl(session_key)
+                .await
+                .map_err(ReplicaStoreError::PrimaryError)
+        } else {
+            self.replica
+                .ttl(session_key)
+                .await
+                .map_err(ReplicaStoreError::ReplicaError)
+        }
+    }
+}
+
+/// Writes are kept in buckets this many to a window.
+const BUCKETS: u32 = 4;
+
+/// Recent writes grouped in buckets of a quarter window, oldest first, so
+/// forgetting expired writes drops whole buckets instead of scanning every
+/// key. A write stays pinned for at least `window` and at most a bucket
+/// longer, which errs towards the primary.
+struct Watermarks {
+    window: Duration,
+    buckets: Mutex<VecDeque<(Instant, HashSet<SessionKey>)>>,
+}
+
+impl Watermarks {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            buckets: Default::default(),
+        }
+    }
+
+    fn span(&self) -> Duration {
+        self.window / BUCKETS
+    }
+
+    fn mark(&self, session_key: &SessionKey) {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        while buckets
+            .front()
+            .is_some_and(|(start, _)| now.duration_since(*start) >= self.window + self.span())
+        {
+            buckets.pop_front();
+        }
+        match buckets.back_mut() {
+            Some((start, keys)) if now.duration_since(*start) < self.span() => {
+                keys.insert(session_key.clone());
+            }
+            _ => buckets.push_back((now, HashSet::from([session_key.clone()]))),
+        }
+    }
+
+    fn pinned(&self, session_key: &SessionKey) -> bool {
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        buckets.iter().rev().any(|(start, keys)| {
+            start.elapsed() < self.window + self.span() && keys.contains(session_key)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watermarks_pin_recent_writes_until_the_window_passes() {
+        let watermarks = Watermarks::new(Duration::from_millis(20));
+        let written = SessionKey::generate();
+        watermarks.mark(&written);
+
+        assert!(watermarks.pinned(&written));
+        assert!(!watermarks.pinned(&SessionKey::generate()));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!watermarks.pinned(&written));
+    }
+
+    #[test]
+    fn watermarks_forget_expired_buckets_on_the_next_write() {
+        let watermarks = Watermarks::new(Duration::from_millis(20));
+        for _ in 0..100 {
+            watermarks.mark(&SessionKey::generate());
+        }
+        std::thread::sleep(Duration::from_millis(30));
+        let written = SessionKey::generate();
+        watermarks.mark(&written);
+
+        let buckets = watermarks.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert!(buckets[0].1.contains(&written));
+    }
+}
@@ -1,15 +1,92 @@
-use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use crate::session_store::KeyFormat;
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+/// Separates the session and device parts of a composite key; no key
+/// encoding uses it and cookies and URLs carry it unescaped.
+const DEVICE_SEPARATOR: char = '~';
+const MAX_DEVICE_ID_LEN: usize = 64;
+
+fn is_device_id(device_id: &str) -> bool {
+    (1..=MAX_DEVICE_ID_LEN).contains(&device_id.len())
+        && device_id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+/// A session key, optionally scoped to one device of a logical login.
+/// Composite keys store each device's state separately under the same
+/// session part, so one device can be revoked without the others.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct SessionKey(String);
 
 impl SessionKey {
+    /// A random key in the default [`KeyFormat`].
     pub fn generate() -> Self {
-        let value = std::iter::repeat(())
-            .map(|()| OsRng.sample(Alphanumeric))
-            .take(64)
-            .collect::<Vec<_>>();
-        let key = String::from_utf8(value).unwrap().try_into().unwrap();
+        Self::generate_in(KeyFormat::default())
+    }
+
+    pub fn generate_in(format: KeyFormat) -> Self {
+        Self(format.generate())
+    }
+
+    /// Accepts `value` only if it has the shape of a key generated in the
+    /// default [`KeyFormat`], with or without a device part, so
+    /// client-supplied cookies cannot pick arbitrary store keys.
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::parse_in(value, KeyFormat::default())
+    }
+
+    /// Like [`parse`](Self::parse), for keys generated in `format`.
+    pub fn parse_in(value: &str, format: KeyFormat) -> Option<Self> {
+        let (session, device_id) = match value.split_once(DEVICE_SEPARATOR) {
+            Some((session, device_id)) => (session, Some(device_id)),
+            None => (value, None),
+        };
+        let valid = format.accepts(session) && device_id.is_none_or(is_device_id);
+        valid.then(|| Self(value.to_string()))
+    }
+
+    /// This key's session part scoped to `device_id`, which must be 1 to 64
+    /// characters of `[A-Za-z0-9_-]`.
+    pub fn for_device(&self, device_id: &str) -> Option<Self> {
+        is_device_id(device_id)
+            .then(|| Self(format!("{}{DEVICE_SEPARATOR}{device_id}", self.session())))
+    }
+
+    /// The session part, shared by every device of a logical login.
+    pub fn session(&self) -> &str {
+        self.split().0
+    }
+
+    pub fn device_id(&self) -> Option<&str> {
+        self.split().1
+    }
+
+    /// The logical login this key belongs to, without its device part.
+    pub fn without_device(&self) -> Self {
+        Self(self.session().to_string())
+    }
+
+    /// A fresh random key for the same device.
+    pub fn regenerated(&self) -> Self {
+        self.regenerated_in(KeyFormat::default())
+    }
+
+    pub fn regenerated_in(&self, format: KeyFormat) -> Self {
+        let fresh = Self::generate_in(format);
+        match self.device_id() {
+            Some(device_id) => fresh.for_device(device_id).unwrap_or(fresh),
+            None => fresh,
+        }
+    }
+
+    fn split(&self) -> (&str, Option<&str>) {
+        match self.0.split_once(DEVICE_SEPARATOR) {
+            Some((session, device_id)) => (session, Some(device_id)),
+            None => (&self.0, None),
+        }
+    }
+
+    pub(crate) fn from_raw(key: String) -> Self {
         Self(key)
     }
 }
@@ -25,3 +102,49 @@ impl Default for SessionKey {
         Self::generate()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::KeyEncoding;
+
+    #[test]
+    fn parse_accepts_only_generated_keys() {
+        let key = SessionKey::generate();
+        assert_eq!(SessionKey::parse(key.as_ref()), Some(key));
+        assert_eq!(SessionKey::parse("short"), None);
+        assert_eq!(SessionKey::parse(&"!".repeat(64)), None);
+    }
+
+    #[test]
+    fn composite_keys_keep_the_session_part_across_devices() {
+        let key = SessionKey::generate();
+        let phone = key.for_device("phone-1").unwrap();
+        assert_eq!(SessionKey::parse(phone.as_ref()), Some(phone.clone()));
+        assert_eq!(phone.session(), key.as_ref());
+        assert_eq!(phone.device_id(), Some("phone-1"));
+        assert_eq!(phone.without_device(), key);
+        assert_eq!(phone.regenerated().device_id(), Some("phone-1"));
+
+        assert_eq!(key.for_device("bad device"), None);
+        assert_eq!(SessionKey::parse(&format!("{}~", key.as_ref())), None);
+    }
+
+    #[test]
+    fn keys_are_parsed_in_the_format_they_were_generated_in() {
+        let format = KeyFormat::new(KeyEncoding::Crockford, 20);
+        let key = SessionKey::generate_in(format);
+        assert_eq!(
+            SessionKey::parse_in(key.as_ref(), format),
+            Some(key.clone())
+        );
+        assert_eq!(SessionKey::parse(key.as_ref()), None);
+        assert_eq!(
+            SessionKey::parse_in(SessionKey::generate().as_ref(), format),
+            None
+        );
+
+        let phone = key.for_device("phone-1").unwrap().regenerated_in(format);
+        assert_eq!(SessionKey::parse_in(phone.as_ref(), format), Some(phone));
+    }
+}
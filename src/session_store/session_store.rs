@@ -1,6 +1,41 @@
-use std::time::Duration;
+use futures::{stream::LocalBoxStream, Stream};
+use std::time::{Duration, SystemTime};
 
-use crate::{session::Session, session_store::session_key::SessionKey};
+use crate::{
+    batch::{self, ImportOptions, ImportReport},
+    session::Session,
+    session_store::{
+        session_key::SessionKey,
+        watch::{self, SessionChange},
+    },
+    storage::Storage,
+};
+
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SelfTestError<S> {
+    #[error("Session store error: {0}")]
+    StoreError(S),
+    #[error("Canary session did not round-trip through the store")]
+    RoundTripError,
+    #[error(
+        "Canary session was stored with a TTL of {0:?}, expected at most {SELF_TEST_TIMEOUT:?}"
+    )]
+    ExpiryError(Duration),
+    #[error("Canary session still exists after being destroyed")]
+    DestroyError,
+}
+
+/// A session picked by [`SessionStore::sample`], described without its
+/// contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionSample {
+    pub session_key: SessionKey,
+    /// The serialized state's length in bytes.
+    pub size: usize,
+    pub ttl: Duration,
+}
 
 #[async_trait::async_trait(?Send)]
 pub trait SessionStore {
@@ -11,7 +46,94 @@ pub trait SessionStore {
     async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error>;
     async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error>;
     async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error>;
+    /// How long the session has left; zero if it does not exist.
     async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error>;
+
+    /// When the session expires, or `None` if it does not exist, e.g. to
+    /// show how much longer a user stays logged in.
+    async fn expires_at(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<SystemTime>, Self::Error> {
+        let ttl = self.ttl(session_key).await?;
+        Ok((!ttl.is_zero()).then(|| SystemTime::now() + ttl))
+    }
+
+    /// Up to `n` live sessions picked uniformly at random, so each stands
+    /// for about the same share of the whole, for analysing session sizes
+    /// and lifetimes without a full export. Stores that cannot pick
+    /// sessions at random return none.
+    async fn sample(&self, n: usize) -> Result<Vec<SessionSample>, Self::Error> {
+        let _ = n;
+        Ok(Vec::new())
+    }
+
+    /// Saves every session the stream yields with its timeout, with bounded
+    /// concurrency and retries, e.g. to seed a new backend during a
+    /// migration. The stream is only read as fast as the store keeps up.
+    async fn import_stream<Sessions>(
+        &self,
+        sessions: Sessions,
+        options: &ImportOptions,
+    ) -> ImportReport<Self::Error>
+    where
+        Sessions: Stream<Item = (Session, Duration)>,
+    {
+        batch::import(self, sessions, options).await
+    }
+
+    /// Changes to the session as they happen, so that e.g. an SSE endpoint
+    /// can react when another device updates it. Stores without change
+    /// notifications reload the session every second.
+    fn watch<'a>(
+        &'a self,
+        session_key: &SessionKey,
+    ) -> LocalBoxStream<'a, Result<SessionChange, Self::Error>>
+    where
+        Self::Error: 'a,
+    {
+        watch::poll(self, session_key.clone(), watch::POLL_INTERVAL)
+    }
+
+    /// Saves, reloads and destroys a canary session, checking that it
+    /// round-trips and that the store applied the expiry. Run it at startup
+    /// to catch a misconfigured or read-only store before serving traffic.
+    async fn self_test(&self) -> Result<(), SelfTestError<Self::Error>> {
+        let mut canary = Session::default();
+        let token = SessionKey::generate();
+        canary
+            .insert("__self_test", &token)
+            .map_err(|_| SelfTestError::RoundTripError)?;
+        self.save(&canary, SELF_TEST_TIMEOUT)
+            .await
+            .map_err(SelfTestError::StoreError)?;
+        let loaded = self
+            .load(canary.id())
+            .await
+            .map_err(SelfTestError::StoreError)?
+            .and_then(|loaded| loaded.get::<SessionKey>("__self_test").ok().flatten());
+        let ttl = self
+            .ttl(canary.id())
+            .await
+            .map_err(SelfTestError::StoreError)?;
+        self.destroy(canary.id())
+            .await
+            .map_err(SelfTestError::StoreError)?;
+        if loaded.as_ref() != Some(&token) {
+            return Err(SelfTestError::RoundTripError);
+        }
+        if ttl.is_zero() || ttl > SELF_TEST_TIMEOUT {
+            return Err(SelfTestError::ExpiryError(ttl));
+        }
+        let exists = self
+            .exists(canary.id())
+            .await
+            .map_err(SelfTestError::StoreError)?;
+        if exists {
+            return Err(SelfTestError::DestroyError);
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait(?Send)]
@@ -44,4 +166,36 @@ where
     async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
         <S as SessionStore>::ttl(self, session_key).await
     }
+
+    async fn expires_at(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<SystemTime>, Self::Error> {
+        <S as SessionStore>::expires_at(self, session_key).await
+    }
+
+    async fn sample(&self, n: usize) -> Result<Vec<SessionSample>, Self::Error> {
+        <S as SessionStore>::sample(self, n).await
+    }
+
+    async fn import_stream<Sessions>(
+        &self,
+        sessions: Sessions,
+        options: &ImportOptions,
+    ) -> ImportReport<Self::Error>
+    where
+        Sessions: Stream<Item = (Session, Duration)>,
+    {
+        <S as SessionStore>::import_stream(self, sessions, options).await
+    }
+
+    fn watch<'a>(
+        &'a self,
+        session_key: &SessionKey,
+    ) -> LocalBoxStream<'a, Result<SessionChange, Self::Error>>
+    where
+        Self::Error: 'a,
+    {
+        <S as SessionStore>::watch(self, session_key)
+    }
 }
@@ -0,0 +1,256 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+/// A difference between the primary and candidate of a
+/// [`ShadowSessionStore`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShadowMismatch {
+    /// The candidate loaded a different state than the primary, or none.
+    StateMismatch {
+        session_key: SessionKey,
+        primary: Option<SessionState>,
+        candidate: Option<SessionState>,
+    },
+    ExistsMismatch {
+        session_key: SessionKey,
+        primary: bool,
+        candidate: bool,
+    },
+    /// The candidate failed an operation the primary completed.
+    CandidateError {
+        session_key: SessionKey,
+        operation: &'static str,
+        error: String,
+    },
+}
+
+type ReportMismatch = Box<dyn Fn(&ShadowMismatch)>;
+
+/// Serves every call from `primary` while issuing the same call to
+/// `candidate`, reporting where the two disagree, to validate a new backend
+/// on real traffic before cutting over to it.
+///
+/// Both run concurrently, so a call takes as long as the slower store, but
+/// the candidate's results and errors never reach the caller. Sessions
+/// written before shadowing began are missing from the candidate until
+/// they are next saved; backfill it first, e.g. with
+/// [`import_stream`](SessionStore::import_stream), to keep those out of the
+/// report. TTLs are served by the primary alone, as they drift between any
+/// two stores.
+pub struct ShadowSessionStore<Primary, Candidate> {
+    primary: Primary,
+    candidate: Candidate,
+    report: ReportMismatch,
+    mismatches: AtomicUsize,
+}
+
+impl<Primary, Candidate> ShadowSessionStore<Primary, Candidate>
+where
+    Primary: SessionStore,
+    Candidate: SessionStore,
+    Candidate::Error: fmt::Display,
+{
+    pub fn new(primary: Primary, candidate: Candidate) -> Self {
+        Self {
+            primary,
+            candidate,
+            report: Box::new(|_| {}),
+            mismatches: AtomicUsize::new(0),
+        }
+    }
+
+    /// Calls `report` with every mismatch, e.g. to log or count it.
+    pub fn with_reporter(mut self, report: impl Fn(&ShadowMismatch) + 'static) -> Self {
+        self.report = Box::new(report);
+        self
+    }
+
+    /// How many mismatches have been reported.
+    pub fn mismatches(&self) -> usize {
+        self.mismatches.load(Ordering::Relaxed)
+    }
+
+    fn mismatch(&self, mismatch: ShadowMismatch) {
+        self.mismatches.fetch_add(1, Ordering::Relaxed);
+        (self.report)(&mismatch);
+    }
+
+    /// Reports a candidate failure, if the primary succeeded.
+    fn compare_write<T>(
+        &self,
+        session_key: &SessionKey,
+        operation: &'static str,
+        primary: &Result<(), Primary::Error>,
+        candidate: Result<T, Candidate::Error>,
+    ) {
+        if let (Ok(()), Err(error)) = (primary, candidate) {
+            self.mismatch(ShadowMismatch::CandidateError {
+                session_key: session_key.clone(),
+                operation,
+                error: error.to_string(),
+            });
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Primary, Candidate> SessionStore for ShadowSessionStore<Primary, Candidate>
+where
+    Primary: SessionStore,
+    Candidate: SessionStore,
+    Candidate::Error: fmt::Display,
+{
+    type Error = Primary::Error;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let (primary, candidate) = futures::join!(
+            self.primary.load(session_key),
+            self.candidate.load(session_key)
+        );
+        let session = primary?;
+        match candidate {
+            Err(error) => self.mismatch(ShadowMismatch::CandidateError {
+                session_key: session_key.clone(),
+                operation: "load",
+                error: error.to_string(),
+            }),
+            Ok(candidate) => {
+                let primary = session.as_ref().map(|session| session.state().clone());
+                let candidate = candidate.map(SessionState::from);
+                if primary != candidate {
+                    self.mismatch(ShadowMismatch::StateMismatch {
+                        session_key: session_key.clone(),
+                        primary,
+                        candidate,
+                    });
+                }
+            }
+        }
+        Ok(session)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let (primary, candidate) = futures::join!(
+            self.primary.save(session, timeout),
+            self.candidate.save(session, timeout)
+        );
+        self.compare_write(session.id(), "save", &primary, candidate);
+        primary
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let (primary, candidate) = futures::join!(
+            self.primary.update(session, timeout),
+            self.candidate.update(session, timeout)
+        );
+        self.compare_write(session.id(), "update", &primary, candidate);
+        primary
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        let (primary, candidate) = futures::join!(
+            self.primary.destroy(session_key),
+            self.candidate.destroy(session_key)
+        );
+        self.compare_write(session_key, "destroy", &primary, candidate);
+        primary
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        let (primary, candidate) = futures::join!(
+            self.primary.exists(session_key),
+            self.candidate.exists(session_key)
+        );
+        let exists = primary?;
+        match candidate {
+            Err(error) => self.mismatch(ShadowMismatch::CandidateError {
+                session_key: session_key.clone(),
+                operation: "exists",
+                error: error.to_string(),
+            }),
+            Ok(candidate) if candidate != exists => self.mismatch(ShadowMismatch::ExistsMismatch {
+                session_key: session_key.clone(),
+                primary: exists,
+                candidate,
+            }),
+            Ok(_) => {}
+        }
+        Ok(exists)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.primary.ttl(session_key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{storage::Storage, MemorySessionStore};
+
+    #[tokio::test]
+    async fn serves_the_primary_and_reports_candidate_divergence() {
+        let primary = MemorySessionStore::new();
+        let candidate = MemorySessionStore::new();
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        // Written before shadowing began, so only the primary has it.
+        primary
+            .save(&session, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        let reported = Rc::new(RefCell::new(Vec::new()));
+        let store = ShadowSessionStore::new(&primary, &candidate).with_reporter({
+            let reported = reported.clone();
+            move |mismatch| reported.borrow_mut().push(mismatch.clone())
+        });
+
+        assert!(store.load(session.id()).await.unwrap().is_some());
+        assert!(store
+            .update(&session, Duration::from_secs(60))
+            .await
+            .is_ok());
+        assert!(store.exists(session.id()).await.unwrap());
+        assert_eq!(store.mismatches(), 3);
+        let reported = reported.take();
+        assert!(matches!(
+            reported[0],
+            ShadowMismatch::StateMismatch {
+                candidate: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            reported[1],
+            ShadowMismatch::CandidateError {
+                operation: "update",
+                ..
+            }
+        ));
+        assert!(matches!(
+            reported[2],
+            ShadowMismatch::ExistsMismatch {
+                primary: true,
+                candidate: false,
+                ..
+            }
+        ));
+
+        session.insert("theme", &"dark").unwrap();
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+        store.load(session.id()).await.unwrap();
+        assert_eq!(store.mismatches(), 3);
+    }
+}
@@ -0,0 +1,16 @@
+//! Pieces shared by the SQL-backed stores.
+
+/// Panics unless `table` is a plain, optionally schema-qualified, SQL
+/// identifier, since the stores splice it into every query.
+pub(crate) fn assert_table_name(table: &str) {
+    let valid = table.split('.').all(|part| {
+        part.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    });
+    assert!(valid, "invalid sessions table name {table:?}");
+}
+
+/// The name of the expiry index on `table`.
+pub(crate) fn expiry_index(table: &str) -> String {
+    format!("{}_expires_at", table.replace('.', "_"))
+}
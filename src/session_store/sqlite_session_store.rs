@@ -0,0 +1,271 @@
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+use std::{
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    codec::{Codec, CodecError, ValueCodec},
+    session::Session,
+    session_state::SessionState,
+    session_store::{sql, SessionKey, SessionSample, SessionStore},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SqliteStoreError {
+    #[error("Storage error: Backend error {0}")]
+    BackendError(String),
+    #[error("Storage error: Unable to serialize or deserialize session from session key: {0}")]
+    SerializationError(#[from] CodecError),
+    #[error("SQLite error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn expires_at(timeout: Duration) -> i64 {
+    now_millis().saturating_add(timeout.as_millis() as i64)
+}
+
+/// Stores each session as a row in a local SQLite database, for embedded
+/// and desktop apps without a session server. Expiry is kept in Unix
+/// milliseconds; a load that finds an expired row deletes it.
+pub struct SqliteSessionStore {
+    table: String,
+    pool: SqlitePool,
+}
+
+impl SqliteSessionStore {
+    /// Opens the database at `url` (e.g. `sqlite://sessions.db`), creating
+    /// the file if it does not exist.
+    pub async fn new(url: &str) -> Result<Self, SqliteStoreError> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        Ok(Self::from_pool(pool))
+    }
+
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        Self {
+            table: "sessions".to_string(),
+            pool,
+        }
+    }
+
+    /// Panics unless `table` is a plain SQL identifier, since it is spliced
+    /// into every query.
+    pub fn with_table(mut self, table: &str) -> Self {
+        sql::assert_table_name(table);
+        self.table = table.to_string();
+        self
+    }
+
+    /// Creates the sessions table and its expiry index if they do not exist.
+    pub async fn migrate(&self) -> Result<(), SqliteStoreError> {
+        let table = &self.table;
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                id TEXT PRIMARY KEY NOT NULL,
+                state TEXT NOT NULL,
+                expires_at INTEGER NOT NULL
+            )"
+        ))
+        .execute(&self.pool)
+        .await?;
+        let index = sql::expiry_index(table);
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {index} ON {table} (expires_at)"
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes every expired row, returning how many were deleted.
+    pub async fn delete_expired(&self) -> Result<u64, SqliteStoreError> {
+        let deleted = sqlx::query(&format!(
+            "DELETE FROM {} WHERE expires_at <= $1",
+            self.table
+        ))
+        .bind(now_millis())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(deleted)
+    }
+
+    async fn expires_at_of(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<i64>, SqliteStoreError> {
+        let expires_at = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT expires_at FROM {} WHERE id = $1",
+            self.table
+        ))
+        .bind(session_key.as_ref())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(expires_at)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for SqliteSessionStore {
+    type Error = SqliteStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let row = sqlx::query_as::<_, (String, i64)>(&format!(
+            "SELECT state, expires_at FROM {} WHERE id = $1",
+            self.table
+        ))
+        .bind(session_key.as_ref())
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some((state, expires_at)) = row else {
+            return Ok(None);
+        };
+        if expires_at <= now_millis() {
+            self.destroy(session_key).await?;
+            return Ok(None);
+        }
+        let state = ValueCodec::decode::<SessionState>(&state)?;
+        Ok(Some(Session::new(session_key.clone(), state)))
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let state = ValueCodec::encode(session.state())?;
+        sqlx::query(&format!(
+            "INSERT INTO {} (id, state, expires_at) VALUES ($1, $2, $3)
+             ON CONFLICT (id) DO UPDATE
+             SET state = excluded.state, expires_at = excluded.expires_at",
+            self.table
+        ))
+        .bind(session.id().as_ref())
+        .bind(state)
+        .bind(expires_at(timeout))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        let state = ValueCodec::encode(session.state())?;
+        let updated = sqlx::query(&format!(
+            "UPDATE {} SET state = $2, expires_at = $3 WHERE id = $1 AND expires_at > $4",
+            self.table
+        ))
+        .bind(session.id().as_ref())
+        .bind(state)
+        .bind(expires_at(timeout))
+        .bind(now_millis())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Err(SqliteStoreError::BackendError(
+                "Update matched no live session".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        sqlx::query(&format!("DELETE FROM {} WHERE id = $1", self.table))
+            .bind(session_key.as_ref())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        let expires_at = self.expires_at_of(session_key).await?;
+        Ok(expires_at.is_some_and(|expires_at| expires_at > now_millis()))
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        let remaining = self
+            .expires_at_of(session_key)
+            .await?
+            .map(|expires_at| expires_at.saturating_sub(now_millis()).max(0))
+            .unwrap_or_default();
+        Ok(Duration::from_millis(remaining as u64))
+    }
+
+    async fn expires_at(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<SystemTime>, Self::Error> {
+        let expires_at = self
+            .expires_at_of(session_key)
+            .await?
+            .filter(|expires_at| *expires_at > now_millis())
+            .map(|expires_at| UNIX_EPOCH + Duration::from_millis(expires_at as u64));
+        Ok(expires_at)
+    }
+
+    async fn sample(&self, n: usize) -> Result<Vec<SessionSample>, Self::Error> {
+        let sampled = sqlx::query_as::<_, (String, i64, i64)>(&format!(
+            "SELECT id, length(CAST(state AS BLOB)), expires_at FROM {}
+             WHERE expires_at > $1
+             ORDER BY random()
+             LIMIT $2",
+            self.table
+        ))
+        .bind(now_millis())
+        .bind(i64::try_from(n).unwrap_or(i64::MAX))
+        .fetch_all(&self.pool)
+        .await?;
+        let now = now_millis();
+        let sample = sampled
+            .into_iter()
+            .map(|(id, size, expires_at)| SessionSample {
+                session_key: SessionKey::from_raw(id),
+                size: size.max(0) as usize,
+                ttl: Duration::from_millis(expires_at.saturating_sub(now).max(0) as u64),
+            })
+            .collect();
+        Ok(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::conformance;
+
+    async fn store() -> SqliteSessionStore {
+        // Every connection to `:memory:` opens its own database, so keep one.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .expect("Unable to open an in-memory database");
+        let store = SqliteSessionStore::from_pool(pool);
+        store.migrate().await.expect("Unable to create the table");
+        store
+    }
+
+    #[tokio::test]
+    async fn sqlite_store_passes_the_conformance_checks() {
+        conformance::run(&store().await).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn load_deletes_an_expired_row() {
+        let store = store().await;
+        let session = Session::default();
+        store.save(&session, Duration::ZERO).await.unwrap();
+
+        assert!(store.load(session.id()).await.unwrap().is_none());
+        assert_eq!(store.delete_expired().await.unwrap(), 0);
+    }
+}
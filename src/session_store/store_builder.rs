@@ -0,0 +1,259 @@
+use std::{fmt, time::Duration};
+
+use crate::{
+    archive::{Archiver, ObjectStorage},
+    conflict::ConflictResolver,
+    history::HistorySink,
+    observer::SessionObserver,
+    session_store::{
+        ArchivingSessionStore, CachedSessionStore, EncryptedSessionStore, HistorySessionStore,
+        MaintenanceMode, MergingSessionStore, MetricsSessionStore, ObservedSessionStore,
+        PrioritySessionStore, ReadOnlySessionStore, SessionStore, StoreMetrics,
+    },
+};
+
+/// The decorators [`StoreBuilder`] can apply, innermost first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Encryption,
+    Merging,
+    History,
+    Archiving,
+    Observed,
+    Priority,
+    Cached,
+    ReadOnly,
+    Metrics,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Layer::Encryption => "encryption",
+            Layer::Merging => "merging",
+            Layer::History => "history",
+            Layer::Archiving => "archiving",
+            Layer::Observed => "observed",
+            Layer::Priority => "priority",
+            Layer::Cached => "cached",
+            Layer::ReadOnly => "read-only",
+            Layer::Metrics => "metrics",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum StoreBuilderError {
+    #[error("The {0} layer was added more than once")]
+    DuplicateLayerError(Layer),
+    #[error("The {layer} layer must wrap the store before the {outer} layer")]
+    OrderError { layer: Layer, outer: Layer },
+}
+
+/// Composes decorator stores around a backend.
+///
+/// Layers must be added innermost first: encryption sits on the backend so
+/// nothing above it handles ciphertext, merging comes next so every other
+/// layer sees resolved state, history and archiving record what was
+/// actually written, observers only hear about writes that landed, priority
+/// admission keeps queued calls from holding backend resources, cache hits
+/// skip that queue, the maintenance switch keeps rejected writes from
+/// queueing, and metrics are outermost so they time every call the app
+/// makes.
+///
+/// ```ignore
+/// let metrics = StoreMetrics::new();
+/// let store = StoreBuilder::new(redis)
+///     .with_encryption(&key)
+///     .with_merging(LastWriteWins)
+///     .with_history(sink)
+///     .with_observer(observer)
+///     .with_priority(64, 16)
+///     .with_cache(1024, Duration::from_secs(5))
+///     .with_metrics(metrics.clone())
+///     .build()?;
+/// ```
+pub struct StoreBuilder<Store> {
+    store: Store,
+    layers: Vec<Layer>,
+    error: Option<StoreBuilderError>,
+}
+
+impl<Store: SessionStore> StoreBuilder<Store> {
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            layers: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Encrypts values with the 32-byte `key`; see [`EncryptedSessionStore`]
+    /// for rotating it.
+    pub fn with_encryption(self, key: &[u8; 32]) -> StoreBuilder<EncryptedSessionStore<Store>> {
+        self.wrap(Layer::Encryption, |store| {
+            EncryptedSessionStore::new(store, key)
+        })
+    }
+
+    pub fn with_merging<Resolver: ConflictResolver>(
+        self,
+        resolver: Resolver,
+    ) -> StoreBuilder<MergingSessionStore<Store, Resolver>> {
+        self.wrap(Layer::Merging, |store| {
+            MergingSessionStore::new(store, resolver)
+        })
+    }
+
+    pub fn with_history<Sink: HistorySink>(
+        self,
+        sink: Sink,
+    ) -> StoreBuilder<HistorySessionStore<Store, Sink>> {
+        self.wrap(Layer::History, |store| {
+            HistorySessionStore::new(store, sink)
+        })
+    }
+
+    pub fn with_archiving<Storage: ObjectStorage>(
+        self,
+        archiver: Archiver<Storage>,
+    ) -> StoreBuilder<ArchivingSessionStore<Store, Storage>> {
+        self.wrap(Layer::Archiving, |store| {
+            ArchivingSessionStore::new(store, archiver)
+        })
+    }
+
+    pub fn with_observer<Observer: SessionObserver>(
+        self,
+        observer: Observer,
+    ) -> StoreBuilder<ObservedSessionStore<Store, Observer>> {
+        self.wrap(Layer::Observed, |store| {
+            ObservedSessionStore::new(store, observer)
+        })
+    }
+
+    pub fn with_priority(
+        self,
+        capacity: usize,
+        reserved: usize,
+    ) -> StoreBuilder<PrioritySessionStore<Store>> {
+        self.wrap(Layer::Priority, |store| {
+            PrioritySessionStore::new(store, capacity, reserved)
+        })
+    }
+
+    pub fn with_cache(
+        self,
+        capacity: usize,
+        max_age: Duration,
+    ) -> StoreBuilder<CachedSessionStore<Store>> {
+        self.wrap(Layer::Cached, |store| {
+            CachedSessionStore::new(store)
+                .with_capacity(capacity)
+                .with_max_age(max_age)
+        })
+    }
+
+    pub fn with_read_only(
+        self,
+        mode: MaintenanceMode,
+    ) -> StoreBuilder<ReadOnlySessionStore<Store>> {
+        self.wrap(Layer::ReadOnly, |store| {
+            ReadOnlySessionStore::new(store, mode)
+        })
+    }
+
+    pub fn with_metrics(self, metrics: StoreMetrics) -> StoreBuilder<MetricsSessionStore<Store>> {
+        self.wrap(Layer::Metrics, |store| {
+            MetricsSessionStore::new(store, metrics)
+        })
+    }
+
+    /// The layers applied so far, innermost first.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn build(self) -> Result<Store, StoreBuilderError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.store),
+        }
+    }
+
+    fn wrap<Outer>(
+        mut self,
+        layer: Layer,
+        wrap: impl FnOnce(Store) -> Outer,
+    ) -> StoreBuilder<Outer> {
+        if self.error.is_none() {
+            self.error = match self.layers.last() {
+                Some(_) if self.layers.contains(&layer) => {
+                    Some(StoreBuilderError::DuplicateLayerError(layer))
+                }
+                Some(&last) if last > layer => {
+                    Some(StoreBuilderError::OrderError { layer, outer: last })
+                }
+                _ => None,
+            };
+        }
+        self.layers.push(layer);
+        StoreBuilder {
+            store: wrap(self.store),
+            layers: self.layers,
+            error: self.error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LastWriteWins, MemoryHistorySink, MemorySessionStore};
+
+    #[test]
+    fn store_builder_accepts_layers_innermost_first() {
+        let builder = StoreBuilder::new(MemorySessionStore::new())
+            .with_encryption(&[7; 32])
+            .with_merging(LastWriteWins)
+            .with_history(MemoryHistorySink::default())
+            .with_priority(8, 2)
+            .with_metrics(StoreMetrics::new());
+        assert_eq!(
+            builder.layers(),
+            [
+                Layer::Encryption,
+                Layer::Merging,
+                Layer::History,
+                Layer::Priority,
+                Layer::Metrics
+            ]
+        );
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn store_builder_rejects_misordered_and_duplicate_layers() {
+        let misordered = StoreBuilder::new(MemorySessionStore::new())
+            .with_priority(8, 2)
+            .with_merging(LastWriteWins)
+            .build();
+        assert_eq!(
+            misordered.err(),
+            Some(StoreBuilderError::OrderError {
+                layer: Layer::Merging,
+                outer: Layer::Priority,
+            })
+        );
+
+        let duplicated = StoreBuilder::new(MemorySessionStore::new())
+            .with_merging(LastWriteWins)
+            .with_merging(LastWriteWins)
+            .build();
+        assert_eq!(
+            duplicated.err(),
+            Some(StoreBuilderError::DuplicateLayerError(Layer::Merging))
+        );
+    }
+}
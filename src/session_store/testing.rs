@@ -0,0 +1,109 @@
+//! A [`MemorySessionStore`] with injected latency and failures, shared by the
+//! tests of wrappers and integrations that react to slow or failing stores.
+
+use std::time::Duration;
+
+use crate::{
+    session::Session,
+    session_store::{
+        MemorySessionStore, MemoryStoreError, SessionKey, SessionStore, StoreOperation,
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum FaultyStoreError {
+    #[error(transparent)]
+    MemoryError(#[from] MemoryStoreError),
+    #[error("Injected failure of {0:?}")]
+    InjectedError(StoreOperation),
+}
+
+type Failures = Box<dyn Fn(StoreOperation, &SessionKey) -> bool + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct FaultyStore {
+    store: MemorySessionStore,
+    delay: Duration,
+    failures: Option<Failures>,
+}
+
+impl FaultyStore {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Holds every call for `delay` before it reaches the store.
+    pub(crate) fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Fails the calls for which `fails` returns true, without reaching the
+    /// store; it is asked once per call, so it may keep state to fail only
+    /// some attempts.
+    pub(crate) fn with_failures(
+        mut self,
+        fails: impl Fn(StoreOperation, &SessionKey) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.failures = Some(Box::new(fails));
+        self
+    }
+
+    pub(crate) fn inner(&self) -> &MemorySessionStore {
+        &self.store
+    }
+
+    async fn before_call(
+        &self,
+        operation: StoreOperation,
+        session_key: &SessionKey,
+    ) -> Result<(), FaultyStoreError> {
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+        match &self.failures {
+            Some(fails) if fails(operation, session_key) => {
+                Err(FaultyStoreError::InjectedError(operation))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl SessionStore for FaultyStore {
+    type Error = FaultyStoreError;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        self.before_call(StoreOperation::Load, session_key).await?;
+        Ok(self.store.load(session_key).await?)
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.before_call(StoreOperation::Save, session.id()).await?;
+        Ok(self.store.save(session, timeout).await?)
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.before_call(StoreOperation::Update, session.id())
+            .await?;
+        Ok(self.store.update(session, timeout).await?)
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.before_call(StoreOperation::Destroy, session_key)
+            .await?;
+        Ok(self.store.destroy(session_key).await?)
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.before_call(StoreOperation::Exists, session_key)
+            .await?;
+        Ok(self.store.exists(session_key).await?)
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.before_call(StoreOperation::Ttl, session_key).await?;
+        Ok(self.store.ttl(session_key).await?)
+    }
+}
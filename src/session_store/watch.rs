@@ -0,0 +1,128 @@
+use futures::stream::{self, LocalBoxStream, StreamExt};
+use std::time::Duration;
+
+use crate::{
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+/// How often the default [`SessionStore::watch`] reloads the session.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A change to a watched session, e.g. made by another device.
+pub enum SessionChange {
+    /// The session was written; carries what it now holds.
+    Updated(Box<Session>),
+    /// The session was destroyed or expired. The stream ends after this.
+    Destroyed,
+}
+
+/// Reloads the session every `interval`, yielding when its state differs
+/// from the last load.
+pub(crate) fn poll<'a, S>(
+    store: &'a S,
+    session_key: SessionKey,
+    interval: Duration,
+) -> LocalBoxStream<'a, Result<SessionChange, S::Error>>
+where
+    S: SessionStore + ?Sized,
+    S::Error: 'a,
+{
+    // `None` once the stream is over; otherwise the last state seen, if any,
+    // and whether the last load failed.
+    let watching: Option<(Option<SessionState>, bool)> = Some((None, false));
+    stream::unfold(watching, move |watching| {
+        let session_key = session_key.clone();
+        async move {
+            let (mut last, failed) = watching?;
+            // Retries after a failure wait too, so a caller that keeps
+            // polling a failing backend does not spin.
+            let mut wait = last.is_some() || failed;
+            loop {
+                if wait {
+                    tokio::time::sleep(interval).await;
+                }
+                wait = true;
+                let session = match store.load(&session_key).await {
+                    Ok(Some(session)) => session,
+                    Ok(None) => return Some((Ok(SessionChange::Destroyed), None)),
+                    Err(error) => return Some((Err(error), Some((last, true)))),
+                };
+                let state = session.state().clone();
+                let changed = last.as_ref().is_some_and(|last| *last != state);
+                last = Some(state);
+                if changed {
+                    let change = SessionChange::Updated(Box::new(session));
+                    return Some((Ok(change), Some((last, false))));
+                }
+            }
+        }
+    })
+    .boxed_local()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Instant,
+    };
+
+    use super::*;
+    use crate::{
+        session_store::testing::FaultyStore, storage::Storage, MemorySessionStore, StoreOperation,
+    };
+
+    #[tokio::test]
+    async fn polling_yields_updates_then_ends_on_destroy() {
+        let store = MemorySessionStore::new();
+        let mut session = Session::default();
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+        let mut changes = poll(&store, session.id().clone(), Duration::from_millis(5));
+
+        let writer = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            session.insert("theme", &"dark").unwrap();
+            store
+                .update(&session, Duration::from_secs(60))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            store.destroy(session.id()).await.unwrap();
+        };
+        let reader = async {
+            let mut seen = Vec::new();
+            while let Some(change) = changes.next().await {
+                seen.push(change.unwrap());
+            }
+            seen
+        };
+        let ((), seen) = futures::join!(writer, reader);
+
+        assert_eq!(seen.len(), 2);
+        let SessionChange::Updated(updated) = &seen[0] else {
+            panic!("expected an update first");
+        };
+        assert_eq!(
+            updated.get::<String>("theme").unwrap().as_deref(),
+            Some("dark")
+        );
+        assert!(matches!(seen[1], SessionChange::Destroyed));
+    }
+
+    #[tokio::test]
+    async fn polling_waits_an_interval_before_retrying_a_failed_load() {
+        let loads = AtomicUsize::new(0);
+        let store = FaultyStore::new().with_failures(move |operation, _| {
+            operation == StoreOperation::Load && loads.fetch_add(1, Ordering::SeqCst) < 2
+        });
+        let interval = Duration::from_millis(30);
+        let mut changes = poll(&store, SessionKey::generate(), interval);
+
+        assert!(changes.next().await.unwrap().is_err());
+        let retried = Instant::now();
+        assert!(changes.next().await.unwrap().is_err());
+        assert!(retried.elapsed() >= interval);
+    }
+}
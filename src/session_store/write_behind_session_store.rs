@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    session::Session,
+    session_state::SessionState,
+    session_store::{SessionKey, SessionStore},
+};
+
+struct Held {
+    state: SessionState,
+    timeout: Duration,
+}
+
+struct Entry {
+    /// Until when writes that only change drafts are held back.
+    quiet_until: Instant,
+    held: Option<Held>,
+}
+
+/// Holds back updates that change nothing but drafts written with
+/// [`Session::autosave`], writing each session to `store` at most once per
+/// draft debounce.
+///
+/// A held update is written once it is due by [`tick`](Self::tick), which
+/// [`run`](Self::run) calls on a schedule, or straight away by the next
+/// update of the same session that changes anything else. Loads through
+/// this store see held updates; other nodes read the previous state until
+/// they are written, and a crash loses at most one debounce of drafts. Call
+/// [`flush`](Self::flush) before shutting down.
+pub struct WriteBehindSessionStore<Store> {
+    store: Store,
+    entries: Mutex<HashMap<SessionKey, Entry>>,
+}
+
+impl<Store: SessionStore> WriteBehindSessionStore<Store> {
+    pub fn new(store: Store) -> Self {
+        Self {
+            store,
+            entries: Default::default(),
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<SessionKey, Entry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Starts the quiet period after writing `session`, if it may be held
+    /// back at all.
+    fn written(&self, session: &Session) {
+        let mut entries = self.entries();
+        match session.autosave_debounce() {
+            Some(debounce) => {
+                let entry = Entry {
+                    quiet_until: Instant::now() + debounce,
+                    held: None,
+                };
+                entries.insert(session.id().clone(), entry);
+            }
+            None => {
+                entries.remove(session.id());
+            }
+        }
+    }
+
+    /// Holds back `session` if it only changes drafts and was written less
+    /// than a debounce ago.
+    fn hold(&self, session: &Session, timeout: Duration) -> bool {
+        if session.autosave_debounce().is_none() {
+            return false;
+        }
+        let mut entries = self.entries();
+        match entries.get_mut(session.id()) {
+            Some(entry) if entry.quiet_until > Instant::now() => {
+                entry.held = Some(Held {
+                    state: session.state().clone(),
+                    timeout,
+                });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Writes every held update that is due and returns how many were
+    /// written.
+    pub async fn tick(&self) -> Result<usize, Store::Error> {
+        self.write_held(false).await
+    }
+
+    /// Writes every held update now, e.g. before shutting down.
+    pub async fn flush(&self) -> Result<usize, Store::Error> {
+        self.write_held(true).await
+    }
+
+    /// Calls [`tick`](Self::tick) every `interval`, returning on the first
+    /// store error.
+    pub async fn run(&self, interval: Duration) -> Result<(), Store::Error> {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            self.tick().await?;
+        }
+    }
+
+    async fn write_held(&self, all: bool) -> Result<usize, Store::Error> {
+        let now = Instant::now();
+        let due = {
+            let mut entries = self.entries();
+            entries.retain(|_, entry| entry.held.is_some() || entry.quiet_until > now);
+            entries
+                .iter_mut()
+                .filter(|(_, entry)| all || entry.quiet_until <= now)
+                .filter_map(|(key, entry)| Some((key.clone(), entry.held.take()?)))
+                .collect::<Vec<_>>()
+        };
+        let mut written = 0;
+        for (key, held) in due {
+            let session = Session::new(key, held.state);
+            self.store.update(&session, held.timeout).await?;
+            self.entries().remove(session.id());
+            written += 1;
+        }
+        Ok(written)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store: SessionStore> SessionStore for WriteBehindSessionStore<Store> {
+    type Error = Store::Error;
+
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<Session>, Self::Error> {
+        let held = self
+            .entries()
+            .get(session_key)
+            .and_then(|entry| entry.held.as_ref())
+            .map(|held| held.state.clone());
+        match held {
+            Some(state) => Ok(Some(Session::new(session_key.clone(), state))),
+            None => self.store.load(session_key).await,
+        }
+    }
+
+    async fn save(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        self.store.save(session, timeout).await?;
+        self.written(session);
+        Ok(())
+    }
+
+    async fn update(&self, session: &Session, timeout: Duration) -> Result<(), Self::Error> {
+        if self.hold(session, timeout) {
+            return Ok(());
+        }
+        self.store.update(session, timeout).await?;
+        self.written(session);
+        Ok(())
+    }
+
+    async fn destroy(&self, session_key: &SessionKey) -> Result<(), Self::Error> {
+        self.entries().remove(session_key);
+        self.store.destroy(session_key).await
+    }
+
+    async fn exists(&self, session_key: &SessionKey) -> Result<bool, Self::Error> {
+        self.store.exists(session_key).await
+    }
+
+    async fn ttl(&self, session_key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.store.ttl(session_key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::Storage, MemorySessionStore};
+
+    const DEBOUNCE: Duration = Duration::from_secs(60);
+    const TIMEOUT: Duration = Duration::from_secs(600);
+
+    async fn draft(store: &MemorySessionStore, session_key: &SessionKey) -> Option<String> {
+        let session = store.load(session_key).await.unwrap()?;
+        session.get::<String>("compose").unwrap()
+    }
+
+    #[tokio::test]
+    async fn drafts_are_written_at_most_once_per_debounce() {
+        let inner = MemorySessionStore::new();
+        let store = WriteBehindSessionStore::new(&inner);
+        let mut session = Session::default();
+        session.autosave("compose", &"Dear", DEBOUNCE).unwrap();
+        store.save(&session, TIMEOUT).await.unwrap();
+        session.mark_persisted();
+
+        session.autosave("compose", &"Dear Sir", DEBOUNCE).unwrap();
+        store.update(&session, TIMEOUT).await.unwrap();
+        assert_eq!(draft(&inner, session.id()).await.unwrap(), "Dear");
+        let loaded = store.load(session.id()).await.unwrap().unwrap();
+        assert_eq!(
+            loaded.get::<String>("compose").unwrap().unwrap(),
+            "Dear Sir"
+        );
+
+        assert_eq!(store.tick().await.unwrap(), 0);
+        assert_eq!(store.flush().await.unwrap(), 1);
+        assert_eq!(draft(&inner, session.id()).await.unwrap(), "Dear Sir");
+    }
+
+    #[tokio::test]
+    async fn other_changes_write_through_with_the_held_drafts() {
+        let inner = MemorySessionStore::new();
+        let store = WriteBehindSessionStore::new(&inner);
+        let mut session = Session::default();
+        session.autosave("compose", &"Dear", DEBOUNCE).unwrap();
+        store.save(&session, TIMEOUT).await.unwrap();
+        session.mark_persisted();
+        session.autosave("compose", &"Dear Sir", DEBOUNCE).unwrap();
+        store.update(&session, TIMEOUT).await.unwrap();
+        session.mark_persisted();
+
+        session.insert("user_id", &"beavis").unwrap();
+        store.update(&session, TIMEOUT).await.unwrap();
+        assert_eq!(draft(&inner, session.id()).await.unwrap(), "Dear Sir");
+        assert_eq!(store.flush().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,275 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    codec::{Codec, ValueCodec},
+    session::{Session, SessionError},
+    storage::{Storage, StorageError, StorageGetError, StorageInsertError},
+};
+
+/// A session handle shared by concurrently running handlers of one request.
+///
+/// Reads go straight to the session; writes are only accepted through a
+/// [`WriteGuard`], which applies all of its mutations in one step when dropped so
+/// handlers interleaving across await points never observe partial updates.
+///
+/// A handler that panics does not poison the session for the rest of the
+/// request: panics in [`read`](Self::read) release the lock cleanly, and a
+/// guard dropped during a panic discards its writes instead of applying
+/// them. If the session is poisoned anyway, [`recover`](Self::recover)
+/// makes it usable again.
+#[derive(Clone)]
+pub struct SharedSession {
+    inner: Arc<Mutex<Session>>,
+}
+
+impl SharedSession {
+    pub fn new(session: Session) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(session)),
+        }
+    }
+
+    pub fn write(&self) -> WriteGuard<'_> {
+        WriteGuard {
+            shared: self,
+            staged: Vec::new(),
+        }
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&Session) -> R) -> Result<R, SessionError> {
+        let session = self.lock()?;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&session)));
+        drop(session);
+        result.map_err(|panic| panic::resume_unwind(panic))
+    }
+
+    /// Moves the session to a fresh key, e.g. after login.
+    pub fn regenerate(&self) -> Result<(), SessionError> {
+        self.lock()?.regenerate();
+        Ok(())
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+
+    /// Clears a poisoned lock so the session can be used again, returning
+    /// whether it was poisoned. Guards only change the session while
+    /// applying their writes, so at worst one guard's writes are partially
+    /// applied.
+    pub fn recover(&self) -> bool {
+        let poisoned = self.inner.is_poisoned();
+        self.inner.clear_poison();
+        poisoned
+    }
+
+    /// Returns the session once every other handle has been dropped.
+    pub fn try_into_inner(self) -> Result<Session, Self> {
+        Arc::try_unwrap(self.inner)
+            .map(|inner| inner.into_inner().unwrap_or_else(|e| e.into_inner()))
+            .map_err(|inner| Self { inner })
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Session>, SessionError> {
+        self.inner
+            .lock()
+            .map_err(|_| SessionError::SessionPoisonedError)
+    }
+}
+
+impl From<Session> for SharedSession {
+    fn from(session: Session) -> Self {
+        Self::new(session)
+    }
+}
+
+impl<K: AsRef<str>> Storage<K> for SharedSession {
+    type Error = SessionError;
+
+    fn insert<T: Serialize>(&mut self, _key: K, _value: &T) -> Result<(), Self::Error> {
+        Err(SessionError::UnguardedWriteError)
+    }
+
+    fn remove<T: DeserializeOwned>(&mut self, _key: K) -> Result<Option<T>, Self::Error> {
+        Err(SessionError::UnguardedWriteError)
+    }
+
+    fn get<T: DeserializeOwned>(&self, key: K) -> Result<Option<T>, Self::Error> {
+        let key = key.as_ref();
+        let session = self.lock()?;
+        Ok(session.get(key)?)
+    }
+}
+
+/// Stages mutations to a [`SharedSession`] and applies them atomically on drop.
+pub struct WriteGuard<'a> {
+    shared: &'a SharedSession,
+    staged: Vec<(String, Option<String>)>,
+}
+
+impl WriteGuard<'_> {
+    /// Applies the staged mutations now, reporting a poisoned session.
+    pub fn commit(mut self) -> Result<(), SessionError> {
+        self.apply()
+    }
+
+    /// Drops the staged mutations without applying them.
+    pub fn discard(mut self) {
+        self.staged.clear();
+    }
+
+    fn staged(&self, key: &str) -> Option<&Option<String>> {
+        self.staged
+            .iter()
+            .rev()
+            .find(|(staged, _)| staged == key)
+            .map(|(_, value)| value)
+    }
+
+    fn apply(&mut self) -> Result<(), SessionError> {
+        if self.staged.is_empty() {
+            return Ok(());
+        }
+        let mut session = self.shared.lock()?;
+        for (key, value) in self.staged.drain(..) {
+            match value {
+                Some(value) => session.insert_raw(&key, value),
+                None => {
+                    session.remove_raw(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<K: AsRef<str>> Storage<K> for WriteGuard<'_> {
+    type Error = SessionError;
+
+    fn insert<T: Serialize>(&mut self, key: K, value: &T) -> Result<(), Self::Error> {
+        let key = key.as_ref();
+        let value = ValueCodec::encode(value)
+            .map_err(|e| StorageInsertError::SerializeError(key.to_string(), e.to_string()))
+            .map_err(StorageError::from)?;
+        self.staged.push((key.to_string(), Some(value)));
+        Ok(())
+    }
+
+    fn remove<T: DeserializeOwned>(&mut self, key: K) -> Result<Option<T>, Self::Error> {
+        let key = key.as_ref();
+        let previous = self.get(key)?;
+        self.staged.push((key.to_string(), None));
+        Ok(previous)
+    }
+
+    fn get<T: DeserializeOwned>(&self, key: K) -> Result<Option<T>, Self::Error> {
+        let key = key.as_ref();
+        match self.staged(key) {
+            Some(value) => value
+                .as_deref()
+                .map(ValueCodec::decode)
+                .transpose()
+                .map_err(|e| StorageGetError::DeserializeError(key.to_string(), e.to_string()))
+                .map_err(StorageError::from)
+                .map_err(SessionError::from),
+            None => self.shared.get(key),
+        }
+    }
+}
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.staged.clear();
+        }
+        let _ = self.apply();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_outside_a_guard_are_rejected() {
+        let mut shared = SharedSession::new(Session::default());
+        let result = shared.insert("user_id", &"brandon");
+        assert!(matches!(result, Err(SessionError::UnguardedWriteError)));
+    }
+
+    #[test]
+    fn guard_mutations_are_only_visible_to_others_after_drop() {
+        let shared = SharedSession::new(Session::default());
+        let other = shared.clone();
+        {
+            let mut guard = shared.write();
+            guard.insert("user_id", &"brandon").unwrap();
+            guard.insert("role", &"admin").unwrap();
+            let user_id = guard.get::<String>("user_id").unwrap();
+            assert_eq!(user_id.as_deref(), Some("brandon"));
+            assert_eq!(other.get::<String>("user_id").unwrap(), None);
+        }
+
+        let role = other.get::<String>("role").unwrap();
+        assert_eq!(role.as_deref(), Some("admin"));
+    }
+
+    #[test]
+    fn discard_drops_staged_mutations() {
+        let shared = SharedSession::new(Session::default());
+        let mut guard = shared.write();
+        guard.insert("user_id", &"brandon").unwrap();
+        guard.discard();
+
+        assert_eq!(shared.get::<String>("user_id").unwrap(), None);
+        let session = shared.try_into_inner().ok().unwrap();
+        assert!(session.journal().is_empty());
+    }
+
+    #[test]
+    fn a_panicking_reader_does_not_poison_the_session() {
+        let shared = SharedSession::new(Session::default());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            shared.read(|_| panic!("handler bug")).unwrap();
+        }));
+        assert!(result.is_err());
+        assert!(!shared.is_poisoned());
+        shared.write().insert("user_id", &"brandon").unwrap();
+        assert!(shared.get::<String>("user_id").unwrap().is_some());
+    }
+
+    #[test]
+    fn a_guard_dropped_during_a_panic_discards_its_writes() {
+        let shared = SharedSession::new(Session::default());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut guard = shared.write();
+            guard.insert("user_id", &"brandon").unwrap();
+            panic!("handler bug");
+        }));
+        assert!(result.is_err());
+        assert_eq!(shared.get::<String>("user_id").unwrap(), None);
+    }
+
+    #[test]
+    fn recover_clears_a_poisoned_session() {
+        let shared = SharedSession::new(Session::default());
+        let poisoner = shared.clone();
+        let _ = std::thread::spawn(move || {
+            let _session = poisoner.inner.lock().unwrap();
+            panic!("panicked while holding the lock");
+        })
+        .join();
+        assert!(matches!(
+            shared.get::<String>("user_id"),
+            Err(SessionError::SessionPoisonedError)
+        ));
+
+        assert!(shared.recover());
+        assert!(!shared.recover());
+        assert_eq!(shared.get::<String>("user_id").unwrap(), None);
+    }
+}
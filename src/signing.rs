@@ -0,0 +1,304 @@
+use std::time::SystemTime;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Clone)]
+struct SigningKey {
+    id: String,
+    secret: Vec<u8>,
+    activates_at: SystemTime,
+}
+
+/// HMAC-SHA256 secrets for signing session cookies and tokens, rotated
+/// without logging everyone out.
+///
+/// Signed values carry the id of the key that signed them. Every key in the
+/// ring verifies; only the newest key whose activation time has passed
+/// signs, so a new secret can be rolled out to every node before it starts
+/// signing, and an old one kept until the values it signed have expired.
+///
+/// A ring tagged with an environment only verifies values signed in that
+/// environment, so a staging cookie is rejected by production even where
+/// both share a secret.
+#[derive(Clone)]
+pub struct Keyring {
+    keys: Vec<SigningKey>,
+    environment: Option<String>,
+}
+
+impl Keyring {
+    /// A ring whose first key is active immediately.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is empty or contains a `.`.
+    pub fn new(id: &str, secret: &[u8]) -> Self {
+        assert_valid_key_id(id);
+        Self {
+            keys: vec![SigningKey {
+                id: id.to_string(),
+                secret: secret.to_vec(),
+                activates_at: SystemTime::UNIX_EPOCH,
+            }],
+            environment: None,
+        }
+    }
+
+    /// Tags signed values with `environment`, e.g. `prod` or `staging`, and
+    /// rejects values tagged otherwise or not at all.
+    ///
+    /// # Panics
+    ///
+    /// If `environment` is empty or contains a `.`.
+    pub fn with_environment(mut self, environment: &str) -> Self {
+        assert!(
+            is_valid_environment(environment),
+            "environment tags must be non-empty and contain no '.'"
+        );
+        self.environment = Some(environment.to_string());
+        self
+    }
+
+    pub fn environment(&self) -> Option<&str> {
+        self.environment.as_deref()
+    }
+
+    /// Adds a key that starts signing at `activates_at`. A key with an
+    /// existing id replaces it.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is empty or contains a `.`.
+    pub fn with_key(mut self, id: &str, secret: &[u8], activates_at: SystemTime) -> Self {
+        assert_valid_key_id(id);
+        self.keys.retain(|key| key.id != id);
+        self.keys.push(SigningKey {
+            id: id.to_string(),
+            secret: secret.to_vec(),
+            activates_at,
+        });
+        self.keys.sort_by_key(|key| key.activates_at);
+        self
+    }
+
+    /// Stops verifying values signed by `id`. The last active key cannot be
+    /// retired; returns whether the key was removed.
+    pub fn retire(&mut self, id: &str) -> bool {
+        let now = SystemTime::now();
+        let active = self.keys.iter().filter(|key| key.activates_at <= now);
+        if active.filter(|key| key.id != id).count() == 0 {
+            return false;
+        }
+        let before = self.keys.len();
+        self.keys.retain(|key| key.id != id);
+        self.keys.len() < before
+    }
+
+    /// The id of the key currently signing.
+    pub fn active_key_id(&self) -> &str {
+        &self.active().id
+    }
+
+    /// Signs `value` as `value.key-id.signature`, or as
+    /// `value.environment.key-id.signature` in a tagged ring.
+    pub fn sign(&self, value: &str) -> String {
+        let key = self.active();
+        let environment = self.environment.as_deref();
+        let signature = to_hex(&mac(key, environment, value).finalize().into_bytes());
+        match environment {
+            Some(environment) => format!("{value}.{environment}.{}.{signature}", key.id),
+            None => format!("{value}.{}.{signature}", key.id),
+        }
+    }
+
+    /// The value of a signed token if a key in the ring signed it, in this
+    /// ring's environment.
+    pub fn verify<'a>(&self, token: &'a str) -> Option<&'a str> {
+        let (value, key, signature) = self.split(token)?;
+        let signature = from_hex(signature)?;
+        mac(key, self.environment.as_deref(), value)
+            .verify_slice(&signature)
+            .ok()?;
+        Some(value)
+    }
+
+    /// Whether `token` names a key in the ring, in this ring's environment,
+    /// but its signature does not verify: a forged or altered value rather
+    /// than one from another ring, environment or a retired key.
+    pub fn is_forged(&self, token: &str) -> bool {
+        self.split(token).is_some() && self.verify(token).is_none()
+    }
+
+    /// The value, signing key and signature of a token in this ring's
+    /// format, before the signature is checked.
+    fn split<'a>(&self, token: &'a str) -> Option<(&'a str, &SigningKey, &'a str)> {
+        let (rest, signature) = token.rsplit_once('.')?;
+        let (rest, id) = rest.rsplit_once('.')?;
+        let value = match &self.environment {
+            Some(environment) => {
+                let (value, tagged) = rest.rsplit_once('.')?;
+                if tagged != environment {
+                    return None;
+                }
+                value
+            }
+            None => rest,
+        };
+        let key = self.keys.iter().find(|key| key.id == id)?;
+        Some((value, key, signature))
+    }
+
+    /// A keyed HMAC-SHA256 digest of `parts`, for binding sessions and
+    /// idempotency records to request attributes without storing them or
+    /// letting anyone without the secret compute a match. Fingerprints use
+    /// the oldest key in the ring, so they stay stable while newer keys
+    /// roll out and change only once that key is retired.
+    pub fn fingerprint(&self, parts: &[&[u8]]) -> String {
+        let key = &self.keys[0];
+        let mut mac =
+            HmacSha256::new_from_slice(&key.secret).expect("HMAC accepts keys of any size");
+        mac.update(b"fingerprint.");
+        if let Some(environment) = &self.environment {
+            mac.update(environment.as_bytes());
+            mac.update(b".");
+        }
+        // Length-prefixed so that `["ab", "c"]` and `["a", "bc"]` differ.
+        for part in parts {
+            mac.update(&(part.len() as u64).to_be_bytes());
+            mac.update(part);
+        }
+        to_hex(&mac.finalize().into_bytes())
+    }
+
+    fn active(&self) -> &SigningKey {
+        let now = SystemTime::now();
+        self.keys
+            .iter()
+            .rev()
+            .find(|key| key.activates_at <= now)
+            .unwrap_or(&self.keys[0])
+    }
+}
+
+pub(crate) fn is_valid_environment(environment: &str) -> bool {
+    !environment.is_empty() && !environment.contains('.')
+}
+
+/// Key ids are written between the dots of a signed value, so one with a
+/// `.` of its own would never verify.
+fn assert_valid_key_id(id: &str) {
+    assert!(
+        !id.is_empty() && !id.contains('.'),
+        "key ids must be non-empty and contain no '.'"
+    );
+}
+
+fn mac(key: &SigningKey, environment: Option<&str>, value: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(&key.secret).expect("HMAC accepts keys of any size");
+    mac.update(key.id.as_bytes());
+    mac.update(b".");
+    if let Some(environment) = environment {
+        mac.update(environment.as_bytes());
+        mac.update(b".");
+    }
+    mac.update(value.as_bytes());
+    mac
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn only_the_newest_active_key_signs_but_every_key_verifies() {
+        let now = SystemTime::now();
+        let keyring = Keyring::new("2026-01", b"old secret");
+        let old = keyring.sign("session");
+
+        let keyring = keyring
+            .with_key("2026-06", b"new secret", now - Duration::from_secs(1))
+            .with_key("2027-01", b"future secret", now + Duration::from_secs(3600));
+        assert_eq!(keyring.active_key_id(), "2026-06");
+        let new = keyring.sign("session");
+        assert!(new.contains(".2026-06."));
+
+        assert_eq!(keyring.verify(&old), Some("session"));
+        assert_eq!(keyring.verify(&new), Some("session"));
+    }
+
+    #[test]
+    fn verify_rejects_tampered_and_unknown_tokens() {
+        let mut keyring = Keyring::new("a", b"secret").with_key("b", b"other", SystemTime::now());
+        let token = keyring.sign("session");
+        assert_eq!(
+            keyring.verify(&token.replacen("session", "forged", 1)),
+            None
+        );
+        assert!(keyring.is_forged(&token.replacen("session", "forged", 1)));
+        assert_eq!(keyring.verify("session.c.00"), None);
+        assert_eq!(keyring.verify("session"), None);
+        assert!(!keyring.is_forged("session.c.00"));
+        assert!(!keyring.is_forged("session"));
+        assert!(!keyring.is_forged(&token));
+
+        let signed_by_a = Keyring::new("a", b"secret").sign("session");
+        assert!(keyring.retire("a"));
+        assert_eq!(keyring.verify(&signed_by_a), None);
+        assert!(!keyring.retire("b"));
+    }
+
+    #[test]
+    #[should_panic(expected = "key ids must be non-empty and contain no '.'")]
+    fn key_ids_cannot_contain_the_separator() {
+        let _ = Keyring::new("a", b"secret").with_key("2026.06", b"new", SystemTime::now());
+    }
+
+    #[test]
+    fn tagged_rings_reject_values_from_other_environments() {
+        let prod = Keyring::new("a", b"secret").with_environment("prod");
+        let staging = Keyring::new("a", b"secret").with_environment("staging");
+        let untagged = Keyring::new("a", b"secret");
+
+        let token = staging.sign("session");
+        assert!(token.starts_with("session.staging.a."));
+        assert_eq!(staging.verify(&token), Some("session"));
+        assert_eq!(prod.verify(&token), None);
+        assert_eq!(prod.verify(&token.replacen("staging", "prod", 1)), None);
+        assert_eq!(untagged.verify(&token), None);
+        assert_eq!(prod.verify(&untagged.sign("session")), None);
+    }
+
+    #[test]
+    fn fingerprints_are_keyed_and_survive_rotation() {
+        let keyring = Keyring::new("a", b"secret");
+        let fingerprint = keyring.fingerprint(&[b"curl", b"10.0.0.1"]);
+        assert_eq!(fingerprint.len(), 64);
+        assert_ne!(fingerprint, keyring.fingerprint(&[b"curl1", b"0.0.0.1"]));
+        assert_ne!(
+            fingerprint,
+            Keyring::new("a", b"other").fingerprint(&[b"curl", b"10.0.0.1"])
+        );
+
+        let rotated = keyring.with_key("b", b"new secret", SystemTime::now());
+        assert_eq!(rotated.fingerprint(&[b"curl", b"10.0.0.1"]), fingerprint);
+    }
+}
@@ -1,6 +1,13 @@
+//! The key-value interface shared by [`Session`](crate::Session) and its
+//! wrappers. Implement [`Storage`] to build components that read and write
+//! session values, and reuse these error types rather than defining new ones.
+//! Error enums are `#[non_exhaustive]` so variants can be added in minor
+//! releases.
+
 use serde::{de::DeserializeOwned, Serialize};
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Clone, Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum StorageError {
     #[error(transparent)]
     StorageInsertError(#[from] StorageInsertError),
@@ -10,19 +17,28 @@ pub enum StorageError {
     StorageRemoveError(#[from] StorageRemoveError),
 }
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Clone, Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum StorageInsertError {
     #[error("Unable to serialize value for key \"{0}\": {1}")]
     SerializeError(String, String),
 }
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Clone, Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum StorageGetError {
     #[error("Unable to deserialize value for key \"{0}\": {1}")]
     DeserializeError(String, String),
+    #[error("Value for key \"{0}\" has version {2}, expected version {1}")]
+    VersionMismatchError(String, u32, u32),
+    #[error("Chunked value for key \"{0}\" failed validation: {1}")]
+    CorruptedError(String, String),
+    #[error("Unable to construct the default value for key \"{0}\": {1}")]
+    DefaultError(String, String),
 }
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Clone, Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum StorageRemoveError {
     #[error("Unable to deserialize value for key \"{0}\": {1}")]
     DeserializeError(String, String),
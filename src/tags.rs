@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use crate::{Session, SessionKey, SessionStore};
+
+/// A store that indexes sessions by the tags set with [`Session::tag`].
+///
+/// The index may lag behind untagging and destruction; `find_by_tag` only
+/// returns sessions that still exist and still carry the tag.
+#[async_trait::async_trait(?Send)]
+pub trait TagStore: SessionStore {
+    async fn find_by_tag(&self, tag: &str) -> Result<Vec<SessionKey>, Self::Error>;
+
+    async fn destroy_by_tag(&self, tag: &str) -> Result<usize, Self::Error> {
+        let keys = self.find_by_tag(tag).await?;
+        for key in &keys {
+            self.destroy(key).await?;
+        }
+        Ok(keys.len())
+    }
+
+    /// Loads each tagged session, applies `update` and writes it back with
+    /// its remaining time to live.
+    async fn update_by_tag<F>(&self, tag: &str, update: F) -> Result<usize, Self::Error>
+    where
+        F: Fn(&mut Session),
+    {
+        let mut updated = 0;
+        for key in self.find_by_tag(tag).await? {
+            let Some(mut session) = self.load(&key).await? else {
+                continue;
+            };
+            let ttl = self.ttl(&key).await?;
+            update(&mut session);
+            self.update(&session, ttl.max(Duration::from_secs(1)))
+                .await?;
+            updated += 1;
+        }
+        Ok(updated)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<S> TagStore for &S
+where
+    S: TagStore,
+{
+    async fn find_by_tag(&self, tag: &str) -> Result<Vec<SessionKey>, Self::Error> {
+        <S as TagStore>::find_by_tag(self, tag).await
+    }
+}
@@ -0,0 +1,323 @@
+//! tonic integration: add [`SessionLayer`] to a server and take the
+//! [`Session`] from the request in RPC handlers.
+//!
+//! ```ignore
+//! Server::builder()
+//!     .layer(SessionLayer::new(store))
+//!     .add_service(GreeterServer::new(greeter))
+//!     .serve(address)
+//!     .await?;
+//!
+//! async fn say_hello(&self, request: Request<HelloRequest>) -> Result<Response<HelloReply>, Status> {
+//!     let session = session(&request)?;
+//!     ...
+//! }
+//! ```
+//!
+//! The session key travels in the `session-key` metadata entry. When an RPC
+//! starts or moves a session, the response carries the new key under the
+//! same name; an empty value tells the client to drop its key.
+//!
+//! The session is written when the handler returns its response, before a
+//! streamed response body is sent, so changes made while a stream is still
+//! producing messages are lost. Make them before returning the stream, or
+//! write them with [`Session::flush_now`].
+
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use http::{HeaderName, HeaderValue, Request, Response};
+use tonic::{metadata::MetadataValue, Code, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    config::SessionConfig,
+    policy::SessionPolicy,
+    signing::Keyring,
+    web::{self, Backend, CookieAction, DetachedStore, RequestParts},
+    SessionError,
+};
+
+pub use crate::web::detached::SessionHandle as Session;
+
+pub const DEFAULT_METADATA_KEY: &str = "session-key";
+
+/// Tells gRPC clients with retries enabled how many milliseconds to wait,
+/// for errors that end on their own.
+const RETRY_PUSHBACK_KEY: &str = "grpc-retry-pushback-ms";
+
+struct Inner<Store> {
+    store: DetachedStore<Store>,
+    metadata_key: HeaderName,
+    policy: SessionPolicy,
+    keyring: Option<Keyring>,
+    strict: bool,
+}
+
+impl<Store> Inner<Store> {
+    /// Panics if strict security is on and `config`'s keys are too weak.
+    fn check_config(&self, config: &SessionConfig) {
+        if self.strict {
+            web::enforce_strict_keys(config);
+        }
+    }
+}
+
+/// Loads the session named by the request metadata into the request
+/// extensions and persists it once the RPC has completed.
+pub struct SessionLayer<Store> {
+    inner: Arc<Inner<Store>>,
+}
+
+impl<Store> Clone for SessionLayer<Store> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Store: Backend> SessionLayer<Store> {
+    pub fn new(store: Store) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                store: DetachedStore::new(store),
+                metadata_key: HeaderName::from_static(DEFAULT_METADATA_KEY),
+                policy: SessionPolicy::default(),
+                keyring: None,
+                strict: false,
+            }),
+        }
+    }
+
+    /// Panics if `metadata_key` is not a valid ASCII metadata key.
+    pub fn with_metadata_key(self, metadata_key: &str) -> Self {
+        let metadata_key = HeaderName::try_from(metadata_key)
+            .unwrap_or_else(|_| panic!("{metadata_key:?} is not a valid metadata key"));
+        self.map(|inner| inner.metadata_key = metadata_key)
+    }
+
+    /// Replaces the timeouts and other settings of the policy, if one was
+    /// given to [`with_policy`](Self::with_policy).
+    pub fn with_config(self, config: SessionConfig) -> Self {
+        self.map(|inner| {
+            inner.check_config(&config);
+            inner.policy.set_config(config);
+        })
+    }
+
+    /// Enforces `policy` on every request: its config, fingerprint rule,
+    /// size limit and required authentication level. A fingerprint rule
+    /// needs a keyring, see [`with_keyring`](Self::with_keyring).
+    pub fn with_policy(self, policy: SessionPolicy) -> Self {
+        self.map(|inner| {
+            inner.check_config(policy.config());
+            inner.policy = policy;
+        })
+    }
+
+    /// Signs session keys. Keys signed by a key the ring does not hold start
+    /// a new session; a forged signature fails the call with
+    /// [`SessionError::SessionTamperedError`].
+    pub fn with_keyring(self, keyring: Keyring) -> Self {
+        self.map(|inner| inner.keyring = Some(keyring))
+    }
+
+    /// Signs session keys with `keyring` and requires 256-bit keys; later
+    /// configs with weaker keys panic.
+    pub fn strict_security(self, keyring: Keyring) -> Self {
+        self.map(|inner| {
+            web::enforce_strict_keys(inner.policy.config());
+            inner.keyring = Some(keyring);
+            inner.strict = true;
+        })
+    }
+
+    fn map(self, update: impl FnOnce(&mut Inner<Store>)) -> Self {
+        let mut inner = Arc::try_unwrap(self.inner)
+            .unwrap_or_else(|_| panic!("SessionLayer is configured before it is shared"));
+        update(&mut inner);
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl<S, Store> Layer<S> for SessionLayer<Store> {
+    type Service = SessionService<S, Store>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        if let Err(error) = web::check_keyring(&self.inner.policy, self.inner.keyring.as_ref()) {
+            panic!("{error}");
+        }
+        SessionService {
+            service,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+pub struct SessionService<S, Store> {
+    service: S,
+    inner: Arc<Inner<Store>>,
+}
+
+impl<S: Clone, Store> Clone for SessionService<S, Store> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S, ReqBody, ResBody, Store> Service<Request<ReqBody>> for SessionService<S, Store>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+    Store: Backend + Send + Sync + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        let ready = self.service.clone();
+        let mut service = std::mem::replace(&mut self.service, ready);
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let session_key = request
+                .headers()
+                .get(&inner.metadata_key)
+                .and_then(|value| value.to_str().ok());
+            let parts =
+                RequestParts::new(&inner.policy, inner.keyring.as_ref(), session_key, |name| {
+                    request.headers().get(name)?.to_str().ok()
+                });
+            let session = match web::load_detached(&inner.store, parts, &inner.policy).await {
+                Ok(session) => session,
+                Err(error) => return Ok(Status::from(error).into_http()),
+            };
+            request.extensions_mut().insert(session.clone());
+
+            let mut response = service.call(request).await?;
+
+            let value = match session.finish().await {
+                Ok(CookieAction::Keep) => return Ok(response),
+                Ok(CookieAction::Set(session_key, _)) => {
+                    web::encode_cookie(inner.keyring.as_ref(), &session_key)
+                }
+                Ok(CookieAction::Carry(value, _)) => {
+                    web::encode_value(inner.keyring.as_ref(), &value)
+                }
+                Ok(CookieAction::Remove) => String::new(),
+                Err(error) => return Ok(Status::from(error).into_http()),
+            };
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                response
+                    .headers_mut()
+                    .insert(inner.metadata_key.clone(), value);
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// The session [`SessionLayer`] attached to `request`.
+pub fn session<T>(request: &tonic::Request<T>) -> Result<Session, Status> {
+    request
+        .extensions()
+        .get::<Session>()
+        .cloned()
+        .ok_or_else(|| Status::internal("SessionLayer is not installed"))
+}
+
+impl From<SessionError> for Status {
+    fn from(error: SessionError) -> Self {
+        let code = match error.http_status() {
+            400 => Code::InvalidArgument,
+            401 => Code::Unauthenticated,
+            403 => Code::PermissionDenied,
+            409 => Code::Aborted,
+            503 => Code::Unavailable,
+            _ => Code::Internal,
+        };
+        let mut status = Status::new(code, error.code().as_str());
+        if let Some(retry_after) = error.retry_after() {
+            let pushback = MetadataValue::from(retry_after.as_millis() as u64);
+            status.metadata_mut().insert(RETRY_PUSHBACK_KEY, pushback);
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{storage::Storage, MemorySessionStore, SessionKey, SessionStore};
+
+    async fn call(store: &MemorySessionStore, session_key: Option<&str>) -> Response<String> {
+        let visit = tower::service_fn(|request: Request<()>| async move {
+            let session = session(&tonic::Request::from_http(request)).unwrap();
+            let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+            session.insert("visits", &visits).unwrap();
+            Ok::<_, Infallible>(Response::new(visits.to_string()))
+        });
+        let mut request = Request::new(());
+        if let Some(session_key) = session_key {
+            let value = HeaderValue::from_str(session_key).unwrap();
+            request.headers_mut().insert("session-key", value);
+        }
+        let service = SessionLayer::new(store.clone()).layer(visit);
+        service.oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn unary_calls_round_trip_the_session_key() {
+        let store = MemorySessionStore::new();
+        let response = call(&store, None).await;
+        let session_key = response.headers()["session-key"]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = call(&store, Some(&session_key)).await;
+        assert_eq!(response.body(), "2");
+        let stored = store
+            .load(&SessionKey::parse(&session_key).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(stored.unwrap().get::<u32>("visits").unwrap(), Some(2));
+    }
+
+    #[test]
+    fn session_errors_map_to_grpc_codes() {
+        let status = Status::from(SessionError::SessionExpiredError);
+        assert_eq!(status.code(), Code::Unauthenticated);
+        assert_eq!(status.message(), "session.expired");
+        let status = Status::from(SessionError::StoreUnavailableError("timeout".to_string()));
+        assert_eq!(status.code(), Code::Unavailable);
+        assert!(status.metadata().get(RETRY_PUSHBACK_KEY).is_none());
+
+        let until = std::time::Instant::now() + std::time::Duration::from_secs(30);
+        let mode = crate::ReadOnlyMode { until: Some(until) };
+        let status = Status::from(SessionError::ReadOnlyError(mode));
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(status.message(), "session.read_only");
+        assert!(status.metadata().get(RETRY_PUSHBACK_KEY).is_some());
+    }
+}
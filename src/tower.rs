@@ -0,0 +1,269 @@
+//! Generic tower integration for any `http::Request`/`Response` stack, such
+//! as hyper, axum or tonic: [`SessionLayer`] loads the session into the
+//! request extensions as a [`Session`] and persists it after the inner
+//! service responds.
+//!
+//! ```ignore
+//! let service = ServiceBuilder::new()
+//!     .layer(SessionLayer::new(store))
+//!     .service(app);
+//!
+//! // In the inner service:
+//! let session = request.extensions().get::<Session>().cloned().unwrap();
+//! ```
+//!
+//! Session stores are not `Send`, so store calls run on worker threads of
+//! the layer's own and any tokio runtime can serve requests.
+
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use http::{header, HeaderValue, Request, Response, StatusCode};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    policy::{PolicyError, PolicyOverride},
+    web::{self, Backend, DetachedStore, RequestParts, Settings},
+    SessionError,
+};
+
+pub use crate::web::detached::SessionHandle as Session;
+
+struct Inner<Store> {
+    store: DetachedStore<Store>,
+    settings: Settings,
+}
+
+/// Loads the session named by the request cookie, creating one for
+/// first-time visitors, and persists it after the handler runs.
+pub struct SessionLayer<Store> {
+    inner: Arc<Inner<Store>>,
+}
+
+impl<Store> Clone for SessionLayer<Store> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Store: Backend> SessionLayer<Store> {
+    pub fn new(store: Store) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                store: DetachedStore::new(store),
+                settings: Settings::default(),
+            }),
+        }
+    }
+
+    web::settings_builders!();
+
+    /// A layer for routes that need `route`'s changes to the policy, e.g. a
+    /// checkout that requires MFA, sharing this layer's store and cookie.
+    /// Give those routes this layer instead of, not inside, this one, e.g.
+    /// through axum's `route_layer`.
+    pub fn with_override(&self, route: &PolicyOverride) -> Result<Self, PolicyError> {
+        let inner = Inner {
+            store: self.inner.store.clone(),
+            settings: self.inner.settings.with_override(route)?,
+        };
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    fn settings(self, update: impl FnOnce(&mut Settings)) -> Self {
+        let mut inner = Arc::try_unwrap(self.inner)
+            .unwrap_or_else(|_| panic!("SessionLayer is configured before it is shared"));
+        update(&mut inner.settings);
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl<S, Store> Layer<S> for SessionLayer<Store> {
+    type Service = SessionService<S, Store>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        self.inner.settings.check();
+        SessionService {
+            service,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+pub struct SessionService<S, Store> {
+    service: S,
+    inner: Arc<Inner<Store>>,
+}
+
+impl<S: Clone, Store> Clone for SessionService<S, Store> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S, ReqBody, ResBody, Store> Service<Request<ReqBody>> for SessionService<S, Store>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default + Send + 'static,
+    Store: Backend + Send + Sync + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        let ready = self.service.clone();
+        let mut service = std::mem::replace(&mut self.service, ready);
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let cookie = request
+                .headers()
+                .get_all(header::COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .find_map(|value| web::cookie_value(value, inner.settings.cookies.config().name()));
+            let timeout = inner.settings.policy.idle_timeout();
+            let parts = RequestParts::new(
+                &inner.settings.policy,
+                inner.settings.cookies.keyring(),
+                cookie,
+                |name| request.headers().get(name)?.to_str().ok(),
+            );
+            let session =
+                match web::load_detached(&inner.store, parts, &inner.settings.policy).await {
+                    Ok(session) => session,
+                    Err(error) => return Ok(error_response(error)),
+                };
+            request.extensions_mut().insert(session.clone());
+
+            let mut response = service.call(request).await?;
+
+            let action = match session.finish().await {
+                Ok(action) => action,
+                Err(error) => return Ok(error_response(error)),
+            };
+            if let Some(cookie) = web::set_cookie(&inner.settings.cookies, &action) {
+                if let Ok(value) = HeaderValue::from_str(&cookie) {
+                    response.headers_mut().append(header::SET_COOKIE, value);
+                }
+            }
+            if inner.settings.expires_in_header {
+                if let Some(expires_in) =
+                    web::expires_in_detached(&inner.store, &session, timeout).await
+                {
+                    let value = HeaderValue::from(expires_in.as_secs());
+                    response.headers_mut().insert(web::EXPIRES_IN_HEADER, value);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// An empty response with the error's status, and when to retry if the
+/// error ends on its own; store errors are not shown to clients.
+fn error_response<ResBody: Default>(error: SessionError) -> Response<ResBody> {
+    let mut response = Response::new(ResBody::default());
+    *response.status_mut() =
+        StatusCode::from_u16(error.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    if let Some(retry_after) = web::retry_after(&error).and_then(|value| value.parse().ok()) {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, retry_after);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::future::{ready, Ready};
+
+    use super::*;
+    use crate::{MaintenanceMode, MemorySessionStore, ReadOnlySessionStore, SessionPolicy};
+
+    /// Counts visits in the session.
+    #[derive(Clone)]
+    struct Visits;
+
+    impl Service<Request<()>> for Visits {
+        type Response = Response<()>;
+        type Error = std::convert::Infallible;
+        type Future = Ready<Result<Response<()>, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<()>) -> Self::Future {
+            let session = request.extensions().get::<Session>().unwrap();
+            let visits = session.get::<u32>("visits").unwrap().unwrap_or_default();
+            session.insert("visits", &(visits + 1)).unwrap();
+            ready(Ok(Response::new(())))
+        }
+    }
+
+    #[tokio::test]
+    async fn overrides_only_apply_to_the_routes_given_their_layer() {
+        let policy = SessionPolicy::builder()
+            .with_levels(&["password", "mfa"])
+            .build()
+            .unwrap();
+        let sessions = SessionLayer::new(MemorySessionStore::new()).with_policy(policy);
+        let checkout = sessions
+            .with_override(&PolicyOverride::new().require_level("mfa"))
+            .unwrap();
+
+        let response = sessions.layer(Visits).call(Request::new(())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key(header::SET_COOKIE));
+
+        let response = checkout.layer(Visits).call(Request::new(())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(!response.headers().contains_key(header::SET_COOKIE));
+    }
+
+    #[test]
+    #[should_panic(expected = "A fingerprint rule needs a keyring")]
+    fn fingerprint_rules_without_a_keyring_fail_when_layered() {
+        let policy = SessionPolicy::builder()
+            .with_fingerprint(crate::FingerprintRule::UserAgent)
+            .build()
+            .unwrap();
+        SessionLayer::new(MemorySessionStore::new())
+            .with_policy(policy)
+            .layer(Visits);
+    }
+
+    #[tokio::test]
+    async fn maintenance_windows_answer_with_when_to_retry() {
+        let mode = MaintenanceMode::new();
+        let store = ReadOnlySessionStore::new(MemorySessionStore::new(), mode.clone());
+        let sessions = SessionLayer::new(store);
+        mode.enable_for(Duration::from_secs(30));
+
+        let response = sessions.layer(Visits).call(Request::new(())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+    }
+}
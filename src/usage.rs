@@ -0,0 +1,178 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Mutex, OnceLock, PoisonError, RwLock},
+};
+
+use crate::Session;
+
+/// Distinct keys counted on their own; usage of any later key is counted
+/// under [`OTHER_KEYS`], as each key name is kept for the life of the
+/// process.
+const MAX_KEYS: usize = 4096;
+
+/// Where usage of keys beyond the first few thousand distinct ones goes.
+pub const OTHER_KEYS: &str = "*";
+
+/// The process-wide copy of `key`, so counting a read does not allocate.
+fn intern(key: &str) -> &'static str {
+    static KEYS: OnceLock<RwLock<HashSet<&'static str>>> = OnceLock::new();
+    let keys = KEYS.get_or_init(Default::default);
+    if let Some(key) = keys.read().unwrap_or_else(PoisonError::into_inner).get(key) {
+        return key;
+    }
+    let mut keys = keys.write().unwrap_or_else(PoisonError::into_inner);
+    if let Some(key) = keys.get(key) {
+        return key;
+    }
+    if keys.len() >= MAX_KEYS {
+        return OTHER_KEYS;
+    }
+    let key: &'static str = Box::leak(key.into());
+    keys.insert(key);
+    key
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyUsage {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct UsageCounters(Mutex<HashMap<&'static str, KeyUsage>>);
+
+impl UsageCounters {
+    pub(crate) fn read(&self, key: &str) {
+        self.update(key, |usage| usage.reads += 1);
+    }
+
+    pub(crate) fn write(&self, key: &str) {
+        self.update(key, |usage| usage.writes += 1);
+    }
+
+    fn update(&self, key: &str, update: impl FnOnce(&mut KeyUsage)) {
+        let mut counters = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        update(counters.entry(intern(key)).or_default());
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, KeyUsage> {
+        let counters = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        counters
+            .iter()
+            .map(|(key, usage)| (key.to_string(), *usage))
+            .collect()
+    }
+
+    fn take(&self) -> HashMap<&'static str, KeyUsage> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl Session {
+    /// Reads and writes per key through this `Session` value since it was
+    /// created or last recorded into [`UsageMetrics`].
+    pub fn usage_stats(&self) -> BTreeMap<String, KeyUsage> {
+        self.usage().snapshot()
+    }
+}
+
+/// Per-key usage aggregated across sessions, e.g. to find keys that are
+/// written but never read.
+#[derive(Default)]
+pub struct UsageMetrics {
+    keys: Mutex<HashMap<&'static str, KeyUsage>>,
+}
+
+impl UsageMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A process-wide instance for apps that do not need several. The web
+    /// integrations record every session they persist into it.
+    pub fn global() -> &'static UsageMetrics {
+        static GLOBAL: OnceLock<UsageMetrics> = OnceLock::new();
+        GLOBAL.get_or_init(UsageMetrics::new)
+    }
+
+    /// Adds the session's counters to the totals and resets them, so
+    /// recording the same session again does not double count.
+    pub fn record(&self, session: &Session) {
+        let usage = session.usage().take();
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        for (key, usage) in usage {
+            let total = keys.entry(key).or_default();
+            total.reads += usage.reads;
+            total.writes += usage.writes;
+        }
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, KeyUsage> {
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        keys.iter()
+            .map(|(key, usage)| (key.to_string(), *usage))
+            .collect()
+    }
+
+    pub fn reset(&self) {
+        self.keys.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+
+    #[test]
+    fn usage_is_counted_per_key_and_aggregated_once() {
+        let mut session = Session::default();
+        session.insert("cart", &vec!["socks"]).unwrap();
+        session.get::<Vec<String>>("cart").unwrap();
+        session.get::<Vec<String>>("cart").unwrap();
+        session.remove::<String>("coupon").unwrap();
+
+        let stats = session.usage_stats();
+        assert_eq!(
+            stats["cart"],
+            KeyUsage {
+                reads: 2,
+                writes: 1
+            }
+        );
+        assert_eq!(
+            stats["coupon"],
+            KeyUsage {
+                reads: 0,
+                writes: 1
+            }
+        );
+
+        let metrics = UsageMetrics::new();
+        metrics.record(&session);
+        metrics.record(&session);
+        assert_eq!(
+            metrics.snapshot()["cart"],
+            KeyUsage {
+                reads: 2,
+                writes: 1
+            }
+        );
+        assert!(session.usage_stats().is_empty());
+    }
+
+    #[test]
+    fn transactional_writes_count_once_they_are_committed() {
+        let mut session = Session::default();
+        session.begin().unwrap();
+        session.insert("cart", &vec!["socks"]).unwrap();
+        session.commit().unwrap();
+        session.begin().unwrap();
+        session.insert("coupon", &"SAVE10").unwrap();
+        session.abort().unwrap();
+
+        let stats = session.usage_stats();
+        assert_eq!(stats["cart"].writes, 1);
+        assert!(!stats.contains_key("coupon"));
+    }
+}
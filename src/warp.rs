@@ -0,0 +1,259 @@
+//! warp integration: [`with_session`] yields the request's [`Session`] and
+//! [`Sessions::commit`] writes it back while building the reply.
+//!
+//! ```ignore
+//! let sessions = Sessions::new(store);
+//! let index = with_session(sessions.clone()).and_then(move |session: Session| {
+//!     let sessions = sessions.clone();
+//!     async move {
+//!         let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+//!         session.insert("visits", &visits).unwrap();
+//!         sessions.commit(session, format!("{visits} visits")).await
+//!     }
+//! });
+//! ```
+//!
+//! Session stores are not `Send`, so store calls run on worker threads of
+//! the filter's own and any tokio runtime can serve requests.
+//! Session failures reject with a [`SessionError`]; add
+//! `.recover(lushus_session::warp::recover)` to answer them with their
+//! status, code and `Retry-After`.
+
+use std::sync::Arc;
+
+use warp::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    reject::{self, Reject},
+    reply::Response,
+    Filter, Rejection, Reply,
+};
+
+use crate::{
+    policy::{PolicyError, PolicyOverride},
+    web::{self, Backend, DetachedStore, RequestParts, Settings},
+    SessionError,
+};
+
+pub use crate::web::detached::SessionHandle as Session;
+
+impl Reject for SessionError {}
+
+struct Inner<Store> {
+    store: DetachedStore<Store>,
+    settings: Settings,
+}
+
+/// Shared configuration for [`with_session`] and [`Sessions::commit`].
+pub struct Sessions<Store> {
+    inner: Arc<Inner<Store>>,
+}
+
+impl<Store> Clone for Sessions<Store> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Store> Sessions<Store>
+where
+    Store: Backend + Send + Sync + 'static,
+{
+    pub fn new(store: Store) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                store: DetachedStore::new(store),
+                settings: Settings::default(),
+            }),
+        }
+    }
+
+    web::settings_builders!();
+
+    /// Sessions for routes that need `route`'s changes to the policy, e.g.
+    /// a checkout that requires MFA, sharing these sessions' store and
+    /// cookie. Give those routes' [`with_session`] and `commit` these
+    /// sessions instead.
+    pub fn with_override(&self, route: &PolicyOverride) -> Result<Self, PolicyError> {
+        let inner = Inner {
+            store: self.inner.store.clone(),
+            settings: self.inner.settings.with_override(route)?,
+        };
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Persists `session` and turns `reply` into a response carrying the
+    /// session cookie.
+    pub async fn commit(&self, session: Session, reply: impl Reply) -> Result<Response, Rejection> {
+        let inner = &self.inner;
+        let action = session.finish().await.map_err(reject::custom)?;
+        let mut response = reply.into_response();
+        let cookie = web::set_cookie(&inner.settings.cookies, &action);
+        if let Some(value) = cookie.and_then(|cookie| HeaderValue::from_str(&cookie).ok()) {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+        if inner.settings.expires_in_header {
+            let timeout = inner.settings.policy.idle_timeout();
+            if let Some(expires_in) =
+                web::expires_in_detached(&inner.store, &session, timeout).await
+            {
+                let value = HeaderValue::from(expires_in.as_secs());
+                response.headers_mut().insert(web::EXPIRES_IN_HEADER, value);
+            }
+        }
+        Ok(response)
+    }
+
+    async fn load(&self, headers: HeaderMap) -> Result<Session, Rejection> {
+        let inner = &self.inner;
+        let cookie = headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|value| web::cookie_value(value, inner.settings.cookies.config().name()));
+        let parts = RequestParts::new(
+            &inner.settings.policy,
+            inner.settings.cookies.keyring(),
+            cookie,
+            |name| headers.get(name)?.to_str().ok(),
+        );
+        web::load_detached(&inner.store, parts, &inner.settings.policy)
+            .await
+            .map_err(reject::custom)
+    }
+
+    fn settings(self, update: impl FnOnce(&mut Settings)) -> Self {
+        let mut inner = Arc::try_unwrap(self.inner)
+            .unwrap_or_else(|_| panic!("Sessions are configured before they are shared"));
+        update(&mut inner.settings);
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+/// Extracts the session named by the request cookie, or a new one for
+/// first-time visitors. Store failures reject with a [`SessionError`].
+pub fn with_session<Store>(
+    sessions: Sessions<Store>,
+) -> impl Filter<Extract = (Session,), Error = Rejection> + Clone
+where
+    Store: Backend + Send + Sync + 'static,
+{
+    sessions.inner.settings.check();
+    warp::header::headers_cloned().and_then(move |headers: HeaderMap| {
+        let sessions = sessions.clone();
+        async move { sessions.load(headers).await }
+    })
+}
+
+/// A `POST` filter for SPAs to keep the session alive: it touches the
+/// session, rotates the CSRF token when called with `?rotate_csrf=true`, and
+/// answers with the TTL the session now has as `{"expires_in": seconds}`.
+pub fn session_refresh_handler<Store>(
+    sessions: Sessions<Store>,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+where
+    Store: Backend + Send + Sync + 'static,
+{
+    let query = warp::query::raw().or(warp::any().map(String::new)).unify();
+    warp::post()
+        .and(with_session(sessions.clone()))
+        .and(query)
+        .and_then(move |session: Session, query: String| {
+            let sessions = sessions.clone();
+            async move {
+                let body = session
+                    .refresh(Some(&query))
+                    .map_err(|error| reject::custom(SessionError::from(error)))?;
+                let reply =
+                    warp::reply::with_header(body, header::CONTENT_TYPE, "application/json");
+                sessions.commit(session, reply).await
+            }
+        })
+}
+
+/// Answers a [`SessionError`] rejection with the error's status and code,
+/// and when to retry if the error ends on its own; other rejections are
+/// passed on.
+pub async fn recover(rejection: Rejection) -> Result<Response, Rejection> {
+    let Some(error) = rejection.find::<SessionError>() else {
+        return Err(rejection);
+    };
+    let status =
+        StatusCode::from_u16(error.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = warp::reply::with_status(error.code().as_str(), status).into_response();
+    if let Some(value) = web::retry_after(error).and_then(|value| value.parse().ok()) {
+        response.headers_mut().insert(header::RETRY_AFTER, value);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        session_store::{testing::FaultyStore, StoreOperation},
+        MemorySessionStore, PolicyOverride, SessionKey, SessionPolicy,
+    };
+
+    fn visit<Store>(
+        sessions: Sessions<Store>,
+    ) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone
+    where
+        Store: Backend + Send + Sync + 'static,
+    {
+        with_session(sessions.clone()).and_then(move |session: Session| {
+            let sessions = sessions.clone();
+            async move {
+                let visits = session.get::<u32>("visits").unwrap().unwrap_or_default() + 1;
+                session.insert("visits", &visits).unwrap();
+                sessions.commit(session, visits.to_string()).await
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn commit_sets_the_cookie_of_the_extracted_session() {
+        let filter = visit(Sessions::new(MemorySessionStore::new()));
+        let response = warp::test::request().path("/").reply(&filter).await;
+        assert!(response.headers().contains_key(header::SET_COOKIE));
+        assert_eq!(response.body(), "1");
+    }
+
+    #[tokio::test]
+    async fn store_failures_are_recovered_into_their_status() {
+        let store =
+            FaultyStore::new().with_failures(|operation, _| operation == StoreOperation::Load);
+        let filter = visit(Sessions::new(store)).recover(recover);
+        let cookie = format!("id={}", SessionKey::generate().as_ref());
+        let response = warp::test::request()
+            .path("/")
+            .header(header::COOKIE, &cookie)
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.body(), "session.store_unavailable");
+    }
+
+    #[tokio::test]
+    async fn overrides_only_apply_to_the_routes_given_their_sessions() {
+        let policy = SessionPolicy::builder()
+            .with_levels(&["password", "mfa"])
+            .build()
+            .unwrap();
+        let sessions = Sessions::new(MemorySessionStore::new()).with_policy(policy);
+        let checkout = sessions
+            .with_override(&PolicyOverride::new().require_level("mfa"))
+            .unwrap();
+
+        let response = warp::test::request().reply(&visit(sessions)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let filter = visit(checkout).recover(recover);
+        let response = warp::test::request().reply(&filter).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,1217 @@
+//! Framework-agnostic request lifecycle shared by the web integrations.
+//!
+//! Integrations load the session when a request arrives and write it to the
+//! store once, when the response is built, however many handlers and
+//! middlewares changed it. Handlers that need the write to be durable
+//! earlier call `flush_now`; the response-time write then only persists
+//! what changed since.
+
+mod client;
+#[cfg(any(
+    feature = "poem",
+    feature = "rocket",
+    feature = "tower",
+    feature = "warp"
+))]
+pub(crate) mod detached;
+
+use std::{error::Error, time::Duration};
+
+use crate::{
+    config::SessionConfig,
+    cookie_config::CookieConfig,
+    policy::{FingerprintRule, PolicyError, PolicyOverride, SessionPolicy},
+    signing::Keyring,
+    storage::{Storage, StorageError},
+    Deadline, DeadlineSessionStore, ReadOnlyMode, Session, SessionDuration, SessionError,
+    SessionKey, SessionState, SessionStatus, SessionStore, UsageMetrics,
+};
+
+#[cfg(any(
+    feature = "poem",
+    feature = "rocket",
+    feature = "tower",
+    feature = "warp"
+))]
+pub(crate) use detached::*;
+
+const STRICT_KEY_BITS: usize = 256;
+
+/// The query parameter asking a refresh endpoint to rotate the CSRF token.
+const ROTATE_CSRF_PARAM: &str = "rotate_csrf";
+
+/// Where a session bound by the policy's fingerprint rule keeps the
+/// fingerprint of the client it was written for.
+const FINGERPRINT_KEY: &str = "__fingerprint";
+
+/// Whole seconds until the session expires, so SPAs can warn before an
+/// inactivity logout without polling a separate endpoint.
+pub(crate) const EXPIRES_IN_HEADER: &str = "x-session-expires-in";
+
+/// Panics unless the keys of `config`'s [`KeyFormat`](crate::KeyFormat)
+/// carry at least 256 random bits.
+pub(crate) fn enforce_strict_keys(config: &SessionConfig) {
+    let format = config.key_format();
+    assert!(
+        format.entropy_bits() >= STRICT_KEY_BITS,
+        "strict security requires {STRICT_KEY_BITS}-bit session keys, not {}",
+        format.entropy_bits()
+    );
+}
+
+/// The cookie value for `session_key`, signed if a keyring is configured.
+pub(crate) fn encode_cookie(keyring: Option<&Keyring>, session_key: &SessionKey) -> String {
+    encode_value(keyring, session_key.as_ref())
+}
+
+/// `value`, signed if a keyring is configured.
+pub(crate) fn encode_value(keyring: Option<&Keyring>, value: &str) -> String {
+    match keyring {
+        Some(keyring) => keyring.sign(value),
+        None => value.to_string(),
+    }
+}
+
+/// The session key carried by a cookie, dropping cookies whose signature
+/// does not verify.
+pub(crate) fn decode_cookie(keyring: Option<&Keyring>, cookie: &str) -> Option<String> {
+    match keyring {
+        Some(keyring) => keyring.verify(cookie).map(str::to_string),
+        None => Some(cookie.to_string()),
+    }
+}
+
+/// Whether a cookie names one of `keyring`'s keys but fails its signature.
+fn is_forged(keyring: Option<&Keyring>, cookie: &str) -> bool {
+    keyring.is_some_and(|keyring| keyring.is_forged(cookie))
+}
+
+/// Fails if `policy` binds sessions to a fingerprint but there is no
+/// keyring to key it with.
+pub(crate) fn check_keyring(
+    policy: &SessionPolicy,
+    keyring: Option<&Keyring>,
+) -> Result<(), PolicyError> {
+    match (policy.fingerprint_rule(), keyring) {
+        (FingerprintRule::Off, _) | (_, Some(_)) => Ok(()),
+        (_, None) => Err(PolicyError::FingerprintKeyringError),
+    }
+}
+
+/// The session cookie an integration emits and the keyring signing it.
+#[derive(Clone, Default)]
+pub(crate) struct CookieSettings {
+    config: CookieConfig,
+    keyring: Option<Keyring>,
+    strict: bool,
+}
+
+impl CookieSettings {
+    pub(crate) fn config(&self) -> &CookieConfig {
+        &self.config
+    }
+
+    pub(crate) fn keyring(&self) -> Option<&Keyring> {
+        self.keyring.as_ref()
+    }
+
+    pub(crate) fn set_config(&mut self, config: CookieConfig) {
+        if self.strict {
+            config.enforce_strict();
+        }
+        self.config = config;
+    }
+
+    pub(crate) fn set_name(&mut self, name: &str) {
+        self.set_config(self.config.clone().with_name(name));
+    }
+
+    pub(crate) fn set_keyring(&mut self, keyring: Keyring) {
+        self.keyring = Some(keyring);
+    }
+
+    /// Switches to a `__Host-` cookie signed with `keyring` and requires
+    /// 256-bit keys of `config`; later settings that weaken either panic.
+    pub(crate) fn make_strict(&mut self, keyring: Keyring, config: &SessionConfig) {
+        enforce_strict_keys(config);
+        self.config = CookieConfig::strict();
+        self.keyring = Some(keyring);
+        self.strict = true;
+    }
+
+    /// Panics if the settings are strict and `config`'s keys are too weak
+    /// for them.
+    pub(crate) fn check_config(&self, config: &SessionConfig) {
+        if self.strict {
+            enforce_strict_keys(config);
+        }
+    }
+
+    pub(crate) fn encode(&self, session_key: &SessionKey) -> String {
+        encode_cookie(self.keyring(), session_key)
+    }
+}
+
+/// What an integration's builder configures: the session cookie, the
+/// policy and whether responses carry [`EXPIRES_IN_HEADER`].
+#[derive(Clone, Default)]
+pub(crate) struct Settings {
+    pub(crate) cookies: CookieSettings,
+    pub(crate) policy: SessionPolicy,
+    pub(crate) expires_in_header: bool,
+}
+
+impl Settings {
+    pub(crate) fn set_config(&mut self, config: SessionConfig) {
+        self.cookies.check_config(&config);
+        self.policy.set_config(config);
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: SessionPolicy) {
+        self.cookies.check_config(policy.config());
+        self.policy = policy;
+    }
+
+    pub(crate) fn make_strict(&mut self, keyring: Keyring) {
+        self.cookies.make_strict(keyring, self.policy.config());
+    }
+
+    /// These settings with `route`'s changes to the policy.
+    pub(crate) fn with_override(&self, route: &PolicyOverride) -> Result<Self, PolicyError> {
+        Ok(Self {
+            policy: self.policy.with_override(route)?,
+            ..self.clone()
+        })
+    }
+
+    /// Panics unless the settings are complete, for integrations to call
+    /// once they are built rather than fail every request.
+    pub(crate) fn check(&self) {
+        if let Err(error) = check_keyring(&self.policy, self.cookies.keyring()) {
+            panic!("{error}");
+        }
+    }
+}
+
+/// The builder methods every web integration offers, for an `impl` block
+/// that also defines `fn settings(self, update: impl FnOnce(&mut Settings))
+/// -> Self`.
+macro_rules! settings_builders {
+    () => {
+        pub fn with_cookie_name(self, cookie_name: &str) -> Self {
+            self.settings(|settings| settings.cookies.set_name(cookie_name))
+        }
+
+        pub fn with_cookie_config(self, cookie: $crate::CookieConfig) -> Self {
+            self.settings(|settings| settings.cookies.set_config(cookie))
+        }
+
+        /// Replaces the timeouts and other settings of the policy, if one was
+        /// given to [`with_policy`](Self::with_policy).
+        pub fn with_config(self, config: $crate::SessionConfig) -> Self {
+            self.settings(|settings| settings.set_config(config))
+        }
+
+        /// Enforces `policy` on every request: its config, fingerprint rule,
+        /// size limit and required authentication level. A fingerprint rule
+        /// needs a keyring, see [`with_keyring`](Self::with_keyring);
+        /// installing the middleware without one panics.
+        pub fn with_policy(self, policy: $crate::SessionPolicy) -> Self {
+            self.settings(|settings| settings.set_policy(policy))
+        }
+
+        /// Adds an `X-Session-Expires-In` header with the seconds the session
+        /// has left, at the cost of a TTL lookup on requests that leave it
+        /// untouched.
+        pub fn with_expires_in_header(self) -> Self {
+            self.settings(|settings| settings.expires_in_header = true)
+        }
+
+        /// Signs session cookies. Cookies signed by a key the ring does not
+        /// hold start a new session; a forged signature fails the request
+        /// with
+        /// [`SessionTamperedError`](crate::SessionError::SessionTamperedError).
+        pub fn with_keyring(self, keyring: $crate::Keyring) -> Self {
+            self.settings(|settings| settings.cookies.set_keyring(keyring))
+        }
+
+        /// Presets [`CookieConfig::strict`](crate::CookieConfig::strict),
+        /// signing with `keyring` and 256-bit session keys. Later settings
+        /// that weaken any of these panic.
+        pub fn strict_security(self, keyring: $crate::Keyring) -> Self {
+            self.settings(|settings| settings.make_strict(keyring))
+        }
+    };
+}
+
+pub(crate) use settings_builders;
+
+/// What the integration must do to the session cookie after persisting.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CookieAction {
+    Keep,
+    /// Set the cookie to the key, living as long as the session's tier.
+    Set(SessionKey, SessionDuration),
+    /// Set the cookie to a value carrying the whole session, for
+    /// client-side stores.
+    Carry(String, SessionDuration),
+    Remove,
+}
+
+/// What the flushes so far in a request did: whether the store holds the
+/// session, and the cookie change the response must carry. Also carries the
+/// request's fingerprint, which every write binds the session to.
+#[derive(Clone)]
+pub struct Progress {
+    loaded: bool,
+    cookie: CookieAction,
+    fingerprint: Option<String>,
+}
+
+impl Progress {
+    pub(crate) fn new(loaded: bool) -> Self {
+        Self {
+            loaded,
+            cookie: CookieAction::Keep,
+            fingerprint: None,
+        }
+    }
+
+    pub(crate) fn cookie(&self) -> &CookieAction {
+        &self.cookie
+    }
+
+    fn record(&mut self, action: CookieAction) {
+        match action {
+            CookieAction::Keep => {}
+            CookieAction::Set(..) | CookieAction::Carry(..) => self.loaded = true,
+            CookieAction::Remove => self.loaded = false,
+        }
+        if action != CookieAction::Keep {
+            self.cookie = action;
+        }
+    }
+}
+
+/// The `Set-Cookie` header value for `action`, if the cookie must change.
+/// Browser sessions get a cookie the browser drops when it closes, unless
+/// the cookie config sets a max age.
+pub(crate) fn set_cookie(cookies: &CookieSettings, action: &CookieAction) -> Option<String> {
+    let cookie = cookies.config();
+    match action {
+        CookieAction::Keep => None,
+        CookieAction::Set(session_key, duration) => {
+            Some(cookie.header(&cookies.encode(session_key), max_age(cookie, duration)))
+        }
+        CookieAction::Carry(value, duration) => {
+            let value = encode_value(cookies.keyring(), value);
+            Some(cookie.header(&value, max_age(cookie, duration)))
+        }
+        CookieAction::Remove => Some(cookie.header("", Some(Duration::ZERO))),
+    }
+}
+
+fn max_age(cookie: &CookieConfig, duration: &SessionDuration) -> Option<Duration> {
+    match duration {
+        SessionDuration::Browser => cookie.max_age(),
+        SessionDuration::Persistent(duration) => Some(*duration),
+    }
+}
+
+/// How long the session has left once the request is done: its tier's TTL
+/// if the request wrote it, nothing if it was removed, otherwise
+/// what the store reports. `None` when no session is stored.
+pub(crate) async fn expires_in<Store: Backend>(
+    store: &Store,
+    session_key: &SessionKey,
+    progress: &Progress,
+    timeout: Duration,
+) -> Result<Option<Duration>, SessionError> {
+    match progress.cookie() {
+        CookieAction::Set(_, duration) | CookieAction::Carry(_, duration) => {
+            Ok(Some(duration.ttl(timeout)))
+        }
+        CookieAction::Remove => Ok(Some(Duration::ZERO)),
+        CookieAction::Keep if progress.loaded => store.remaining(session_key).await,
+        CookieAction::Keep => Ok(None),
+    }
+}
+
+/// Touches the session for a refresh endpoint, rotating the CSRF token if
+/// the query string asks for it, and returns the JSON response body with
+/// the TTL the response-time write will give the session.
+pub(crate) fn refresh(
+    session: &mut Session,
+    query: Option<&str>,
+    timeout: Duration,
+) -> Result<String, StorageError> {
+    session.touch();
+    let expires_in = session.duration().ttl(timeout);
+    let mut body = serde_json::json!({ "expires_in": expires_in.as_secs() });
+    let rotate = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .any(|pair| match pair.split_once('=') {
+            Some((key, value)) => key == ROTATE_CSRF_PARAM && matches!(value, "1" | "true"),
+            None => pair == ROTATE_CSRF_PARAM,
+        });
+    if rotate {
+        body["csrf_token"] = session.rotate_csrf_token()?.into();
+    }
+    Ok(body.to_string())
+}
+
+/// The request attributes the session lifecycle reads, gathered by each
+/// integration from its own request type.
+#[derive(Clone, Debug, Default)]
+pub struct RequestParts {
+    /// The session key the cookie carries once its signature is checked.
+    pub(crate) cookie: Option<String>,
+    /// Whether the cookie's signature was forged, failing the request.
+    pub(crate) tampered: bool,
+    pub(crate) device: Option<String>,
+    pub(crate) accept_language: Option<String>,
+    /// The client's fingerprint under the policy's rule, if it has one.
+    pub(crate) fingerprint: Option<String>,
+}
+
+impl RequestParts {
+    /// Reads the session `cookie`, checking its signature against
+    /// `keyring`, and the headers `policy` cares about through `header`,
+    /// which looks one up by its lowercase name. Fingerprints are keyed by
+    /// the same keyring, and take the client IP
+    /// from the first `X-Forwarded-For` entry or `X-Real-IP`, as set by the
+    /// proxy in front of the app.
+    /// Without a keyring there is no fingerprint, which integrations rule
+    /// out when they are built, see [`check_keyring`].
+    pub(crate) fn new<'a>(
+        policy: &SessionPolicy,
+        keyring: Option<&Keyring>,
+        cookie: Option<&str>,
+        header: impl Fn(&str) -> Option<&'a str>,
+    ) -> Self {
+        let tampered = cookie.is_some_and(|cookie| is_forged(keyring, cookie));
+        let cookie = cookie.and_then(|cookie| decode_cookie(keyring, cookie));
+        let config = policy.config();
+        let device = config.device_header().and_then(&header);
+        let accept_language = match config.locales() {
+            [] => None,
+            _ => header("accept-language"),
+        };
+        let fingerprint = match (policy.fingerprint_rule(), keyring) {
+            (FingerprintRule::Off, _) | (_, None) => None,
+            (_, Some(keyring)) => {
+                let user_agent = header("user-agent").unwrap_or_default();
+                let ip = header("x-forwarded-for")
+                    .and_then(|forwarded| forwarded.split(',').next())
+                    .or_else(|| header("x-real-ip"))
+                    .unwrap_or_default()
+                    .trim();
+                policy.fingerprint(keyring, user_agent, ip)
+            }
+        };
+        Self {
+            cookie,
+            tampered,
+            device: device.map(str::to_string),
+            accept_language: accept_language.map(str::to_string),
+            fingerprint,
+        }
+    }
+}
+
+/// The error a request fails with when the store does. A [`ReadOnlyMode`]
+/// anywhere in the error's source chain is kept, so the response can tell
+/// the client when to retry.
+pub(crate) fn unavailable(error: impl Error + 'static) -> SessionError {
+    let read_only = std::iter::successors(Some(&error as &(dyn Error + 'static)), |&error| {
+        error.source()
+    })
+    .find_map(|error| error.downcast_ref::<ReadOnlyMode>());
+    match read_only {
+        Some(mode) => SessionError::ReadOnlyError(*mode),
+        None => SessionError::StoreUnavailableError(error.to_string()),
+    }
+}
+
+/// The `Retry-After` value of a response failing with `error`, for errors
+/// that end on their own: whole seconds, rounded up so clients do not retry
+/// too early.
+pub(crate) fn retry_after(error: &SessionError) -> Option<String> {
+    let wait = error.retry_after()?;
+    let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    Some(secs.to_string())
+}
+
+/// `session_key` scoped to `device`, if the request named a valid one.
+fn scoped(session_key: SessionKey, device: Option<&str>) -> SessionKey {
+    device
+        .and_then(|device| session_key.for_device(device))
+        .unwrap_or(session_key)
+}
+
+/// Whether `session` may serve a request with `fingerprint`: sessions
+/// written before the rule applied, or without one, are not bound yet.
+fn bound_to(session: &Session, fingerprint: Option<&str>) -> bool {
+    let stored = session.get::<String>(FINGERPRINT_KEY).ok().flatten();
+    match (fingerprint, stored) {
+        (Some(fingerprint), Some(stored)) => fingerprint == stored,
+        _ => true,
+    }
+}
+
+/// Loads the session named by the cookie, scoped to the request's device if
+/// the config names a device header, or starts a new one under a fresh key.
+/// The config's defaults are installed either way, and sessions without a
+/// locale get one negotiated from `Accept-Language`. The progress tells
+/// whether the session came from the store.
+///
+/// A cookie with a forged signature fails the request with
+/// [`SessionError::SessionTamperedError`]; one from a retired key or
+/// another environment starts a new session.
+///
+/// With a [store deadline](SessionConfig::with_store_deadline), a load that
+/// runs out of time fails, or starts a new session if the config fails
+/// open. A session older than the absolute timeout is destroyed and the
+/// request fails with [`SessionError::SessionExpiredError`]. A session bound
+/// to another client's fingerprint is left in the store for its owner and
+/// the request starts a new one. Sessions below the policy's required
+/// authentication level fail with [`SessionError::AuthLevelError`].
+pub(crate) async fn load<Store: Backend>(
+    store: &Store,
+    request: &RequestParts,
+    policy: &SessionPolicy,
+) -> Result<(Session, Progress), SessionError> {
+    if request.tampered {
+        return Err(SessionError::SessionTamperedError);
+    }
+    let config = policy.config();
+    let device = request.device.as_deref();
+    let format = config.key_format();
+    let loaded = match request.cookie.as_deref() {
+        Some(cookie) => store.fetch(cookie, request, config).await?,
+        None => None,
+    };
+    if let (Some(session), Some(absolute_timeout)) = (&loaded, config.absolute_timeout()) {
+        if session.outlived(absolute_timeout) {
+            store.discard(session).await?;
+            return Err(SessionError::SessionExpiredError);
+        }
+    }
+    let loaded = loaded.filter(|session| bound_to(session, request.fingerprint.as_deref()));
+    let found = loaded.is_some();
+    let mut session = loaded.unwrap_or_else(|| {
+        let session_key = scoped(SessionKey::generate_in(format), device);
+        Session::new(session_key, SessionState::default())
+    });
+    session.set_key_format(format);
+    session.set_defaults(config.defaults().clone());
+    if let Some(accept_language) = &request.accept_language {
+        let supported = config
+            .locales()
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        // Storing a negotiated locale cannot fail; a stored locale that no
+        // longer decodes is left for the app to replace.
+        let _ = session.negotiate_locale(accept_language, &supported);
+    }
+    if !policy.permits(&session) {
+        return Err(SessionError::AuthLevelError);
+    }
+    let mut progress = Progress::new(found);
+    progress.fingerprint = request.fingerprint.clone();
+    Ok((session, progress))
+}
+
+/// Writes what changed since the last flush according to the session's
+/// [`SessionStatus`], with the TTL of its [`SessionDuration`]. Written
+/// sessions are bound to the request's fingerprint, and fail with
+/// [`SessionError::SessionTooLargeError`] instead if they outgrow the
+/// policy's size limit.
+pub(crate) async fn flush<Store: Backend>(
+    store: &Store,
+    session: &mut Session,
+    progress: &mut Progress,
+    policy: &SessionPolicy,
+) -> Result<(), SessionError> {
+    session.settle_defaults();
+    if matches!(
+        session.status(),
+        SessionStatus::Changed | SessionStatus::Renewed
+    ) {
+        session.stamp_created();
+        if let Some(fingerprint) = &progress.fingerprint {
+            if session.get::<String>(FINGERPRINT_KEY)?.as_ref() != Some(fingerprint) {
+                session.insert(FINGERPRINT_KEY, fingerprint)?;
+            }
+        }
+        if let Err(PolicyError::SessionTooLargeError { size, limit }) = policy.check_size(session) {
+            return Err(SessionError::SessionTooLargeError { size, limit });
+        }
+    }
+    let timeout = policy.idle_timeout();
+    let action = store.write(session, progress.loaded, timeout).await?;
+    UsageMetrics::global().record(session);
+    session.mark_persisted();
+    progress.record(action);
+    Ok(())
+}
+
+/// Where an integration keeps its sessions. A [`SessionStore`] holds them
+/// under the key the cookie carries; a client-side store such as
+/// [`JwtSessionStore`](crate::JwtSessionStore) seals the whole session into
+/// the cookie value instead.
+#[async_trait::async_trait(?Send)]
+pub trait Backend {
+    /// The session `cookie` names or carries, if there still is one.
+    async fn fetch(
+        &self,
+        cookie: &str,
+        request: &RequestParts,
+        config: &SessionConfig,
+    ) -> Result<Option<Session>, SessionError>;
+
+    /// Forgets a session that outlived its absolute timeout.
+    async fn discard(&self, session: &Session) -> Result<(), SessionError>;
+
+    /// Writes `session` as its status calls for, returning the cookie
+    /// change that follows.
+    async fn write(
+        &self,
+        session: &Session,
+        loaded: bool,
+        timeout: Duration,
+    ) -> Result<CookieAction, SessionError>;
+
+    /// How long the session under `session_key` has left, if the backend
+    /// can tell.
+    async fn remaining(&self, session_key: &SessionKey) -> Result<Option<Duration>, SessionError>;
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Store> Backend for Store
+where
+    Store: SessionStore,
+    Store::Error: Error + 'static,
+{
+    async fn fetch(
+        &self,
+        cookie: &str,
+        request: &RequestParts,
+        config: &SessionConfig,
+    ) -> Result<Option<Session>, SessionError> {
+        let Some(session_key) = SessionKey::parse_in(cookie, config.key_format()) else {
+            return Ok(None);
+        };
+        let session_key = scoped(session_key, request.device.as_deref());
+        match config.store_deadline() {
+            Some(budget) => {
+                let mut store = DeadlineSessionStore::new(self, Deadline::after(budget));
+                if config.fail_open() {
+                    store = store.fail_open();
+                }
+                store.load(&session_key).await.map_err(unavailable)
+            }
+            None => self.load(&session_key).await.map_err(unavailable),
+        }
+    }
+
+    async fn discard(&self, session: &Session) -> Result<(), SessionError> {
+        self.destroy(session.id()).await.map_err(unavailable)
+    }
+
+    async fn write(
+        &self,
+        session: &Session,
+        loaded: bool,
+        timeout: Duration,
+    ) -> Result<CookieAction, SessionError> {
+        persist(self, session, loaded, timeout)
+            .await
+            .map_err(unavailable)
+    }
+
+    async fn remaining(&self, session_key: &SessionKey) -> Result<Option<Duration>, SessionError> {
+        self.ttl(session_key).await.map(Some).map_err(unavailable)
+    }
+}
+
+async fn persist<Store: SessionStore>(
+    store: &Store,
+    session: &Session,
+    loaded: bool,
+    timeout: Duration,
+) -> Result<CookieAction, Store::Error> {
+    let duration = session.duration();
+    let timeout = duration.ttl(timeout);
+    let action = match session.status() {
+        SessionStatus::Unchanged => return Ok(CookieAction::Keep),
+        SessionStatus::Changed => {
+            if loaded {
+                store.update(session, timeout).await?;
+            } else {
+                store.save(session, timeout).await?;
+            }
+            CookieAction::Set(session.id().clone(), duration)
+        }
+        SessionStatus::Renewed => {
+            // The new key is written first, so a failed save keeps the old
+            // session and a failed destroy leaves a row that expires.
+            store.save(session, timeout).await?;
+            if let Some(previous) = session.take_regenerated_from().filter(|_| loaded) {
+                store.destroy(&previous).await?;
+            }
+            CookieAction::Set(session.id().clone(), duration)
+        }
+        SessionStatus::Purged => {
+            if loaded {
+                store.destroy(session.id()).await?;
+            }
+            return Ok(CookieAction::Remove);
+        }
+    };
+    session.run_post_commit();
+    Ok(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PolicyOverride;
+
+    fn policy(config: SessionConfig) -> SessionPolicy {
+        let mut policy = SessionPolicy::default();
+        policy.set_config(config);
+        policy
+    }
+
+    #[test]
+    fn progress_keeps_the_latest_cookie_change() {
+        let session_key = SessionKey::generate();
+        let mut progress = Progress::new(false);
+        progress.record(CookieAction::Set(
+            session_key.clone(),
+            SessionDuration::Browser,
+        ));
+        progress.record(CookieAction::Keep);
+        assert!(progress.loaded);
+        assert_eq!(
+            progress.cookie(),
+            &CookieAction::Set(session_key, SessionDuration::Browser)
+        );
+
+        progress.record(CookieAction::Remove);
+        assert!(!progress.loaded);
+        assert_eq!(progress.cookie(), &CookieAction::Remove);
+    }
+
+    #[test]
+    fn set_cookie_only_changes_the_cookie_when_needed() {
+        let cookies = CookieSettings::default();
+        let session_key = SessionKey::generate();
+        assert_eq!(set_cookie(&cookies, &CookieAction::Keep), None);
+        let browser = CookieAction::Set(session_key.clone(), SessionDuration::Browser);
+        assert_eq!(
+            set_cookie(&cookies, &browser).unwrap(),
+            format!(
+                "id={}; Path=/; HttpOnly; Secure; SameSite=Lax",
+                session_key.as_ref()
+            )
+        );
+        let remembered = SessionDuration::Persistent(Duration::from_secs(3600));
+        assert!(
+            set_cookie(&cookies, &CookieAction::Set(session_key, remembered))
+                .unwrap()
+                .contains("; Max-Age=3600;")
+        );
+        assert!(set_cookie(&cookies, &CookieAction::Remove)
+            .unwrap()
+            .starts_with("id=; Path=/; Max-Age=0;"));
+    }
+
+    #[test]
+    fn browser_cookies_keep_an_explicit_max_age() {
+        let cookie = CookieConfig::default().with_max_age(Duration::from_secs(60));
+        let mut cookies = CookieSettings::default();
+        cookies.set_config(cookie);
+        let browser = CookieAction::Set(SessionKey::generate(), SessionDuration::Browser);
+        assert!(set_cookie(&cookies, &browser)
+            .unwrap()
+            .contains("; Max-Age=60;"));
+    }
+
+    #[test]
+    #[should_panic(expected = "strict security requires a __Host- cookie name")]
+    fn strict_settings_reject_a_weaker_cookie() {
+        let mut cookies = CookieSettings::default();
+        cookies.make_strict(Keyring::new("1", b"secret"), &SessionConfig::default());
+        cookies.set_name("id");
+    }
+
+    #[test]
+    #[should_panic(expected = "strict security requires 256-bit session keys, not 128")]
+    fn strict_settings_reject_configs_with_weaker_keys() {
+        let mut cookies = CookieSettings::default();
+        cookies.make_strict(Keyring::new("1", b"secret"), &SessionConfig::default());
+        let weak = crate::KeyFormat::new(crate::KeyEncoding::Hex, 16);
+        cookies.check_config(&SessionConfig::default().with_key_format(weak));
+    }
+
+    #[test]
+    fn refresh_touches_the_session_and_rotates_csrf_on_request() {
+        let timeout = Duration::from_secs(60);
+        let mut session = Session::default();
+        let body = refresh(&mut session, None, timeout).unwrap();
+        assert_eq!(body, r#"{"expires_in":60}"#);
+        assert_eq!(session.status(), SessionStatus::Changed);
+        assert_eq!(session.csrf_token().unwrap(), None);
+
+        let body = refresh(&mut session, Some("a=b&rotate_csrf=true"), timeout).unwrap();
+        let body = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert_eq!(body["csrf_token"], session.csrf_token().unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn expires_in_only_asks_the_store_about_untouched_sessions() {
+        let store = crate::MemorySessionStore::new();
+        let session = Session::default();
+        store.save(&session, Duration::from_secs(42)).await.unwrap();
+        let session_key = session.id().clone();
+        let timeout = Duration::from_secs(60);
+        let remaining = |progress: Progress| {
+            let session_key = session_key.clone();
+            let store = &store;
+            async move { expires_in(store, &session_key, &progress, timeout).await }
+        };
+
+        let untouched = remaining(Progress::new(true)).await.unwrap().unwrap();
+        assert!(untouched > Duration::from_secs(41) && untouched <= Duration::from_secs(42));
+        assert_eq!(remaining(Progress::new(false)).await.unwrap(), None);
+
+        let mut written = Progress::new(false);
+        written.record(CookieAction::Set(
+            session_key.clone(),
+            SessionDuration::Browser,
+        ));
+        assert_eq!(remaining(written.clone()).await.unwrap(), Some(timeout));
+        written.record(CookieAction::Remove);
+        let removed = remaining(written).await.unwrap();
+        assert_eq!(removed, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn signed_cookies_round_trip_and_reject_forgeries() {
+        let keyring = Keyring::new("1", b"secret");
+        let session_key = SessionKey::generate();
+        let cookie = encode_cookie(Some(&keyring), &session_key);
+        assert_eq!(
+            decode_cookie(Some(&keyring), &cookie).as_deref(),
+            Some(session_key.as_ref())
+        );
+        assert_eq!(decode_cookie(Some(&keyring), session_key.as_ref()), None);
+    }
+
+    #[tokio::test]
+    async fn load_scopes_the_session_to_the_request_device() {
+        let store = crate::MemorySessionStore::new();
+        let config = SessionConfig::default().with_device_header("x-device-id");
+        let login = SessionKey::generate();
+        let phone = login.for_device("phone").unwrap();
+        let mut session = Session::new(phone.clone(), SessionState::default());
+        session.insert("theme", &"dark").unwrap();
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+
+        let on = |device: &str| RequestParts {
+            cookie: Some(login.as_ref().to_string()),
+            device: Some(device.to_string()),
+            ..Default::default()
+        };
+        let policy = policy(config);
+        let (session, progress) = load(&store, &on("phone"), &policy).await.unwrap();
+        assert!(progress.loaded);
+        assert_eq!(session.id(), &phone);
+
+        let (session, progress) = load(&store, &on("laptop"), &policy).await.unwrap();
+        assert!(!progress.loaded);
+        assert_eq!(session.id().device_id(), Some("laptop"));
+        assert_ne!(session.id().session(), login.session());
+    }
+
+    #[tokio::test]
+    async fn load_generates_and_accepts_keys_in_the_config_format() {
+        let store = crate::MemorySessionStore::new();
+        let format = crate::KeyFormat::new(crate::KeyEncoding::Base64Url, 32);
+        let policy = policy(SessionConfig::default().with_key_format(format));
+        let (mut session, _) = load(&store, &RequestParts::default(), &policy)
+            .await
+            .unwrap();
+        assert_eq!(
+            SessionKey::parse_in(session.id().as_ref(), format).as_ref(),
+            Some(session.id())
+        );
+        session.regenerate();
+        assert!(SessionKey::parse_in(session.id().as_ref(), format).is_some());
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+
+        let request = |cookie: &SessionKey| RequestParts {
+            cookie: Some(cookie.as_ref().to_string()),
+            ..Default::default()
+        };
+        let (loaded, progress) = load(&store, &request(session.id()), &policy).await.unwrap();
+        assert!(progress.loaded);
+        assert_eq!(loaded.id(), session.id());
+
+        let default_key = SessionKey::generate();
+        store
+            .save(
+                &Session::new(default_key.clone(), SessionState::default()),
+                Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        let (_, progress) = load(&store, &request(&default_key), &policy).await.unwrap();
+        assert!(!progress.loaded);
+    }
+
+    #[tokio::test]
+    async fn sessions_round_trip_through_the_cookie() {
+        let store = crate::MemorySessionStore::new();
+        let policy = SessionPolicy::default();
+        let (mut session, mut progress) = load(&store, &RequestParts::default(), &policy)
+            .await
+            .unwrap();
+        session.insert("visits", &1).unwrap();
+        flush(&store, &mut session, &mut progress, &policy)
+            .await
+            .unwrap();
+        let CookieAction::Set(session_key, _) = progress.cookie().clone() else {
+            panic!("a new session sets its cookie");
+        };
+
+        let request = RequestParts::new(&policy, None, Some(session_key.as_ref()), |_| None);
+        let (session, progress) = load(&store, &request, &policy).await.unwrap();
+        assert_eq!(session.get::<u32>("visits").unwrap(), Some(1));
+        assert_eq!(progress.cookie(), &CookieAction::Keep);
+    }
+
+    #[tokio::test]
+    async fn purge_destroys_the_session_and_removes_the_cookie() {
+        let store = crate::MemorySessionStore::new();
+        let policy = SessionPolicy::default();
+        let session = Session::default();
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+        let request = RequestParts::new(&policy, None, Some(session.id().as_ref()), |_| None);
+
+        let (mut session, mut progress) = load(&store, &request, &policy).await.unwrap();
+        session.purge();
+        flush(&store, &mut session, &mut progress, &policy)
+            .await
+            .unwrap();
+        assert_eq!(progress.cookie(), &CookieAction::Remove);
+        assert!(!store.exists(session.id()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn client_side_stores_carry_the_session_in_the_cookie() {
+        async fn round_trip(store: impl Backend) {
+            let policy = SessionPolicy::default();
+            let (mut session, mut progress) = load(&store, &RequestParts::default(), &policy)
+                .await
+                .unwrap();
+            session.insert("visits", &1).unwrap();
+            flush(&store, &mut session, &mut progress, &policy)
+                .await
+                .unwrap();
+            let CookieAction::Carry(value, _) = progress.cookie().clone() else {
+                panic!("a client-side session is carried in the cookie");
+            };
+
+            let request = RequestParts::new(&policy, None, Some(&value), |_| None);
+            let (mut session, mut progress) = load(&store, &request, &policy).await.unwrap();
+            assert_eq!(session.get::<u32>("visits").unwrap(), Some(1));
+            session.purge();
+            flush(&store, &mut session, &mut progress, &policy)
+                .await
+                .unwrap();
+            assert_eq!(progress.cookie(), &CookieAction::Remove);
+        }
+
+        round_trip(crate::CookieSessionStore::new(&[7; 32])).await;
+        round_trip(crate::JwtSessionStore::new(
+            crate::JwtAlgorithm::Hs256,
+            b"secret",
+        ))
+        .await;
+    }
+
+    #[tokio::test]
+    async fn sealed_sessions_over_the_cookie_limit_are_too_large() {
+        let store = crate::CookieSessionStore::new(&[7; 32]).with_max_size(128);
+        let mut session = Session::default();
+        session.insert("notes", &"x".repeat(256)).unwrap();
+        let flushed = flush(
+            &store,
+            &mut session,
+            &mut Progress::new(false),
+            &SessionPolicy::default(),
+        )
+        .await;
+        assert!(matches!(
+            flushed,
+            Err(SessionError::SessionTooLargeError { limit: 128, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_failed_save_of_a_regenerated_session_keeps_the_old_one() {
+        let store = crate::session_store::testing::FaultyStore::new()
+            .with_failures(|operation, _| operation == crate::StoreOperation::Save);
+        let mut session = Session::default();
+        let previous = session.id().clone();
+        store
+            .inner()
+            .save(&session, Duration::from_secs(60))
+            .await
+            .unwrap();
+        session.regenerate();
+
+        assert!(persist(&store, &session, true, Duration::from_secs(60))
+            .await
+            .is_err());
+        assert!(store.inner().exists(&previous).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn load_honors_the_store_deadline() {
+        let store = crate::session_store::testing::FaultyStore::new()
+            .with_delay(Duration::from_millis(200));
+        let session = Session::default();
+        store
+            .inner()
+            .save(&session, Duration::from_secs(60))
+            .await
+            .unwrap();
+        let request = RequestParts {
+            cookie: Some(session.id().as_ref().to_string()),
+            ..Default::default()
+        };
+        let config = SessionConfig::default().with_store_deadline(Duration::from_millis(20));
+
+        let closed = load(&store, &request, &policy(config.clone())).await;
+        assert!(matches!(
+            closed,
+            Err(SessionError::StoreUnavailableError(_))
+        ));
+
+        let config = config.with_fail_open(true);
+        let (fresh, progress) = load(&store, &request, &policy(config)).await.unwrap();
+        assert!(!progress.loaded);
+        assert_ne!(fresh.id(), session.id());
+    }
+
+    #[tokio::test]
+    async fn load_fails_on_forged_cookies_and_ignores_foreign_ones() {
+        let store = crate::MemorySessionStore::new();
+        let policy = SessionPolicy::default();
+        let keyring = Keyring::new("1", b"secret");
+        let request =
+            |cookie: &str| RequestParts::new(&policy, Some(&keyring), Some(cookie), |_| None);
+        let signed = keyring.sign(SessionKey::generate().as_ref());
+
+        let (signed_value, last) = signed.split_at(signed.len() - 1);
+        let forged = format!("{signed_value}{}", if last == "0" { "1" } else { "0" });
+        let loaded = load(&store, &request(&forged), &policy).await;
+        assert!(matches!(loaded, Err(SessionError::SessionTamperedError)));
+
+        let foreign = Keyring::new("2", b"other").sign(SessionKey::generate().as_ref());
+        let (_, progress) = load(&store, &request(&foreign), &policy).await.unwrap();
+        assert!(!progress.loaded);
+        assert!(load(&store, &request(&signed), &policy).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn load_destroys_sessions_past_the_absolute_timeout() {
+        let store = crate::MemorySessionStore::new();
+        let policy = policy(
+            SessionConfig::default()
+                .with_timeout(Duration::from_secs(60))
+                .with_absolute_timeout(Duration::from_secs(3600)),
+        );
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        flush(&store, &mut session, &mut Progress::new(false), &policy)
+            .await
+            .unwrap();
+        assert!(session.created_at().is_some());
+        let request = RequestParts {
+            cookie: Some(session.id().as_ref().to_string()),
+            ..Default::default()
+        };
+        assert!(load(&store, &request, &policy).await.unwrap().1.loaded);
+
+        session.backdate_created(Duration::from_secs(7200));
+        store.update(&session, policy.idle_timeout()).await.unwrap();
+        let expired = load(&store, &request, &policy).await;
+        assert!(matches!(expired, Err(SessionError::SessionExpiredError)));
+        assert!(!store.exists(session.id()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn flush_records_key_usage_once() {
+        let store = crate::MemorySessionStore::new();
+        let key = format!("usage-{}", SessionKey::generate().as_ref());
+        let mut session = Session::default();
+        session.insert(&key, &1).unwrap();
+        let mut progress = Progress::new(false);
+        let policy = SessionPolicy::default();
+        flush(&store, &mut session, &mut progress, &policy)
+            .await
+            .unwrap();
+        flush(&store, &mut session, &mut progress, &policy)
+            .await
+            .unwrap();
+
+        let usage = UsageMetrics::global().snapshot()[&key];
+        assert_eq!(usage.writes, 1);
+    }
+
+    #[tokio::test]
+    async fn load_negotiates_a_locale_for_sessions_without_one() {
+        let store = crate::MemorySessionStore::new();
+        let policy = policy(SessionConfig::default().with_locales(&["en-US", "de-DE"]));
+        let headers = [("accept-language", "de-AT, en;q=0.5")];
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(known, _)| *known == name)
+                .map(|(_, value)| *value)
+        };
+        let request = RequestParts::new(&policy, None, None, header);
+        let (mut session, _) = load(&store, &request, &policy).await.unwrap();
+        assert_eq!(session.locale().unwrap().as_deref(), Some("de-DE"));
+
+        session.set_locale("en-US").unwrap();
+        store.save(&session, Duration::from_secs(60)).await.unwrap();
+        let request = RequestParts {
+            cookie: Some(session.id().as_ref().to_string()),
+            ..request
+        };
+        let (session, _) = load(&store, &request, &policy).await.unwrap();
+        assert_eq!(session.locale().unwrap().as_deref(), Some("en-US"));
+    }
+
+    #[test]
+    fn fingerprint_rules_need_a_keyring() {
+        let policy = SessionPolicy::builder()
+            .with_fingerprint(FingerprintRule::UserAgent)
+            .build()
+            .unwrap();
+        let keyring = Keyring::new("1", b"secret");
+        assert!(matches!(
+            check_keyring(&policy, None),
+            Err(PolicyError::FingerprintKeyringError)
+        ));
+        assert!(check_keyring(&policy, Some(&keyring)).is_ok());
+        assert!(check_keyring(&SessionPolicy::default(), None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn load_starts_over_for_sessions_bound_to_another_client() {
+        let store = crate::MemorySessionStore::new();
+        let keyring = Keyring::new("1", b"secret");
+        let policy = SessionPolicy::builder()
+            .with_fingerprint(FingerprintRule::UserAgentAndIp)
+            .build()
+            .unwrap();
+        let from = |user_agent: &'static str, cookie: Option<&SessionKey>| {
+            let headers = [("user-agent", user_agent), ("x-forwarded-for", "10.0.0.1")];
+            let header = move |name: &str| {
+                headers
+                    .iter()
+                    .find(|(known, _)| *known == name)
+                    .map(|(_, value)| *value)
+            };
+            let cookie = cookie.map(|session_key| keyring.sign(session_key.as_ref()));
+            RequestParts::new(&policy, Some(&keyring), cookie.as_deref(), header)
+        };
+
+        let (mut session, mut progress) = load(&store, &from("curl", None), &policy).await.unwrap();
+        session.insert("user_id", &"beavis").unwrap();
+        flush(&store, &mut session, &mut progress, &policy)
+            .await
+            .unwrap();
+
+        let owner = from("curl", Some(session.id()));
+        let (loaded, progress) = load(&store, &owner, &policy).await.unwrap();
+        assert!(progress.loaded);
+        assert_eq!(loaded.id(), session.id());
+
+        let thief = from("wget", Some(session.id()));
+        let (fresh, progress) = load(&store, &thief, &policy).await.unwrap();
+        assert!(!progress.loaded);
+        assert_ne!(fresh.id(), session.id());
+        assert!(store.exists(session.id()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn maintenance_windows_survive_wrapping_stores() {
+        let mode = crate::MaintenanceMode::new();
+        let read_only =
+            crate::ReadOnlySessionStore::new(crate::MemorySessionStore::new(), mode.clone());
+        let store = crate::EncryptedSessionStore::new(read_only, &[1; 32]);
+        mode.enable_for(Duration::from_secs(30));
+        let mut session = Session::default();
+        session.insert("user_id", &"beavis").unwrap();
+        let mut progress = Progress::new(false);
+        let policy = SessionPolicy::default();
+        let error = flush(&store, &mut session, &mut progress, &policy)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SessionError::ReadOnlyError(_)));
+        assert_eq!(error.http_status(), 503);
+        assert_eq!(retry_after(&error).as_deref(), Some("30"));
+
+        mode.enable();
+        let error = flush(&store, &mut session, &mut progress, &policy)
+            .await
+            .unwrap_err();
+        assert_eq!(retry_after(&error), None);
+    }
+
+    #[tokio::test]
+    async fn policy_limits_size_and_authentication_level() {
+        let store = crate::MemorySessionStore::new();
+        let policy = SessionPolicy::builder()
+            .with_max_size(256)
+            .with_levels(&["password", "mfa"])
+            .build()
+            .unwrap();
+        let (mut session, mut progress) = load(&store, &RequestParts::default(), &policy)
+            .await
+            .unwrap();
+        session.insert("notes", &"x".repeat(512)).unwrap();
+        let flushed = flush(&store, &mut session, &mut progress, &policy).await;
+        assert!(matches!(
+            flushed,
+            Err(SessionError::SessionTooLargeError { limit: 256, .. })
+        ));
+        assert!(!store.exists(session.id()).await.unwrap());
+
+        session.remove::<String>("notes").unwrap();
+        session.set_auth_level("password").unwrap();
+        flush(&store, &mut session, &mut progress, &policy)
+            .await
+            .unwrap();
+        let request = RequestParts {
+            cookie: Some(session.id().as_ref().to_string()),
+            ..Default::default()
+        };
+        let checkout = policy
+            .with_override(&PolicyOverride::new().require_level("mfa"))
+            .unwrap();
+        let denied = load(&store, &request, &checkout).await;
+        assert!(matches!(denied, Err(SessionError::AuthLevelError)));
+        assert!(load(&store, &request, &policy).await.is_ok());
+    }
+}
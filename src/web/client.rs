@@ -0,0 +1,102 @@
+//! [`Backend`] for client-side stores, which carry the whole session in the
+//! cookie value rather than under a key on the server.
+
+use std::time::Duration;
+
+use super::{unavailable, Backend, CookieAction, RequestParts};
+use crate::{
+    config::SessionConfig, CookieSessionStore, CookieStoreError, JwtSessionStore, Session,
+    SessionError, SessionKey, SessionStatus,
+};
+
+/// The cookie change that writing `session` calls for, with `seal` turning
+/// the session into the cookie value for the TTL it is given.
+fn carry(
+    session: &Session,
+    timeout: Duration,
+    seal: impl FnOnce(&Session, Duration) -> Result<String, SessionError>,
+) -> Result<CookieAction, SessionError> {
+    let duration = session.duration();
+    match session.status() {
+        SessionStatus::Unchanged => Ok(CookieAction::Keep),
+        // The old value dies with the cookie it replaces, so there is
+        // nothing to destroy.
+        SessionStatus::Changed | SessionStatus::Renewed => {
+            let value = seal(session, duration.ttl(timeout))?;
+            session.take_regenerated_from();
+            session.run_post_commit();
+            Ok(CookieAction::Carry(value, duration))
+        }
+        SessionStatus::Purged => Ok(CookieAction::Remove),
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Backend for CookieSessionStore {
+    async fn fetch(
+        &self,
+        cookie: &str,
+        _: &RequestParts,
+        _: &SessionConfig,
+    ) -> Result<Option<Session>, SessionError> {
+        self.open(cookie).map_err(unavailable)
+    }
+
+    async fn discard(&self, _: &Session) -> Result<(), SessionError> {
+        Ok(())
+    }
+
+    async fn write(
+        &self,
+        session: &Session,
+        _: bool,
+        timeout: Duration,
+    ) -> Result<CookieAction, SessionError> {
+        carry(session, timeout, |session, ttl| {
+            self.seal(session, ttl).map_err(|error| match error {
+                CookieStoreError::TooLargeError { size, limit } => {
+                    SessionError::SessionTooLargeError { size, limit }
+                }
+                error => unavailable(error),
+            })
+        })
+    }
+
+    /// The expiry is sealed in with the session, out of reach.
+    async fn remaining(&self, _: &SessionKey) -> Result<Option<Duration>, SessionError> {
+        Ok(None)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Backend for JwtSessionStore {
+    async fn fetch(
+        &self,
+        cookie: &str,
+        _: &RequestParts,
+        _: &SessionConfig,
+    ) -> Result<Option<Session>, SessionError> {
+        self.decode(cookie).map_err(unavailable)
+    }
+
+    async fn discard(&self, _: &Session) -> Result<(), SessionError> {
+        Ok(())
+    }
+
+    async fn write(
+        &self,
+        session: &Session,
+        _: bool,
+        timeout: Duration,
+    ) -> Result<CookieAction, SessionError> {
+        carry(session, timeout, |session, ttl| {
+            self.encode(session, ttl).map_err(unavailable)
+        })
+    }
+
+    /// The expiry is signed into the token, which the request does not
+    /// hand over.
+    async fn remaining(&self, _: &SessionKey) -> Result<Option<Duration>, SessionError> {
+        Ok(None)
+    }
+}
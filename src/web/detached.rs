@@ -0,0 +1,407 @@
+//! The lifecycle for integrations whose futures must be `Send` while store
+//! futures are not.
+
+use std::{
+    num::NonZeroUsize,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+use futures::{
+    future::{BoxFuture, LocalBoxFuture},
+    FutureExt,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{
+    runtime,
+    sync::{mpsc, oneshot},
+    task::{self, LocalSet},
+};
+
+use super::{expires_in, flush, load, Backend, CookieAction, Progress, RequestParts};
+use crate::{
+    storage::{Storage, StorageError},
+    Session, SessionError, SessionKey, SessionPolicy, SessionStatus,
+};
+
+type Job = Box<dyn FnOnce() -> LocalBoxFuture<'static, ()> + Send>;
+
+/// The most worker threads one layer starts.
+const MAX_WORKERS: usize = 4;
+
+/// The threads that run a layer's store calls. Their futures are not
+/// `Send`, so they cannot be spawned onto the integration's runtime;
+/// instead each worker interleaves them on a `LocalSet` of its own,
+/// whatever kind of runtime the requests come from. Workers stop once the
+/// layer and its sessions are dropped.
+struct Workers {
+    jobs: Vec<mpsc::UnboundedSender<Job>>,
+    next: AtomicUsize,
+}
+
+impl Workers {
+    fn start() -> Self {
+        let count = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .min(MAX_WORKERS);
+        let jobs = (0..count).map(|_| Self::spawn()).collect();
+        Self {
+            jobs,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Starts one worker. If its thread or runtime cannot start, the queue
+    /// is dropped and calls sent to it fail instead.
+    fn spawn() -> mpsc::UnboundedSender<Job> {
+        let (jobs, mut queue) = mpsc::unbounded_channel::<Job>();
+        let _ = thread::Builder::new()
+            .name("lushus-session-store".to_string())
+            .spawn(move || {
+                let Ok(runtime) = runtime::Builder::new_current_thread().enable_all().build()
+                else {
+                    return;
+                };
+                LocalSet::new().block_on(&runtime, async move {
+                    while let Some(job) = queue.recv().await {
+                        task::spawn_local(job());
+                    }
+                })
+            });
+        jobs
+    }
+
+    fn send(&self, job: Job) -> Result<(), SessionError> {
+        let next = self.next.fetch_add(1, Ordering::Relaxed) % self.jobs.len();
+        self.jobs[next].send(job).map_err(|_| stopped())
+    }
+}
+
+fn stopped() -> SessionError {
+    SessionError::StoreUnavailableError("the session store worker has stopped".to_string())
+}
+
+/// A store together with the [`Workers`] its calls run on. Integrations
+/// create one per layer; clones share both.
+pub(crate) struct DetachedStore<Store> {
+    store: Arc<Store>,
+    workers: Arc<Workers>,
+}
+
+impl<Store> DetachedStore<Store> {
+    pub(crate) fn new(store: Store) -> Self {
+        Self {
+            store: Arc::new(store),
+            workers: Arc::new(Workers::start()),
+        }
+    }
+
+    /// Runs `op` against the store on one of the workers, resuming its
+    /// panic if it panics.
+    async fn run<T, F>(&self, op: F) -> Result<T, SessionError>
+    where
+        Store: Send + Sync + 'static,
+        T: Send + 'static,
+        F: FnOnce(Arc<Store>) -> LocalBoxFuture<'static, T> + Send + 'static,
+    {
+        let store = self.store.clone();
+        let (sender, receiver) = oneshot::channel();
+        let job: Job = Box::new(move || {
+            Box::pin(async move {
+                let result = AssertUnwindSafe(op(store)).catch_unwind().await;
+                let _ = sender.send(result);
+            })
+        });
+        self.workers.send(job)?;
+        match receiver.await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(panic)) => std::panic::resume_unwind(panic),
+            Err(_) => Err(stopped()),
+        }
+    }
+}
+
+impl<Store> Clone for DetachedStore<Store> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            workers: self.workers.clone(),
+        }
+    }
+}
+
+type Flushed = (Session, Progress, Result<(), SessionError>);
+
+/// Writes a handle's session to the store it was loaded from.
+trait Flush: Send + Sync {
+    fn flush(
+        &self,
+        session: Session,
+        progress: Progress,
+    ) -> BoxFuture<'static, Result<Flushed, SessionError>>;
+    fn timeout(&self) -> Duration;
+}
+
+struct StoreFlush<Store> {
+    store: DetachedStore<Store>,
+    policy: SessionPolicy,
+}
+
+impl<Store> Flush for StoreFlush<Store>
+where
+    Store: Backend + Send + Sync + 'static,
+{
+    fn flush(
+        &self,
+        mut session: Session,
+        mut progress: Progress,
+    ) -> BoxFuture<'static, Result<Flushed, SessionError>> {
+        let store = self.store.clone();
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            store
+                .run(move |store| {
+                    Box::pin(async move {
+                        let result = flush(&*store, &mut session, &mut progress, &policy).await;
+                        (session, progress, result)
+                    })
+                })
+                .await
+        })
+    }
+
+    fn timeout(&self) -> Duration {
+        self.policy.idle_timeout()
+    }
+}
+
+pub(crate) async fn load_detached<Store>(
+    store: &DetachedStore<Store>,
+    request: RequestParts,
+    policy: &SessionPolicy,
+) -> Result<SessionHandle, SessionError>
+where
+    Store: Backend + Send + Sync + 'static,
+{
+    let load_policy = policy.clone();
+    let (session, progress) = store
+        .run(move |store| Box::pin(async move { load(&*store, &request, &load_policy).await }))
+        .await??;
+    let flusher = Arc::new(StoreFlush {
+        store: store.clone(),
+        policy: policy.clone(),
+    });
+    Ok(SessionHandle {
+        session: Arc::new(Mutex::new(session)),
+        progress: Arc::new(Mutex::new(progress)),
+        flusher,
+    })
+}
+
+/// [`expires_in`](super::expires_in) for a finished [`SessionHandle`]. The
+/// result only feeds a hint header, so store errors leave it out.
+pub(crate) async fn expires_in_detached<Store>(
+    store: &DetachedStore<Store>,
+    handle: &SessionHandle,
+    timeout: Duration,
+) -> Option<Duration>
+where
+    Store: Backend + Send + Sync + 'static,
+{
+    let session_key = handle.id();
+    let progress = handle.progress().clone();
+    store
+        .run(move |store| {
+            Box::pin(async move { expires_in(&*store, &session_key, &progress, timeout).await })
+        })
+        .await
+        .ok()?
+        .ok()
+        .flatten()
+}
+
+/// The value of the cookie called `name` in a `Cookie` request header.
+#[cfg(any(feature = "poem", feature = "tower", feature = "warp"))]
+pub(crate) fn cookie_value<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.trim_matches('"'))
+}
+
+/// The request's session, shared between the handler and the middleware
+/// that persists it once the handler returns.
+#[derive(Clone)]
+pub struct SessionHandle {
+    session: Arc<Mutex<Session>>,
+    progress: Arc<Mutex<Progress>>,
+    flusher: Arc<dyn Flush>,
+}
+
+impl SessionHandle {
+    pub fn id(&self) -> SessionKey {
+        self.lock().id().clone()
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        self.lock().status()
+    }
+
+    /// Moves the session to a fresh key, e.g. after login.
+    pub fn regenerate(&self) {
+        self.lock().regenerate();
+    }
+
+    /// Clears the session and destroys it in the store, e.g. on logout.
+    pub fn purge(&self) {
+        self.lock().purge();
+    }
+
+    /// Makes this a "remember me" session, stored and kept by the browser
+    /// for `duration`.
+    pub fn persist_for(&self, duration: Duration) -> Result<(), StorageError> {
+        self.lock().persist_for(duration)
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, StorageError> {
+        self.lock().get(key)
+    }
+
+    pub fn insert<T: Serialize>(&self, key: &str, value: &T) -> Result<(), StorageError> {
+        self.lock().insert(key, value)
+    }
+
+    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, StorageError> {
+        self.lock().remove(key)
+    }
+
+    /// Writes the changes made so far instead of waiting for the response,
+    /// for handlers that need them durable mid-request. The response-time
+    /// write then only covers later changes. Other clones of the handle
+    /// can read the session meanwhile, but changes they make before this
+    /// returns are lost.
+    pub async fn flush_now(&self) -> Result<(), SessionError> {
+        self.flush().await
+    }
+
+    /// Touches the session so the response-time write extends its expiry,
+    /// rotating the CSRF token if `query` has `rotate_csrf=true`, and
+    /// returns the JSON body a refresh endpoint answers with. The
+    /// integrations' `session_refresh_handler` wraps this.
+    pub fn refresh(&self, query: Option<&str>) -> Result<String, StorageError> {
+        super::refresh(&mut self.lock(), query, self.flusher.timeout())
+    }
+
+    /// Writes whatever is left and returns the cookie change that the whole
+    /// request calls for.
+    pub(crate) async fn finish(&self) -> Result<CookieAction, SessionError> {
+        self.flush().await?;
+        Ok(self.progress().cookie().clone())
+    }
+
+    async fn flush(&self) -> Result<(), SessionError> {
+        if self.status() == SessionStatus::Unchanged {
+            return Ok(());
+        }
+        let session = {
+            let mut session = self.lock();
+            let copy = session.read_copy();
+            std::mem::replace(&mut *session, copy)
+        };
+        let progress = self.progress().clone();
+        let (session, progress, result) = self.flusher.flush(session, progress).await?;
+        *self.lock() = session;
+        *self.progress() = progress;
+        result
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Session> {
+        self.session.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn progress(&self) -> MutexGuard<'_, Progress> {
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K: AsRef<str>> Storage<K> for SessionHandle {
+    type Error = StorageError;
+
+    fn insert<T: Serialize>(&mut self, key: K, value: &T) -> Result<(), Self::Error> {
+        self.lock().insert(key, value)
+    }
+
+    fn remove<T: DeserializeOwned>(&mut self, key: K) -> Result<Option<T>, Self::Error> {
+        self.lock().remove(key)
+    }
+
+    fn get<T: DeserializeOwned>(&self, key: K) -> Result<Option<T>, Self::Error> {
+        self.lock().get(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{session_store::testing::FaultyStore, SessionStore};
+
+    #[test]
+    #[cfg(any(feature = "poem", feature = "tower", feature = "warp"))]
+    fn cookie_value_finds_the_named_cookie() {
+        let header = "theme=dark; id=abc; other=\"quoted\"";
+        assert_eq!(cookie_value(header, "id"), Some("abc"));
+        assert_eq!(cookie_value(header, "other"), Some("quoted"));
+        assert_eq!(cookie_value(header, "missing"), None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn store_calls_interleave_without_blocking_the_runtime() {
+        let delay = Duration::from_millis(100);
+        let store = DetachedStore::new(FaultyStore::new().with_delay(delay));
+        let policy = SessionPolicy::default();
+        let started = std::time::Instant::now();
+        let loads = (0..8).map(|_| load_detached(&store, RequestParts::default(), &policy));
+        let handles = futures::future::join_all(loads).await;
+        assert!(
+            started.elapsed() < delay * 4,
+            "took {:?}",
+            started.elapsed()
+        );
+
+        let handle = handles.into_iter().next().unwrap().unwrap();
+        handle.insert("user_id", &"beavis").unwrap();
+        handle.flush_now().await.unwrap();
+        let stored = store
+            .store
+            .inner()
+            .load(&handle.id())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.get::<String>("user_id").unwrap().unwrap(), "beavis");
+    }
+
+    #[tokio::test]
+    async fn a_stopped_worker_fails_the_call_instead_of_panicking() {
+        let (jobs, queue) = mpsc::unbounded_channel();
+        drop(queue);
+        let store = DetachedStore {
+            store: Arc::new(FaultyStore::new()),
+            workers: Arc::new(Workers {
+                jobs: vec![jobs],
+                next: AtomicUsize::new(0),
+            }),
+        };
+        let error = load_detached(&store, RequestParts::default(), &SessionPolicy::default())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(error, SessionError::StoreUnavailableError(_)));
+    }
+}
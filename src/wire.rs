@@ -0,0 +1,250 @@
+//! The versioned envelope sessions are exchanged in with non-Rust services.
+//!
+//! An envelope is a JSON object:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "minor": 0,
+//!   "codec": "json",
+//!   "compressed": false,
+//!   "metadata": { "created_by": "checkout" },
+//!   "state": { "user_id": "\"beavis\"" }
+//! }
+//! ```
+//!
+//! - `version` is the envelope version; readers reject versions they do not
+//!   know unless given a [`Downgrades`] path from it to theirs.
+//! - `minor` is optional and defaults to 0. A minor version only adds fields
+//!   older readers can ignore, so readers accept any minor of a version they
+//!   know.
+//! - `codec` names how each value in `state` is encoded; `json` is the only
+//!   codec defined by version 1.
+//! - `compressed` must be `false` in version 1 and is reserved for later versions.
+//! - `metadata` is an optional object of string values for the writer's use.
+//! - `state` maps each session key to its codec-encoded value, so a `json`
+//!   value is itself a JSON document stored as a string.
+//!
+//! Unknown top-level fields are ignored so later versions can add fields.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::session_state::SessionState;
+
+pub const WIRE_VERSION: u32 = 1;
+pub const JSON_CODEC: &str = "json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum WireError {
+    #[error("Unsupported session envelope version {0}")]
+    VersionError(u32),
+    #[error("Unsupported session value codec \"{0}\"")]
+    CodecError(String),
+    #[error("Compressed session envelopes are not supported")]
+    CompressionError,
+    #[error("Malformed session envelope: {0}")]
+    SerializationError(#[from] serde_json::Error),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WireEnvelope {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub minor: u32,
+    pub codec: String,
+    pub compressed: bool,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    pub state: SessionState,
+}
+
+impl WireEnvelope {
+    pub fn new(state: SessionState) -> Self {
+        Self {
+            version: WIRE_VERSION,
+            minor: 0,
+            codec: JSON_CODEC.to_string(),
+            compressed: false,
+            metadata: BTreeMap::new(),
+            state,
+        }
+    }
+
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn encode(&self) -> Result<String, WireError> {
+        self.check()?;
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn decode(encoded: &str) -> Result<Self, WireError> {
+        Self::decode_with(encoded, &Downgrades::default())
+    }
+
+    /// Like [`decode`](Self::decode), but first steps envelopes written by
+    /// newer versions down to this one with `downgrades`, so a rolled-back
+    /// deploy can still read them.
+    pub fn decode_with(encoded: &str, downgrades: &Downgrades) -> Result<Self, WireError> {
+        let mut envelope = serde_json::from_str::<Map<String, Value>>(encoded)?;
+        while let Some(version) = envelope.get("version").and_then(Value::as_u64) {
+            let version = u32::try_from(version).unwrap_or(u32::MAX);
+            if version <= WIRE_VERSION {
+                break;
+            }
+            let Some(step) = downgrades.steps.get(&version) else {
+                return Err(WireError::VersionError(version));
+            };
+            envelope = step(envelope)?;
+            envelope.insert("version".to_string(), (version - 1).into());
+            envelope.remove("minor");
+        }
+        let envelope = serde_json::from_value::<Self>(Value::Object(envelope))?;
+        envelope.check()?;
+        Ok(envelope)
+    }
+
+    fn check(&self) -> Result<(), WireError> {
+        if self.version != WIRE_VERSION {
+            return Err(WireError::VersionError(self.version));
+        }
+        if self.codec != JSON_CODEC {
+            return Err(WireError::CodecError(self.codec.clone()));
+        }
+        if self.compressed {
+            return Err(WireError::CompressionError);
+        }
+        Ok(())
+    }
+}
+
+fn is_zero(minor: &u32) -> bool {
+    *minor == 0
+}
+
+type DowngradeStep = Box<dyn Fn(Map<String, Value>) -> Result<Map<String, Value>, WireError>>;
+
+/// Transformers that rewrite an envelope of one version as the version
+/// before it, for [`WireEnvelope::decode_with`].
+///
+/// A deploy ships the step down from its own version alongside the upgrade,
+/// so the previous release can be given it and keep reading after a
+/// rollback. Steps see the raw JSON object, as its fields are those of a
+/// version this build knows nothing about; the version is set for them.
+#[derive(Default)]
+pub struct Downgrades {
+    steps: BTreeMap<u32, DowngradeStep>,
+}
+
+impl Downgrades {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rewrites envelopes of version `from` as version `from - 1`.
+    pub fn with_step(
+        mut self,
+        from: u32,
+        step: impl Fn(Map<String, Value>) -> Result<Map<String, Value>, WireError> + 'static,
+    ) -> Self {
+        self.steps.insert(from, Box::new(step));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> SessionState {
+        let mut state = SessionState::default();
+        state.insert("user_id", "\"beavis\"".to_string());
+        state
+    }
+
+    #[test]
+    fn decodes_the_documented_example() {
+        let encoded = r#"{
+            "version": 1,
+            "codec": "json",
+            "compressed": false,
+            "metadata": { "created_by": "checkout" },
+            "state": { "user_id": "\"beavis\"" }
+        }"#;
+        let envelope = WireEnvelope::decode(encoded).unwrap();
+        assert_eq!(
+            envelope,
+            WireEnvelope::new(state()).with_metadata("created_by", "checkout")
+        );
+    }
+
+    #[test]
+    fn metadata_is_optional_and_unknown_fields_are_ignored() {
+        let encoded = r#"{"version":1,"codec":"json","compressed":false,"state":{},"extra":[1]}"#;
+        let envelope = WireEnvelope::decode(encoded).unwrap();
+        assert!(envelope.metadata.is_empty());
+        assert!(envelope.state.is_empty());
+    }
+
+    #[test]
+    fn newer_minor_versions_decode_and_newer_versions_step_down() {
+        let minor = r#"{"version":1,"minor":3,"codec":"json","compressed":false,"state":{}}"#;
+        assert_eq!(WireEnvelope::decode(minor).unwrap().minor, 3);
+
+        // Version 3 renamed `state` to `values`; version 2 added `ttl`.
+        let newer = r#"{"version":3,"codec":"json","compressed":false,"ttl":60,
+            "values":{"user_id":"\"beavis\""}}"#;
+        assert!(matches!(
+            WireEnvelope::decode(newer),
+            Err(WireError::VersionError(3))
+        ));
+        let downgrades = Downgrades::new()
+            .with_step(3, |mut envelope| {
+                let values = envelope.remove("values").unwrap_or_default();
+                envelope.insert("state".to_string(), values);
+                Ok(envelope)
+            })
+            .with_step(2, |mut envelope| {
+                envelope.remove("ttl");
+                Ok(envelope)
+            });
+        let envelope = WireEnvelope::decode_with(newer, &downgrades).unwrap();
+        assert_eq!(envelope, WireEnvelope::new(state()));
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        let envelope = WireEnvelope::new(state()).with_metadata("node", "a");
+        let decoded = WireEnvelope::decode(&envelope.encode().unwrap()).unwrap();
+        assert_eq!(decoded, envelope);
+    }
+
+    #[test]
+    fn rejects_unknown_versions_codecs_and_compression() {
+        let decode = |version: u32, codec: &str, compressed: bool| {
+            WireEnvelope::decode(&format!(
+                r#"{{"version":{version},"codec":"{codec}","compressed":{compressed},"state":{{}}}}"#
+            ))
+        };
+        assert!(matches!(
+            decode(2, "json", false),
+            Err(WireError::VersionError(2))
+        ));
+        assert!(matches!(
+            decode(1, "bincode", false),
+            Err(WireError::CodecError(_))
+        ));
+        assert!(matches!(
+            decode(1, "json", true),
+            Err(WireError::CompressionError)
+        ));
+        assert!(matches!(
+            WireEnvelope::decode(r#"{"version":1}"#),
+            Err(WireError::SerializationError(_))
+        ));
+    }
+}